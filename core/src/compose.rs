@@ -6,6 +6,8 @@
 //! `multipart/alternative` for alternative bodies and
 //! `multipart/related` for including embedded resources which
 //! can be used in the mail bodies  like e.g. a logo.
+//! Additionally `multipart/digest` and `multipart/report` (e.g.
+//! for delivery status notifications) mails can be created.
 //!
 //! This module provides the needed utilities to more simply
 //! create a `Mail` instance which represents this kind of
@@ -21,11 +23,18 @@ use media_type::{ALTERNATIVE, MIXED, MULTIPART, RELATED};
 use vec1::Vec1;
 
 use headers::{
-    header_components::{Disposition, DispositionKind, MediaType},
+    error::ComponentCreationError,
+    header_components::{Disposition, DispositionKind, FileMeta, MediaType, TransferEncoding},
     headers, HeaderKind,
 };
+use internals::MailType;
 
-use crate::{mail::Mail, resource::Resource};
+use crate::{
+    context::Context,
+    error::{CompositionError, MailError, PartPosition},
+    mail::{EncodableMail, Mail},
+    resource::{EncData, Metadata, Resource, UseMediaType},
+};
 
 /// Parts used to create a mail body (in a multipart mail).
 ///
@@ -114,6 +123,139 @@ pub struct MailParts {
 //-------------------------------------------------------//
 
 impl MailParts {
+    /// Create a `MailParts` instance with given alternative bodies.
+    ///
+    /// The bodies are used in the given order, i.e. the first one
+    /// is the last fallback while the last one should be shown if
+    /// possible. Each `Resource` will use the media type it is paired
+    /// with (see `Resource::with_media_type`).
+    ///
+    /// The returned instance has neither embeddings nor attachments.
+    pub fn alternatives(bodies: Vec1<(MediaType, Resource)>) -> MailParts {
+        let alternative_bodies = bodies.mapped(|(media_type, resource)| BodyPart {
+            resource: resource.with_media_type(media_type),
+            inline_embeddings: Vec::new(),
+            attachments: Vec::new(),
+        });
+
+        MailParts {
+            alternative_bodies,
+            inline_embeddings: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Create a `multipart/digest` `Mail` instance containing the given messages.
+    ///
+    /// Each message is encoded (as a `MailType::Ascii` mail) and placed
+    /// as is into a `message/rfc822` body of the digest, which is the
+    /// default media type for bodies of a `multipart/digest` (rfc2046).
+    /// The encoded messages are used as already transfer encoded
+    /// resources with a `7bit` transfer encoding, i.e. they are not
+    /// encoded again when the digest is encoded.
+    ///
+    /// The `Context` is used to generate a `ContentId` for each body.
+    ///
+    /// # Error
+    ///
+    /// Fails if any of the messages can not be encoded.
+    pub fn digest(messages: Vec<EncodableMail>, ctx: &impl Context) -> Result<Mail, MailError> {
+        let mut bodies = Vec::with_capacity(messages.len());
+        for message in messages {
+            let encoded = message.encode_into_bytes(MailType::Ascii)?;
+            let meta = Metadata {
+                file_meta: FileMeta::default(),
+                //UNWRAP_SAFE: it's a valid media type
+                media_type: MediaType::parse("message/rfc822").unwrap(),
+                content_id: ctx.generate_content_id(),
+            };
+            let enc_data = EncData::new(encoded, meta, TransferEncoding::_7Bit);
+            bodies.push(Resource::EncData(enc_data).create_mail());
+        }
+        Ok(new_multipart("digest", bodies))
+    }
+
+    /// Create a `multipart/report` `Mail` instance (rfc6522).
+    ///
+    /// The `report_type` is used as the `report-type` parameter of the
+    /// `Content-Type` header, e.g. `"delivery-status"` for a delivery
+    /// status notification, and is kept as is when the mail is encoded
+    /// (only the `boundary` parameter is added).
+    ///
+    /// The bodies are placed in the order required by rfc6522:
+    ///
+    /// 1. the `human_readable` explanation of the report,
+    /// 2. the machine readable `report`, e.g. a `message/delivery-status`
+    ///    body created with `DeliveryStatus::into_resource`,
+    /// 3. if given, the `returned_content`, i.e. the original message
+    ///    (`message/rfc822`) or just its headers (`text/rfc822-headers`).
+    ///
+    /// # Error
+    ///
+    /// Fails if `report_type` can not be represented as a media type
+    /// parameter value (values which are not a token are quoted).
+    pub fn report(
+        report_type: &str,
+        human_readable: Resource,
+        report: Resource,
+        returned_content: Option<Resource>,
+    ) -> Result<Mail, ComponentCreationError> {
+        let content_type = MediaType::builder("multipart", "report")
+            .param("report-type", report_type)
+            .build()?;
+
+        let mut bodies = vec![human_readable.create_mail(), report.create_mail()];
+        bodies.extend(returned_content.map(Resource::create_mail));
+
+        Ok(Mail::new_multipart_mail(content_type, bodies))
+    }
+
+    /// Checks if this instance can be composed into a sensible mail.
+    ///
+    /// This checks that:
+    ///
+    /// - every loaded inline embedding (shared or body specific) has a
+    ///   content id, as bodies can only refer to it through it's content
+    ///   id (resources which are not loaded yet, i.e. `Resource::Source`
+    ///   and `Resource::Message`, get their content id when loaded)
+    /// - no body, embedding or attachment has a `multipart` media type,
+    ///   as far as the media type is known without loading the resource
+    ///
+    /// There always is at least one body as `alternative_bodies` is a `Vec1`.
+    ///
+    /// This is meant to catch mistakes before composing the mail instead
+    /// of only when turning it into an encodable mail.
+    ///
+    /// # Error
+    ///
+    /// Returns an error describing the first problem found.
+    pub fn validate(&self) -> Result<(), CompositionError> {
+        for (body_idx, body) in self.alternative_bodies.iter().enumerate() {
+            check_singlepart(&body.resource, PartPosition::AlternativeBody(body_idx))?;
+            for (index, embedding) in body.inline_embeddings.iter().enumerate() {
+                let position = PartPosition::BodyInlineEmbedding {
+                    body: body_idx,
+                    index,
+                };
+                check_inline_embedding(embedding, position)?;
+            }
+            for (index, attachment) in body.attachments.iter().enumerate() {
+                let position = PartPosition::BodyAttachment {
+                    body: body_idx,
+                    index,
+                };
+                check_singlepart(attachment, position)?;
+            }
+        }
+        for (idx, embedding) in self.inline_embeddings.iter().enumerate() {
+            check_inline_embedding(embedding, PartPosition::InlineEmbedding(idx))?;
+        }
+        for (idx, attachment) in self.attachments.iter().enumerate() {
+            check_singlepart(attachment, PartPosition::Attachment(idx))?;
+        }
+        Ok(())
+    }
+
     /// Create a `Mail` instance based on this `MailParts` instance.
     ///
     ///
@@ -233,9 +375,14 @@ impl Resource {
         Mail::new_singlepart_mail(self)
     }
 
+    /// Create a `Mail` instance representing this `Resource` with a `Content-Disposition` header.
+    ///
+    /// The parameters of the disposition (`filename`, `creation-date`,
+    /// `modification-date`, `read-date` and `size`) are filled in from
+    /// the `FileMeta` of the resource once it is loaded, i.e. when the
+    /// mail is turned into an encodable mail.
     pub fn create_mail_with_disposition(self, disposition_kind: DispositionKind) -> Mail {
         let mut mail = self.create_mail();
-        //TODO[1.0] grab meta from resource
         let disposition = Disposition::new(disposition_kind, Default::default());
         mail.insert_header(headers::ContentDisposition::body(disposition));
         mail
@@ -265,8 +412,7 @@ impl Mail {
     /// meant to be the _main_ body.
     pub fn wrap_with_alternatives(self, alternates: Vec<Mail>) -> Mail {
         let mut bodies = alternates;
-        //TODO[opt] accept iter and prepend instead of insert in vec
-        bodies.insert(0, self);
+        bodies.push(self);
         new_multipart(&ALTERNATIVE, bodies)
     }
 
@@ -289,3 +435,370 @@ fn new_multipart(sub_type: &'static str, bodies: Vec<Mail>) -> Mail {
     let content_type = MediaType::new(MULTIPART, sub_type).unwrap();
     Mail::new_multipart_mail(content_type, bodies)
 }
+
+fn check_inline_embedding(
+    embedding: &Resource,
+    position: PartPosition,
+) -> Result<(), CompositionError> {
+    match *embedding {
+        // the content id is assigned when the resource is loaded
+        Resource::Source(..) | Resource::Message(..) => {}
+        Resource::Data(..) | Resource::EncData(..) => {
+            if embedding.content_id().is_none() {
+                return Err(CompositionError::EmbeddingWithoutContentId(position));
+            }
+        }
+    }
+    check_singlepart(embedding, position)
+}
+
+fn check_singlepart(resource: &Resource, position: PartPosition) -> Result<(), CompositionError> {
+    let media_type = match *resource {
+        Resource::Data(ref data) => data.media_type(),
+        Resource::EncData(ref enc_data) => enc_data.media_type(),
+        Resource::Source(ref source) => match source.use_media_type {
+            UseMediaType::Default(ref media_type) => media_type,
+            UseMediaType::Auto => return Ok(()),
+        },
+        Resource::Message(..) => return Ok(()),
+    };
+
+    if media_type.type_() == MULTIPART {
+        return Err(CompositionError::MultipartBodyPart {
+            position,
+            media_type: media_type.full_type().to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    mod alternatives {
+        use headers::header_components::MediaType;
+        use vec1::Vec1;
+
+        use super::super::*;
+        use crate::{default_impl::test_context, mail::MailBody};
+
+        test!(composes_bodies_in_given_order, {
+            let ctx = test_context();
+            let media_types = [
+                "text/plain; charset=utf-8",
+                "text/enriched; charset=utf-8",
+                "text/html; charset=utf-8",
+            ];
+
+            let bodies = media_types
+                .iter()
+                .map(|media_type| {
+                    let media_type = MediaType::parse(media_type).unwrap();
+                    (media_type, Resource::plain_text("some text", &ctx))
+                })
+                .collect::<Vec<_>>();
+
+            let mail = MailParts::alternatives(Vec1::try_from_vec(bodies).unwrap()).compose();
+
+            let content_type = mail.headers().get_single(headers::ContentType).unwrap()?;
+            assert_eq!(content_type.body().full_type(), "multipart/alternative");
+
+            let bodies = match *mail.body() {
+                MailBody::MultipleBodies { ref bodies, .. } => bodies,
+                _ => panic!("expected multipart body"),
+            };
+
+            assert_eq!(bodies.len(), 3);
+            for (body, expected) in bodies.iter().zip(media_types.iter()) {
+                match *body.body() {
+                    MailBody::SingleBody {
+                        body: Resource::Data(ref data),
+                    } => assert_eq!(data.media_type().as_str_repr(), *expected),
+                    _ => panic!("expected singlepart body with data"),
+                }
+            }
+        });
+    }
+
+    mod digest {
+        use futures::Future;
+
+        use headers::headers::{Subject, _From};
+
+        use super::super::*;
+        use crate::{default_impl::test_context, mail::MailBody};
+
+        fn encodable_mail(subject: &str) -> EncodableMail {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("some text", &ctx);
+            mail.insert_headers(
+                headers! {
+                    _From: ["alice@example.test"],
+                    Subject: subject
+                }
+                .unwrap(),
+            );
+            mail.into_encodable_mail(ctx).wait().unwrap()
+        }
+
+        test!(wraps_encoded_messages_as_rfc822_bodies, {
+            let ctx = test_context();
+            let messages = vec![encodable_mail("first"), encodable_mail("second")];
+            let expected = messages
+                .iter()
+                .map(|message| message.encode_into_bytes(MailType::Ascii))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut mail = MailParts::digest(messages, &ctx)?;
+
+            let content_type = mail.headers().get_single(headers::ContentType).unwrap()?;
+            assert_eq!(content_type.body().full_type(), "multipart/digest");
+
+            {
+                let bodies = match *mail.body() {
+                    MailBody::MultipleBodies { ref bodies, .. } => bodies,
+                    _ => panic!("expected multipart body"),
+                };
+
+                assert_eq!(bodies.len(), 2);
+                for (body, expected) in bodies.iter().zip(expected.iter()) {
+                    match *body.body() {
+                        MailBody::SingleBody {
+                            body: Resource::EncData(ref enc_data),
+                        } => {
+                            assert_eq!(enc_data.media_type().full_type(), "message/rfc822");
+                            assert_eq!(enc_data.encoding(), TransferEncoding::_7Bit);
+                            assert_eq!(&**enc_data.transfer_encoded_buffer(), &expected[..]);
+                        }
+                        _ => panic!("expected singlepart body with encoded data"),
+                    }
+                }
+            }
+
+            mail.insert_headers(headers! {
+                _From: ["bob@example.test"],
+                Subject: "digest"
+            }?);
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            for inner in expected {
+                assert!(encoded.contains(&String::from_utf8(inner).unwrap()));
+            }
+        });
+    }
+    mod report {
+        use futures::Future;
+
+        use headers::{
+            header_components::{DsnAction, TypedAddress},
+            headers::{Subject, _From},
+        };
+
+        use super::super::*;
+        use crate::{
+            default_impl::test_context,
+            delivery_status::{DeliveryStatus, RecipientStatus},
+            mail::MailBody,
+            resource::Data,
+        };
+
+        test!(creates_delivery_status_report, {
+            let ctx = test_context();
+            let recipient = RecipientStatus::new(
+                TypedAddress::rfc822("user@example.com")?,
+                DsnAction::Failed,
+                "5.1.1".parse()?,
+            );
+            let status = DeliveryStatus::new(TypedAddress::dns("mta.example.com")?, recipient);
+            let original_headers = Data::new(
+                &b"From: alice@example.test\r\nSubject: hy\r\n"[..],
+                Metadata {
+                    file_meta: FileMeta::default(),
+                    media_type: MediaType::parse("text/rfc822-headers")?,
+                    content_id: ctx.generate_content_id(),
+                },
+            );
+
+            let mut mail = MailParts::report(
+                "delivery-status",
+                Resource::plain_text("The mail could not be delivered.", &ctx),
+                status.into_resource(&ctx)?,
+                Some(Resource::Data(original_headers)),
+            )?;
+
+            {
+                let bodies = match *mail.body() {
+                    MailBody::MultipleBodies { ref bodies, .. } => bodies,
+                    _ => panic!("expected multipart body"),
+                };
+                let media_types = bodies
+                    .iter()
+                    .map(|body| match *body.body() {
+                        MailBody::SingleBody {
+                            body: Resource::Data(ref data),
+                        } => data.media_type().full_type(),
+                        MailBody::SingleBody {
+                            body: Resource::EncData(ref enc_data),
+                        } => enc_data.media_type().full_type(),
+                        _ => panic!("expected singlepart body"),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    media_types,
+                    vec![
+                        "text/plain",
+                        "message/delivery-status",
+                        "text/rfc822-headers"
+                    ]
+                );
+            }
+
+            mail.insert_headers(headers! {
+                _From: ["mailer-daemon@example.com"],
+                Subject: "Undelivered Mail"
+            }?);
+            let enc_mail = mail.into_encodable_mail(ctx).wait()?;
+            let content_type = enc_mail
+                .headers()
+                .get_single(headers::ContentType)
+                .unwrap()?;
+            assert_eq!(content_type.body().full_type(), "multipart/report");
+            assert_eq!(
+                content_type.get_param("report-type").unwrap().to_content(),
+                "delivery-status"
+            );
+            assert!(content_type.get_param("boundary").is_some());
+
+            let encoded = String::from_utf8(enc_mail.encode_into_bytes(MailType::Ascii)?).unwrap();
+            assert!(encoded.contains("report-type=delivery-status"));
+            assert!(encoded.contains("Final-Recipient: rfc822; user@example.com\r\n"));
+        });
+    }
+    mod validate {
+        use vec1::Vec1;
+
+        use super::super::*;
+        use crate::default_impl::test_context;
+
+        fn parts(ctx: &impl Context) -> MailParts {
+            MailParts::alternatives(Vec1::new((
+                MediaType::parse("text/html; charset=utf-8").unwrap(),
+                Resource::html("<img src=\"cid:logo\">", ctx),
+            )))
+        }
+
+        #[test]
+        fn accepts_valid_parts() {
+            let ctx = test_context();
+            let mut parts = parts(&ctx);
+            parts
+                .inline_embeddings
+                .push(Resource::plain_text("logo", &ctx));
+            parts.attachments.push(Resource::from_path("./report.pdf"));
+            assert_ok!(parts.validate());
+        }
+
+        #[test]
+        fn accepts_inline_embeddings_which_are_not_loaded_yet() {
+            let ctx = test_context();
+            let mut parts = parts(&ctx);
+            parts.alternative_bodies[0]
+                .inline_embeddings
+                .push(Resource::from_path("./logo.png"));
+            parts
+                .inline_embeddings
+                .push(Resource::message(Mail::plain_text("hy", &ctx)));
+            assert_ok!(parts.validate());
+        }
+
+        #[test]
+        fn embedding_without_content_id_error_names_the_position() {
+            let err =
+                CompositionError::EmbeddingWithoutContentId(PartPosition::BodyInlineEmbedding {
+                    body: 0,
+                    index: 0,
+                });
+            assert_eq!(
+                err.to_string(),
+                "inline embedding 0 of alternative body 0 has no content id"
+            );
+        }
+
+        #[test]
+        fn rejects_multipart_body_parts() {
+            let ctx = test_context();
+            let mut parts = parts(&ctx);
+            parts.attachments.push(Resource::plain_text("a", &ctx));
+            parts.attachments.push(
+                Resource::from_path("./mail.eml")
+                    .with_media_type(MediaType::parse("multipart/mixed").unwrap()),
+            );
+
+            let err = assert_err!(parts.validate());
+            assert_eq!(
+                err.to_string(),
+                "attachment 1 has multipart media type multipart/mixed"
+            );
+        }
+    }
+
+    mod attachments {
+        use chrono::{TimeZone, Utc};
+        use futures::Future;
+        use vec1::Vec1;
+
+        use headers::{header_components::DateTime, headers::_From};
+
+        use super::super::*;
+        use crate::{default_impl::test_context, resource::Data};
+
+        test!(file_meta_is_used_for_content_disposition, {
+            let ctx = test_context();
+            let file_meta = FileMeta::builder()
+                .file_name("report 2013.txt")
+                .creation_date(Utc.with_ymd_and_hms(2013, 8, 6, 7, 11, 0).unwrap())
+                .modification_date(Utc.with_ymd_and_hms(2013, 8, 7, 9, 30, 0).unwrap())
+                .size(3)
+                .build();
+            let attachment = Data::new(
+                &b"abc"[..],
+                Metadata {
+                    file_meta,
+                    media_type: MediaType::parse("text/plain; charset=us-ascii")?,
+                    content_id: ctx.generate_content_id(),
+                },
+            );
+
+            let mut parts = MailParts::alternatives(Vec1::new((
+                MediaType::parse("text/plain; charset=utf-8")?,
+                Resource::plain_text("see attachment", &ctx),
+            )));
+            parts.attachments.push(Resource::Data(attachment));
+            let mut mail = parts.compose();
+            mail.insert_headers(headers! { _From: ["alice@example.test"] }?);
+
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+            // the Content-Disposition header is long enough to be folded
+            let unfolded = String::from_utf8(encoded).unwrap().replace("\r\n ", " ");
+
+            let creation_date =
+                DateTime::new(Utc.with_ymd_and_hms(2013, 8, 6, 7, 11, 0).unwrap()).to_rfc2822();
+            let modification_date =
+                DateTime::new(Utc.with_ymd_and_hms(2013, 8, 7, 9, 30, 0).unwrap()).to_rfc2822();
+            let expected = format!(
+                concat!(
+                    "Content-Disposition: attachment; filename=\"report 2013.txt\"",
+                    "; creation-date=\"{}\"; modification-date=\"{}\"; size=3\r\n"
+                ),
+                creation_date, modification_date
+            );
+            assert!(unfolded.contains(&expected), "{}", unfolded);
+        });
+    }
+}
@@ -8,10 +8,11 @@ use futures::{
 };
 use utils::SendBoxFuture;
 
-use headers::header_components::{ContentId, MessageId};
+use headers::header_components::{ContentId, DateTime, Mailbox, MailboxList, MessageId};
 
 use crate::{
-    error::ResourceLoadingError,
+    error::{ResourceLoadingError, ResourceLoadingErrorKind},
+    mime::{create_structured_random_boundary, find_boundary_absent_from},
     resource::{Data, EncData, Resource, Source},
 };
 
@@ -112,6 +113,11 @@ pub trait Context: Debug + Clone + Send + Sync + 'static {
     /// 2. transfer encodes the data "offloaded" if `Resource::Data` is found
     /// 3. just returns the encoded data if `Resource::EncData` is found
     ///
+    /// A `Resource::Message` is never passed to this function when turning
+    /// a mail into an encodable mail, as the embedded mail is turned into an
+    /// encodable mail instead. The default impl fails with
+    /// `ResourceLoadingErrorKind::LoadingFailed` if one is passed in.
+    ///
     /// A more advances implementation could for example integrate
     /// a LRU cache.
     ///
@@ -158,6 +164,116 @@ pub trait Context: Debug + Clone + Send + Sync + 'static {
     /// in terms of calling `generate_message_id`.
     fn generate_content_id(&self) -> ContentId;
 
+    /// generate `n` unique content ids at once
+    ///
+    /// This is meant for mails with many embeddings/attachments,
+    /// implementations which need to synchronize the id generation
+    /// (e.g. through a lock around a rng or counter) can override
+    /// this to only synchronize once for the whole batch.
+    ///
+    /// The default implementation calls `generate_content_id` `n` times.
+    fn generate_content_ids(&self, n: usize) -> Vec<ContentId> {
+        (0..n).map(|_| self.generate_content_id()).collect()
+    }
+
+    /// generate a multipart boundary
+    ///
+    /// This is used to generate the candidates for `generate_boundary_absent_from`,
+    /// so it should return a different boundary each time it's called, at least
+    /// if a boundary might collide with the content of a body. If it keeps
+    /// returning colliding boundaries random boundaries are used after a few
+    /// attempts. Overriding it allows e.g. generating predictable boundaries
+    /// in tests.
+    ///
+    /// The default implementation uses `mime::create_structured_random_boundary`.
+    fn generate_boundary(&self) -> String {
+        create_structured_random_boundary(0)
+    }
+
+    /// generate a multipart boundary which is not contained in any of the given bodies
+    ///
+    /// While randomly generated boundaries are astronomically unlikely to
+    /// collide with the content of the bodies, this guarantees it by generating
+    /// new boundaries until one is found which is not a substring of any body.
+    /// This matters for adversarial or pre-encoded content.
+    ///
+    /// The default implementation uses `generate_boundary` to generate the candidates.
+    fn generate_boundary_absent_from(&self, bodies: &[&[u8]]) -> String {
+        find_boundary_absent_from(bodies, |_attempt| self.generate_boundary())
+    }
+
+    /// returns the `From` header body used for mails without a `From` header
+    ///
+    /// When a mail is turned into an encodable mail and has no `From`
+    /// header a `From` header with the returned mailboxes is inserted
+    /// (before checking that the mail has a `From` header). Mails which
+    /// have a `From` header are not affected.
+    ///
+    /// The default implementation returns `None`.
+    fn default_from(&self) -> Option<MailboxList> {
+        None
+    }
+
+    /// returns the `Sender` header body used for mails without a `Sender` header
+    ///
+    /// This works the same way as `default_from` but for the `Sender` header.
+    ///
+    /// The default implementation returns `None`.
+    fn default_sender(&self) -> Option<Mailbox> {
+        None
+    }
+
+    /// returns true if a `Sender` header should be generated for mails with multiple `From` mailboxes
+    ///
+    /// A mail with more than one mailbox in its `From` header has to have a
+    /// `Sender` header. If this returns true and such a mail has no `Sender`
+    /// header (even after `default_sender` was applied) the first mailbox of
+    /// the `From` header is inserted as `Sender` instead of failing the
+    /// validation.
+    ///
+    /// The default implementation returns `false`.
+    fn auto_sender(&self) -> bool {
+        false
+    }
+
+    /// returns true if mails without a `Message-Id` header should be rejected
+    ///
+    /// Normally a `Message-Id` header is generated using `generate_message_id`
+    /// for mails without one when they are turned into an encodable mail. If
+    /// this returns true turning such a mail into an encodable mail fails with
+    /// `OtherValidationError::NoMessageId` instead, e.g. for a strict relay.
+    ///
+    /// The default implementation returns `false`.
+    fn require_message_id(&self) -> bool {
+        false
+    }
+
+    /// returns true if `auto-replied` mails with `List-*` headers should be rejected
+    ///
+    /// A mail with `Auto-Submitted: auto-replied` is a automatic response to
+    /// another mail, as such it normally should not have any `List-*` headers
+    /// (rfc2369), having them is a common misconfiguration of auto responders
+    /// which can lead to mail loops. Such mails always produce a warning when
+    /// they are turned into an encodable mail, if this returns true turning
+    /// them into an encodable mail fails with `OtherValidationError::AutoRepliedListMail`
+    /// instead.
+    ///
+    /// The default implementation returns `false`.
+    fn reject_auto_replied_list_mail(&self) -> bool {
+        false
+    }
+
+    /// returns the current time
+    ///
+    /// This is used for the `Date` header inserted into mails which have none
+    /// when they are turned into an encodable mail. Overriding it allows using
+    /// a trusted time source or a fixed time in tests.
+    ///
+    /// The default implementation returns the current system time.
+    fn now(&self) -> DateTime {
+        DateTime::now()
+    }
+
     //TODO[futures/v>=0.2]: integrate this with Context
     /// offloads the execution of the future `fut` to somewhere else e.g. a cpu pool
     fn offload<F>(&self, fut: F) -> SendBoxFuture<F::Item, F::Error>
@@ -194,18 +310,21 @@ pub fn default_impl_for_load_transfer_encoded_resource(
             let fut = ctx
                 .load_resource(&source)
                 .and_then(move |me_data| match me_data {
-                    MaybeEncData::Data(data) => Either::A(
-                        ctx2.offload_fn(move || Ok(data.transfer_encode(Default::default()))),
-                    ),
+                    MaybeEncData::Data(data) => Either::A(ctx2.offload_fn(move || {
+                        Ok(data.transfer_encode(data.transfer_encoding_hint()))
+                    })),
                     MaybeEncData::EncData(enc_data) => Either::B(future::ok(enc_data)),
                 });
             Box::new(fut)
         }
         Resource::Data(data) => {
             let data = data.clone();
-            ctx.offload_fn(move || Ok(data.transfer_encode(Default::default())))
+            ctx.offload_fn(move || Ok(data.transfer_encode(data.transfer_encoding_hint())))
         }
         Resource::EncData(enc_data) => Box::new(future::ok(enc_data.clone())),
+        Resource::Message(..) => {
+            Box::new(future::err(ResourceLoadingErrorKind::LoadingFailed.into()))
+        }
     }
 }
 
@@ -264,6 +383,13 @@ pub trait MailIdGenComponent: Debug + Send + Sync + 'static {
 
     /// Calls to `Context::generate_content_id` will be forwarded to this method.
     fn generate_content_id(&self) -> ContentId;
+
+    /// Calls to `Context::generate_content_ids` will be forwarded to this method.
+    ///
+    /// The default implementation calls `generate_content_id` `n` times.
+    fn generate_content_ids(&self, n: usize) -> Vec<ContentId> {
+        (0..n).map(|_| self.generate_content_id()).collect()
+    }
 }
 
 /// The `CompositeContext` is the simplest way to get an `Context` implementation.
@@ -286,6 +412,11 @@ pub struct CompositeContext<
     M: MailIdGenComponent,
 > {
     inner: Arc<(R, O, M)>,
+    default_from: Option<Arc<MailboxList>>,
+    default_sender: Option<Arc<Mailbox>>,
+    auto_sender: bool,
+    require_message_id: bool,
+    reject_auto_replied_list_mail: bool,
 }
 
 impl<R, O, M> Clone for CompositeContext<R, O, M>
@@ -297,6 +428,11 @@ where
     fn clone(&self) -> Self {
         CompositeContext {
             inner: self.inner.clone(),
+            default_from: self.default_from.clone(),
+            default_sender: self.default_sender.clone(),
+            auto_sender: self.auto_sender,
+            require_message_id: self.require_message_id,
+            reject_auto_replied_list_mail: self.reject_auto_replied_list_mail,
         }
     }
 }
@@ -311,9 +447,54 @@ where
     pub fn new(resource_loader: R, offloader: O, message_id_gen: M) -> Self {
         CompositeContext {
             inner: Arc::new((resource_loader, offloader, message_id_gen)),
+            default_from: None,
+            default_sender: None,
+            auto_sender: false,
+            require_message_id: false,
+            reject_auto_replied_list_mail: false,
         }
     }
 
+    /// Sets the `From` header body used for mails without a `From` header and returns self.
+    ///
+    /// See `Context::default_from` for more details.
+    pub fn with_default_from(mut self, from: MailboxList) -> Self {
+        self.default_from = Some(Arc::new(from));
+        self
+    }
+
+    /// Sets the `Sender` header body used for mails without a `Sender` header and returns self.
+    ///
+    /// See `Context::default_sender` for more details.
+    pub fn with_default_sender(mut self, sender: Mailbox) -> Self {
+        self.default_sender = Some(Arc::new(sender));
+        self
+    }
+
+    /// Enables or disables the generation of `Sender` headers and returns self.
+    ///
+    /// See `Context::auto_sender` for more details.
+    pub fn with_auto_sender(mut self, auto_sender: bool) -> Self {
+        self.auto_sender = auto_sender;
+        self
+    }
+
+    /// Enables or disables rejecting mails without `Message-Id` header and returns self.
+    ///
+    /// See `Context::require_message_id` for more details.
+    pub fn with_require_message_id(mut self, require_message_id: bool) -> Self {
+        self.require_message_id = require_message_id;
+        self
+    }
+
+    /// Enables or disables rejecting `auto-replied` mails with `List-*` headers and returns self.
+    ///
+    /// See `Context::reject_auto_replied_list_mail` for more details.
+    pub fn with_reject_auto_replied_list_mail(mut self, reject: bool) -> Self {
+        self.reject_auto_replied_list_mail = reject;
+        self
+    }
+
     /// Returns a reference to the resource loader component.
     pub fn resource_loader(&self) -> &R {
         &self.inner.0
@@ -361,9 +542,35 @@ where
         self.id_gen().generate_content_id()
     }
 
+    fn generate_content_ids(&self, n: usize) -> Vec<ContentId> {
+        self.id_gen().generate_content_ids(n)
+    }
+
     fn generate_message_id(&self) -> MessageId {
         self.id_gen().generate_message_id()
     }
+
+    fn default_from(&self) -> Option<MailboxList> {
+        self.default_from.as_ref().map(|from| (**from).clone())
+    }
+
+    fn default_sender(&self) -> Option<Mailbox> {
+        self.default_sender
+            .as_ref()
+            .map(|sender| (**sender).clone())
+    }
+
+    fn auto_sender(&self) -> bool {
+        self.auto_sender
+    }
+
+    fn require_message_id(&self) -> bool {
+        self.require_message_id
+    }
+
+    fn reject_auto_replied_list_mail(&self) -> bool {
+        self.reject_auto_replied_list_mail
+    }
 }
 
 /// Allows using a part of an context as an component.
@@ -378,6 +585,10 @@ where
     fn generate_content_id(&self) -> ContentId {
         <Self as Context>::generate_content_id(self)
     }
+
+    fn generate_content_ids(&self, n: usize) -> Vec<ContentId> {
+        <Self as Context>::generate_content_ids(self, n)
+    }
 }
 
 /// Allows using a part of an context as an component.
@@ -4,13 +4,14 @@ use std::{
     io::{self, Read},
     marker::PhantomData,
     path::{Path, PathBuf},
+    str,
 };
 
 use checked_command::CheckedCommand;
 use failure::Fail;
 use futures::IntoFuture;
 
-use headers::header_components::{FileMeta, MediaType};
+use headers::header_components::{ContentId, FileMeta, MediaType};
 
 use crate::{
     context::{Context, MaybeEncData, ResourceLoaderComponent},
@@ -33,6 +34,7 @@ use crate::{
 pub struct FsResourceLoader<SchemeValidation: ConstSwitch = Enabled> {
     root: PathBuf,
     scheme: &'static str,
+    refine_media_type: bool,
     _marker: PhantomData<SchemeValidation>,
 }
 
@@ -52,10 +54,27 @@ where
         FsResourceLoader {
             root: root.into(),
             scheme,
+            refine_media_type: false,
             _marker: PhantomData,
         }
     }
 
+    /// Enables/disables refining the media type of loaded resources.
+    ///
+    /// If enabled resources which are loaded with a `application/octet-stream`
+    /// media type will get a more specific media type if the leading bytes
+    /// indicate one (see `Data::with_refined_media_type`).
+    ///
+    /// This is disabled by default.
+    pub fn with_media_type_refinement(mut self, enable: bool) -> Self {
+        self.refine_media_type = enable;
+        self
+    }
+
+    pub fn does_refine_media_type(&self) -> bool {
+        self.refine_media_type
+    }
+
     pub fn with_cwd_root() -> Result<Self, io::Error> {
         let cwd = env::current_dir()?;
         Ok(Self::new(cwd))
@@ -98,9 +117,17 @@ where
         let use_media_type = source.use_media_type.clone();
         let use_file_name = source.use_file_name.clone();
 
-        load_data(path, use_media_type, use_file_name, ctx, |data| {
+        let refine_media_type = self.refine_media_type;
+
+        load_data(path, use_media_type, use_file_name, ctx, move |data| {
+            let data = if refine_media_type {
+                data.with_refined_media_type()
+            } else {
+                data
+            };
+
             Ok(MaybeEncData::EncData(
-                data.transfer_encode(Default::default()),
+                data.transfer_encode(data.transfer_encoding_hint()),
             ))
         })
     }
@@ -124,43 +151,74 @@ where
 {
     let content_id = ctx.generate_content_id();
     ctx.offload_fn(move || {
-        let mut fd = File::open(&path).map_err(|err| {
-            if err.kind() == io::ErrorKind::NotFound {
-                err.context(ResourceLoadingErrorKind::NotFound)
-            } else {
-                err.context(ResourceLoadingErrorKind::LoadingFailed)
-            }
-        })?;
-
-        let mut file_meta = file_meta_from_metadata(fd.metadata()?);
+        let data = read_data(&path, use_media_type, use_file_name, content_id)
+            .map_err(|err| err.with_path_or_else(|| Some(path.clone())))?;
+        post_process(data)
+    })
+}
 
-        if let Some(name) = use_file_name {
-            file_meta.file_name = Some(name)
-        } else {
-            file_meta.file_name = path
-                .file_name()
-                .map(|name| name.to_string_lossy().into_owned())
-        }
+fn read_data(
+    path: &Path,
+    use_media_type: UseMediaType,
+    use_file_name: Option<String>,
+    content_id: ContentId,
+) -> Result<Data, ResourceLoadingError> {
+    let mut fd = File::open(path)?;
 
-        let mut buffer = Vec::new();
-        fd.read_to_end(&mut buffer)?;
+    let mut file_meta = file_meta_from_metadata(fd.metadata()?);
 
-        let media_type = match use_media_type {
-            UseMediaType::Auto => sniff_media_type(&path)?,
-            UseMediaType::Default(media_type) => media_type,
-        };
+    if let Some(name) = use_file_name {
+        file_meta.file_name = Some(name)
+    } else {
+        file_meta.file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }
 
-        let data = Data::new(
-            buffer,
-            Metadata {
-                file_meta,
-                content_id,
-                media_type,
+    let mut buffer = Vec::new();
+    fd.read_to_end(&mut buffer)?;
+
+    let media_type = match use_media_type {
+        UseMediaType::Auto => match media_type_from_extension(path) {
+            Some(ref media_type) if media_type.type_() == "text" => {
+                // the extension doesn't tell us the charset
+                if str::from_utf8(&buffer).is_ok() {
+                    let mut media_type = media_type.clone();
+                    media_type.set_param("charset", "utf-8");
+                    media_type
+                } else {
+                    sniff_media_type(path)?
+                }
+            }
+            Some(media_type) => media_type,
+            None => match MediaType::guess_from_bytes(&buffer) {
+                Some(media_type) => media_type,
+                None => sniff_media_type(path)?,
             },
-        );
+        },
+        UseMediaType::Default(media_type) => media_type,
+    };
 
-        post_process(data)
-    })
+    let data = Data::new(
+        buffer,
+        Metadata {
+            file_meta,
+            content_id,
+            media_type,
+        },
+    );
+
+    Ok(data)
+}
+
+/// Guesses the media type of a file based on its file extension.
+///
+/// Only a small set of common extensions is known, for all other
+/// files `None` is returned.
+fn media_type_from_extension(path: &Path) -> Option<MediaType> {
+    path.extension()?
+        .to_str()
+        .and_then(MediaType::guess_from_extension)
 }
 
 fn sniff_media_type(path: impl AsRef<Path>) -> Result<MediaType, ResourceLoadingError> {
@@ -207,14 +265,14 @@ fn get_file_size(meta: &fs::Metadata) -> Option<u64> {
     None
 }
 
-fn path_from_tail(path_iri: &IRI) -> &Path {
-    let tail = path_iri.tail();
+fn path_from_tail(path_iri: &IRI) -> PathBuf {
+    let tail = path_iri.decoded_tail();
     let path = if tail.starts_with("///") {
         &tail[2..]
     } else {
         &tail
     };
-    Path::new(path)
+    PathBuf::from(path)
 }
 
 #[cfg(test)]
@@ -232,4 +290,129 @@ mod tests {
             assert_eq!(res.as_str_repr(), "text/plain; charset=us-ascii");
         }
     }
+
+    mod media_type_from_extension {
+        use super::super::*;
+
+        #[test]
+        fn knows_common_extensions() {
+            let media_type = media_type_from_extension(Path::new("a/b/logo.PNG")).unwrap();
+            assert_eq!(media_type.as_str_repr(), "image/png");
+        }
+
+        #[test]
+        fn returns_none_for_unknown_or_missing_extensions() {
+            assert!(media_type_from_extension(Path::new("Cargo.toml")).is_none());
+            assert!(media_type_from_extension(Path::new("README")).is_none());
+        }
+    }
+
+    mod load_data {
+        use super::super::*;
+        use futures::Future;
+
+        use crate::{default_impl::test_context, resource::Resource};
+
+        #[test]
+        fn not_found_error_contains_path() {
+            let path = PathBuf::from("./this/path/does/not/exist.txt");
+            let err = load_data(
+                path.clone(),
+                UseMediaType::Default(MediaType::parse("text/plain").unwrap()),
+                None,
+                &test_context(),
+                Ok,
+            )
+            .wait()
+            .unwrap_err();
+
+            assert_eq!(err.kind(), ResourceLoadingErrorKind::NotFound);
+            assert_eq!(err.path(), Some(&*path));
+            assert_eq!(
+                err.to_string(),
+                "resource not found (path: ./this/path/does/not/exist.txt)"
+            );
+        }
+
+        #[test]
+        fn permission_denied_is_reported_as_such() {
+            let err = ResourceLoadingError::from(io::Error::from(io::ErrorKind::PermissionDenied));
+            assert_eq!(err.kind(), ResourceLoadingErrorKind::PermissionDenied);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn unreadable_file_fails_with_permission_denied() {
+            use std::os::unix::fs::PermissionsExt;
+
+            let path = env::temp_dir().join("mail_core_permission_denied_test.txt");
+            fs::write(&path, "hy there").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o000)).unwrap();
+
+            // e.g. root can read the file anyway
+            let is_readable = File::open(&path).is_ok();
+            let res = load_data(
+                path.clone(),
+                UseMediaType::Default(MediaType::parse("text/plain").unwrap()),
+                None,
+                &test_context(),
+                Ok,
+            )
+            .wait();
+            fs::remove_file(&path).unwrap();
+
+            if !is_readable {
+                let err = res.unwrap_err();
+                assert_eq!(err.kind(), ResourceLoadingErrorKind::PermissionDenied);
+                assert_eq!(err.path(), Some(&*path));
+            }
+        }
+
+        #[test]
+        fn source_from_path_is_loaded_lazily() {
+            let path = env::temp_dir().join("mail_core_source_from_path_test.txt");
+            let source = Source::from_path(&path);
+            assert_eq!(path_from_tail(&source.iri), path);
+
+            fs::write(&path, "hy there").unwrap();
+            let ctx = test_context();
+            let res =
+                Context::load_transfer_encoded_resource(&ctx, &Resource::Source(source)).wait();
+            fs::remove_file(&path).unwrap();
+
+            let data = res.unwrap();
+            assert_eq!(data.media_type().as_str_repr(), "text/plain; charset=utf-8");
+            assert_eq!(
+                data.file_meta().file_name,
+                Some("mail_core_source_from_path_test.txt".to_owned())
+            );
+        }
+
+        #[test]
+        fn percent_encoded_paths_are_decoded() {
+            let path = env::temp_dir().join("mail core 100% grüße.txt");
+            let iri = IRI::with_scheme_and_tail("path", &path.to_string_lossy()).unwrap();
+            assert_eq!(path_from_tail(&iri), path);
+            assert_eq!(path_from_tail(&Source::from_path(&path).iri), path);
+
+            fs::write(&path, "hy there").unwrap();
+            let ctx = test_context();
+            let res = Context::load_transfer_encoded_resource(
+                &ctx,
+                &Resource::Source(Source {
+                    iri,
+                    use_media_type: Default::default(),
+                    use_file_name: None,
+                }),
+            )
+            .wait();
+            fs::remove_file(&path).unwrap();
+
+            let data = res.unwrap();
+            assert_eq!(
+                data.file_meta().file_name,
+                Some("mail core 100% grüße.txt".to_owned())
+            );
+        }
+    }
 }
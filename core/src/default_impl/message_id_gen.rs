@@ -15,6 +15,12 @@ fn counter_next() -> usize {
     MAIL_COUNTER.fetch_add(1, Ordering::AcqRel)
 }
 
+/// reserves `n` consecutive counter values with a single atomic operation
+fn counter_next_n(n: usize) -> impl Iterator<Item = usize> {
+    let start = MAIL_COUNTER.fetch_add(n, Ordering::AcqRel);
+    (0..n).map(move |offset| start.wrapping_add(offset))
+}
+
 fn anonymize_through_random_hash(num: usize) -> u64 {
     let rnum = rand::random::<u32>();
     let mut hasher = DefaultHasher::new();
@@ -84,20 +90,32 @@ impl HashedIdGen {
     }
 }
 
-impl MailIdGenComponent for HashedIdGen {
-    fn generate_message_id(&self) -> MessageId {
+impl HashedIdGen {
+    fn id_from_unique_number(&self, number: u64) -> MessageId {
         let msg_id = format!(
             "{unique}.{hash:x}@{domain}",
             unique = self.part_unique_in_domain,
-            hash = gen_next_program_unique_number(),
+            hash = number,
             domain = self.domain
         );
         MessageId::from_unchecked(msg_id)
     }
+}
+
+impl MailIdGenComponent for HashedIdGen {
+    fn generate_message_id(&self) -> MessageId {
+        self.id_from_unique_number(gen_next_program_unique_number())
+    }
 
     fn generate_content_id(&self) -> ContentId {
         self.generate_message_id()
     }
+
+    fn generate_content_ids(&self, n: usize) -> Vec<ContentId> {
+        counter_next_n(n)
+            .map(|num| self.id_from_unique_number(anonymize_through_random_hash(num)))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -148,5 +166,22 @@ mod test {
                 }
             }
         }
+
+        mod generate_content_ids {
+            use super::*;
+
+            #[test]
+            fn should_return_n_unique_ids() {
+                let id_gen = setup();
+                let mut cids = HashSet::new();
+                cids.insert(id_gen.generate_content_id());
+                let batch = id_gen.generate_content_ids(20);
+                assert_eq!(batch.len(), 20);
+                for cid in batch {
+                    assert!(cids.insert(cid))
+                }
+                assert!(cids.insert(id_gen.generate_content_id()))
+            }
+        }
     }
 }
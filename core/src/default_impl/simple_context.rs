@@ -10,7 +10,7 @@
 //! ```
 //! # extern crate mail_core as mail;
 //! # extern crate mail_headers as headers;
-//! # use headers::header_components::Domain;
+//! # use headers::{error::ComponentCreationError, header_components::Domain};
 //! # // It's re-exported in the facade under `default_impl`.
 //! # use std::str::FromStr;
 //! use mail::default_impl::simple_context;
@@ -31,7 +31,7 @@ use std::io;
 use futures_cpupool::{Builder, CpuPool};
 use soft_ascii_string::SoftAsciiString;
 
-use headers::header_components::Domain;
+use headers::{error::ComponentCreationError, header_components::Domain};
 use internals::error::EncodingError;
 
 use context::CompositeContext;
@@ -49,6 +49,48 @@ pub enum ContextSetupError {
     /// Punny encoding a non us-ascii domain failed.
     #[fail(display = "{}", _0)]
     PunyCodingDomain(EncodingError),
+
+    /// Parsing the domain failed.
+    #[fail(display = "{}", _0)]
+    InvalidDomain(ComponentCreationError),
+
+    /// The unique part contained non us-ascii chars.
+    #[fail(display = "unique part is not us-ascii: {:?}", _0)]
+    NonAsciiUniquePart(String),
+
+    /// The `PoolConfig` requested a thread pool without any threads.
+    #[fail(display = "thread pool needs at least one thread")]
+    NoPoolThreads,
+}
+
+/// Configuration of the `CpuPool` used by the context.
+///
+/// Fields which are `None` use the default settings of
+/// `futures_cpupool::Builder`, i.e. one thread per cpu and
+/// no name prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// The number of threads in the pool, must not be zero.
+    pub threads: Option<usize>,
+
+    /// The prefix used for the names of the threads in the pool.
+    pub name_prefix: Option<String>,
+}
+
+impl PoolConfig {
+    fn create_pool(&self) -> Result<CpuPool, ContextSetupError> {
+        let mut builder = Builder::new();
+        if let Some(threads) = self.threads {
+            if threads == 0 {
+                return Err(ContextSetupError::NoPoolThreads);
+            }
+            builder.pool_size(threads);
+        }
+        if let Some(ref name_prefix) = self.name_prefix {
+            builder.name_prefix(name_prefix.as_str());
+        }
+        Ok(builder.create())
+    }
 }
 
 /// Type Alias for a the type returned by `simple_context::new`.
@@ -67,13 +109,135 @@ pub type Context = CompositeContext<FsResourceLoader, CpuPool, HashedIdGen>;
 /// under any circumstances (expect if they use different domains, but then you
 /// also should only use domain you actually own).
 pub fn new(domain: Domain, unique_part: SoftAsciiString) -> Result<Context, ContextSetupError> {
+    with_pool_config(domain, unique_part, PoolConfig::default())
+}
+
+/// create a new CompositeContext<FsResourceLoader, CpuPool, HashedIdGen> with a configured `CpuPool`
+///
+/// This is like `new` but the number of threads and the thread name prefix of
+/// the `CpuPool` are taken from the given `PoolConfig`, e.g. to not use one
+/// thread per cpu in a constrained environment.
+///
+/// # Error
+///
+/// Besides the errors `new` can return this fails with
+/// `ContextSetupError::NoPoolThreads` if the config requests zero threads.
+pub fn with_pool_config(
+    domain: Domain,
+    unique_part: SoftAsciiString,
+    pool_config: PoolConfig,
+) -> Result<Context, ContextSetupError> {
     let resource_loader =
         FsResourceLoader::with_cwd_root().map_err(ContextSetupError::ReadingEnv)?;
 
-    let cpu_pool = Builder::new().create();
+    let cpu_pool = pool_config.create_pool()?;
 
     let id_gen =
         HashedIdGen::new(domain, unique_part).map_err(ContextSetupError::PunyCodingDomain)?;
 
     Ok(CompositeContext::new(resource_loader, cpu_pool, id_gen))
 }
+
+/// create a new CompositeContext<FsResourceLoader, CpuPool, HashedIdGen> from `&str`'s
+///
+/// This is like `new` but parses the `domain` and checks that the
+/// `unique_part` is us-ascii, so that no `Domain` or `SoftAsciiString`
+/// needs to be constructed by the caller.
+///
+/// # Error
+///
+/// Besides the errors `new` can return this fails with
+/// `ContextSetupError::InvalidDomain` if the domain can not be parsed
+/// and with `ContextSetupError::NonAsciiUniquePart` if the unique part
+/// is not us-ascii.
+pub fn from_str_parts(domain: &str, unique_part: &str) -> Result<Context, ContextSetupError> {
+    let domain = domain
+        .parse::<Domain>()
+        .map_err(ContextSetupError::InvalidDomain)?;
+
+    let unique_part = SoftAsciiString::from_string(unique_part)
+        .map_err(|_| ContextSetupError::NonAsciiUniquePart(unique_part.to_owned()))?;
+
+    new(domain, unique_part)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_str_parts_accepts_ascii_unique_part() {
+        let ctx = from_str_parts("example.com", "xm3r2u");
+        assert!(ctx.is_ok());
+    }
+
+    #[test]
+    fn from_str_parts_rejects_non_ascii_unique_part() {
+        let err = from_str_parts("example.com", "xm3r2ü").unwrap_err();
+        match err {
+            ContextSetupError::NonAsciiUniquePart(unique_part) => {
+                assert_eq!(unique_part, "xm3r2ü")
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn generates_distinct_message_ids_in_the_domain() {
+        use context::Context as _;
+
+        let ctx = from_str_parts("example.com", "xm3r2u").unwrap();
+        let first = ctx.generate_message_id();
+        let second = ctx.generate_message_id();
+
+        assert_ne!(first, second);
+        for id in &[first, second] {
+            assert!(id.as_str().starts_with("xm3r2u."));
+            assert!(id.as_str().ends_with("@example.com"));
+        }
+    }
+
+    #[test]
+    fn with_pool_config_uses_name_prefix() {
+        use std::thread;
+
+        use futures::Future;
+
+        use context::Context as _;
+
+        let config = PoolConfig {
+            threads: Some(1),
+            name_prefix: Some("mail-ctx-".to_owned()),
+        };
+        let ctx = with_pool_config(
+            Domain::from_unchecked("example.com".to_owned()),
+            SoftAsciiString::from_unchecked("xm3r2u"),
+            config,
+        )
+        .unwrap();
+
+        let name = ctx
+            .offload_fn(|| Ok::<_, ()>(thread::current().name().map(ToOwned::to_owned)))
+            .wait()
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("mail-ctx-0"));
+    }
+
+    #[test]
+    fn with_pool_config_rejects_zero_threads() {
+        let config = PoolConfig {
+            threads: Some(0),
+            ..Default::default()
+        };
+        let err = with_pool_config(
+            Domain::from_unchecked("example.com".to_owned()),
+            SoftAsciiString::from_unchecked("xm3r2u"),
+            config,
+        )
+        .unwrap_err();
+        match err {
+            ContextSetupError::NoPoolThreads => {}
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! This module provides a way to create `message/delivery-status` bodies (rfc3464).
+//!
+//! A `message/delivery-status` body is the machine readable part of a
+//! delivery status notification (DSN). It consists of a group of
+//! per-message fields followed by one group of per-recipient fields
+//! for each recipient the notification is about.
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+use vec1::Vec1;
+
+use headers::header_components::{
+    DateTime, DsnAction, DsnStatus, FileMeta, MediaType, TransferEncoding, TypedAddress,
+};
+use internals::{
+    encoder::{EncodableInHeader, EncodingBuffer},
+    error::EncodingError,
+    MailType,
+};
+
+use crate::{
+    context::Context,
+    resource::{EncData, Metadata, Resource},
+};
+
+/// The content of a `message/delivery-status` body.
+#[derive(Debug, Clone)]
+pub struct DeliveryStatus {
+    /// The MTA which attempted the delivery, normally a `dns` typed address.
+    pub reporting_mta: TypedAddress,
+
+    /// The date at which the message arrived at the reporting MTA.
+    pub arrival_date: Option<DateTime>,
+
+    /// The per-recipient fields, a delivery status is always about at last one recipient.
+    pub recipients: Vec1<RecipientStatus>,
+}
+
+/// The per-recipient fields of a delivery status.
+#[derive(Debug, Clone)]
+pub struct RecipientStatus {
+    /// The recipient as originally specified by the sender (`Original-Recipient`).
+    pub original_recipient: Option<TypedAddress>,
+
+    /// The recipient for which the status is reported (`Final-Recipient`).
+    pub final_recipient: TypedAddress,
+
+    /// The action performed by the reporting MTA (`Action`).
+    pub action: DsnAction,
+
+    /// The status code of the delivery attempt (`Status`).
+    pub status: DsnStatus,
+}
+
+impl RecipientStatus {
+    /// Creates a new instance without an original recipient.
+    pub fn new(final_recipient: TypedAddress, action: DsnAction, status: DsnStatus) -> Self {
+        RecipientStatus {
+            original_recipient: None,
+            final_recipient,
+            action,
+            status,
+        }
+    }
+}
+
+impl DeliveryStatus {
+    /// Creates a new delivery status for given reporting MTA and recipient.
+    ///
+    /// More recipients can be pushed to `recipients`.
+    pub fn new(reporting_mta: TypedAddress, recipient: RecipientStatus) -> Self {
+        DeliveryStatus {
+            reporting_mta,
+            arrival_date: None,
+            recipients: Vec1::new(recipient),
+        }
+    }
+
+    /// Encodes the fields of this delivery status into a body.
+    ///
+    /// The per-message fields and each group of per-recipient fields
+    /// are separated by a blank line.
+    pub fn encode_body(&self) -> Result<Vec<u8>, EncodingError> {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+
+        write_field(&mut encoder, "Reporting-MTA", &self.reporting_mta)?;
+        if let Some(ref arrival_date) = self.arrival_date {
+            write_field(&mut encoder, "Arrival-Date", arrival_date)?;
+        }
+
+        for recipient in self.recipients.iter() {
+            encoder.write_blank_line()?;
+            if let Some(ref original_recipient) = recipient.original_recipient {
+                write_field(&mut encoder, "Original-Recipient", original_recipient)?;
+            }
+            write_field(&mut encoder, "Final-Recipient", &recipient.final_recipient)?;
+            write_field(&mut encoder, "Action", &recipient.action)?;
+            write_field(&mut encoder, "Status", &recipient.status)?;
+        }
+
+        Ok(encoder.into())
+    }
+
+    /// Creates a `message/delivery-status` `Resource` from this delivery status.
+    ///
+    /// The body always uses the `7bit` transfer encoding, as required for
+    /// `message/delivery-status` bodies by rfc3464 (the fields are encoded
+    /// as us-ascii lines, see `encode_body`). The `Context` is used to
+    /// generate a `ContentId`.
+    pub fn into_resource(self, ctx: &impl Context) -> Result<Resource, EncodingError> {
+        let body = self.encode_body()?;
+        let meta = Metadata {
+            file_meta: FileMeta::default(),
+            //UNWRAP_SAFE: it's a valid media type
+            media_type: MediaType::parse("message/delivery-status").unwrap(),
+            content_id: ctx.generate_content_id(),
+        };
+        let enc_data = EncData::new(body, meta, TransferEncoding::_7Bit);
+        Ok(Resource::EncData(enc_data))
+    }
+}
+
+fn write_field(
+    encoder: &mut EncodingBuffer,
+    name: &str,
+    value: &impl EncodableInHeader,
+) -> Result<(), EncodingError> {
+    encoder.write_header_line(|handle| {
+        handle.write_str(SoftAsciiStr::from_unchecked(name))?;
+        handle.write_char(SoftAsciiChar::from_unchecked(':'))?;
+        handle.write_fws();
+        value.encode(handle)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::default_impl::test_context;
+
+    test!(encodes_delivery_status_with_one_recipient, {
+        let recipient = RecipientStatus::new(
+            TypedAddress::rfc822("user@example.com")?,
+            DsnAction::Failed,
+            "5.1.1".parse()?,
+        );
+        let status = DeliveryStatus::new(TypedAddress::dns("mta.example.com")?, recipient);
+
+        let body = String::from_utf8(status.encode_body()?).unwrap();
+        assert_eq!(
+            body,
+            concat!(
+                "Reporting-MTA: dns; mta.example.com\r\n",
+                "\r\n",
+                "Final-Recipient: rfc822; user@example.com\r\n",
+                "Action: failed\r\n",
+                "Status: 5.1.1\r\n"
+            )
+        );
+    });
+
+    test!(creates_delivery_status_resource, {
+        let ctx = test_context();
+        let recipient = RecipientStatus::new(
+            TypedAddress::rfc822("user@example.com")?,
+            DsnAction::Failed,
+            "5.1.1".parse()?,
+        );
+        let status = DeliveryStatus::new(TypedAddress::dns("mta.example.com")?, recipient);
+
+        match status.into_resource(&ctx)? {
+            Resource::EncData(enc_data) => {
+                assert_eq!(
+                    enc_data.media_type().as_str_repr(),
+                    "message/delivery-status"
+                );
+                assert_eq!(enc_data.encoding(), TransferEncoding::_7Bit);
+            }
+            other => panic!("unexpected resource: {:?}", other),
+        }
+    });
+}
@@ -0,0 +1,449 @@
+//! Module implementing the signing of mails with DKIM (rfc6376).
+//!
+//! Only the `rsa-sha256` signing algorithm combined with the
+//! `relaxed/relaxed` canonicalization is supported.
+//!
+//! The signature is computed over the mail as it is encoded with a
+//! given `MailType`, so the signed mail has to be encoded with the same
+//! `MailType` (and must not be changed otherwise) for the signature
+//! to stay valid.
+use headers::{header_components::Domain, parse::RawHeader, HeaderName};
+use internals::{bind::base64, MailType};
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    rsa::Rsa,
+    sha::sha256,
+    sign::Signer,
+};
+
+use error::{DkimError, MailError};
+use EncodableMail;
+
+/// The name of the header containing the DKIM signature.
+pub const DKIM_SIGNATURE_HEADER_NAME: &str = "DKIM-Signature";
+
+/// Signs mails with DKIM using the `rsa-sha256` algorithm and `relaxed/relaxed` canonicalization.
+#[derive(Debug, Clone)]
+pub struct DkimSigner {
+    selector: String,
+    domain: Domain,
+    private_key: PKey<Private>,
+    signed_headers: Vec<HeaderName>,
+}
+
+impl DkimSigner {
+    /// Creates a new signer.
+    ///
+    /// The signature is made for the `selector` of the signing `domain`
+    /// (i.e. the public key is published at `<selector>._domainkey.<domain>`),
+    /// it covers the body and all headers in `signed_headers` which
+    /// appear in the signed mail.
+    ///
+    /// # Error
+    ///
+    /// Fails if the private key can not be used for signing.
+    pub fn new(
+        selector: impl Into<String>,
+        domain: Domain,
+        private_key: Rsa<Private>,
+        signed_headers: Vec<HeaderName>,
+    ) -> Result<Self, DkimError> {
+        let private_key = PKey::from_rsa(private_key).map_err(DkimError::InvalidKey)?;
+        Ok(DkimSigner {
+            selector: selector.into(),
+            domain,
+            private_key,
+            signed_headers,
+        })
+    }
+
+    /// Creates a new signer from a PEM encoded RSA private key.
+    ///
+    /// See `DkimSigner::new` for more details.
+    pub fn from_pem(
+        selector: impl Into<String>,
+        domain: Domain,
+        private_key_pem: &[u8],
+        signed_headers: Vec<HeaderName>,
+    ) -> Result<Self, DkimError> {
+        let private_key =
+            Rsa::private_key_from_pem(private_key_pem).map_err(DkimError::InvalidKey)?;
+        Self::new(selector, domain, private_key, signed_headers)
+    }
+
+    /// The selector of the signing domain used for the signature.
+    pub fn selector(&self) -> &str {
+        &self.selector
+    }
+
+    /// The signing domain.
+    pub fn domain(&self) -> &Domain {
+        &self.domain
+    }
+
+    /// The names of the headers which are signed if they appear in the mail.
+    pub fn signed_headers(&self) -> &[HeaderName] {
+        &self.signed_headers
+    }
+
+    /// Signs the mail, inserting a `DKIM-Signature` header in front of all other headers.
+    ///
+    /// The signature is computed over the mail encoded with the given
+    /// `mail_type` (i.e. after the bodies had been transfer encoded),
+    /// so the mail has to be encoded with the same `mail_type` afterwards.
+    ///
+    /// If a header in `signed_headers` appears multiple times in the
+    /// mail only the last instance is signed.
+    ///
+    /// # Error
+    ///
+    /// Fails if the mail can not be encoded with given mail type or
+    /// computing the signature fails.
+    pub fn sign(&self, mail: &mut EncodableMail, mail_type: MailType) -> Result<(), MailError> {
+        let encoded = mail.encode_into_bytes(mail_type)?;
+        let (header_section, body) = split_header_section(&encoded);
+        let header_fields = header_fields(header_section);
+
+        let body_hash = sha256(&canonicalize_body_relaxed(body));
+
+        let mut signed_names = Vec::new();
+        let mut signed_data = Vec::new();
+        for name in &self.signed_headers {
+            let field = header_fields
+                .iter()
+                .rev()
+                .find(|(field_name, _)| field_name.eq_ignore_ascii_case(name.as_str().as_bytes()));
+
+            if let Some((_, field)) = field {
+                signed_names.push(name.as_str());
+                signed_data.extend(canonicalize_header_relaxed(field));
+                signed_data.extend(b"\r\n");
+            }
+        }
+
+        let mut value = format!(
+            "v=1; a=rsa-sha256; c=relaxed/relaxed; d={domain}; s={selector}; h={headers}; bh={bh}; b=",
+            domain = self.domain.to_ascii_string()?,
+            selector = self.selector,
+            headers = signed_names.join(":"),
+            bh = base64::normal_encode(body_hash),
+        );
+
+        let unsigned_field = format!("{}: {}", DKIM_SIGNATURE_HEADER_NAME, value);
+        signed_data.extend(canonicalize_header_relaxed(unsigned_field.as_bytes()));
+
+        let signature = self.compute_signature(&signed_data)?;
+        // the line breaks of the base64 encoding become fold positions
+        let signature = base64::normal_encode(&signature);
+        value.push_str(&signature.as_str().replace("\r\n", " "));
+
+        mail.headers_mut().prepend_untyped(Box::new(RawHeader::new(
+            HeaderName::from_ascii_unchecked(DKIM_SIGNATURE_HEADER_NAME),
+            value,
+        )));
+        Ok(())
+    }
+
+    fn compute_signature(&self, data: &[u8]) -> Result<Vec<u8>, DkimError> {
+        let mut signer =
+            Signer::new(MessageDigest::sha256(), &self.private_key).map_err(DkimError::Signing)?;
+        signer.update(data).map_err(DkimError::Signing)?;
+        signer.sign_to_vec().map_err(DkimError::Signing)
+    }
+}
+
+/// Splits an encoded mail into its header section (including the CRLF of the last header) and body.
+fn split_header_section(encoded: &[u8]) -> (&[u8], &[u8]) {
+    if encoded.starts_with(b"\r\n") {
+        return (&[], &encoded[2..]);
+    }
+
+    match encoded.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(idx) => (&encoded[..idx + 2], &encoded[idx + 4..]),
+        None => (encoded, &[]),
+    }
+}
+
+/// Splits a header section into `(name, field)` pairs, the field excludes the trailing CRLF.
+fn header_fields(header_section: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut fields: Vec<(&[u8], &[u8])> = Vec::new();
+    let mut field_start = 0;
+    let mut idx = 0;
+
+    while idx + 1 < header_section.len() {
+        if &header_section[idx..idx + 2] != b"\r\n" {
+            idx += 1;
+            continue;
+        }
+
+        let is_folded = header_section
+            .get(idx + 2)
+            .map(|&next| next == b' ' || next == b'\t')
+            .unwrap_or(false);
+
+        if !is_folded {
+            let field = &header_section[field_start..idx];
+            let name_end = field
+                .iter()
+                .position(|&bch| bch == b':')
+                .unwrap_or(field.len());
+            fields.push((trim_wsp_end(&field[..name_end]), field));
+            field_start = idx + 2;
+        }
+        idx += 2;
+    }
+
+    fields
+}
+
+/// Canonicalizes a header field (without trailing CRLF) with the "relaxed" algorithm.
+///
+/// The name is lowercased, the field is unfolded, sequences of whitespace
+/// are reduced to a single space and whitespace at the end of the
+/// value or around the colon is removed.
+fn canonicalize_header_relaxed(field: &[u8]) -> Vec<u8> {
+    let colon_idx = field
+        .iter()
+        .position(|&bch| bch == b':')
+        .unwrap_or(field.len());
+    let (name, value) = field.split_at(colon_idx);
+
+    let mut out: Vec<u8> = trim_wsp_end(name)
+        .iter()
+        .map(|bch| bch.to_ascii_lowercase())
+        .collect();
+    out.push(b':');
+
+    let value = value.get(1..).unwrap_or(&[]);
+    let mut pending_space = false;
+    let mut at_value_start = true;
+    for &bch in value {
+        match bch {
+            b'\r' | b'\n' => {}
+            b' ' | b'\t' => pending_space = true,
+            _ => {
+                // whitespace in front of the value is dropped
+                if pending_space && !at_value_start {
+                    out.push(b' ');
+                }
+                pending_space = false;
+                at_value_start = false;
+                out.push(bch);
+            }
+        }
+    }
+
+    out
+}
+
+/// Canonicalizes a body with the "relaxed" algorithm.
+///
+/// Whitespace at the end of lines is removed, other sequences of whitespace
+/// are reduced to a single space and all empty lines at the end of the body
+/// are removed. A non-empty body always ends with CRLF.
+fn canonicalize_body_relaxed(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut empty_lines = 0;
+
+    let mut lines = body.split(|&bch| bch == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if lines.peek().is_none() && line.is_empty() {
+            // the split after the last line ending
+            break;
+        }
+
+        let line = trim_wsp_end(line);
+        if line.is_empty() {
+            empty_lines += 1;
+            continue;
+        }
+
+        for _ in 0..empty_lines {
+            out.extend(b"\r\n");
+        }
+        empty_lines = 0;
+
+        let mut pending_space = false;
+        for &bch in line {
+            if bch == b' ' || bch == b'\t' {
+                pending_space = true;
+            } else {
+                if pending_space {
+                    out.push(b' ');
+                }
+                pending_space = false;
+                out.push(bch);
+            }
+        }
+        out.extend(b"\r\n");
+    }
+
+    out
+}
+
+fn trim_wsp_end(bytes: &[u8]) -> &[u8] {
+    let end = bytes
+        .iter()
+        .rposition(|&bch| bch != b' ' && bch != b'\t')
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    &bytes[..end]
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+    use openssl::{pkey::Public, sign::Verifier};
+
+    use default_impl::test_context;
+    use headers::{
+        headers::{_From, _To, Subject},
+        HeaderKind,
+    };
+    use Mail;
+
+    use super::*;
+
+    mod canonicalize {
+        use super::*;
+
+        // examples from rfc6376 section 3.4.5
+        #[test]
+        fn relaxed_header() {
+            assert_eq!(canonicalize_header_relaxed(b"A: X"), b"a:X");
+            assert_eq!(
+                canonicalize_header_relaxed(b"B : Y\t\r\n\tZ  "),
+                b"b:Y Z".to_vec()
+            );
+        }
+
+        #[test]
+        fn relaxed_body() {
+            let body = b" C \r\nD \t E\r\n\r\n\r\n";
+            assert_eq!(canonicalize_body_relaxed(body), b" C\r\nD E\r\n".to_vec());
+        }
+
+        #[test]
+        fn relaxed_body_keeps_inner_empty_lines() {
+            let body = b"a\r\n\r\nb";
+            assert_eq!(canonicalize_body_relaxed(body), b"a\r\n\r\nb\r\n".to_vec());
+        }
+
+        #[test]
+        fn relaxed_empty_body() {
+            assert_eq!(canonicalize_body_relaxed(b""), b"".to_vec());
+            assert_eq!(canonicalize_body_relaxed(b"\r\n\r\n"), b"".to_vec());
+        }
+
+        #[test]
+        fn splits_header_fields() {
+            let section = b"A: X\r\nB : Y\r\n\tZ\r\n";
+            let fields = header_fields(section);
+            assert_eq!(
+                fields,
+                vec![(&b"A"[..], &b"A: X"[..]), (&b"B"[..], &b"B : Y\r\n\tZ"[..])]
+            );
+        }
+    }
+
+    fn signer_and_public_key() -> (DkimSigner, PKey<Public>) {
+        let private_key = Rsa::generate(1024).unwrap();
+        let public_key =
+            PKey::public_key_from_pem(&private_key.public_key_to_pem().unwrap()).unwrap();
+        let signer = DkimSigner::new(
+            "sel",
+            Domain::from_unchecked("example.test".to_owned()),
+            private_key,
+            vec![
+                _From::name(),
+                _To::name(),
+                Subject::name(),
+                HeaderName::from_ascii_unchecked("Cc"),
+            ],
+        )
+        .unwrap();
+        (signer, public_key)
+    }
+
+    fn encodable_mail() -> EncodableMail {
+        let ctx = test_context();
+        let mut mail = Mail::plain_text("hy there  \r\n\r\n", &ctx);
+        mail.insert_headers(
+            headers! {
+                _From: ["alice@example.test"],
+                _To: ["bob@example.test"],
+                Subject: "a greeting"
+            }
+            .unwrap(),
+        );
+        mail.into_encodable_mail(ctx).wait().unwrap()
+    }
+
+    fn tag<'a>(value: &'a str, name: &str) -> &'a str {
+        value
+            .split(';')
+            .map(|tag| tag.trim())
+            .find(|tag| tag.starts_with(name) && tag[name.len()..].starts_with('='))
+            .map(|tag| &tag[name.len() + 1..])
+            .unwrap()
+    }
+
+    #[test]
+    fn signature_can_be_verified() {
+        let (signer, public_key) = signer_and_public_key();
+        let mut mail = encodable_mail();
+        signer.sign(&mut mail, MailType::Ascii).unwrap();
+
+        let encoded = mail.encode_into_bytes(MailType::Ascii).unwrap();
+        let (header_section, body) = split_header_section(&encoded);
+        let fields = header_fields(header_section);
+        let (_, dkim_field) = fields
+            .iter()
+            .find(|(name, _)| *name == b"DKIM-Signature")
+            .unwrap();
+
+        let canonical_dkim_field = canonicalize_header_relaxed(dkim_field);
+        let canonical_dkim_field = String::from_utf8(canonical_dkim_field).unwrap();
+        let value = &canonical_dkim_field["dkim-signature:".len()..];
+
+        assert_eq!(tag(value, "c"), "relaxed/relaxed");
+        assert_eq!(tag(value, "a"), "rsa-sha256");
+        assert_eq!(tag(value, "d"), "example.test");
+        assert_eq!(tag(value, "s"), "sel");
+        // absent headers are not signed
+        assert_eq!(tag(value, "h"), "From:To:Subject");
+
+        let body_hash = base64::normal_encode(sha256(&canonicalize_body_relaxed(body)));
+        assert_eq!(tag(value, "bh"), body_hash.as_str());
+
+        let mut signed_data = Vec::new();
+        for name in &["From", "To", "Subject"] {
+            let (_, field) = fields
+                .iter()
+                .find(|(fname, _)| fname == &name.as_bytes())
+                .unwrap();
+            signed_data.extend(canonicalize_header_relaxed(field));
+            signed_data.extend(b"\r\n");
+        }
+        let b_start = canonical_dkim_field.find("; b=").unwrap() + 4;
+        signed_data.extend(&canonical_dkim_field.as_bytes()[..b_start]);
+
+        let signature =
+            base64::normal_decode(canonical_dkim_field[b_start..].replace(' ', "")).unwrap();
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &public_key).unwrap();
+        verifier.update(&signed_data).unwrap();
+        assert!(verifier.verify(&signature).unwrap());
+    }
+
+    #[test]
+    fn signature_header_is_placed_in_front_of_other_headers() {
+        let (signer, _) = signer_and_public_key();
+        let mut mail = encodable_mail();
+        signer.sign(&mut mail, MailType::Ascii).unwrap();
+
+        let (name, _) = mail.headers().iter().next().unwrap();
+        assert_eq!(name.as_str(), DKIM_SIGNATURE_HEADER_NAME);
+    }
+}
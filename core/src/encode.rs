@@ -1,13 +1,17 @@
+use std::io;
+
 use media_type::BOUNDARY;
 use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
 
 use headers::{
+    header_components::TransferEncoding,
     headers::{ContentTransferEncoding, ContentType},
     HeaderKind, HeaderName, HeaderObj, HeaderObjTrait,
 };
 use internals::{
     encoder::{EncodingBuffer, EncodingWriter},
     error::{EncodingError, EncodingErrorKind, Place, US_ASCII, UTF_8},
+    MailType,
 };
 
 use {
@@ -26,8 +30,149 @@ pub(crate) fn encode_mail(
     top: bool,
     encoder: &mut EncodingBuffer,
 ) -> Result<(), MailError> {
-    _encode_mail(&*mail, top, encoder).map_err(|err| {
-        let mail_type = encoder.mail_type();
+    let mut output = Output {
+        buffer: encoder,
+        sink: None,
+        elide_encoded_bodies: false,
+    };
+    encode_mail_to_output(mail, top, &mut output)
+}
+
+/// Encodes a mail as a body part, i.e. like it's encoded as a body of a multipart body.
+///
+/// # Panics
+/// if the body is not yet resolved, see `mail::load_body_part`
+pub(crate) fn encode_body_part(mail: &Mail, encoder: &mut EncodingBuffer) -> Result<(), MailError> {
+    let mut output = Output {
+        buffer: encoder,
+        sink: None,
+        elide_encoded_bodies: false,
+    };
+    let mail_type = output.mail_type();
+    _encode_mail(mail, false, false, &mut output).map_err(|err| match err {
+        MailError::Encoding(enc_err) => {
+            MailError::Encoding(enc_err.with_mail_type_or_else(|| Some(mail_type)))
+        }
+        other => other,
+    })
+}
+
+/// Returns the length of the encoded header section of the top-level mail.
+///
+/// See `EncodableMail::encoded_header_len`.
+pub(crate) fn encoded_header_len(
+    mail: &EncodableMail,
+    mail_type: MailType,
+) -> Result<usize, MailError> {
+    let mut buffer = EncodingBuffer::new(mail_type);
+    encode_headers(mail, true, mail.writes_mime_version(), &mut buffer)
+        .and_then(|()| Ok(buffer.write_blank_line()?))
+        .map_err(|err| match err {
+            MailError::Encoding(enc_err) => {
+                MailError::Encoding(enc_err.with_mail_type_or_else(|| Some(mail_type)))
+            }
+            other => other,
+        })?;
+    Ok(buffer.len())
+}
+
+/// Encodes the mail into a human readable string, see `EncodableMail::to_debug_string`.
+pub(crate) fn encode_mail_for_debugging(mail: &EncodableMail) -> String {
+    let mut buffer = EncodingBuffer::new(MailType::Internationalized);
+    let res = {
+        let mut output = Output {
+            buffer: &mut buffer,
+            sink: None,
+            elide_encoded_bodies: true,
+        };
+        encode_mail_to_output(mail, true, &mut output)
+    };
+
+    let encoded: Vec<u8> = buffer.into();
+    let mut debug_string = String::from_utf8_lossy(&encoded).into_owned();
+    if let Err(err) = res {
+        debug_string.push_str(&format!("\r\n<encoding failed: {}>\r\n", err));
+    }
+    debug_string
+}
+
+/// Encodes the mail writing it to the given sink.
+///
+/// Only the headers are encoded into an intermediate buffer (which
+/// is flushed to the sink after each header section), the bodies are
+/// directly written to the sink.
+///
+/// # Panics
+/// if the body is not yet resolved use `Body::poll_body` or `IntoFuture`
+/// on `Mail` to prevent this from happening
+///
+pub(crate) fn encode_mail_to_writer(
+    mail: &EncodableMail,
+    buffer: &mut EncodingBuffer,
+    out: &mut dyn io::Write,
+) -> Result<(), MailError> {
+    let mut output = Output {
+        buffer,
+        sink: Some(out),
+        elide_encoded_bodies: false,
+    };
+    encode_mail_to_output(mail, true, &mut output)?;
+    output.flush()
+}
+
+/// The destination the encoded mail is written to.
+///
+/// Headers are always written to the `EncodingBuffer`, as it's
+/// needed to keep track of the line length limits. If there is
+/// a `sink` the buffer is flushed to it once a header section is
+/// complete and bodies are written directly to it.
+///
+/// If `elide_encoded_bodies` is true `base64` and `binary` bodies are
+/// replaced by a placeholder, this is only meant for debugging output.
+struct Output<'a> {
+    buffer: &'a mut EncodingBuffer,
+    sink: Option<&'a mut dyn io::Write>,
+    elide_encoded_bodies: bool,
+}
+
+impl<'a> Output<'a> {
+    fn mail_type(&self) -> MailType {
+        self.buffer.mail_type()
+    }
+
+    /// Flushes all buffered data to the sink (if there is one).
+    fn flush(&mut self) -> Result<(), MailError> {
+        if let Some(ref mut sink) = self.sink {
+            self.buffer.flush_to(&mut **sink)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a body, adding a trailing newline if needed and `verbatim` is false.
+    fn write_body(&mut self, body: &[u8], verbatim: bool) -> Result<(), MailError> {
+        match self.sink {
+            None if verbatim => self.buffer.write_body_verbatim(&body)?,
+            None => self.buffer.write_body_unchecked(&body)?,
+            Some(ref mut sink) => {
+                self.buffer.flush_to(&mut **sink)?;
+                sink.write_all(body)?;
+                if !verbatim && !body.ends_with(b"\r\n") {
+                    sink.write_all(b"\r\n")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encode_mail_to_output(
+    mail: &EncodableMail,
+    top: bool,
+    output: &mut Output,
+) -> Result<(), MailError> {
+    let mime_version = top && mail.writes_mime_version();
+    let mail_type = output.mail_type();
+    _encode_mail(&*mail, top, mime_version, output).map_err(|err| {
         use self::MailError::*;
 
         match err {
@@ -37,13 +182,19 @@ pub(crate) fn encode_mail(
     })
 }
 
-fn _encode_mail(mail: &Mail, top: bool, encoder: &mut EncodingBuffer) -> Result<(), MailError> {
-    encode_headers(&mail, top, encoder)?;
+fn _encode_mail(
+    mail: &Mail,
+    top: bool,
+    mime_version: bool,
+    output: &mut Output,
+) -> Result<(), MailError> {
+    encode_headers(&mail, top, mime_version, output.buffer)?;
 
     //the empty line between the headers and the body
-    encoder.write_blank_line();
+    output.buffer.write_blank_line()?;
+    output.flush()?;
 
-    encode_mail_part(&mail, encoder)?;
+    encode_mail_part(&mail, top, output)?;
 
     Ok(())
 }
@@ -53,18 +204,34 @@ fn _encode_mail(mail: &Mail, top: bool, encoder: &mut EncodingBuffer) -> Result<
 /// if the body is not yet resolved use `Body::poll_body` or `IntoFuture`
 /// on `Mail` to prevent this from happening
 ///
+/// The `MIME-Version` header is only written if `mime_version` is true,
+/// which should only be the case for the top level mail. Any `MIME-Version`
+/// header in the header map is ignored to make sure it's written at most once.
 #[allow(clippy::nonminimal_bool)]
-fn encode_headers(mail: &Mail, top: bool, encoder: &mut EncodingBuffer) -> Result<(), MailError> {
-    use super::MailBody::*;
-
+fn encode_headers(
+    mail: &Mail,
+    top: bool,
+    mime_version: bool,
+    encoder: &mut EncodingBuffer,
+) -> Result<(), MailError> {
     let mut handle = encoder.writer();
-    if top {
+    if mime_version {
         handle.write_str(SoftAsciiStr::from_unchecked("MIME-Version: 1.0"))?;
         handle.finish_header();
     }
 
+    if !top {
+        // for body parts the structural headers go before any other header
+        encode_generated_headers(&mut handle, mail)?;
+    }
+
     for (name, hbody) in mail.headers().iter() {
         let name_as_str = name.as_str();
+        if name_as_str.eq_ignore_ascii_case("MIME-Version") {
+            warn!("ignoring explicitly set `MIME-Version` header: {:?}", hbody);
+            continue;
+        }
+
         let ignored_header =
             !top && !(name_as_str.starts_with("Content-") || name_as_str.starts_with("X-"));
 
@@ -78,13 +245,33 @@ fn encode_headers(mail: &Mail, top: bool, encoder: &mut EncodingBuffer) -> Resul
         encode_header(&mut handle, name, hbody)?;
     }
 
+    if top {
+        encode_generated_headers(&mut handle, mail)?;
+    }
+    Ok(())
+}
+
+/// Encodes the headers derived from the body, i.e. `Content-Type`,
+/// `Content-Transfer-Encoding` and (for compressed data) `Content-Encoding`
+/// for a non-multipart body.
+fn encode_generated_headers(handle: &mut EncodingWriter, mail: &Mail) -> Result<(), MailError> {
+    use super::MailBody::*;
+
     match mail.body() {
         SingleBody { ref body } => {
             let data = assume_encoded(body);
-            let header = ContentTransferEncoding::body(data.encoding());
-            encode_header(&mut handle, header.name(), &header)?;
             let header = ContentType::body(data.media_type().clone());
-            encode_header(&mut handle, header.name(), &header)?;
+            encode_header(handle, header.name(), &header)?;
+            let header = ContentTransferEncoding::body(data.encoding());
+            encode_header(handle, header.name(), &header)?;
+            #[cfg(feature = "compression")]
+            {
+                if let Some(coding) = data.content_coding() {
+                    handle.write_str(SoftAsciiStr::from_unchecked("Content-Encoding: "))?;
+                    handle.write_str(SoftAsciiStr::from_unchecked(coding.as_str()))?;
+                    handle.finish_header();
+                }
+            }
         }
         MultipleBodies { .. } => {}
     }
@@ -107,6 +294,7 @@ fn encode_header(
     })();
 
     res.map_err(|err| {
+        handle.undo_header();
         err.with_place_or_else(|| {
             Some(Place::Header {
                 name: name.as_str(),
@@ -120,7 +308,7 @@ fn encode_header(
 /// if the body is not yet resolved use `Body::poll_body` or `IntoFuture`
 /// on `Mail` to prevent this from happening
 ///
-fn encode_mail_part(mail: &Mail, encoder: &mut EncodingBuffer) -> Result<(), MailError> {
+fn encode_mail_part(mail: &Mail, top: bool, output: &mut Output) -> Result<(), MailError> {
     use super::MailBody::*;
 
     let minus = SoftAsciiChar::from_unchecked('-');
@@ -128,20 +316,34 @@ fn encode_mail_part(mail: &Mail, encoder: &mut EncodingBuffer) -> Result<(), Mai
     match mail.body() {
         SingleBody { ref body } => {
             let data = assume_encoded(body);
-            let buffer = data.transfer_encoded_buffer();
-            encoder.write_body_unchecked(buffer);
+            let buffer = data.transfer_encoded_data_buffer();
+            let elide_body = output.elide_encoded_bodies
+                && [TransferEncoding::Base64, TransferEncoding::Binary].contains(&data.encoding());
+            if elide_body {
+                let placeholder = format!("<{} bytes of {}>", buffer.len(), data.encoding().repr());
+                output.write_body(placeholder.as_bytes(), false)?;
+            } else if data.encoding() == TransferEncoding::Binary {
+                if !output.buffer.allows_binary_bodies() {
+                    return Err(EncodingError::from(EncodingErrorKind::NotEncodable {
+                        encoding: "binary",
+                    })
+                    .with_place_or_else(|| Some(Place::Body))
+                    .into());
+                }
+                output.write_body(buffer, true)?;
+                if !top {
+                    // the CRLF in front of the boundary is part of the
+                    // delimiter, so it must not be merged with the body
+                    output.buffer.write_blank_line()?;
+                }
+            } else {
+                output.write_body(buffer, false)?;
+            }
         }
         MultipleBodies {
             ref hidden_text,
             ref bodies,
         } => {
-            if !hidden_text.is_empty() {
-                //TODO find out if there is any source using the hidden text
-                // (e.g. for some form of validation, prove of senders validity etc.)
-                // if not drop the "hidden_text" field
-                warn!("\"hidden text\" in multipart bodies is dropped")
-            }
-
             let mail_was_validated_err_msg = "[BUG] mail was already validated";
             let boundary = mail
                 .headers()
@@ -165,17 +367,26 @@ fn encode_mail_part(mail: &Mail, encoder: &mut EncodingBuffer) -> Result<(), Mai
                 .with_str_context(orig_string.into_source())
             })?;
 
+            if !hidden_text.is_empty() {
+                let preamble = hidden_text
+                    .as_str()
+                    .lines()
+                    .collect::<Vec<_>>()
+                    .join("\r\n");
+                output.write_body(preamble.as_bytes(), false)?;
+            }
+
             for mail in bodies.iter() {
-                encoder.write_header_line(|handle| {
+                output.buffer.write_header_line(|handle| {
                     handle.write_char(minus)?;
                     handle.write_char(minus)?;
                     handle.write_str(&*boundary)
                 })?;
-                _encode_mail(mail, false, encoder)?;
+                _encode_mail(mail, false, false, output)?;
             }
 
             if !bodies.is_empty() {
-                encoder.write_header_line(|handle| {
+                output.buffer.write_header_line(|handle| {
                     handle.write_char(minus)?;
                     handle.write_char(minus)?;
                     handle.write_str(&*boundary)?;
@@ -1,6 +1,7 @@
 //! Module containing all custom errors produced by this crate.
 use std::fmt::{self, Display};
 use std::io;
+use std::path::{Path, PathBuf};
 
 use failure::{Backtrace, Context, Fail};
 
@@ -8,6 +9,8 @@ use headers::error::{
     BuildInValidationError, ComponentCreationError, HeaderTypeError, HeaderValidationError,
 };
 use internals::error::EncodingError;
+#[cfg(feature = "dkim")]
+use openssl::error::ErrorStack;
 use IRI;
 // errors from loading a Resource (which includes encoding it's body)
 //                /  NotFound       | IRI (no Backtrace neede)     \ MailError::ResourceLoading
@@ -51,6 +54,10 @@ pub enum ResourceLoadingErrorKind {
     #[fail(display = "resource not found")]
     NotFound,
 
+    /// The resource can not be accessed due to missing permissions.
+    #[fail(display = "permission denied")]
+    PermissionDenied,
+
     /// The act of loading it failed (e.g. because of an I/0-Error)
     #[fail(display = "loading failed")]
     LoadingFailed,
@@ -59,16 +66,35 @@ pub enum ResourceLoadingErrorKind {
     MediaTypeDetectionFailed,
 }
 
+impl From<io::ErrorKind> for ResourceLoadingErrorKind {
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::NotFound => ResourceLoadingErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ResourceLoadingErrorKind::PermissionDenied,
+            _ => ResourceLoadingErrorKind::LoadingFailed,
+        }
+    }
+}
+
 /// The loading of an Resource failed.
 #[derive(Debug)]
 pub struct ResourceLoadingError {
     inner: Context<ResourceLoadingErrorKind>,
     iri: Option<IRI>,
+    path: Option<PathBuf>,
+    container_key: Option<String>,
 }
 
 impl Display for ResourceLoadingError {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, fter)
+        Display::fmt(&self.inner, fter)?;
+        if let Some(path) = self.path.as_ref() {
+            write!(fter, " (path: {})", path.display())?;
+        }
+        if let Some(key) = self.container_key.as_ref() {
+            write!(fter, " (resource: {})", key)?;
+        }
+        Ok(())
     }
 }
 
@@ -103,6 +129,44 @@ impl ResourceLoadingError {
         }
         self
     }
+
+    /// The file system path which was used when failing to load the Resource.
+    ///
+    /// This is only set by resource loaders loading resources from the
+    /// file system.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Sets the path if not already set and returns self.
+    pub fn with_path_or_else<F>(mut self, func: F) -> Self
+    where
+        F: FnOnce() -> Option<PathBuf>,
+    {
+        if self.path.is_none() {
+            self.path = func();
+        }
+        self
+    }
+
+    /// The key (e.g. map key or vector index) of the resource which failed to load.
+    ///
+    /// This is only set if the resource was loaded as part of a container
+    /// using `Resource::load_container`.
+    pub fn container_key(&self) -> Option<&str> {
+        self.container_key.as_deref()
+    }
+
+    /// Sets the container key if not already set and returns self.
+    pub fn with_container_key_or_else<F>(mut self, func: F) -> Self
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        if self.container_key.is_none() {
+            self.container_key = func();
+        }
+        self
+    }
 }
 
 impl From<ResourceLoadingErrorKind> for ResourceLoadingError {
@@ -137,13 +201,19 @@ impl From<(Option<IRI>, ResourceLoadingErrorKind)> for ResourceLoadingError {
 
 impl From<(Option<IRI>, Context<ResourceLoadingErrorKind>)> for ResourceLoadingError {
     fn from((iri, inner): (Option<IRI>, Context<ResourceLoadingErrorKind>)) -> Self {
-        ResourceLoadingError { inner, iri }
+        ResourceLoadingError {
+            inner,
+            iri,
+            path: None,
+            container_key: None,
+        }
     }
 }
 
 impl From<io::Error> for ResourceLoadingError {
     fn from(err: io::Error) -> Self {
-        err.context(ResourceLoadingErrorKind::LoadingFailed).into()
+        let kind = ResourceLoadingErrorKind::from(err.kind());
+        err.context(kind).into()
     }
 }
 
@@ -178,6 +248,19 @@ pub enum OtherValidationError {
     /// A mail (top level, not in multipart) requires a `From` header to be given.
     #[fail(display = "mail did not contain a From header")]
     NoFrom,
+
+    /// A mail (top level) has no `Message-Id` header but the context requires one.
+    ///
+    /// See `Context::require_message_id`.
+    #[fail(display = "mail did not contain a Message-Id header")]
+    NoMessageId,
+
+    /// A mail (top level) with `Auto-Submitted: auto-replied` has `List-*` headers.
+    ///
+    /// This is only returned if the context rejects such mails,
+    /// see `Context::reject_auto_replied_list_mail`.
+    #[fail(display = "auto-replied mail contained List-* headers")]
+    AutoRepliedListMail,
 }
 
 impl From<OtherValidationError> for HeaderValidationError {
@@ -223,6 +306,26 @@ pub enum MailError {
     /// be found.
     #[fail(display = "{}", _0)]
     ResourceLoading(ResourceLoadingError),
+
+    /// Writing the encoded mail to a `io::Write` sink failed.
+    #[fail(display = "{}", _0)]
+    Io(io::Error),
+
+    /// Parsing a mail failed.
+    #[fail(display = "{}", _0)]
+    Parse(MailParseError),
+
+    /// Creating the signature of a `multipart/signed` body failed.
+    ///
+    /// This contains the error returned by the signing function passed
+    /// to `MultipartSigned::sign`.
+    #[fail(display = "{}", _0)]
+    Signing(failure::Error),
+
+    /// Signing the mail with DKIM failed.
+    #[cfg(feature = "dkim")]
+    #[fail(display = "{}", _0)]
+    Dkim(DkimError),
 }
 
 impl From<BuildInValidationError> for MailError {
@@ -264,12 +367,136 @@ impl From<ResourceError> for MailError {
     }
 }
 
+impl From<io::Error> for MailError {
+    fn from(err: io::Error) -> Self {
+        MailError::Io(err)
+    }
+}
+
+impl From<MailParseError> for MailError {
+    fn from(err: MailParseError) -> Self {
+        MailError::Parse(err)
+    }
+}
+
 impl From<ComponentCreationError> for MailError {
     fn from(err: ComponentCreationError) -> Self {
         MailError::Component(err)
     }
 }
 
+/// Error returned if the structure of a mail can not be parsed.
+///
+/// Errors from parsing header bodies are returned as `MailError::Component`.
+#[derive(Debug, Fail)]
+pub enum MailParseError {
+    /// The header section contains non utf-8 bytes.
+    #[fail(display = "header section is not valid utf-8")]
+    NonUtf8HeaderSection,
+
+    /// A header line is neither a header field nor the continuation of one.
+    #[fail(display = "malformed header line: {:?}", _0)]
+    MalformedHeaderLine(String),
+
+    /// A multipart body has a `Content-Type` without a boundary parameter.
+    #[fail(display = "multipart Content-Type without boundary parameter")]
+    MissingBoundary,
+}
+
+#[cfg(feature = "dkim")]
+impl From<DkimError> for MailError {
+    fn from(err: DkimError) -> Self {
+        MailError::Dkim(err)
+    }
+}
+
+/// Error returned if creating a DKIM signature failed.
+#[cfg(feature = "dkim")]
+#[derive(Debug, Fail)]
+pub enum DkimError {
+    /// The given private key is not a usable RSA key.
+    #[fail(display = "invalid DKIM private key: {}", _0)]
+    InvalidKey(ErrorStack),
+
+    /// Computing the signature failed.
+    #[fail(display = "computing DKIM signature failed: {}", _0)]
+    Signing(ErrorStack),
+}
+
+/// Error returned if transcoding text to utf-8 failed.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum TranscodingError {
+    /// The charset is not supported.
+    #[fail(display = "unsupported charset: {:?}", _0)]
+    UnsupportedCharset(String),
+
+    /// The text contains a byte sequence which is not valid in the charset.
+    #[fail(
+        display = "invalid byte sequence for charset {} at byte offset {}",
+        charset, offset
+    )]
+    InvalidByteSequence {
+        /// The (normalized) name of the charset.
+        charset: &'static str,
+        /// The offset of the first invalid byte.
+        offset: usize,
+    },
+}
+
+/// Error returned if `MailParts::validate` finds a problem.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum CompositionError {
+    /// A inline embedding has no content id, so no body can refer to it.
+    ///
+    /// Only loaded resources (i.e. `Resource::Data` and `Resource::EncData`)
+    /// are checked, all other resources get a content id when loaded.
+    #[fail(display = "{} has no content id", _0)]
+    EmbeddingWithoutContentId(PartPosition),
+
+    /// A body, embedding or attachment has a multipart media type.
+    ///
+    /// Multipart bodies are created when composing the mail, the parts
+    /// themselves have to be singlepart bodies.
+    #[fail(display = "{} has multipart media type {}", position, media_type)]
+    MultipartBodyPart {
+        position: PartPosition,
+        media_type: String,
+    },
+}
+
+/// The position of a resource in a `MailParts` instance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PartPosition {
+    /// The resource of the alternative body with the given index.
+    AlternativeBody(usize),
+    /// A inline embedding specific to the alternative body with index `body`.
+    BodyInlineEmbedding { body: usize, index: usize },
+    /// A attachment specific to the alternative body with index `body`.
+    BodyAttachment { body: usize, index: usize },
+    /// A inline embedding shared by all alternative bodies.
+    InlineEmbedding(usize),
+    /// A attachment shared by all alternative bodies.
+    Attachment(usize),
+}
+
+impl Display for PartPosition {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PartPosition::AlternativeBody(idx) => write!(fter, "alternative body {}", idx),
+            PartPosition::BodyInlineEmbedding { body, index } => write!(
+                fter,
+                "inline embedding {} of alternative body {}",
+                index, body
+            ),
+            PartPosition::BodyAttachment { body, index } => {
+                write!(fter, "attachment {} of alternative body {}", index, body)
+            }
+            PartPosition::InlineEmbedding(idx) => write!(fter, "inline embedding {}", idx),
+            PartPosition::Attachment(idx) => write!(fter, "attachment {}", idx),
+        }
+    }
+}
+
 /// Error returned when trying to _unload_ and `Resource` and it fails.
 #[derive(Copy, Clone, Debug, Fail)]
 pub enum ResourceNotUnloadableError {
@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{borrow::Cow, str::FromStr};
 
 #[cfg(feature = "serde")]
 use serde::{
@@ -57,6 +57,42 @@ impl IRI {
         })
     }
 
+    /// Create a new IRI from a scheme part and a tail part percent-encoding the tail.
+    ///
+    /// Like `from_parts` this validates the scheme and converts it into
+    /// lower case. But instead of using the tail as is all characters
+    /// which are not allowed in a IRI (whitespace, control characters and
+    /// any of `"<>\^`{|}`) as well as `%` are percent-encoded. Non us-ascii
+    /// characters are allowed in IRIs and are kept as is.
+    ///
+    /// Use `decoded_tail` to get back the original tail.
+    ///
+    /// Note that `tail` returns the encoded tail, the default `path:`
+    /// resource loader uses the decoded tail.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mail_core::IRI;
+    /// let iri = IRI::with_scheme_and_tail("path", "./my logo%.png").unwrap();
+    /// assert_eq!(iri.as_str(), "path:./my%20logo%25.png");
+    /// assert_eq!(iri.decoded_tail(), "./my logo%.png");
+    /// ```
+    pub fn with_scheme_and_tail(scheme: &str, tail: &str) -> Result<Self, InvalidIRIScheme> {
+        let mut encoded = String::with_capacity(tail.len());
+        for ch in tail.chars() {
+            if needs_percent_encoding(ch) {
+                let mut buf = [0u8; 4];
+                for byte in ch.encode_utf8(&mut buf).bytes() {
+                    encoded.push_str(&format!("%{:02X}", byte));
+                }
+            } else {
+                encoded.push(ch);
+            }
+        }
+        Self::from_parts(scheme, &encoded)
+    }
+
     /// crates a new a IRI
     ///
     /// 1. this determines the first occurrence of `:` to split the input into scheme and tail
@@ -138,6 +174,50 @@ impl IRI {
         &self.iri[self.scheme_end_idx + 1..]
     }
 
+    /// the scheme specific part of the uri with percent-encoded sequences decoded
+    ///
+    /// Invalid percent-encoded sequences (e.g. `"%zz"`) are kept as is and
+    /// decoded sequences which are not valid utf8 are replaced with `U+FFFD`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mail_core::IRI;
+    /// let uri = IRI::new("path:./Gr%C3%BC%C3%9Fe%20und%20so.txt").unwrap();
+    /// assert_eq!(uri.decoded_tail(), "./Grüße und so.txt");
+    /// ```
+    pub fn decoded_tail(&self) -> Cow<'_, str> {
+        let tail = self.tail();
+        if !tail.contains('%') {
+            return Cow::Borrowed(tail);
+        }
+
+        let bytes = tail.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut idx = 0;
+        while idx < bytes.len() {
+            let byte = bytes[idx];
+            if byte == b'%' {
+                let hex_value = tail
+                    .get(idx + 1..idx + 3)
+                    .filter(|hex| hex.bytes().all(|bch| bch.is_ascii_hexdigit()))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(value) = hex_value {
+                    decoded.push(value);
+                    idx += 3;
+                    continue;
+                }
+            }
+            decoded.push(byte);
+            idx += 1;
+        }
+
+        match String::from_utf8(decoded) {
+            Ok(decoded) => Cow::Owned(decoded),
+            Err(err) => Cow::Owned(String::from_utf8_lossy(err.as_bytes()).into_owned()),
+        }
+    }
+
     /// returns the underlying string representation
     ///
     /// Note that it does not implement Display even through
@@ -149,6 +229,12 @@ impl IRI {
     }
 }
 
+fn needs_percent_encoding(ch: char) -> bool {
+    ch.is_whitespace()
+        || ch.is_control()
+        || ['%', '"', '<', '>', '\\', '^', '`', '{', '|', '}'].contains(&ch)
+}
+
 impl FromStr for IRI {
     type Err = InvalidIRIScheme;
 
@@ -255,6 +341,44 @@ mod test {
         assert_eq!(iri.as_str(), "foo:bar/bazz");
     }
 
+    #[test]
+    fn with_scheme_and_tail_encodes_tail() {
+        let iri = IRI::with_scheme_and_tail("PATH", "./my dir/logo {1}.png").unwrap();
+        assert_eq!(iri.scheme(), "path");
+        assert_eq!(iri.tail(), "./my%20dir/logo%20%7B1%7D.png");
+
+        let iri = IRI::with_scheme_and_tail("path", "./grüße\t.txt").unwrap();
+        assert_eq!(iri.tail(), "./grüße%09.txt");
+
+        assert!(IRI::with_scheme_and_tail("1path", "./logo.png").is_err());
+    }
+
+    #[test]
+    fn with_scheme_and_tail_round_trips() {
+        let tails = [
+            "./logo.png",
+            "./my logo.png",
+            "./Grüße und so/100%.txt",
+            "/opt/{weird}|\"<names>\"",
+        ];
+        for tail in tails.iter() {
+            let iri = IRI::with_scheme_and_tail("path", tail).unwrap();
+            assert_eq!(iri.decoded_tail(), *tail);
+
+            let reparsed = IRI::new(iri.as_str()).unwrap();
+            assert_eq!(reparsed.decoded_tail(), *tail);
+        }
+    }
+
+    #[test]
+    fn decoded_tail_keeps_invalid_sequences() {
+        let iri = IRI::new("path:./100%zz%+1%2").unwrap();
+        assert_eq!(iri.decoded_tail(), "./100%zz%+1%2");
+
+        let iri = IRI::new("path:./%FF%41").unwrap();
+        assert_eq!(iri.decoded_tail(), "./\u{FFFD}A");
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn serde_works_for_str_iri() {
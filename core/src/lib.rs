@@ -15,6 +15,9 @@ extern crate chrono;
 extern crate futures;
 #[cfg_attr(test, macro_use)]
 extern crate mail_headers as headers;
+// Used by the `mail!` macro.
+#[doc(hidden)]
+pub extern crate mail_headers as __mail_headers;
 extern crate mail_internals as internals;
 extern crate media_type;
 extern crate rand;
@@ -26,21 +29,32 @@ extern crate serde;
 #[cfg(all(test, feature = "serde"))]
 extern crate serde_test;
 
+#[cfg(feature = "bytes")]
+extern crate bytes;
 #[cfg(feature = "default_impl_cpupool")]
 extern crate futures_cpupool;
 #[cfg(feature = "test-utils")]
 extern crate lazy_static;
+#[cfg(any(feature = "dkim", feature = "digest"))]
+extern crate openssl;
+#[cfg(feature = "compression")]
+extern crate miniz_oxide;
 
 #[macro_use]
 mod macros;
 pub mod compose;
 pub mod context;
+pub mod delivery_status;
+#[cfg(feature = "dkim")]
+pub mod dkim;
 mod encode;
 pub mod error;
 mod iri;
 mod mail;
 pub mod mime;
+mod parse;
 mod resource;
+pub mod signed;
 #[cfg(feature = "test-utils")]
 pub mod test_utils;
 pub mod utils;
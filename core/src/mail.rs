@@ -1,26 +1,30 @@
 //! Module containing all the parts for creating/encoding Mails.
 //!
 
-use std::{fmt, mem, ops::Deref};
+use std::{collections::HashSet, fmt, io, mem, ops::Deref};
 
 use futures::{future, Async, Future, Poll};
 use media_type::BOUNDARY;
-use soft_ascii_string::SoftAsciiString;
+use soft_ascii_string::{SoftAsciiStr, SoftAsciiString};
 
 use headers::{
-    error::HeaderValidationError,
-    header_components::{DateTime, MediaType},
+    error::{HeaderTypeError, HeaderValidationError},
+    header_components::{self, AutoSubmittedKind, Email, Mailbox, MediaType, TransferEncoding},
     headers::{
-        ContentDisposition, ContentId, ContentTransferEncoding, ContentType, Date, MessageId, _From,
+        AutoSubmitted, Bcc, Cc, ContentDisposition, ContentId, ContentMd5, ContentTransferEncoding, ContentType,
+        Date, DispositionNotificationTo, Importance, MessageId, Sender, XPriority, _From, _To,
     },
-    Header, HeaderKind, HeaderMap,
+    HasHeaderName, Header, HeaderKind, HeaderMap,
+};
+use internals::{
+    encoder::EncodingBuffer,
+    error::{EncodingError, EncodingErrorKind, Place},
+    MailType,
 };
-use internals::{encoder::EncodingBuffer, MailType};
 
 use {
     context::Context,
-    error::{MailError, OtherValidationError, ResourceLoadingError},
-    mime::create_structured_random_boundary,
+    error::{MailError, OtherValidationError},
     resource::*,
     utils::SendBoxFuture,
 };
@@ -144,7 +148,13 @@ use {
 /// }.unwrap());
 /// # }
 /// ```
-#[derive(Clone, Debug)]
+///
+/// Mails are compared structurally, i.e. two mails are equal if their
+/// headers are equal and in the same order (see `HeaderMap`'s `PartialEq`
+/// implementation) and they have equal bodies (see `Resource`'s `PartialEq`
+/// implementation). This allows comparing composed mails in tests
+/// without encoding them.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Mail {
     headers: HeaderMap,
     body: MailBody,
@@ -156,7 +166,7 @@ pub struct Mail {
 /// single body _and_ it being semantically important to be this way,
 /// so we have to differ between both kinds (instead of just having
 /// a `Vec` of mails)
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum MailBody {
     SingleBody {
         body: Resource,
@@ -164,11 +174,12 @@ pub enum MailBody {
     MultipleBodies {
         //TODO[now]: use Vec1
         bodies: Vec<Mail>,
-        /// This is part of the standard! But we won't
-        /// make it public available for now. Through
-        /// there is a chance that we need to do so
-        /// in the future as some mechanisms might
-        /// misuse this, well unusual think.
+        /// The preamble of the multipart body (rfc2046).
+        ///
+        /// It's placed between the blank line ending the headers and the
+        /// first boundary and is ignored by MIME aware clients, but is
+        /// shown by legacy clients. It's empty by default, in which case
+        /// no preamble is written. See `Mail::set_preamble`.
         hidden_text: SoftAsciiString,
     },
 }
@@ -194,6 +205,55 @@ impl Mail {
         self.body.is_multipart()
     }
 
+    /// Returns the preamble of a multipart body.
+    ///
+    /// Returns `None` if this mail doesn't have a multipart body and an
+    /// empty string if no preamble was set.
+    pub fn preamble(&self) -> Option<&SoftAsciiStr> {
+        match self.body {
+            MailBody::MultipleBodies {
+                ref hidden_text, ..
+            } => Some(hidden_text),
+            MailBody::SingleBody { .. } => None,
+        }
+    }
+
+    /// Sets the preamble of a multipart body.
+    ///
+    /// The preamble is written between the blank line ending the headers
+    /// and the first boundary. It's ignored by MIME aware clients but is
+    /// shown by legacy clients, e.g. `"This is a multipart message in MIME format."`.
+    /// Line breaks in it are written as CRLF. An empty preamble means no
+    /// preamble is written. The generated boundary is guaranteed to not
+    /// appear in the preamble.
+    ///
+    /// # Panic
+    ///
+    /// Panics if this mail doesn't have a multipart body.
+    pub fn set_preamble(&mut self, preamble: SoftAsciiString) {
+        match self.body {
+            MailBody::MultipleBodies {
+                ref mut hidden_text,
+                ..
+            } => *hidden_text = preamble,
+            MailBody::SingleBody { .. } => {
+                panic!("preamble can only be set for mails with a multipart body")
+            }
+        }
+    }
+
+    /// Sets the preamble of a multipart body and returns self.
+    ///
+    /// See `Mail::set_preamble`.
+    ///
+    /// # Panic
+    ///
+    /// Panics if this mail doesn't have a multipart body.
+    pub fn with_preamble(mut self, preamble: SoftAsciiString) -> Self {
+        self.set_preamble(preamble);
+        self
+    }
+
     /// Create a new multipart mail with given content type and given bodies.
     ///
     /// Note that while the given `content_type` has to be a `multipart` content
@@ -221,6 +281,28 @@ impl Mail {
         }
     }
 
+    /// Parses an encoded mail (e.g. received through IMAP).
+    ///
+    /// The bodies are kept transfer encoded, i.e. they are represented as
+    /// `Resource::EncData` and multipart bodies are split at their boundary.
+    /// Folded header lines are unfolded. Common headers like `From`, `To`,
+    /// `Subject`, `Date` or `Message-Id` are parsed into their typed
+    /// representation, all other headers are kept as raw headers which can
+    /// be encoded again but not be accessed as a typed header. See the
+    /// `parse` module for details.
+    ///
+    /// The `Context` is used to generate a `ContentId` for bodies which
+    /// don't have a `Content-Id` header.
+    ///
+    /// # Error
+    ///
+    /// Fails with `MailError::Parse` if the structure of the mail is
+    /// malformed and with `MailError::Component` if the body of a
+    /// parsed header is invalid.
+    pub fn parse(bytes: &[u8], ctx: &impl Context) -> Result<Mail, MailError> {
+        ::parse::parse_mail(bytes, ctx)
+    }
+
     /// Inserts a new header into the header map.
     ///
     /// This will call `insert` on the inner `HeaderMap`,
@@ -268,6 +350,95 @@ impl Mail {
         &mut self.body
     }
 
+    /// Returns an iterator over all body resources of this mail.
+    ///
+    /// The bodies of nested multipart bodies are included, the resources
+    /// are returned in the order they appear in the mail. This doesn't
+    /// load any resources, e.g. it can be used to inspect the media types
+    /// of the bodies before calling `into_encodable_mail`.
+    pub fn bodies(&self) -> impl Iterator<Item = &Resource> {
+        let mut bodies = Vec::new();
+        self.collect_bodies(&mut bodies);
+        bodies.into_iter()
+    }
+
+    fn collect_bodies<'a>(&'a self, out: &mut Vec<&'a Resource>) {
+        use self::MailBody::*;
+        match self.body {
+            SingleBody { ref body } => out.push(body),
+            MultipleBodies { ref bodies, .. } => {
+                for body in bodies {
+                    body.collect_bodies(out)
+                }
+            }
+        }
+    }
+
+    /// Returns the emails of all recipients of this mail.
+    ///
+    /// This are the emails from the `To`, `Cc` and `Bcc` headers (in
+    /// that order) with duplicates removed. When checking for duplicates
+    /// the domain is compared case insensitive while the local part is
+    /// compared case sensitive (as it's up to the receiving host how to
+    /// interpret it).
+    ///
+    /// If none of this headers is present an empty vector is returned.
+    ///
+    /// # Error
+    ///
+    /// Fails with a `HeaderTypeError` if any of this headers is present
+    /// but is not of the expected type (e.g. a `To` header which was
+    /// inserted with a custom header type).
+    pub fn recipients(&self) -> Result<Vec<Email>, HeaderTypeError> {
+        let headers = self.headers();
+        let lists = [
+            headers.get_single(_To).transpose()?.map(|h| h.body()),
+            headers.get_single(Cc).transpose()?.map(|h| h.body()),
+            headers.get_single(Bcc).transpose()?.map(|h| h.body()),
+        ];
+
+        let mut seen = HashSet::new();
+        let mut recipients = Vec::new();
+        for list in lists.iter().flatten() {
+            for mailbox in list.iter() {
+                let email = &mailbox.email;
+                let key = (
+                    email.local_part.as_str(),
+                    email.domain.as_str().to_lowercase(),
+                );
+                if seen.insert(key) {
+                    recipients.push(email.clone());
+                }
+            }
+        }
+        Ok(recipients)
+    }
+
+    /// Requests a read receipt to be send to the given mailbox.
+    ///
+    /// This sets the `Disposition-Notification-To` header (rfc8098)
+    /// replacing any previously set value. As `Mailbox` can only be
+    /// created from a valid address no further validation is needed.
+    ///
+    /// Note that mail clients are free to ignore the request, so there
+    /// is no guarantee that a read receipt will be send.
+    pub fn request_read_receipt(&mut self, to: Mailbox) {
+        let list = header_components::MailboxList::from_single(to);
+        self.insert_header(DispositionNotificationTo::body(list));
+    }
+
+    /// Sets the importance of the mail.
+    ///
+    /// As different clients read different headers this sets both the
+    /// `Importance` header (rfc2156) and the corresponding (non-standard)
+    /// `X-Priority` header, i.e. `1` for `high`, `3` for `normal` and `5`
+    /// for `low`, replacing any previously set value.
+    pub fn set_importance(&mut self, importance: header_components::Importance) {
+        let priority = header_components::XPriority::from(importance);
+        self.insert_header(Importance::body(importance));
+        self.insert_header(XPriority::body(priority));
+    }
+
     /// Validate the mail.
     ///
     /// This will mainly validate the mail headers by
@@ -312,9 +483,17 @@ impl Mail {
     /// While this future resolves it will do following thinks:
     ///
     /// 1. Validate the mail.
+    ///    - Before validating it inserts the `From`/`Sender` headers
+    ///      provided by `Context::default_from`/`Context::default_sender`
+    ///      if the mail has no such header. If `Context::auto_sender` is
+    ///      true and there is still no `Sender` header but multiple `From`
+    ///      mailboxes the first of them is inserted as `Sender`.
     ///    - This uses `generally_validate_mail`.
     ///    - Additionally it does check for required top level headers
-    ///      which will not be auto-generated (the `From` header).
+    ///      which will not be auto-generated (the `From` header and if
+    ///      `Context::require_message_id` is true the `Message-Id` header)
+    ///      and for `auto-replied` mails with `List-*` headers (see
+    ///      `Context::reject_auto_replied_list_mail`).
     ///
     /// 2. Make sure all resources are loaded and transfer encoded.
     ///    - This will concurrently load + transfer encode all resources
@@ -323,6 +502,10 @@ impl Mail {
     ///      successfully.
     ///
     /// 3. Insert all auto generated headers (like e.g. `Date`).
+    ///    - This also removes the `Bcc` header, as encoding it would leak
+    ///      the blind carbon copy recipients to all other recipients. So
+    ///      if the recipients are derived from the headers (e.g. for smtp)
+    ///      this has to be done before calling this method.
     ///
     /// 4. Insert boundary parameters into all multipart media types
     ///    (overriding any existing one).
@@ -404,7 +587,7 @@ enum InnerMailFuture<C: Context> {
     },
     Loading {
         mail: Mail,
-        pending: future::JoinAll<Vec<SendBoxFuture<EncData, ResourceLoadingError>>>,
+        pending: future::JoinAll<Vec<SendBoxFuture<EncData, MailError>>>,
         ctx: C,
     },
     Poison,
@@ -433,23 +616,15 @@ where
         loop {
             let state = mem::replace(&mut self.inner, InnerMailFuture::Poison);
             match state {
-                New { mail, ctx } => {
+                New { mut mail, ctx } => {
+                    insert_default_senders(&mut mail, &ctx);
                     mail.generally_validate_mail()?;
-                    top_level_validation(&mail)?;
-
-                    let mut futures = Vec::new();
-                    mail.visit_mail_bodies(&mut |resource: &Resource| {
-                        let fut = ctx.load_transfer_encoded_resource(resource);
-                        futures.push(fut);
-                    });
+                    top_level_validation(&mail, &ctx)?;
 
+                    let pending = load_bodies(&mail, &ctx);
                     mem::replace(
                         &mut self.inner,
-                        InnerMailFuture::Loading {
-                            mail,
-                            ctx,
-                            pending: future::join_all(futures),
-                        },
+                        InnerMailFuture::Loading { mail, ctx, pending },
                     );
                 }
                 Loading {
@@ -457,7 +632,7 @@ where
                     mut pending,
                     ctx,
                 } => match pending.poll() {
-                    Err(err) => return Err(err.into()),
+                    Err(err) => return Err(err),
                     Ok(Async::NotReady) => {
                         mem::replace(
                             &mut self.inner,
@@ -467,7 +642,10 @@ where
                     }
                     Ok(Async::Ready(encoded_bodies)) => {
                         auto_gen_headers(&mut mail, encoded_bodies, &ctx);
-                        return Ok(Async::Ready(EncodableMail(mail)));
+                        return Ok(Async::Ready(EncodableMail {
+                            mail,
+                            suppress_mime_version: false,
+                        }));
                     }
                 },
                 Poison => panic!("called again after completion (through value, error or panic)"),
@@ -476,9 +654,128 @@ where
     }
 }
 
+/// starts loading the transfer encoded form of all bodies of the mail (in `visit_mail_bodies` order)
+fn load_bodies<C: Context>(
+    mail: &Mail,
+    ctx: &C,
+) -> future::JoinAll<Vec<SendBoxFuture<EncData, MailError>>> {
+    let mut futures = Vec::new();
+    mail.visit_mail_bodies(&mut |resource: &Resource| {
+        let fut: SendBoxFuture<EncData, MailError> = match *resource {
+            Resource::Message(ref message) => load_message(message, ctx),
+            _ => Box::new(
+                ctx.load_transfer_encoded_resource(resource)
+                    .map_err(MailError::from),
+            ),
+        };
+        futures.push(fut);
+    });
+    future::join_all(futures)
+}
+
+/// replaces all bodies of the mail with the loaded bodies returned by `load_bodies`
+fn replace_with_loaded_bodies(mail: &mut Mail, encoded_resources: Vec<EncData>) {
+    let mut iter = encoded_resources.into_iter();
+    mail.visit_mail_bodies_mut(&mut move |resource: &mut Resource| {
+        let enc_data = iter
+            .next()
+            .expect("[BUG] mail structure modified while turing it into encoded mail");
+        mem::replace(resource, Resource::EncData(enc_data));
+    });
+}
+
+/// Loads all bodies of a mail which will be used as a body part (i.e. not as top-level mail).
+///
+/// The resulting mail is validated, has all resources replaced by their
+/// transfer encoded form and has the auto-generated headers of body parts
+/// (e.g. `Content-Id` and multipart boundaries) but none of the top-level
+/// headers. As such it can be encoded with `encode::encode_body_part`.
+pub(crate) fn load_body_part<C: Context>(mail: Mail, ctx: &C) -> SendBoxFuture<Mail, MailError> {
+    if let Err(err) = mail.generally_validate_mail() {
+        return Box::new(future::err(err));
+    }
+
+    let ctx = ctx.clone();
+    let fut = load_bodies(&mail, &ctx).map(move |encoded_resources| {
+        let mut mail = mail;
+        replace_with_loaded_bodies(&mut mail, encoded_resources);
+        recursive_auto_gen_headers(&mut mail, false, &ctx);
+        mail
+    });
+    Box::new(fut)
+}
+
+/// turns a mail embedded as `Resource::Message` into an encoded `message/rfc822` body
+///
+/// The transfer encoding (and the mail type used to encode the embedded mail)
+/// is the least demanding one which can represent the transfer encodings of the
+/// bodies and the headers of the embedded mail, as `message/rfc822` bodies can
+/// not be encoded with `base64` or `quoted-printable`. Headers which can only
+/// be represented with utf-8 (e.g. a mailbox with a non us-ascii local part)
+/// make the embedded mail be encoded with `MailType::Internationalized` (rfc6532).
+fn load_message<C: Context>(message: &Mail, ctx: &C) -> SendBoxFuture<EncData, MailError> {
+    let content_id = ctx.generate_content_id();
+    let fut = message
+        .clone()
+        .into_encodable_mail(ctx.clone())
+        .and_then(move |message| {
+            let encodings = message.transfer_encodings();
+            let has_8bit_bodies = encodings.iter().any(|&encoding| {
+                encoding == TransferEncoding::_8Bit || encoding == TransferEncoding::Binary
+            });
+            let mail_type = if requires_internationalized(&message) {
+                MailType::Internationalized
+            } else if has_8bit_bodies {
+                MailType::Mime8BitEnabled
+            } else {
+                MailType::Ascii
+            };
+            let encoding = if encodings.contains(&TransferEncoding::Binary) {
+                TransferEncoding::Binary
+            } else if mail_type == MailType::Ascii {
+                TransferEncoding::_7Bit
+            } else {
+                TransferEncoding::_8Bit
+            };
+
+            let mut buffer = EncodingBuffer::new(mail_type);
+            if encoding == TransferEncoding::Binary {
+                buffer = buffer.with_binary_bodies();
+            }
+            message.encode(&mut buffer)?;
+            let buffer: Vec<u8> = buffer.into();
+            let meta = Metadata {
+                file_meta: Default::default(),
+                //UNWRAP_SAFE: it's a valid media type
+                media_type: MediaType::parse("message/rfc822").unwrap(),
+                content_id,
+            };
+            Ok(EncData::new(buffer, meta, encoding))
+        });
+    Box::new(fut)
+}
+
+/// Returns true if the headers of the mail or of any of its body parts require `MailType::Internationalized`.
+///
+/// See `HeaderMap::requires_internationalized`.
+fn requires_internationalized(mail: &Mail) -> bool {
+    if mail.headers().requires_internationalized() {
+        return true;
+    }
+    match *mail.body() {
+        MailBody::SingleBody { .. } => false,
+        MailBody::MultipleBodies { ref bodies, .. } => {
+            bodies.iter().any(requires_internationalized)
+        }
+    }
+}
+
 /// a mail with all contained futures resolved, so that it can be encoded
 #[derive(Clone)]
-pub struct EncodableMail(Mail);
+pub struct EncodableMail {
+    mail: Mail,
+    suppress_mime_version: bool,
+}
 
 impl EncodableMail {
     /// Encode the mail using the given encoding buffer.
@@ -496,45 +793,309 @@ impl EncodableMail {
         ::encode::encode_mail(self, true, encoder)
     }
 
+    /// Encodes the mail into a human readable string, e.g. for printing it in a failing test.
+    ///
+    /// The mail is encoded with `MailType::Internationalized` and converted
+    /// to a string lossily. Bodies with a `base64` or `binary` transfer encoding
+    /// are replaced with a placeholder like `<1204 bytes of base64>`.
+    ///
+    /// This never fails, if encoding the mail fails the output contains
+    /// everything encoded up to the failure followed by a description of
+    /// the error. As such it must not be used to actually encode a mail.
+    pub fn to_debug_string(&self) -> String {
+        ::encode::encode_mail_for_debugging(self)
+    }
+
+    /// Returns the byte length the header section of the mail has once encoded.
+    ///
+    /// Unlike `HeaderMap::encoded_len` this includes the headers generated
+    /// when encoding, i.e. the `MIME-Version` header (if it's written, see
+    /// `writes_mime_version`) and for singlepart mails the `Content-Type`
+    /// and `Content-Transfer-Encoding` headers. Like it, it includes the
+    /// blank line terminating the header section.
+    ///
+    /// # Error
+    ///
+    /// Fails if any of the headers can not be encoded for the given mail type.
+    pub fn encoded_header_len(&self, mail_type: MailType) -> Result<usize, MailError> {
+        ::encode::encoded_header_len(self, mail_type)
+    }
+
     /// A wrapper for `encode` which will create a buffer, enocde the mail and then returns the buffers content.
     pub fn encode_into_bytes(&self, mail_type: MailType) -> Result<Vec<u8>, MailError> {
         let mut buffer = EncodingBuffer::new(mail_type);
         self.encode(&mut buffer)?;
         Ok(buffer.into())
     }
+
+    /// Encode the mail writing it to the given writer.
+    ///
+    /// This produces the same output as `encode_into_bytes` but doesn't
+    /// accumulate the whole encoded mail in memory. Only the headers are
+    /// buffered while being encoded (to keep track of the line length
+    /// limits) and are written to `out` once a header section is complete,
+    /// bodies are written directly to `out`.
+    ///
+    /// # Error
+    ///
+    /// Fails for the same reasons as `encode` or if writing to `out` fails,
+    /// in the later case some data might already have been written to `out`.
+    pub fn encode_to_writer<W: io::Write>(
+        &self,
+        mail_type: MailType,
+        out: &mut W,
+    ) -> Result<(), MailError> {
+        self.encode_to_writer_with_buffer(&mut EncodingBuffer::new(mail_type), out)
+    }
+
+    /// Like `encode_to_writer` but uses the given (empty) buffer for the headers.
+    ///
+    /// The mail type and other settings of the buffer, e.g. if bodies with
+    /// the `binary` transfer encoding are allowed, are used when encoding.
+    pub fn encode_to_writer_with_buffer<W: io::Write>(
+        &self,
+        buffer: &mut EncodingBuffer,
+        out: &mut W,
+    ) -> Result<(), MailError> {
+        ::encode::encode_mail_to_writer(self, buffer, out)
+    }
+
+    /// Returns a mutable reference to the headers of the top-level mail.
+    ///
+    /// This is crate internal as changing e.g. the `Content-Type` header
+    /// would invalidate the guarantees of an `EncodableMail`.
+    #[cfg(feature = "dkim")]
+    pub(crate) fn headers_mut(&mut self) -> &mut HeaderMap {
+        self.mail.headers_mut()
+    }
+
+    /// Inserts a `Content-MD5` header into every non-multipart body.
+    ///
+    /// The digest is computed from the body before it was transfer
+    /// encoded. Any already existing `Content-MD5` header of such a
+    /// body is overwritten.
+    ///
+    /// # Error
+    ///
+    /// Fails if a transfer encoded body can not be decoded again,
+    /// which should only happen for bodies not encoded by this crate.
+    pub fn insert_content_md5_headers(&mut self) -> Result<(), MailError> {
+        recursive_insert_content_md5(&mut self.mail)
+    }
+
+    /// Sets if the `MIME-Version` header should be omitted when encoding.
+    ///
+    /// By default the `MIME-Version: 1.0` header is written exactly once, in the
+    /// header section of the top-level mail (never in the header section of
+    /// body parts). For simple non-MIME mails (e.g. a singlepart `text/plain`
+    /// us-ascii mail) it can be suppressed with this method.
+    ///
+    /// Only mails with a `text/*` body consisting of us-ascii text with
+    /// the `7bit` transfer encoding can be interpreted without the
+    /// `MIME-Version` header, for all other mails (e.g. multipart mails
+    /// or mails with a `quoted-printable` encoded body) the header is
+    /// always written. The `Content-Type` and `Content-Transfer-Encoding`
+    /// headers are written in either case.
+    pub fn set_suppress_mime_version(&mut self, suppress: bool) {
+        self.suppress_mime_version = suppress;
+    }
+
+    /// Returns true if the `MIME-Version` header will be written when encoding.
+    pub fn writes_mime_version(&self) -> bool {
+        !self.suppress_mime_version || !self.has_non_mime_body()
+    }
+
+    fn has_non_mime_body(&self) -> bool {
+        match *self.mail.body() {
+            MailBody::SingleBody { ref body } => {
+                let data = assume_encoded(body);
+                data.encoding() == TransferEncoding::_7Bit
+                    && data.media_type().type_() == "text"
+                    && data.transfer_encoded_buffer().is_ascii()
+            }
+            MailBody::MultipleBodies { .. } => false,
+        }
+    }
+
+    /// Returns the transfer encodings of all non-multipart bodies in the order they appear in the mail.
+    ///
+    /// This can e.g. be used to check if the mail contains `8bit` or
+    /// `binary` bodies, which can only be send to a server supporting
+    /// the `8BITMIME` or `BINARYMIME` extension.
+    pub fn transfer_encodings(&self) -> Vec<TransferEncoding> {
+        self.mail
+            .bodies()
+            .map(|body| assume_encoded(body).encoding())
+            .collect()
+    }
+
+    /// Re-encodes all bodies using the `8bit` transfer encoding with a 7bit transfer encoding.
+    ///
+    /// The bodies are transfer decoded and encoded again with
+    /// `TransferEncodingHint::Auto`, i.e. with `quoted-printable` or
+    /// `base64` (compressed bodies always use `base64`). The metadata,
+    /// content coding and computed digest of the bodies are kept. This
+    /// is needed to send the mail to a server which doesn't support the
+    /// `8BITMIME` extension.
+    ///
+    /// Returns true if any body was re-encoded.
+    ///
+    /// # Error
+    ///
+    /// Fails if a `message/*` body uses the `8bit` transfer encoding, as
+    /// composite media types can't be encoded with `quoted-printable` or
+    /// `base64` (rfc2046).
+    pub fn downgrade_8bit_bodies(&mut self) -> Result<bool, MailError> {
+        recursive_downgrade_bodies(&mut self.mail, TransferEncoding::_8Bit)
+    }
+
+    /// Re-encodes all bodies using the `binary` transfer encoding with a 7bit transfer encoding.
+    ///
+    /// This works like `downgrade_8bit_bodies` and is needed to send the
+    /// mail to a server which doesn't support the `BINARYMIME` extension.
+    ///
+    /// Returns true if any body was re-encoded.
+    ///
+    /// # Error
+    ///
+    /// Fails if a `message/*` body uses the `binary` transfer encoding.
+    pub fn downgrade_binary_bodies(&mut self) -> Result<bool, MailError> {
+        recursive_downgrade_bodies(&mut self.mail, TransferEncoding::Binary)
+    }
 }
 
-fn top_level_validation(mail: &Mail) -> Result<(), HeaderValidationError> {
-    if mail.headers().contains(_From) {
-        Ok(())
-    } else {
+fn recursive_downgrade_bodies(
+    mail: &mut Mail,
+    encoding: TransferEncoding,
+) -> Result<bool, MailError> {
+    use self::MailBody::*;
+
+    match mail.body {
+        SingleBody { ref mut body } => {
+            let downgraded = {
+                let data = assume_encoded(body);
+                if data.encoding() != encoding {
+                    return Ok(false);
+                }
+                if data.media_type().type_() == "message" {
+                    return Err(EncodingError::from(EncodingErrorKind::NotEncodable {
+                        encoding: "7bit",
+                    })
+                    .with_place_or_else(|| Some(Place::Body))
+                    .with_str_context(data.media_type().full_type().to_string())
+                    .into());
+                }
+                data.downgrade_to_7bit()?
+            };
+            *body = Resource::EncData(downgraded);
+            Ok(true)
+        }
+        MultipleBodies { ref mut bodies, .. } => {
+            let mut any_downgraded = false;
+            for sub_mail in bodies {
+                any_downgraded |= recursive_downgrade_bodies(sub_mail, encoding)?;
+            }
+            Ok(any_downgraded)
+        }
+    }
+}
+
+fn recursive_insert_content_md5(mail: &mut Mail) -> Result<(), MailError> {
+    use self::MailBody::*;
+
+    let digest = match mail.body {
+        SingleBody { ref body } => {
+            let data = assume_encoded(body);
+            ContentMd5::body(header_components::ContentMd5::of(
+                data.transfer_decoded_buffer()?,
+            ))
+        }
+        MultipleBodies { ref mut bodies, .. } => {
+            for sub_mail in bodies {
+                recursive_insert_content_md5(sub_mail)?;
+            }
+            return Ok(());
+        }
+    };
+
+    mail.headers_mut().insert(digest);
+    Ok(())
+}
+
+/// inserts the `From`/`Sender` headers provided by the context if the mail has none
+fn insert_default_senders<C: Context>(mail: &mut Mail, ctx: &C) {
+    let headers = mail.headers_mut();
+    if !headers.contains(_From) {
+        if let Some(from) = ctx.default_from() {
+            headers.insert(_From::body(from));
+        }
+    }
+    if !headers.contains(Sender) {
+        if let Some(sender) = ctx.default_sender() {
+            headers.insert(Sender::body(sender));
+        }
+    }
+    if !headers.contains(Sender) && ctx.auto_sender() {
+        let first_of_many_from = headers
+            .get_single(_From)
+            .and_then(Result::ok)
+            .filter(|from| from.body().len() > 1)
+            .map(|from| from.body().first().clone());
+        if let Some(sender) = first_of_many_from {
+            headers.insert(Sender::body(sender));
+        }
+    }
+}
+
+fn top_level_validation<C: Context>(mail: &Mail, ctx: &C) -> Result<(), HeaderValidationError> {
+    if !mail.headers().contains(_From) {
         Err(OtherValidationError::NoFrom.into())
+    } else if ctx.require_message_id() && !mail.headers().contains(MessageId) {
+        Err(OtherValidationError::NoMessageId.into())
+    } else if is_auto_replied_list_mail(mail.headers()) {
+        if ctx.reject_auto_replied_list_mail() {
+            Err(OtherValidationError::AutoRepliedListMail.into())
+        } else {
+            warn!("auto-replied mail contains List-* headers, this can lead to mail loops");
+            Ok(())
+        }
+    } else {
+        Ok(())
     }
 }
 
+/// Returns true if the headers contain `Auto-Submitted: auto-replied` and any `List-*` header.
+fn is_auto_replied_list_mail(headers: &HeaderMap) -> bool {
+    let auto_replied = headers
+        .get(AutoSubmitted)
+        .filter_map(|res| res.ok())
+        .any(|body| *body.kind() == AutoSubmittedKind::AutoReplied);
+
+    auto_replied
+        && headers
+            .iter()
+            .any(|(name, _)| name.as_str().starts_with("List-"))
+}
+
 /// insert auto-generated headers like `Date`, `Message-Id` and `Content-Id`
 fn auto_gen_headers<C: Context>(mail: &mut Mail, encoded_resources: Vec<EncData>, ctx: &C) {
     {
         let headers = mail.headers_mut();
         if !headers.contains(Date) {
-            headers.insert(Date::body(DateTime::now()));
+            headers.insert(Date::body(ctx.now()));
         }
 
         if !headers.contains(MessageId) {
             headers.insert(MessageId::body(ctx.generate_message_id()));
         }
+
+        // never encode the Bcc recipients into the mail
+        headers.remove(Bcc);
     }
 
-    let mut iter = encoded_resources.into_iter();
-    mail.visit_mail_bodies_mut(&mut move |resource: &mut Resource| {
-        let enc_data = iter
-            .next()
-            .expect("[BUG] mail structure modified while turing it into encoded mail");
-        mem::replace(resource, Resource::EncData(enc_data));
-    });
+    replace_with_loaded_bodies(mail, encoded_resources);
 
-    let mut boundary_count = 0;
-    recursive_auto_gen_headers(mail, &mut boundary_count, ctx);
+    recursive_auto_gen_headers(mail, true, ctx);
 
     // Make sure no **top-level** body has a content-id field, as it already has a Message-Id
     mail.headers_mut().remove(ContentId);
@@ -560,9 +1121,17 @@ pub(crate) fn assume_encoded(resource: &Resource) -> &EncData {
 ///   - this overwrites any already contained content-id header
 ///
 /// For multipart mails this does:
-/// - create/overwrite the boundary for the `Content-Type` header
 /// - call this method for all bodies in the multipart body
-fn recursive_auto_gen_headers<C: Context>(mail: &mut Mail, boundary_count: &mut usize, ctx: &C) {
+/// - create/overwrite the boundary for the `Content-Type` header, the
+///   boundary is guaranteed to not appear in any (nested) body or in
+///   any boundary of a nested multipart body
+///
+/// For all mails which are not the top-level mail this also moves the
+/// structural headers (`Content-Type`, `Content-Id`, `Content-Disposition`)
+/// in front of any other headers of the body part.
+///
+/// Returns all boundaries generated for the mail (including nested bodies).
+fn recursive_auto_gen_headers<C: Context>(mail: &mut Mail, top: bool, ctx: &C) -> Vec<String> {
     let &mut Mail {
         ref mut headers,
         ref mut body,
@@ -576,26 +1145,93 @@ fn recursive_auto_gen_headers<C: Context>(mail: &mut Mail, boundary_count: &mut
                 current_file_meta_mut.replace_empty_fields_with(data.file_meta())
             }
 
-            headers.insert(ContentId::body(data.content_id().clone()));
+            let content_id = ContentId::body(data.content_id().clone());
+            if top {
+                headers.insert(content_id);
+            } else {
+                move_to_front(headers, ContentDisposition);
+                headers.prepend(content_id);
+            }
+
+            Vec::new()
         }
-        MailBody::MultipleBodies { ref mut bodies, .. } => {
-            let headers: &mut HeaderMap = headers;
-            let content_type: &mut Header<ContentType> = headers
-                .get_single_mut(ContentType)
-                .expect("[BUG] mail was already validated")
-                .expect("[BUG] mail was already validated");
+        MailBody::MultipleBodies {
+            ref mut bodies,
+            ref hidden_text,
+        } => {
+            let mut boundaries = Vec::new();
+            for sub_mail in bodies.iter_mut() {
+                boundaries.extend(recursive_auto_gen_headers(sub_mail, false, ctx));
+            }
+
+            let boundary = {
+                let mut contents = boundaries
+                    .iter()
+                    .map(|boundary| boundary.as_bytes())
+                    .collect::<Vec<_>>();
+                contents.push(hidden_text.as_bytes());
+                for sub_mail in bodies.iter() {
+                    collect_encoded_bodies(sub_mail, &mut contents);
+                }
+                ctx.generate_boundary_absent_from(&contents)
+            };
+
+            {
+                let headers: &mut HeaderMap = headers;
+                let content_type: &mut Header<ContentType> = headers
+                    .get_single_mut(ContentType)
+                    .expect("[BUG] mail was already validated")
+                    .expect("[BUG] mail was already validated");
+
+                content_type.set_param(BOUNDARY, boundary.clone());
+            }
+
+            if !top {
+                move_to_front(headers, ContentType);
+            }
 
-            let boundary = create_structured_random_boundary(*boundary_count);
-            *boundary_count += 1;
-            content_type.set_param(BOUNDARY, boundary);
+            boundaries.push(boundary);
+            boundaries
+        }
+    }
+}
 
+/// Pushes the transfer encoded buffers of all (nested) bodies of the mail to `out`.
+///
+/// # Panics
+///
+/// Panics if the resources are not transfer encoded.
+fn collect_encoded_bodies<'a>(mail: &'a Mail, out: &mut Vec<&'a [u8]>) {
+    match mail.body {
+        MailBody::SingleBody { ref body } => {
+            out.push(assume_encoded(body).transfer_encoded_data_buffer());
+        }
+        MailBody::MultipleBodies { ref bodies, .. } => {
             for sub_mail in bodies {
-                recursive_auto_gen_headers(sub_mail, boundary_count, ctx);
+                collect_encoded_bodies(sub_mail, out);
             }
         }
     }
 }
 
+/// Moves all headers with given name in front of all other headers.
+fn move_to_front<H: HasHeaderName>(headers: &mut HeaderMap, name: H) {
+    let name = name.get_name();
+    let moved = headers
+        .get_untyped(name)
+        .map(|header| header.boxed_clone())
+        .collect::<Vec<_>>();
+
+    if moved.is_empty() {
+        return;
+    }
+
+    headers.remove(name);
+    for header in moved.into_iter().rev() {
+        headers.prepend_untyped(header);
+    }
+}
+
 pub(crate) fn validate_multipart_headermap(headers: &HeaderMap) -> Result<(), MailError> {
     if headers.contains(ContentTransferEncoding) {
         return Err(OtherValidationError::ContentTransferEncodingHeaderGiven.into());
@@ -628,14 +1264,13 @@ pub(crate) fn validate_singlepart_headermap(
 impl Deref for EncodableMail {
     type Target = Mail;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.mail
     }
 }
 
 impl Into<Mail> for EncodableMail {
     fn into(self) -> Mail {
-        let EncodableMail(mail) = self;
-        mail
+        self.mail
     }
 }
 
@@ -645,6 +1280,42 @@ impl fmt::Debug for EncodableMail {
     }
 }
 
+/// Create a singlepart `Mail` from a body and a list of headers.
+///
+/// The headers are created the same way as with the `headers!` macro,
+/// i.e. this returns a `Result<Mail, ComponentCreationError>`.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use] extern crate mail_core;
+/// # extern crate mail_headers as headers;
+/// # use headers::{headers::*, header_components::Domain, error::ComponentCreationError};
+/// # use mail_core::{Mail, Resource};
+/// # use mail_core::default_impl::simple_context;
+/// # fn main() { (|| -> Result<(), ComponentCreationError> {
+/// # let domain = Domain::from_unchecked("example.com".to_owned());
+/// # let ctx = simple_context::new(domain, "xqi93".parse().unwrap()).unwrap();
+/// let mail = mail! {
+///     body: Resource::plain_text("Hy there!", &ctx),
+///     _From: [("I'm Awesome", "bla@examle.com")],
+///     _To: ["unknow@example.com"],
+///     Subject: "Hy there message"
+/// }?;
+/// # Ok(()) })().unwrap(); }
+/// ```
+#[macro_export]
+macro_rules! mail {
+    (body: $body:expr $(, $header:ty : $val:expr)* $(,)*) => ({
+        $crate::__mail_headers::headers! { $($header : $val),* }
+            .map(|headers| {
+                let mut mail = $crate::Mail::new_singlepart_mail($body);
+                mail.insert_headers(headers);
+                mail
+            })
+    });
+}
+
 #[cfg(test)]
 mod test {
     use std::fmt::Debug;
@@ -658,7 +1329,9 @@ mod test {
         use super::super::*;
         use super::{AssertDebug, AssertSend, AssertSync};
         use default_impl::test_context;
-        use headers::headers::{Comments, Subject};
+        use headers::headers::{Bcc, Cc, Comments, Subject, _To};
+        use headers::HeaderTryFrom;
+        use internals::MailType;
 
         impl AssertDebug for Mail {}
         impl AssertSend for Mail {}
@@ -718,6 +1391,52 @@ mod test {
             assert_eq!(body_count, 3);
         }
 
+        #[test]
+        fn bodies_iterates_in_document_order() {
+            let ctx = test_context();
+            let inner = Mail::new_multipart_mail(
+                MediaType::parse("multipart/alternative").unwrap(),
+                vec![Mail::plain_text("r1", &ctx), Mail::plain_text("r2", &ctx)],
+            );
+            let mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed").unwrap(),
+                vec![inner, Mail::plain_text("r3", &ctx)],
+            );
+
+            let texts = mail
+                .bodies()
+                .map(|body| match *body {
+                    Resource::Data(ref data) => data.buffer().to_vec(),
+                    ref other => panic!("unexpected body: {:?}", other),
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(texts, vec![b"r1".to_vec(), b"r2".to_vec(), b"r3".to_vec()]);
+        }
+
+        test!(mail_macro_creates_singlepart_mail_with_headers, {
+            let ctx = test_context();
+            let mail = mail! {
+                body: Resource::plain_text("r0", &ctx),
+                _To: ["bob@example.test"],
+                Subject: "hy",
+            }?;
+            assert!(mail.headers().contains(_To));
+            assert!(mail.headers().contains(Subject));
+            assert!(mail.bodies().next().is_some());
+            assert!(!mail.body().is_multipart());
+        });
+
+        #[test]
+        fn mail_macro_propagates_component_creation_errors() {
+            let ctx = test_context();
+            let res = mail! {
+                body: Resource::plain_text("r0", &ctx),
+                _To: ["not an email"]
+            };
+            assert_err!(res);
+        }
+
         test!(insert_header_set_a_header, {
             let ctx = test_context();
             let mut mail = Mail::plain_text("r0", &ctx);
@@ -736,15 +1455,175 @@ mod test {
             assert!(mail.headers().contains(Subject));
             assert!(mail.headers().contains(Comments));
         });
-    }
 
-    mod EncodableMail {
-        #![allow(non_snake_case)]
+        test!(recipients_unions_to_cc_and_bcc, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r0", &ctx);
+            mail.insert_headers(headers! {
+                _To: ["a@example.test", "b@example.test"],
+                Cc: ["b@EXAMPLE.test", "c@example.test"],
+                Bcc: ["A@example.test"]
+            }?);
+
+            let recipients = mail
+                .recipients()?
+                .iter()
+                .map(|email| format!("{}@{}", email.local_part.as_str(), email.domain.as_str()))
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                recipients,
+                vec![
+                    "a@example.test",
+                    "b@example.test",
+                    "c@example.test",
+                    "A@example.test"
+                ]
+            );
+        });
+
+        test!(request_read_receipt_sets_header, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r0", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["a@example.test"],
+                Subject: "hy"
+            }?);
+            mail.request_read_receipt(Mailbox::try_from(("Alice", "a@example.test"))?);
+
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            assert!(encoded.contains("\r\nDisposition-Notification-To: Alice <a@example.test>\r\n"));
+        });
+
+        test!(set_importance_sets_both_headers, {
+            use headers::header_components::Importance as ImportanceLevel;
+
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r0", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["a@example.test"],
+                Subject: "hy",
+                XPriority: 5u8
+            }?);
+            mail.set_importance(ImportanceLevel::High);
+
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            assert!(encoded.contains("\r\nImportance: high\r\n"));
+            assert!(encoded.contains("\r\nX-Priority: 1\r\n"));
+            assert!(!encoded.contains("X-Priority: 5"));
+        });
+
+        test!(mails_are_compared_structurally, {
+            let ctx = test_context();
+            let make_mail = |text: &str, subject: &str| {
+                let mut mail = Mail::new_multipart_mail(
+                    MediaType::parse("multipart/mixed").unwrap(),
+                    vec![
+                        Mail::plain_text(text, &ctx),
+                        Mail::new_singlepart_mail(Resource::from_path("./logo.png")),
+                    ],
+                );
+                mail.insert_headers(
+                    headers! {
+                        _From: ["a@example.test"],
+                        Subject: subject
+                    }
+                    .unwrap(),
+                );
+                mail
+            };
+
+            // content ids are generated per resource but are not compared
+            assert_eq!(make_mail("r0", "hy"), make_mail("r0", "hy"));
+            assert_ne!(make_mail("r0", "hy"), make_mail("r1", "hy"));
+            assert_ne!(make_mail("r0", "hy"), make_mail("r0", "hy there"));
+
+            let mut reordered = Mail::plain_text("r0", &ctx);
+            reordered.insert_headers(headers! { Subject: "hy", Comments: "c" }?);
+            let mut mail = Mail::plain_text("r0", &ctx);
+            mail.insert_headers(headers! { Comments: "c", Subject: "hy" }?);
+            assert_ne!(mail, reordered);
+
+            let image = Mail::new_singlepart_mail(Resource::from_path("./logo.png"));
+            let other_image = Mail::new_singlepart_mail(Resource::from_path("./other.png"));
+            assert_ne!(image, other_image);
+        });
+
+        test!(recipients_of_mail_without_recipients, {
+            let ctx = test_context();
+            let mail = Mail::plain_text("r0", &ctx);
+            assert!(mail.recipients()?.is_empty());
+        });
+
+        test!(recipients_fails_if_a_recipient_header_is_mistyped, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r0", &ctx);
+            mail.insert_headers(headers! { Cc: ["a@example.test"] }?);
+            mail.headers_mut()
+                .insert_raw(_To::name(), SoftAsciiString::from_unchecked("b@example.test"));
+
+            assert_err!(mail.recipients());
+        });
+
+        #[test]
+        fn preamble_of_singlepart_mail_is_none() {
+            let ctx = test_context();
+            let mail = Mail::plain_text("r0", &ctx);
+            assert!(mail.preamble().is_none());
+        }
+
+        test!(preamble_is_written_before_first_boundary, {
+            let ctx = test_context();
+            let preamble = "This is a multipart message in MIME format.";
+            let bodies = vec![Mail::plain_text("r0", &ctx), Mail::plain_text("r1", &ctx)];
+            let mut mail = Mail::new_multipart_mail(MediaType::parse("multipart/mixed")?, bodies)
+                .with_preamble(SoftAsciiString::from_unchecked(preamble));
+            assert_eq!(mail.preamble().unwrap().as_str(), preamble);
+            mail.insert_headers(headers! { _From: ["a@example.test"] }?);
+
+            let enc_mail = mail.into_encodable_mail(ctx).wait()?;
+            let boundary = enc_mail
+                .headers()
+                .get_single(ContentType)
+                .unwrap()?
+                .get_param(BOUNDARY)
+                .unwrap()
+                .to_content();
+            let encoded = String::from_utf8(enc_mail.encode_into_bytes(MailType::Ascii)?).unwrap();
+
+            let expected = format!("\r\n\r\n{}\r\n--{}\r\n", preamble, boundary);
+            assert!(encoded.contains(&expected));
+            assert_eq!(encoded.matches(preamble).count(), 1);
+        });
+
+        #[test]
+        #[should_panic]
+        fn setting_preamble_of_singlepart_mail_panics() {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r0", &ctx);
+            mail.set_preamble(SoftAsciiString::from_unchecked("preamble"));
+        }
+    }
+
+    mod EncodableMail {
+        #![allow(non_snake_case)]
         use super::super::*;
         use super::{AssertDebug, AssertSend, AssertSync};
         use chrono::{TimeZone, Utc};
         use default_impl::test_context;
-        use headers::headers::{ContentTransferEncoding, ContentType, Date, Subject, _From};
+        use headers::headers::{
+            Bcc, ContentDescription, ContentLanguage, ContentLocation, ContentMd5,
+            ContentTransferEncoding, ContentType, Date, ListUnsubscribe, Subject, _From, _To,
+        };
+        use headers::{header_components::MailboxList, HeaderTryFrom};
 
         impl AssertDebug for EncodableMail {}
         impl AssertSend for EncodableMail {}
@@ -813,6 +1692,95 @@ mod test {
             }
         }
 
+        #[test]
+        fn transfer_encodings_are_in_document_order() {
+            let ctx = test_context();
+            let binary = Resource::plain_text("abc", &ctx)
+                .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed").unwrap(),
+                vec![
+                    Mail::plain_text("abc", &ctx),
+                    Mail::new_singlepart_mail(binary),
+                    Mail::plain_text("Grüße from a mostly us-ascii text", &ctx),
+                ],
+            );
+            mail.insert_headers(headers! { _From: ["random@this.is.no.mail"] }.unwrap());
+
+            let enc_mail = mail.into_encodable_mail(ctx).wait().unwrap();
+            assert_eq!(
+                enc_mail.transfer_encodings(),
+                vec![
+                    TransferEncoding::_7Bit,
+                    TransferEncoding::Binary,
+                    TransferEncoding::QuotedPrintable
+                ]
+            );
+        }
+
+        #[test]
+        fn downgrades_8bit_bodies() {
+            let ctx = test_context();
+            let eight_bit = Resource::plain_text("Grüße from a mostly us-ascii text", &ctx)
+                .with_transfer_encoding_hint(TransferEncodingHint::AutoAllow8Bit);
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed").unwrap(),
+                vec![
+                    Mail::plain_text("abc", &ctx),
+                    Mail::new_singlepart_mail(eight_bit),
+                ],
+            );
+            mail.insert_headers(headers! { _From: ["random@this.is.no.mail"] }.unwrap());
+
+            let mut enc_mail = mail.into_encodable_mail(ctx).wait().unwrap();
+            assert_eq!(
+                enc_mail.transfer_encodings(),
+                vec![TransferEncoding::_7Bit, TransferEncoding::_8Bit]
+            );
+
+            assert!(enc_mail.downgrade_8bit_bodies().unwrap());
+            assert_eq!(
+                enc_mail.transfer_encodings(),
+                vec![TransferEncoding::_7Bit, TransferEncoding::QuotedPrintable]
+            );
+            let decoded = assume_encoded(enc_mail.bodies().nth(1).unwrap())
+                .transfer_decoded_buffer()
+                .unwrap()
+                .into_owned();
+            assert_eq!(decoded, "Grüße from a mostly us-ascii text".as_bytes());
+
+            assert!(!enc_mail.downgrade_8bit_bodies().unwrap());
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii).unwrap();
+            assert!(encoded.is_ascii());
+        }
+
+        #[test]
+        fn downgrades_binary_bodies() {
+            let ctx = test_context();
+            let binary = Resource::plain_text("Grüße\r\nwith binary data\0", &ctx)
+                .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+            let mut mail = Mail::new_singlepart_mail(binary);
+            mail.insert_headers(headers! { _From: ["random@this.is.no.mail"] }.unwrap());
+
+            let mut enc_mail = mail.into_encodable_mail(ctx).wait().unwrap();
+            assert!(!enc_mail.downgrade_8bit_bodies().unwrap());
+            assert_eq!(
+                enc_mail.transfer_encodings(),
+                vec![TransferEncoding::Binary]
+            );
+
+            assert!(enc_mail.downgrade_binary_bodies().unwrap());
+            assert_eq!(
+                enc_mail.transfer_encodings(),
+                vec![TransferEncoding::Base64]
+            );
+            let decoded = assume_encoded(enc_mail.bodies().next().unwrap())
+                .transfer_decoded_buffer()
+                .unwrap()
+                .into_owned();
+            assert_eq!(decoded, "Grüße\r\nwith binary data\0".as_bytes());
+        }
+
         #[test]
         fn runs_contextual_validators() {
             let ctx = test_context();
@@ -830,6 +1798,248 @@ mod test {
             assert_err!(mail.into_encodable_mail(ctx).wait());
         }
 
+        #[test]
+        fn uses_default_senders_of_context_if_absent() {
+            let ctx = test_context()
+                .with_default_from(MailboxList::try_from(["noreply@example.test"]).unwrap())
+                .with_default_sender(Mailbox::try_from("sender@example.test").unwrap());
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! { Subject: "hoho" }.unwrap());
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+
+            let from = enc_mail.headers().get_single(_From).unwrap().unwrap();
+            assert_eq!(
+                from.body().first().email,
+                Email::new("noreply@example.test").unwrap()
+            );
+            let sender = enc_mail.headers().get_single(Sender).unwrap().unwrap();
+            assert_eq!(
+                sender.body().email,
+                Email::new("sender@example.test").unwrap()
+            );
+        }
+
+        #[test]
+        fn mail_from_overrides_default_from_of_context() {
+            let ctx = test_context()
+                .with_default_from(MailboxList::try_from(["noreply@example.test"]).unwrap());
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! { _From: ["alice@example.test"] }.unwrap());
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+
+            let from = enc_mail.headers().get_single(_From).unwrap().unwrap();
+            assert_eq!(from.body().len(), 1);
+            assert_eq!(
+                from.body().first().email,
+                Email::new("alice@example.test").unwrap()
+            );
+            assert_not!(enc_mail.headers().contains(Sender));
+        }
+
+        #[test]
+        fn auto_sender_uses_first_from_mailbox() {
+            let ctx = test_context().with_auto_sender(true);
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(
+                headers! { _From: ["alice@example.test", "bob@example.test"] }.unwrap(),
+            );
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+
+            let sender = enc_mail.headers().get_single(Sender).unwrap().unwrap();
+            assert_eq!(
+                sender.body().email,
+                Email::new("alice@example.test").unwrap()
+            );
+        }
+
+        #[test]
+        fn auto_sender_is_disabled_by_default() {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(
+                headers! { _From: ["alice@example.test", "bob@example.test"] }.unwrap(),
+            );
+
+            assert_err!(mail.into_encodable_mail(ctx).wait());
+        }
+
+        #[test]
+        fn auto_sender_does_not_override_sender() {
+            let ctx = test_context().with_auto_sender(true);
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(
+                headers! {
+                    _From: ["alice@example.test", "bob@example.test"],
+                    Sender: "carol@example.test"
+                }
+                .unwrap(),
+            );
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+
+            let sender = enc_mail.headers().get_single(Sender).unwrap().unwrap();
+            assert_eq!(
+                sender.body().email,
+                Email::new("carol@example.test").unwrap()
+            );
+        }
+
+        #[test]
+        fn auto_sender_ignores_single_from_mailbox() {
+            let ctx = test_context().with_auto_sender(true);
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! { _From: ["alice@example.test"] }.unwrap());
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            assert_not!(enc_mail.headers().contains(Sender));
+        }
+
+        fn forwarding_mail(inner: Mail, ctx: &impl Context) -> Mail {
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed").unwrap(),
+                vec![
+                    Mail::plain_text("see attached", ctx),
+                    Mail::new_singlepart_mail(Resource::message(inner)),
+                ],
+            );
+            mail.insert_headers(headers! { _From: ["bob@example.test"] }.unwrap());
+            mail
+        }
+
+        #[test]
+        fn embeds_message_resource_as_rfc822_body() {
+            let ctx = test_context();
+            let mut inner = Mail::plain_text("forwarded text", &ctx);
+            inner.insert_headers(
+                headers! {
+                    _From: ["alice@example.test"],
+                    Subject: "original"
+                }
+                .unwrap(),
+            );
+
+            let enc_mail = assert_ok!(forwarding_mail(inner, &ctx).into_encodable_mail(ctx).wait());
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii).unwrap();
+            let encoded = String::from_utf8(encoded).unwrap();
+
+            assert!(encoded.contains("Content-Type: message/rfc822\r\n"));
+            assert!(encoded.contains("Content-Transfer-Encoding: 7bit\r\n"));
+            assert!(encoded.contains("Subject: original\r\n"));
+            assert!(encoded.contains("forwarded text"));
+            // the outer and the embedded mail got a date and message id
+            assert_eq!(encoded.matches("\r\nDate: ").count(), 2);
+            assert_eq!(encoded.matches("\r\nMessage-Id: ").count(), 2);
+
+            let message = enc_mail.bodies().nth(1).unwrap();
+            let enc_data = assume_encoded(message);
+            assert_eq!(enc_data.media_type().as_str_repr(), "message/rfc822");
+            assert_eq!(enc_data.encoding(), TransferEncoding::_7Bit);
+        }
+
+        #[test]
+        fn embeds_message_with_utf8_headers_as_internationalized_mail() {
+            let ctx = test_context();
+            let mut inner = Mail::plain_text("forwarded text", &ctx);
+            inner.insert_headers(headers! { _From: ["jörg@example.test"] }.unwrap());
+
+            let enc_mail = assert_ok!(forwarding_mail(inner, &ctx).into_encodable_mail(ctx).wait());
+            let message = enc_mail.bodies().nth(1).unwrap();
+            let enc_data = assume_encoded(message);
+            assert_eq!(enc_data.encoding(), TransferEncoding::_8Bit);
+            let encoded = String::from_utf8(enc_data.transfer_encoded_buffer().to_vec()).unwrap();
+            assert!(encoded.contains("From: <jörg@example.test>\r\n"));
+        }
+
+        #[test]
+        fn message_resources_are_validated() {
+            let ctx = test_context();
+            let inner = Mail::plain_text("forwarded text", &ctx);
+
+            assert_err!(forwarding_mail(inner, &ctx).into_encodable_mail(ctx).wait());
+        }
+
+        #[test]
+        fn generates_message_id_by_default() {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! { _From: ["alice@example.test"] }.unwrap());
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            assert!(enc_mail.headers().contains(MessageId));
+        }
+
+        #[test]
+        fn require_message_id_rejects_mails_without_message_id() {
+            let ctx = test_context().with_require_message_id(true);
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! { _From: ["alice@example.test"] }.unwrap());
+
+            match mail.into_encodable_mail(ctx).wait() {
+                Err(MailError::Validation(err)) => {
+                    assert_eq!(err.to_string(), "mail did not contain a Message-Id header")
+                }
+                other => panic!("unexpected result: {:?}", other.map(|_| ())),
+            }
+        }
+
+        #[test]
+        fn require_message_id_accepts_mails_with_message_id() {
+            let ctx = test_context().with_require_message_id(true);
+            let message_id = ctx.generate_message_id();
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(
+                headers! {
+                    _From: ["alice@example.test"],
+                    MessageId: message_id.clone()
+                }
+                .unwrap(),
+            );
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            let used_id = enc_mail.headers().get_single(MessageId).unwrap().unwrap();
+            assert_eq!(used_id.body(), &message_id);
+        }
+
+        fn auto_replied_list_mail(ctx: &impl Context) -> Mail {
+            let mut mail = Mail::plain_text("r9", ctx);
+            mail.insert_headers(
+                headers! {
+                    _From: ["alice@example.test"],
+                    AutoSubmitted: AutoSubmittedKind::AutoReplied,
+                    ListUnsubscribe: ["mailto:leave@example.test"]
+                }
+                .unwrap(),
+            );
+            mail
+        }
+
+        #[test]
+        fn auto_replied_list_mails_are_accepted_by_default() {
+            let ctx = test_context();
+            let mail = auto_replied_list_mail(&ctx);
+            assert_ok!(mail.into_encodable_mail(ctx).wait());
+        }
+
+        #[test]
+        fn auto_replied_list_mails_can_be_rejected() {
+            let ctx = test_context().with_reject_auto_replied_list_mail(true);
+            let mail = auto_replied_list_mail(&ctx);
+
+            match mail.into_encodable_mail(ctx.clone()).wait() {
+                Err(MailError::Validation(err)) => {
+                    assert_eq!(err.to_string(), "auto-replied mail contained List-* headers")
+                }
+                other => panic!("unexpected result: {:?}", other.map(|_| ())),
+            }
+
+            let mut mail = auto_replied_list_mail(&ctx);
+            mail.insert_header(AutoSubmitted::body(AutoSubmittedKind::AutoGenerated.into()));
+            assert_ok!(mail.into_encodable_mail(ctx).wait());
+        }
+
         #[test]
         fn checks_there_is_from() {
             let ctx = test_context();
@@ -846,6 +2056,352 @@ mod test {
             assert_err!(mail.into_encodable_mail(ctx).wait());
         }
 
+        test!(debug_string_elides_base64_and_binary_bodies, {
+            let ctx = test_context();
+            let attachment = Data::new(
+                vec![0xFF; 30],
+                Metadata {
+                    file_meta: Default::default(),
+                    media_type: MediaType::parse("application/octet-stream")?,
+                    content_id: ctx.generate_content_id(),
+                },
+            );
+            let binary = Data::plain_text("bin\0ary", ctx.generate_content_id())
+                .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed")?,
+                vec![
+                    Mail::plain_text("Viele Grüße aus Berlin", &ctx),
+                    Mail::new_singlepart_mail(Resource::Data(attachment)),
+                    Mail::new_singlepart_mail(Resource::Data(binary)),
+                ],
+            );
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            let debug_string = enc_mail.to_debug_string();
+
+            assert!(debug_string.contains("Subject: hoho\r\n"));
+            assert!(debug_string.contains("Viele Gr=C3=BC=C3=9Fe aus Berlin"));
+            assert!(debug_string.contains("\r\n<40 bytes of base64>\r\n"));
+            assert!(debug_string.contains("\r\n<7 bytes of binary>\r\n"));
+            assert!(!debug_string.contains("encoding failed"));
+            // the binary body can't be encoded with `MailType::Internationalized`
+            assert!(enc_mail
+                .encode_into_bytes(MailType::Internationalized)
+                .is_err());
+        });
+
+        test!(structural_headers_precede_user_headers_in_body_parts, {
+            let ctx = test_context();
+            let mut body_part = Mail::plain_text("r9", &ctx);
+            body_part.insert_header(ContentDescription::auto_body("user header")?);
+
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed")?,
+                vec![body_part]
+            );
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+
+            let body_part_start = encoded.find("\r\n--").unwrap();
+            let body_part = &encoded[body_part_start..];
+            let user_header_idx = body_part.find("Content-Description: ").unwrap();
+            let content_type_idx = body_part.find("Content-Type: ").unwrap();
+            let encoding_idx = body_part.find("Content-Transfer-Encoding: ").unwrap();
+            let content_id_idx = body_part.find("Content-Id: ").unwrap();
+
+            assert!(content_type_idx < encoding_idx);
+            assert!(encoding_idx < content_id_idx);
+            assert!(content_id_idx < user_header_idx);
+        });
+
+        test!(body_parts_can_have_language_and_location_headers, {
+            let ctx = test_context();
+            let mut body_part = Mail::plain_text("Hallo", &ctx);
+            body_part.insert_headers(headers! {
+                ContentLanguage: ["de", "de-AT"],
+                ContentLocation: "https://example.com/greeting.txt"
+            }?);
+
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed")?,
+                vec![body_part]
+            );
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+
+            let body_part_start = encoded.find("\r\n--").unwrap();
+            let body_part = &encoded[body_part_start..];
+            assert!(body_part.contains("\r\nContent-Language: de, de-AT\r\n"));
+            assert!(body_part.contains(
+                "\r\nContent-Location: https://example.com/greeting.txt\r\n"
+            ));
+        });
+
+        test!(bcc_is_not_part_of_the_encoded_mail, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                _To: ["to@this.is.no.mail"],
+                Bcc: ["secret@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            assert!(!enc_mail.headers().contains(Bcc));
+
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            assert!(!encoded.contains("Bcc:"));
+            assert!(!encoded.contains("secret@this.is.no.mail"));
+        });
+
+        test!(mime_version_only_on_top_level_mail, {
+            let ctx = test_context();
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed")?,
+                vec![Mail::plain_text("abc", &ctx), Mail::plain_text("def", &ctx)],
+            );
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let mut enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            // can't be suppressed for multipart mails
+            enc_mail.set_suppress_mime_version(true);
+            assert!(enc_mail.writes_mime_version());
+
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            assert!(encoded.starts_with("MIME-Version: 1.0\r\n"));
+            assert_eq!(encoded.matches("MIME-Version").count(), 1);
+        });
+
+        test!(encoded_header_len_matches_encoded_header_section, {
+            let ctx = test_context();
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed")?,
+                vec![Mail::plain_text("abc", &ctx), Mail::plain_text("def", &ctx)],
+            );
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "this is a quite long subject which will need to be folded \
+                          as it goes well beyond the soft line length limit"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            let header_section_len = encoded.find("\r\n\r\n").unwrap() + 4;
+
+            // a multipart mail has no generated headers except `MIME-Version`
+            let mime_version = "MIME-Version: 1.0\r\n";
+            assert!(encoded.starts_with(mime_version));
+            assert!(encoded[..header_section_len].contains("\r\n "));
+            assert_eq!(
+                enc_mail.encoded_header_len(MailType::Ascii)?,
+                header_section_len
+            );
+            assert_eq!(
+                enc_mail.headers().encoded_len(MailType::Ascii)? + mime_version.len(),
+                header_section_len
+            );
+        });
+
+        test!(encoded_header_len_includes_generated_headers, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("abc", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"]
+            }?);
+            let mut enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+
+            for &suppress in &[false, true] {
+                enc_mail.set_suppress_mime_version(suppress);
+                let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+                let encoded = String::from_utf8(encoded).unwrap();
+                let header_section_len = encoded.find("\r\n\r\n").unwrap() + 4;
+
+                assert_eq!(encoded.starts_with("MIME-Version: 1.0\r\n"), !suppress);
+                assert_eq!(
+                    enc_mail.encoded_header_len(MailType::Ascii)?,
+                    header_section_len
+                );
+            }
+        });
+
+        test!(mime_version_can_be_suppressed_for_singlepart_mails, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("abc", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let mut enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            assert!(enc_mail.writes_mime_version());
+            enc_mail.set_suppress_mime_version(true);
+
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            assert_not!(encoded.contains("MIME-Version"));
+        });
+
+        test!(mime_version_is_not_suppressed_for_non_ascii_bodies, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("Grüße", &ctx);
+            mail.insert_headers(headers! { _From: ["random@this.is.no.mail"] }?);
+
+            let mut enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            enc_mail.set_suppress_mime_version(true);
+            assert!(enc_mail.writes_mime_version());
+
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            assert!(encoded.starts_with("MIME-Version: 1.0\r\n"));
+        });
+
+        test!(inserts_content_md5_of_body_parts, {
+            let ctx = test_context();
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed")?,
+                vec![Mail::plain_text("abc", &ctx)]
+            );
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let mut enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            enc_mail.insert_content_md5_headers()?;
+
+            assert_not!(enc_mail.headers().contains(ContentMd5));
+            if let MailBody::MultipleBodies { ref bodies, .. } = enc_mail.body {
+                let md5 = bodies[0].headers().get_single(ContentMd5).unwrap().unwrap();
+                assert_eq!(md5.body().digest(), &[
+                    0x90, 0x01, 0x50, 0x98, 0x3c, 0xd2, 0x4f, 0xb0,
+                    0xd6, 0x96, 0x3f, 0x7d, 0x28, 0xe1, 0x7f, 0x72
+                ]);
+            } else {
+                unreachable!()
+            }
+
+            let encoded = enc_mail.encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            assert!(encoded.contains("\r\nContent-Md5: kAFQmDzST7DWlj99KOF/cg==\r\n"));
+        });
+
+        test!(encodes_binary_body_verbatim, {
+            let ctx = test_context();
+            let body = b"\0raw\rbinary\ndata\xff".to_vec();
+            let data = Data::new(
+                body.clone(),
+                Metadata {
+                    file_meta: Default::default(),
+                    media_type: MediaType::parse("application/octet-stream")?,
+                    content_id: ctx.generate_content_id(),
+                },
+            )
+            .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+
+            let mut mail = Mail::new_singlepart_mail(Resource::Data(data));
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            assert_err!(enc_mail.encode_into_bytes(MailType::Ascii));
+
+            let mut buffer = EncodingBuffer::new(MailType::Ascii).with_binary_bodies();
+            enc_mail.encode(&mut buffer)?;
+            let encoded: Vec<u8> = buffer.into();
+            let header_end = encoded.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+            let header_section = String::from_utf8(encoded[..header_end].to_vec()).unwrap();
+
+            assert!(header_section.contains("\r\nContent-Transfer-Encoding: binary"));
+            assert_eq!(&encoded[header_end + 4..], &body[..]);
+        });
+
+        test!(encode_to_writer_produces_same_output_as_buffer, {
+            let ctx = test_context();
+            let binary = Data::new(
+                b"\0raw\rbinary\ndata\xff".to_vec(),
+                Metadata {
+                    file_meta: Default::default(),
+                    media_type: MediaType::parse("application/octet-stream")?,
+                    content_id: ctx.generate_content_id(),
+                },
+            )
+            .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+
+            let mut mail = Mail::new_multipart_mail(
+                MediaType::parse("multipart/mixed")?,
+                vec![
+                    Mail::plain_text("r9", &ctx),
+                    Mail::new_singlepart_mail(Resource::Data(binary)),
+                ],
+            );
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            let mut buffer = EncodingBuffer::new(MailType::Ascii).with_binary_bodies();
+            enc_mail.encode(&mut buffer)?;
+            let expected: Vec<u8> = buffer.into();
+
+            let mut buffer = EncodingBuffer::new(MailType::Ascii).with_binary_bodies();
+            let mut written = Vec::new();
+            enc_mail.encode_to_writer_with_buffer(&mut buffer, &mut written)?;
+
+            assert_eq!(written, expected);
+        });
+
+        test!(encode_to_writer_reports_io_errors, {
+            struct FailingWriter;
+            impl io::Write for FailingWriter {
+                fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                    Err(io::ErrorKind::BrokenPipe.into())
+                }
+                fn flush(&mut self) -> io::Result<()> {
+                    Ok(())
+                }
+            }
+
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"]
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            match enc_mail.encode_to_writer(MailType::Ascii, &mut FailingWriter) {
+                Err(MailError::Io(_)) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        });
+
         test!(does_not_override_date_if_set, {
             let ctx = test_context();
             let provided_date = Utc.ymd(1992, 5, 25).and_hms(23, 41, 12);
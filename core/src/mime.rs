@@ -65,9 +65,92 @@ pub fn create_structured_random_boundary(count: usize) -> String {
     out
 }
 
+/// The number of candidates `find_boundary_absent_from` takes from `gen`.
+const MAX_GENERATED_BOUNDARY_ATTEMPTS: usize = 16;
+
+/// Generates boundaries with `gen` until one is found which is absent from all `bodies`.
+///
+/// `gen` is called with the number of the attempt (starting with 0) and
+/// should return a new boundary candidate each time it is called. A
+/// candidate is rejected if it is a substring of any of the bodies.
+///
+/// Given that `gen` returns random boundaries (like e.g.
+/// `create_structured_random_boundary`) this will practically never
+/// need more than one attempt, but it guarantees that the returned
+/// boundary does not collide with the bodies, even if they contain
+/// adversarial content.
+///
+/// If none of the first `MAX_GENERATED_BOUNDARY_ATTEMPTS` candidates is
+/// absent from the bodies (e.g. because `gen` is deterministic) random
+/// boundaries created with `create_structured_random_boundary` are used
+/// instead of the ones returned by `gen`.
+pub(crate) fn find_boundary_absent_from<F>(bodies: &[&[u8]], mut gen: F) -> String
+where
+    F: FnMut(usize) -> String,
+{
+    let mut attempt = 0;
+    loop {
+        let boundary = if attempt < MAX_GENERATED_BOUNDARY_ATTEMPTS {
+            gen(attempt)
+        } else {
+            create_structured_random_boundary(attempt)
+        };
+        let collides = bodies
+            .iter()
+            .any(|body| contains_subslice(body, boundary.as_bytes()));
+
+        if !collides {
+            return boundary;
+        }
+        attempt += 1;
+    }
+}
+
+fn contains_subslice(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty()
+        || haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
+
 #[cfg(test)]
 mod test {
 
+    mod find_boundary_absent_from {
+        use super::super::*;
+
+        #[test]
+        fn skips_candidates_contained_in_a_body() {
+            let body: &[u8] = b"some text\r\n--=_^0.collision\r\nmore text";
+            let other: &[u8] = b"=_^1.collision";
+            let boundary = find_boundary_absent_from(&[body, other], |attempt| {
+                format!("=_^{}.collision", attempt)
+            });
+            assert_eq!(boundary, "=_^2.collision");
+        }
+
+        #[test]
+        fn falls_back_to_random_boundaries_if_gen_keeps_colliding() {
+            let body: &[u8] = b"--=_^0.constant--";
+            let mut calls = 0;
+            let boundary = find_boundary_absent_from(&[body], |_attempt| {
+                calls += 1;
+                "=_^0.constant".to_owned()
+            });
+            assert_eq!(calls, MAX_GENERATED_BOUNDARY_ATTEMPTS);
+            assert!(boundary.starts_with(ANTI_COLLISION_CHARS));
+            assert!(!contains_subslice(body, boundary.as_bytes()));
+        }
+
+        #[test]
+        fn returns_first_candidate_if_there_is_no_collision() {
+            let boundary = find_boundary_absent_from(&[b"abc", b"def"], |attempt| {
+                format!("=_^{}.ok", attempt)
+            });
+            assert_eq!(boundary, "=_^0.ok");
+        }
+    }
+
     mod write_random_boundary_to {
         use super::super::*;
 
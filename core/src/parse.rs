@@ -0,0 +1,482 @@
+//! Module implementing the parsing of already encoded mails.
+//!
+//! Only the structure of the mail is parsed, i.e. the bodies are kept
+//! transfer encoded (as `Resource::EncData`) and multipart bodies are
+//! split at their boundaries. Header bodies are parsed using
+//! `mail_headers::parse::parse_header`, i.e. some common headers are
+//! parsed into their typed representation and all other headers (as well
+//! as headers which can't be parsed into it, e.g. address lists using the
+//! group syntax) are kept as raw (unstructured) headers. The `Content-Type`,
+//! `Content-Transfer-Encoding` and `Content-Id` headers of non-multipart
+//! bodies are moved into the `Resource` and the `MIME-Version` header is
+//! dropped (it's generated when encoding the mail). A malformed
+//! `Content-Type` header is treated as `text/plain; charset=us-ascii`
+//! (rfc2045, section 5.2). The preamble of a multipart body is kept as
+//! it's hidden text if it is ascii, the epilogue is dropped.
+//!
+//! Encoded words (rfc2047) are not decoded.
+use std::str;
+
+use headers::{
+    error::ComponentCreationError,
+    header_components::{FileMeta, MediaType, TransferEncoding},
+    parse::{parse_header, parse_message_id},
+    HeaderKind, HeaderMap, HeaderName,
+};
+use internals::grammar::{is_ftext, is_ws};
+use soft_ascii_string::SoftAsciiString;
+
+use crate::{
+    context::Context,
+    error::{MailError, MailParseError},
+    mail::Mail,
+    resource::{EncData, Metadata, Resource},
+};
+
+/// Parses a mail (or a body part of a multipart body).
+pub(crate) fn parse_mail(bytes: &[u8], ctx: &impl Context) -> Result<Mail, MailError> {
+    let (header_section, body) = split_header_section(bytes);
+    let header_section =
+        str::from_utf8(header_section).map_err(|_| MailParseError::NonUtf8HeaderSection)?;
+
+    let mut headers = HeaderMap::new();
+    let mut media_type = None;
+    let mut encoding = TransferEncoding::_7Bit;
+    let mut content_id = None;
+
+    for (name, value) in unfold_header_lines(header_section)? {
+        if name.eq_ignore_ascii_case("Content-Type") {
+            media_type = MediaType::parse(&value).ok();
+        } else if name.eq_ignore_ascii_case("Content-Transfer-Encoding") {
+            encoding = parse_transfer_encoding(&value)?;
+        } else if name.eq_ignore_ascii_case("Content-Id") {
+            content_id = Some(parse_message_id(&value)?);
+        } else if !name.eq_ignore_ascii_case("MIME-Version") {
+            insert_header(&mut headers, name, value)?;
+        }
+    }
+
+    let media_type = match media_type {
+        Some(media_type) => media_type,
+        //UNWRAP_SAFE: it's a valid media type
+        None => MediaType::parse("text/plain; charset=us-ascii").unwrap(),
+    };
+
+    let mut mail = if media_type.is_multipart() {
+        let boundary = media_type
+            .get_param("boundary")
+            .ok_or(MailParseError::MissingBoundary)?
+            .to_content();
+
+        let (preamble, parts) = split_multipart_body(body, &boundary);
+        let bodies = parts
+            .into_iter()
+            .map(|part| parse_mail(part, ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut mail = Mail::new_multipart_mail(media_type, bodies);
+        if let Some(preamble) = ascii_preamble(preamble) {
+            mail.set_preamble(preamble);
+        }
+        if let Some(content_id) = content_id {
+            mail.insert_header(::headers::headers::ContentId::body(content_id));
+        }
+        mail
+    } else {
+        let meta = Metadata {
+            file_meta: FileMeta::default(),
+            media_type,
+            content_id: content_id.unwrap_or_else(|| ctx.generate_content_id()),
+        };
+        let enc_data = EncData::new(body.to_vec(), meta, encoding);
+        Mail::new_singlepart_mail(Resource::EncData(enc_data))
+    };
+
+    mail.insert_headers(headers);
+    Ok(mail)
+}
+
+/// Splits the header section from the body at the first blank line.
+///
+/// Neither the header section nor the body contain the blank line. If
+/// there is no blank line everything is treated as header section.
+fn split_header_section(bytes: &[u8]) -> (&[u8], &[u8]) {
+    let mut line_start = 0;
+    while line_start < bytes.len() {
+        let line_end = find_line_end(bytes, line_start);
+        let line = &bytes[line_start..line_end];
+        if line.is_empty() || line == b"\r" {
+            let body_start = (line_end + 1).min(bytes.len());
+            return (&bytes[..line_start], &bytes[body_start..]);
+        }
+        line_start = line_end + 1;
+    }
+    (bytes, &[])
+}
+
+/// Returns the index of the next `'\n'` starting at `start` or the length of `bytes`.
+fn find_line_end(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|&bch| bch == b'\n')
+        .map(|idx| start + idx)
+        .unwrap_or_else(|| bytes.len())
+}
+
+/// Splits the header section into (name, value) pairs unfolding folded lines.
+fn unfold_header_lines(header_section: &str) -> Result<Vec<(&str, String)>, MailParseError> {
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    for line in header_section.split('\n') {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with(is_ws) {
+            match fields.last_mut() {
+                Some(&mut (_, ref mut value)) => value.push_str(line),
+                None => return Err(MailParseError::MalformedHeaderLine(line.to_owned())),
+            }
+            continue;
+        }
+
+        let colon_idx = line
+            .find(':')
+            .ok_or_else(|| MailParseError::MalformedHeaderLine(line.to_owned()))?;
+        let name = &line[..colon_idx];
+        if name.is_empty() || !name.chars().all(is_ftext) {
+            return Err(MailParseError::MalformedHeaderLine(line.to_owned()));
+        }
+        fields.push((name, line[colon_idx + 1..].to_owned()));
+    }
+
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| (name, value.trim_matches(is_ws).to_owned()))
+        .collect();
+    Ok(fields)
+}
+
+fn insert_header(headers: &mut HeaderMap, name: &str, value: String) -> Result<(), MailError> {
+    let name = HeaderName::parse(name)
+        .map_err(|_| MailParseError::MalformedHeaderLine(format!("{}: {}", name, value)))?;
+    headers.insert_untyped(parse_header(name, &value));
+    Ok(())
+}
+
+fn parse_transfer_encoding(value: &str) -> Result<TransferEncoding, ComponentCreationError> {
+    let encoding = match value.to_ascii_lowercase().as_str() {
+        "7bit" => TransferEncoding::_7Bit,
+        "8bit" => TransferEncoding::_8Bit,
+        "binary" => TransferEncoding::Binary,
+        "quoted-printable" => TransferEncoding::QuotedPrintable,
+        "base64" => TransferEncoding::Base64,
+        _ => {
+            return Err(ComponentCreationError::new_with_str(
+                "TransferEncoding",
+                value,
+            ))
+        }
+    };
+    Ok(encoding)
+}
+
+/// Returns the preamble as `SoftAsciiString` if it is ascii.
+fn ascii_preamble(preamble: &[u8]) -> Option<SoftAsciiString> {
+    let preamble = str::from_utf8(preamble).ok()?;
+    SoftAsciiString::from_string(preamble.to_owned()).ok()
+}
+
+/// Splits a multipart body into it's preamble and the bodies of it's parts.
+///
+/// The epilogue is dropped. The line break in front of a delimiter line
+/// is part of the delimiter, so it's neither included in the preamble
+/// nor in the part in front of it. If there is no delimiter at all the
+/// whole body is the preamble. If the closing delimiter is missing the
+/// last part ends at the end of the body.
+fn split_multipart_body<'a>(body: &'a [u8], boundary: &str) -> (&'a [u8], Vec<&'a [u8]>) {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+
+    let mut preamble = body;
+    let mut parts = Vec::new();
+    let mut part_start = None;
+    let mut line_start = 0;
+    while line_start < body.len() {
+        let line_end = find_line_end(body, line_start);
+        let next_line_start = (line_end + 1).min(body.len());
+
+        let mut line = &body[line_start..line_end];
+        while let Some((&last, rest)) = line.split_last() {
+            if last == b'\r' || last == b' ' || last == b'\t' {
+                line = rest;
+            } else {
+                break;
+            }
+        }
+
+        if line.starts_with(delimiter) {
+            let rest = &line[delimiter.len()..];
+            let is_close_delimiter = rest == b"--";
+            if rest.is_empty() || is_close_delimiter {
+                match part_start {
+                    Some(start) => {
+                        parts.push(&body[start..end_before_line_break(body, start, line_start)])
+                    }
+                    None => preamble = &body[..end_before_line_break(body, 0, line_start)],
+                }
+                if is_close_delimiter {
+                    return (preamble, parts);
+                }
+                part_start = Some(next_line_start);
+            }
+        }
+        line_start = line_end + 1;
+    }
+
+    if let Some(start) = part_start {
+        parts.push(&body[start.min(body.len())..]);
+    }
+    (preamble, parts)
+}
+
+/// Returns `end` moved in front of the line break directly in front of it (if there is one).
+fn end_before_line_break(body: &[u8], start: usize, end: usize) -> usize {
+    let mut end = end;
+    if end > start && body[end - 1] == b'\n' {
+        end -= 1;
+        if end > start && body[end - 1] == b'\r' {
+            end -= 1;
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+
+    use headers::{
+        header_components::Email,
+        headers::{_From, _To, Subject},
+    };
+    use internals::MailType;
+
+    use super::*;
+    use crate::{default_impl::test_context, mail::MailBody};
+
+    const NESTED_MAIL: &str = concat!(
+        "From: Alice <alice@example.test>\r\n",
+        "To: bob@example.test, \"Carl, Jr.\" <carl@example.test>\r\n",
+        "Subject: a folded\r\n",
+        " subject\r\n",
+        "X-CUSTOM-HEADER: some\r\n",
+        "\tvalue\r\n",
+        "MIME-Version: 1.0\r\n",
+        "Content-Type: multipart/mixed; boundary=\"outer\"\r\n",
+        "\r\n",
+        "preamble\r\n",
+        "--outer\r\n",
+        "Content-Type: multipart/alternative; boundary=inner\r\n",
+        "\r\n",
+        "--inner\r\n",
+        "Content-Type: text/plain; charset=utf-8\r\n",
+        "\r\n",
+        "plain text\r\n",
+        "--inner\r\n",
+        "Content-Type: text/html; charset=utf-8\r\n",
+        "Content-Transfer-Encoding: quoted-printable\r\n",
+        "\r\n",
+        "<p>html</p>\r\n",
+        "--inner--\r\n",
+        "--outer\r\n",
+        "Content-Type: application/octet-stream\r\n",
+        "Content-Transfer-Encoding: base64\r\n",
+        "Content-Id: <attachment@example.test>\r\n",
+        "\r\n",
+        "AAEC\r\n",
+        "--outer--\r\n",
+        "epilogue\r\n"
+    );
+
+    fn bodies(mail: &Mail) -> &[Mail] {
+        match *mail.body() {
+            MailBody::MultipleBodies { ref bodies, .. } => bodies,
+            _ => panic!("expected multipart body"),
+        }
+    }
+
+    fn enc_data(mail: &Mail) -> &EncData {
+        match *mail.body() {
+            MailBody::SingleBody {
+                body: Resource::EncData(ref enc_data),
+            } => enc_data,
+            _ => panic!("expected singlepart body with encoded data"),
+        }
+    }
+
+    test!(parses_headers_and_nested_multipart_bodies, {
+        let ctx = test_context();
+        let mail = Mail::parse(NESTED_MAIL.as_bytes(), &ctx)?;
+
+        let from = mail.headers().get_single(_From).unwrap()?;
+        assert_eq!(from.body().first().email, Email::new("alice@example.test")?);
+        let to = mail.headers().get_single(_To).unwrap()?;
+        assert_eq!(to.body().0.len(), 2);
+        assert_eq!(to.body().last().email, Email::new("carl@example.test")?);
+        let subject = mail.headers().get_single(Subject).unwrap()?;
+        assert_eq!(subject.body().as_str(), "a folded subject");
+        let content_type = mail
+            .headers()
+            .get_single(::headers::headers::ContentType)
+            .unwrap()?;
+        assert_eq!(content_type.body().full_type(), "multipart/mixed");
+
+        let outer = bodies(&mail);
+        assert_eq!(outer.len(), 2);
+
+        let inner = bodies(&outer[0]);
+        assert_eq!(inner.len(), 2);
+        let plain = enc_data(&inner[0]);
+        assert_eq!(plain.media_type().full_type(), "text/plain");
+        assert_eq!(&**plain.transfer_encoded_buffer(), b"plain text");
+        let html = enc_data(&inner[1]);
+        assert_eq!(html.encoding(), TransferEncoding::QuotedPrintable);
+        assert_eq!(&**html.transfer_encoded_buffer(), b"<p>html</p>");
+
+        assert_eq!(mail.preamble().unwrap().as_str(), "preamble");
+        assert_eq!(outer[0].preamble().unwrap().as_str(), "");
+
+        let attachment = enc_data(&outer[1]);
+        assert_eq!(attachment.encoding(), TransferEncoding::Base64);
+        assert_eq!(attachment.content_id().as_str(), "attachment@example.test");
+        assert_eq!(&*attachment.transfer_decoded_buffer()?, &[0u8, 1, 2][..]);
+    });
+
+    test!(keeps_other_headers_as_raw_headers, {
+        let ctx = test_context();
+        let mail = Mail::parse(NESTED_MAIL.as_bytes(), &ctx)?;
+        let encoded = mail
+            .into_encodable_mail(ctx)
+            .wait()?
+            .encode_into_bytes(MailType::Ascii)?;
+        let encoded = String::from_utf8(encoded).unwrap();
+
+        assert!(encoded.contains("\r\nX-Custom-Header: some\tvalue\r\n"));
+        assert_eq!(encoded.matches("MIME-Version").count(), 1);
+    });
+
+    test!(keeps_unparsable_address_lists_as_raw_headers, {
+        let ctx = test_context();
+        let mail = Mail::parse(
+            concat!(
+                "From: alice@example.test\r\n",
+                "To: undisclosed-recipients:;\r\n",
+                "Cc: bob@example.test (Bob)\r\n",
+                "\r\n",
+                "body\r\n"
+            )
+            .as_bytes(),
+            &ctx,
+        )?;
+
+        assert!(mail.headers().get_single(_From).unwrap().is_ok());
+        assert!(mail.headers().get_single(_To).unwrap().is_err());
+
+        let encoded = mail
+            .into_encodable_mail(ctx)
+            .wait()?
+            .encode_into_bytes(MailType::Ascii)?;
+        let encoded = String::from_utf8(encoded).unwrap();
+
+        assert!(encoded.contains("\r\nTo: undisclosed-recipients:;\r\n"));
+        assert!(encoded.contains("\r\nCc: bob@example.test (Bob)\r\n"));
+    });
+
+    test!(round_trips_encoded_mails, {
+        let ctx = test_context();
+        let mut mail = Mail::plain_text("hy there", &ctx)
+            .wrap_with_mixed(vec![Resource::plain_text("attached", &ctx).create_mail()]);
+        mail.insert_headers(headers! {
+            _From: ["alice@example.test"],
+            Subject: "round trip"
+        }?);
+        let encoded = mail
+            .into_encodable_mail(ctx.clone())
+            .wait()?
+            .encode_into_bytes(MailType::Ascii)?;
+
+        let parsed = Mail::parse(&encoded, &ctx)?;
+        let reencoded = parsed
+            .into_encodable_mail(ctx)
+            .wait()?
+            .encode_into_bytes(MailType::Ascii)?;
+
+        // the boundary is regenerated, everything else should be the same
+        let without_boundary = |encoded: Vec<u8>| {
+            let encoded = String::from_utf8(encoded).unwrap();
+            let start = encoded.find("boundary=\"").unwrap() + "boundary=\"".len();
+            let end = start + encoded[start..].find('"').unwrap();
+            let boundary = encoded[start..end].to_owned();
+            encoded.replace(&boundary, "BOUNDARY")
+        };
+        assert_eq!(without_boundary(reencoded), without_boundary(encoded));
+    });
+
+    test!(treats_malformed_content_types_as_plain_text, {
+        let ctx = test_context();
+        let mail = Mail::parse(
+            concat!(
+                "From: alice@example.test\r\n",
+                "Content-Type: text/\r\n",
+                "\r\n",
+                "body\r\n"
+            )
+            .as_bytes(),
+            &ctx,
+        )?;
+
+        let body = enc_data(&mail);
+        assert_eq!(body.media_type().full_type(), "text/plain");
+        assert_eq!(
+            body.media_type().get_param("charset").unwrap().to_content(),
+            "us-ascii"
+        );
+    });
+
+    test!(keeps_multiline_preambles, {
+        let ctx = test_context();
+        let mail = Mail::parse(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=b\r\n",
+                "\r\n",
+                "line one\r\n",
+                "line two\r\n",
+                "\r\n",
+                "--b\r\n",
+                "\r\n",
+                "part\r\n",
+                "--b--\r\n"
+            )
+            .as_bytes(),
+            &ctx,
+        )?;
+
+        assert_eq!(
+            mail.preamble().unwrap().as_str(),
+            "line one\r\nline two\r\n"
+        );
+        assert_eq!(bodies(&mail).len(), 1);
+    });
+
+    #[test]
+    fn rejects_malformed_header_lines() {
+        let ctx = test_context();
+        match Mail::parse(b"From alice@example.test\r\n\r\nbody", &ctx) {
+            Err(MailError::Parse(MailParseError::MalformedHeaderLine(line))) => {
+                assert_eq!(line, "From alice@example.test")
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Compression of `Data` instances marked with a `ContentCoding`.
+//!
+//! Compressed bodies are marked with a `Content-Encoding` header, which
+//! is not part of the mail standards but understood by some consumers
+//! (it's used like the HTTP header of the same name). The `Content-Type`
+//! header still contains the media type of the uncompressed data.
+use miniz_oxide::deflate::{compress_to_vec, compress_to_vec_zlib};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The compression level passed to the deflate implementation.
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// The content coding used to compress the data of a body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ContentCoding {
+    /// The gzip file format (rfc1952).
+    Gzip,
+    /// The zlib format (rfc1950), named `deflate` like in HTTP.
+    Deflate,
+}
+
+impl ContentCoding {
+    /// Returns the value used for the `Content-Encoding` header.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+        }
+    }
+
+    /// Compresses the given data.
+    pub(crate) fn compress(&self, data: &[u8]) -> Vec<u8> {
+        match *self {
+            ContentCoding::Gzip => gzip(data),
+            ContentCoding::Deflate => compress_to_vec_zlib(data, COMPRESSION_LEVEL),
+        }
+    }
+}
+
+/// Wraps the deflate compressed data into a minimal gzip member.
+fn gzip(data: &[u8]) -> Vec<u8> {
+    // magic, CM=deflate, no flags, no mtime, no extra flags, OS=unknown
+    const HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+
+    let deflated = compress_to_vec(data, COMPRESSION_LEVEL);
+    let mut out = Vec::with_capacity(HEADER.len() + deflated.len() + 8);
+    out.extend_from_slice(&HEADER);
+    out.extend_from_slice(&deflated);
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Computes the CRC-32 (ISO-HDLC) checksum used by gzip.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use miniz_oxide::inflate::{decompress_to_vec, decompress_to_vec_zlib};
+
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn gzip_member_can_be_decompressed() {
+        let data = b"abcabcabcabcabcabcabcabcabcabc\r\n".repeat(10);
+        let compressed = ContentCoding::Gzip.compress(&data);
+
+        assert_eq!(&compressed[..3], &[0x1f, 0x8b, 8]);
+        let trailer = &compressed[compressed.len() - 8..];
+        assert_eq!(&trailer[..4], &crc32(&data).to_le_bytes());
+        assert_eq!(&trailer[4..], &(data.len() as u32).to_le_bytes());
+
+        let deflated = &compressed[10..compressed.len() - 8];
+        assert_eq!(decompress_to_vec(deflated).unwrap(), data);
+    }
+
+    #[test]
+    fn deflate_uses_the_zlib_format() {
+        let data = b"hy there, hy there, hy there";
+        let compressed = ContentCoding::Deflate.compress(data);
+        assert_eq!(decompress_to_vec_zlib(&compressed).unwrap(), &data[..]);
+    }
+}
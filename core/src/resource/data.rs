@@ -1,14 +1,32 @@
 use std::{
+    borrow::Cow,
     default::Default,
     ops::{Deref, DerefMut},
+    str,
     sync::Arc,
 };
 
+#[cfg(feature = "bytes")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+
 #[cfg(feature = "serde")]
 use serde::{de::Deserializer, ser::Serializer, Deserialize, Serialize};
 
 use headers::header_components::{ContentId, FileMeta, MediaType, TransferEncoding};
-use internals::bind::{base64, quoted_printable};
+use internals::{
+    bind::{base64, quoted_printable},
+    error::EncodingError,
+};
+
+#[cfg(feature = "digest")]
+use openssl::sha::sha256;
+
+#[cfg(feature = "compression")]
+use super::compression::ContentCoding;
+use super::flowed::format_flowed;
 
 /// POD type containing FileMeta, Content-Type and Content-Id
 ///
@@ -71,35 +89,171 @@ impl DerefMut for Metadata {
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Data {
-    #[cfg_attr(feature = "serde", serde(with = "arc_buffer_serde"))]
-    buffer: Arc<[u8]>,
+    #[cfg_attr(feature = "serde", serde(with = "data_buffer_serde"))]
+    buffer: DataBuffer,
     #[cfg_attr(feature = "serde", serde(flatten))]
     #[cfg_attr(feature = "serde", serde(with = "arc_serde"))]
     meta: Arc<Metadata>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    transfer_encoding_hint: TransferEncodingHint,
+    #[cfg(feature = "digest")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    compute_digest: bool,
+    #[cfg(feature = "compression")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    content_coding: Option<ContentCoding>,
 }
 
 impl Data {
     /// Create a new data instance.
-    pub fn new(buffer: impl Into<Arc<[u8]>>, meta: impl Into<Arc<Metadata>>) -> Self {
+    pub fn new(buffer: impl Into<DataBuffer>, meta: impl Into<Arc<Metadata>>) -> Self {
         Data {
             buffer: buffer.into(),
             meta: meta.into(),
+            transfer_encoding_hint: Default::default(),
+            #[cfg(feature = "digest")]
+            compute_digest: false,
+            #[cfg(feature = "compression")]
+            content_coding: None,
+        }
+    }
+
+    /// Sets the hint used when this data is transfer encoded by the context.
+    ///
+    /// E.g. setting `TransferEncodingHint::UseBinary` will make the
+    /// data be "encoded" with the `binary` transfer encoding.
+    pub fn with_transfer_encoding_hint(mut self, hint: TransferEncodingHint) -> Self {
+        self.transfer_encoding_hint = hint;
+        self
+    }
+
+    /// Returns the hint used when this data is transfer encoded by the context.
+    pub fn transfer_encoding_hint(&self) -> TransferEncodingHint {
+        self.transfer_encoding_hint
+    }
+
+    /// Enables or disables computing a SHA-256 digest of the data when it's transfer encoded.
+    ///
+    /// The digest is computed over the raw data (not the transfer encoded
+    /// one) and can be accessed through `EncData::computed_digest`. This
+    /// is disabled by default.
+    ///
+    /// This is only available with the `digest` feature, which uses the
+    /// SHA-256 implementation of `openssl`.
+    #[cfg(feature = "digest")]
+    pub fn with_digest_computation(mut self, enable: bool) -> Self {
+        self.compute_digest = enable;
+        self
+    }
+
+    /// Returns true if a digest is computed when this data is transfer encoded.
+    #[cfg(feature = "digest")]
+    pub fn computes_digest(&self) -> bool {
+        self.compute_digest
+    }
+
+    /// Makes the data be compressed with given content coding when it's transfer encoded.
+    ///
+    /// The compressed data is then transfer encoded instead of the data and
+    /// the body gets a `Content-Encoding` header, while the `Content-Type`
+    /// header still contains the media type of the uncompressed data. As
+    /// compressed data is binary, the `Auto` hints will pick `base64`.
+    ///
+    /// This is disabled by default and only available with the `compression` feature.
+    #[cfg(feature = "compression")]
+    pub fn with_content_coding(mut self, coding: ContentCoding) -> Self {
+        self.content_coding = Some(coding);
+        self
+    }
+
+    /// Returns the content coding used to compress the data when it's transfer encoded.
+    #[cfg(feature = "compression")]
+    pub fn content_coding(&self) -> Option<ContentCoding> {
+        self.content_coding
+    }
+
+    /// Replaces the media type in the metadata of this instance.
+    pub fn with_media_type(mut self, media_type: MediaType) -> Self {
+        Arc::make_mut(&mut self.meta).media_type = media_type;
+        self
+    }
+
+    /// Refines a generic `application/octet-stream` media type based on the data.
+    ///
+    /// If the media type is `application/octet-stream` the media type is
+    /// guessed from the leading bytes of the buffer (see
+    /// `MediaType::guess_from_bytes`). If a media type was guessed it
+    /// replaces the current one, else (or if the media type is not
+    /// `application/octet-stream`) the data is returned unchanged.
+    pub fn with_refined_media_type(self) -> Self {
+        if self.media_type().full_type() != "application/octet-stream" {
+            return self;
+        }
+
+        match MediaType::guess_from_bytes(&self.buffer) {
+            Some(media_type) => self.with_media_type(media_type),
+            None => self,
         }
     }
 
+    /// Creates a new `Data` instance with `text/plain; charset=utf-8` media type.
     pub fn plain_text(text: impl Into<String>, cid: ContentId) -> Data {
-        let text = text.into();
-        let buf = text.into_bytes();
+        Self::utf8_text(text.into(), "plain", cid)
+    }
+
+    /// Creates a new `Data` instance with `text/html; charset=utf-8` media type.
+    pub fn html(html: impl Into<String>, cid: ContentId) -> Data {
+        Self::utf8_text(html.into(), "html", cid)
+    }
+
+    /// Creates a new `Data` instance with `text/plain; charset=utf-8; format=flowed` media type.
+    ///
+    /// The text is formatted as `format=flowed` text (rfc3676): lines
+    /// longer than `width` chars are wrapped at spaces (keeping a trailing
+    /// space to mark the soft line break), lines starting with a space,
+    /// `>` or `From ` are space-stuffed and trailing spaces of the lines
+    /// of the text are removed (except for the signature delimiter `-- `).
+    /// Line breaks in the text are kept as hard line breaks.
+    pub fn flowed_text(text: impl AsRef<str>, width: usize, cid: ContentId) -> Data {
+        //UNWRAP_SAFE: "text/plain; charset=utf-8; format=flowed" is a valid media type
+        let media_type = MediaType::builder("text", "plain")
+            .param("charset", "utf-8")
+            .param("format", "flowed")
+            .build()
+            .unwrap();
         let meta = Metadata {
             file_meta: Default::default(),
-            media_type: MediaType::parse("text/plain; charset=utf-8").unwrap(),
+            media_type,
             content_id: cid,
         };
-        Self::new(buf, meta)
+        Self::new(format_flowed(text.as_ref(), width).into_bytes(), meta)
+    }
+
+    fn utf8_text(text: String, sub_type: &str, cid: ContentId) -> Data {
+        //UNWRAP_SAFE: "text/<sub_type>; charset=utf-8" is a valid media type
+        let media_type = MediaType::builder("text", sub_type)
+            .param("charset", "utf-8")
+            .build()
+            .unwrap();
+        let meta = Metadata {
+            file_meta: Default::default(),
+            media_type,
+            content_id: cid,
+        };
+        Self::new(text.into_bytes(), meta)
     }
 
     /// Access the raw data buffer of this instance.
+    ///
+    /// If the data is backed by a `bytes::Bytes` instance it's copied into
+    /// an `Arc<[u8]>` the first time this is called, use `data_buffer` to
+    /// access it without copying.
     pub fn buffer(&self) -> &Arc<[u8]> {
+        self.buffer.as_shared()
+    }
+
+    /// Access the raw data buffer of this instance without copying it.
+    pub fn data_buffer(&self) -> &DataBuffer {
         &self.buffer
     }
 
@@ -140,6 +294,88 @@ impl Data {
     }
 }
 
+/// The buffer containing the raw data of a `Data` instance.
+///
+/// It derefs to `[u8]` and, like `Data`, is cheap to clone.
+///
+/// Normally it's backed by an `Arc<[u8]>` but if the `bytes` feature is
+/// enabled it can also be created from a `bytes::Bytes` instance without
+/// copying the data.
+#[derive(Debug, Clone)]
+pub struct DataBuffer(InnerDataBuffer);
+
+#[derive(Debug, Clone)]
+enum InnerDataBuffer {
+    Shared(Arc<[u8]>),
+    #[cfg(feature = "bytes")]
+    Bytes(Bytes, OnceLock<Arc<[u8]>>),
+}
+
+impl DataBuffer {
+    /// Returns the data as `Arc<[u8]>`.
+    ///
+    /// If the buffer isn't already stored in an `Arc<[u8]>` it's copied
+    /// into one the first time this is called.
+    pub fn as_shared(&self) -> &Arc<[u8]> {
+        match self.0 {
+            InnerDataBuffer::Shared(ref buffer) => buffer,
+            #[cfg(feature = "bytes")]
+            InnerDataBuffer::Bytes(ref buffer, ref shared) => {
+                shared.get_or_init(|| Arc::from(&**buffer))
+            }
+        }
+    }
+}
+
+impl Deref for DataBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self.0 {
+            InnerDataBuffer::Shared(ref buffer) => buffer,
+            #[cfg(feature = "bytes")]
+            InnerDataBuffer::Bytes(ref buffer, _) => buffer,
+        }
+    }
+}
+
+impl AsRef<[u8]> for DataBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<Arc<[u8]>> for DataBuffer {
+    fn from(buffer: Arc<[u8]>) -> Self {
+        DataBuffer(InnerDataBuffer::Shared(buffer))
+    }
+}
+
+impl From<Vec<u8>> for DataBuffer {
+    fn from(buffer: Vec<u8>) -> Self {
+        DataBuffer::from(Arc::<[u8]>::from(buffer))
+    }
+}
+
+impl From<Box<[u8]>> for DataBuffer {
+    fn from(buffer: Box<[u8]>) -> Self {
+        DataBuffer::from(Arc::<[u8]>::from(buffer))
+    }
+}
+
+impl<'a> From<&'a [u8]> for DataBuffer {
+    fn from(buffer: &'a [u8]) -> Self {
+        DataBuffer::from(Arc::<[u8]>::from(buffer))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<Bytes> for DataBuffer {
+    fn from(buffer: Bytes) -> Self {
+        DataBuffer(InnerDataBuffer::Bytes(buffer, OnceLock::new()))
+    }
+}
+
 /// `EncData` is like `Data` but the buffer contains transfer encoded data.
 ///
 /// # Clone
@@ -149,12 +385,18 @@ impl Data {
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EncData {
-    #[cfg_attr(feature = "serde", serde(with = "arc_buffer_serde"))]
-    buffer: Arc<[u8]>,
+    #[cfg_attr(feature = "serde", serde(with = "data_buffer_serde"))]
+    buffer: DataBuffer,
     #[cfg_attr(feature = "serde", serde(flatten))]
     #[cfg_attr(feature = "serde", serde(with = "arc_serde"))]
     meta: Arc<Metadata>,
     encoding: TransferEncoding,
+    #[cfg(feature = "digest")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    digest: Option<[u8; 32]>,
+    #[cfg(feature = "compression")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    content_coding: Option<ContentCoding>,
 }
 
 impl EncData {
@@ -166,7 +408,7 @@ impl EncData {
     /// from a `Data` instance the `Arc<Metadata>` from that
     /// `Data` instance can be passed in directly as `meta`.
     pub(crate) fn new(
-        buffer: impl Into<Arc<[u8]>>,
+        buffer: impl Into<DataBuffer>,
         meta: impl Into<Arc<Metadata>>,
         encoding: TransferEncoding,
     ) -> Self {
@@ -174,14 +416,92 @@ impl EncData {
             buffer: buffer.into(),
             meta: meta.into(),
             encoding,
+            #[cfg(feature = "digest")]
+            digest: None,
+            #[cfg(feature = "compression")]
+            content_coding: None,
         }
     }
 
     /// Access the raw transfer encoded data.
+    ///
+    /// If the data is backed by a `bytes::Bytes` instance (e.g. a not
+    /// encoded `Data` created from `Bytes`) it's copied into an `Arc<[u8]>`
+    /// the first time this is called, use `transfer_encoded_data_buffer`
+    /// to access it without copying.
     pub fn transfer_encoded_buffer(&self) -> &Arc<[u8]> {
+        self.buffer.as_shared()
+    }
+
+    /// Access the raw transfer encoded data without copying it.
+    pub fn transfer_encoded_data_buffer(&self) -> &DataBuffer {
         &self.buffer
     }
 
+    /// Returns the data with the transfer encoding reverted.
+    ///
+    /// Note that this is the data in its canonical form, i.e. with
+    /// the line endings "fixed" by the transfer encoding, not
+    /// necessarily the exact bytes of the original `Data` instance.
+    ///
+    /// # Error
+    ///
+    /// Fails if the buffer is not valid for the transfer encoding,
+    /// which can only happen for `EncData` instances not created
+    /// through `Data::transfer_encode`.
+    pub fn transfer_decoded_buffer(&self) -> Result<Cow<'_, [u8]>, EncodingError> {
+        use self::TransferEncoding::*;
+        Ok(match self.encoding {
+            _7Bit | _8Bit | Binary => Cow::Borrowed(&*self.buffer),
+            QuotedPrintable => Cow::Owned(quoted_printable::normal_decode(&self.buffer)?),
+            Base64 => Cow::Owned(base64::normal_decode(&self.buffer)?),
+        })
+    }
+
+    /// Re-encodes `8bit` or `binary` transfer encoded data with a 7bit transfer encoding.
+    ///
+    /// The data is transfer decoded and encoded again with the 7bit
+    /// counterpart of the hint used to create it, i.e. `TransferEncodingHint::Auto`
+    /// (or `base64` if the data is compressed). The metadata, the content
+    /// coding and the computed digest are kept. Data which already uses a
+    /// 7bit transfer encoding is returned unchanged.
+    pub(crate) fn downgrade_to_7bit(&self) -> Result<EncData, EncodingError> {
+        use self::TransferEncoding::*;
+        match self.encoding {
+            _8Bit | Binary => {}
+            _7Bit | QuotedPrintable | Base64 => return Ok(self.clone()),
+        }
+
+        let decoded = Data::new(
+            self.transfer_decoded_buffer()?.into_owned(),
+            self.meta.clone(),
+        );
+        #[cfg_attr(not(feature = "compression"), allow(unused_mut))]
+        let mut hint = TransferEncodingHint::Auto;
+        #[cfg(feature = "compression")]
+        {
+            // the transfer decoded buffer contains the compressed data
+            if self.content_coding.is_some() {
+                hint = TransferEncodingHint::UseBase64;
+            }
+        }
+
+        #[cfg_attr(
+            not(any(feature = "compression", feature = "digest")),
+            allow(unused_mut)
+        )]
+        let mut enc_data = decoded.transfer_encode(hint);
+        #[cfg(feature = "digest")]
+        {
+            enc_data.digest = self.digest;
+        }
+        #[cfg(feature = "compression")]
+        {
+            enc_data.content_coding = self.content_coding;
+        }
+        Ok(enc_data)
+    }
+
     /// Access the metadata.
     pub fn metadata(&self) -> &Arc<Metadata> {
         &self.meta
@@ -197,11 +517,37 @@ impl EncData {
         &self.meta.media_type
     }
 
+    /// Replaces the media type in the metadata of this instance.
+    ///
+    /// This does not change the transfer encoding of the already
+    /// encoded buffer.
+    pub fn with_media_type(mut self, media_type: MediaType) -> Self {
+        Arc::make_mut(&mut self.meta).media_type = media_type;
+        self
+    }
+
     /// Access the transfer encoding used to encode the buffer.
     pub fn encoding(&self) -> TransferEncoding {
         self.encoding
     }
 
+    /// Access the SHA-256 digest of the data before it was transfer encoded.
+    ///
+    /// This is only available if the `Data` instance this was created
+    /// from had digest computation enabled.
+    #[cfg(feature = "digest")]
+    pub fn computed_digest(&self) -> Option<&[u8; 32]> {
+        self.digest.as_ref()
+    }
+
+    /// Access the content coding used to compress the data before it was transfer encoded.
+    ///
+    /// If there is one, the transfer decoded buffer contains the compressed data.
+    #[cfg(feature = "compression")]
+    pub fn content_coding(&self) -> Option<ContentCoding> {
+        self.content_coding
+    }
+
     /// Access the content id.
     ///
     /// The content id is for the data itself so it should not
@@ -221,15 +567,63 @@ impl EncData {
 }
 
 /// Hint to change how data should be transfer encoded.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TransferEncodingHint {
     /// Use Base64 encoding.
+    ///
+    /// Lines are wrapped at 76 chars (rfc2045).
     UseBase64,
 
+    /// Use Base64 encoding wrapping lines at the given length.
+    ///
+    /// This is meant for interacting with systems expecting a specific
+    /// line length (e.g. 64). The length excludes the CRLF. Lengths of 0
+    /// and lengths larger than 76 are not allowed by rfc2045 and are
+    /// clamped to 76.
+    UseBase64WithLineLength(usize),
+
     /// Use Quoted-Printable encoding.
+    ///
+    /// This is well suited for mostly us-ascii text. Lines are wrapped at
+    /// 76 columns with soft line breaks (`=\r\n`), CRLF line breaks in
+    /// the data are kept as hard line breaks and `=`, control characters,
+    /// non us-ascii bytes as well as whitespace in front of a line break
+    /// are encoded.
     UseQuotedPrintable,
 
+    /// Use the binary "encoding", i.e. don't encode the data at all.
+    ///
+    /// The data is used as is, neither line endings are fixed nor
+    /// is any byte (including `\0`) escaped. Mails with such bodies
+    /// can only be encoded with an `EncodingBuffer` allowing binary bodies
+    /// (see `EncodingBuffer::with_binary_bodies`).
+    UseBinary,
+
+    /// Choose the transfer encoding based on the data.
+    ///
+    /// For `text/*` data:
+    ///
+    /// - `7bit` is used for us-ascii data without `\0`, with all line
+    ///   breaks being CRLF and no line longer than 998 bytes,
+    /// - else `quoted-printable` is used if at most one in six bytes
+    ///   needs to be escaped,
+    /// - else `base64` is used.
+    ///
+    /// All other data (including compressed data) is encoded with `base64`,
+    /// as `quoted-printable` would turn CRLF byte pairs into line breaks
+    /// (which can be changed in transit) and `7bit` requires line oriented
+    /// data.
+    ///
+    /// This is the default hint.
+    Auto,
+
+    /// Like `Auto` but use `8bit` for utf-8 data which fulfills the `7bit` rules otherwise.
+    ///
+    /// Mails with such bodies can only be encoded with a mail type which
+    /// supports 8bit bodies, i.e. not with `MailType::Ascii`.
+    AutoAllow8Bit,
+
     // /// Do not assume Mime8Bit is available.
     // ///
     // /// As such do not encode ascii/utf-8 "as is" (e.g. not encoding them).
@@ -237,7 +631,7 @@ pub enum TransferEncodingHint {
     // /// Note: This is the default until I'm more sure about the whole thing
     // /// with puthing things in unecoded.
     // DoNotUseNoEncoding,
-    /// No hint for transfer encoding.
+    /// No hint for transfer encoding, this behaves like `Auto`.
     NoHint,
 
     #[cfg_attr(feature = "serde", serde(skip))]
@@ -247,16 +641,12 @@ pub enum TransferEncodingHint {
 
 impl Default for TransferEncodingHint {
     fn default() -> Self {
-        TransferEncodingHint::UseBase64
+        TransferEncodingHint::Auto
     }
 }
 
 /// Transfer encodes Data.
 ///
-/// Util we have a reasonable "non latin letter text" heuristic
-/// or enable none encoded text as default this will always encode
-/// with `Base64` except if asked not to do so.
-///
 /// # Panic
 ///
 /// Panics if TransferEncodingHint::__NonExhaustive
@@ -264,23 +654,69 @@ impl Default for TransferEncodingHint {
 fn transfer_encode(data: &Data, encoding_hint: TransferEncodingHint) -> EncData {
     use self::TransferEncodingHint::*;
 
-    match encoding_hint {
+    #[cfg(feature = "compression")]
+    {
+        if let Some(coding) = data.content_coding {
+            let compressed = Data {
+                buffer: coding.compress(data.data_buffer()).into(),
+                #[cfg(feature = "digest")]
+                compute_digest: false,
+                content_coding: None,
+                ..data.clone()
+            };
+            // the compressed data is binary even if the media type is `text/*`
+            let encoding_hint = match encoding_hint {
+                Auto | NoHint | AutoAllow8Bit => UseBase64,
+                hint => hint,
+            };
+            let mut enc_data = transfer_encode(&compressed, encoding_hint);
+            enc_data.content_coding = Some(coding);
+            // the digest is always over the uncompressed data
+            #[cfg(feature = "digest")]
+            {
+                if data.computes_digest() {
+                    enc_data.digest = Some(sha256(data.data_buffer()));
+                }
+            }
+            return enc_data;
+        }
+    }
+
+    let mut enc_data = match encoding_hint {
         UseQuotedPrintable => tenc_quoted_printable(data),
-        UseBase64 | NoHint => tenc_base64(data),
+        UseBinary => tenc_binary(data),
+        UseBase64 => tenc_base64(data, base64::DEFAULT_LINE_LENGTH),
+        UseBase64WithLineLength(line_length) => tenc_base64(data, line_length),
+        Auto | NoHint => tenc_auto(data, false),
+        AutoAllow8Bit => tenc_auto(data, true),
         __NonExhaustive { .. } => {
             panic!("__NonExhaustive encoding should not be passed to any place")
         }
+    };
+
+    #[cfg(feature = "digest")]
+    {
+        if data.computes_digest() {
+            enc_data.digest = Some(sha256(data.data_buffer()));
+        }
     }
+
+    enc_data
 }
 
-fn tenc_base64(data: &Data) -> EncData {
-    let enc_data = base64::normal_encode(data.buffer()).into_bytes();
+fn tenc_base64(data: &Data, line_length: usize) -> EncData {
+    let line_length = match line_length {
+        0 => base64::DEFAULT_LINE_LENGTH,
+        line_length => line_length.min(base64::DEFAULT_LINE_LENGTH),
+    };
+    let enc_data =
+        base64::normal_encode_with_line_length(data.data_buffer(), line_length).into_bytes();
 
     EncData::new(enc_data, data.metadata().clone(), TransferEncoding::Base64)
 }
 
 fn tenc_quoted_printable(data: &Data) -> EncData {
-    let enc_data = quoted_printable::normal_encode(data.buffer()).into_bytes();
+    let enc_data = quoted_printable::normal_encode(data.data_buffer()).into_bytes();
 
     EncData::new(
         enc_data,
@@ -289,11 +725,74 @@ fn tenc_quoted_printable(data: &Data) -> EncData {
     )
 }
 
+fn tenc_binary(data: &Data) -> EncData {
+    tenc_unencoded(data, TransferEncoding::Binary)
+}
+
+fn tenc_unencoded(data: &Data, encoding: TransferEncoding) -> EncData {
+    EncData::new(
+        data.data_buffer().clone(),
+        data.metadata().clone(),
+        encoding,
+    )
+}
+
+fn tenc_auto(data: &Data, allow_8bit: bool) -> EncData {
+    if data.media_type().type_() != "text" {
+        return tenc_base64(data, base64::DEFAULT_LINE_LENGTH);
+    }
+
+    let buffer = data.data_buffer();
+    if fulfills_7bit_line_rules(buffer) {
+        if buffer.is_ascii() {
+            return tenc_unencoded(data, TransferEncoding::_7Bit);
+        }
+        if allow_8bit && str::from_utf8(buffer).is_ok() {
+            return tenc_unencoded(data, TransferEncoding::_8Bit);
+        }
+    }
+
+    let needs_escaping = buffer
+        .iter()
+        .filter(|&&bch| !(bch.is_ascii_graphic() || b" \t\r\n".contains(&bch)) || bch == b'=')
+        .count();
+
+    if needs_escaping * 6 <= buffer.len() {
+        tenc_quoted_printable(data)
+    } else {
+        tenc_base64(data, base64::DEFAULT_LINE_LENGTH)
+    }
+}
+
+/// Maximal length of a line (excluding the CRLF) in a not encoded body (rfc5322).
+const MAX_LINE_LEN: usize = 998;
+
+/// Returns true if there is no `\0`, no CR/LF outside of a CRLF and no line longer than 998 bytes.
+fn fulfills_7bit_line_rules(buffer: &[u8]) -> bool {
+    let mut line_len = 0;
+    for (idx, &bch) in buffer.iter().enumerate() {
+        match bch {
+            b'\0' => return false,
+            b'\r' if buffer.get(idx + 1) != Some(&b'\n') => return false,
+            b'\r' => {}
+            b'\n' if idx == 0 || buffer[idx - 1] != b'\r' => return false,
+            b'\n' => line_len = 0,
+            _ => {
+                line_len += 1;
+                if line_len > MAX_LINE_LEN {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
 #[cfg(feature = "serde")]
-mod arc_buffer_serde {
+mod data_buffer_serde {
     use super::*;
 
-    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Arc<[u8]>, D::Error>
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<DataBuffer, D::Error>
     where
         D: Deserializer<'de>,
     {
@@ -301,7 +800,7 @@ mod arc_buffer_serde {
         Ok(bytes.into())
     }
 
-    pub(crate) fn serialize<S>(data: &Arc<[u8]>, serializer: S) -> Result<S::Ok, S::Error>
+    pub(crate) fn serialize<S>(data: &DataBuffer, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
@@ -330,3 +829,327 @@ mod arc_serde {
         IN::serialize(&**data, serializer)
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    #[cfg(feature = "digest")]
+    mod computed_digest {
+        use super::super::*;
+
+        const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        fn hex(digest: &[u8; 32]) -> String {
+            digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+
+        #[test]
+        fn is_not_computed_by_default() {
+            let data = Data::plain_text("abc", ContentId::from_unchecked("a@b.c".into()));
+            assert!(!data.computes_digest());
+            let enc_data = data.transfer_encode(TransferEncodingHint::UseBase64);
+            assert_eq!(enc_data.computed_digest(), None);
+        }
+
+        #[test]
+        fn is_computed_over_the_raw_data() {
+            let data = Data::plain_text("abc", ContentId::from_unchecked("a@b.c".into()))
+                .with_digest_computation(true);
+
+            for &hint in &[
+                TransferEncodingHint::UseBase64,
+                TransferEncodingHint::UseQuotedPrintable,
+                TransferEncodingHint::UseBinary,
+            ] {
+                let enc_data = data.transfer_encode(hint);
+                assert_eq!(hex(enc_data.computed_digest().unwrap()), ABC_SHA256);
+            }
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    mod content_coding {
+        use super::super::*;
+
+        fn data() -> Data {
+            Data::plain_text("abc", ContentId::from_unchecked("a@b.c".into()))
+        }
+
+        #[test]
+        fn is_not_used_by_default() {
+            let data = data();
+            assert_eq!(data.content_coding(), None);
+            let enc_data = data.transfer_encode(TransferEncodingHint::Auto);
+            assert_eq!(enc_data.content_coding(), None);
+            assert_eq!(&**enc_data.transfer_encoded_buffer(), b"abc");
+        }
+
+        #[test]
+        fn compresses_before_transfer_encoding() {
+            let data = data().with_content_coding(ContentCoding::Deflate);
+            let enc_data = data.transfer_encode(TransferEncodingHint::Auto);
+
+            assert_eq!(enc_data.content_coding(), Some(ContentCoding::Deflate));
+            assert_eq!(enc_data.encoding(), TransferEncoding::Base64);
+            assert_eq!(enc_data.media_type().full_type(), "text/plain");
+            let decoded = enc_data.transfer_decoded_buffer().unwrap();
+            assert_eq!(&*decoded, &*ContentCoding::Deflate.compress(b"abc"));
+        }
+
+        #[cfg(feature = "digest")]
+        #[test]
+        fn digest_is_computed_over_the_uncompressed_data() {
+            let data = data()
+                .with_content_coding(ContentCoding::Deflate)
+                .with_digest_computation(true);
+            let enc_data = data.transfer_encode(TransferEncodingHint::Auto);
+            assert_eq!(enc_data.computed_digest(), Some(&sha256(b"abc")));
+        }
+
+        #[test]
+        fn is_kept_when_downgrading_binary_data() {
+            let data = data().with_content_coding(ContentCoding::Deflate);
+            let enc_data = data.transfer_encode(TransferEncodingHint::UseBinary);
+            assert_eq!(enc_data.encoding(), TransferEncoding::Binary);
+
+            let downgraded = enc_data.downgrade_to_7bit().unwrap();
+            assert_eq!(downgraded.encoding(), TransferEncoding::Base64);
+            assert_eq!(downgraded.content_coding(), Some(ContentCoding::Deflate));
+            assert_eq!(
+                &*downgraded.transfer_decoded_buffer().unwrap(),
+                &*ContentCoding::Deflate.compress(b"abc")
+            );
+        }
+    }
+
+    mod base64_encoding {
+        use super::super::*;
+
+        fn base64_encode(hint: TransferEncodingHint) -> String {
+            let data = Data::new(
+                vec![0xAB; 100],
+                Metadata {
+                    file_meta: Default::default(),
+                    media_type: MediaType::parse("application/octet-stream").unwrap(),
+                    content_id: ContentId::from_unchecked("a@b.c".into()),
+                },
+            )
+            .with_transfer_encoding_hint(hint);
+            let enc_data = data.transfer_encode(data.transfer_encoding_hint());
+            assert_eq!(enc_data.encoding(), TransferEncoding::Base64);
+            String::from_utf8(enc_data.transfer_encoded_buffer().to_vec()).unwrap()
+        }
+
+        fn line_lengths(encoded: &str) -> Vec<usize> {
+            encoded.split("\r\n").map(str::len).collect()
+        }
+
+        #[test]
+        fn wraps_at_76_chars_by_default() {
+            let encoded = base64_encode(TransferEncodingHint::UseBase64);
+            assert_eq!(line_lengths(&encoded), vec![76, 60]);
+            assert!(encoded.ends_with("qw=="));
+        }
+
+        #[test]
+        fn wraps_at_the_given_line_length() {
+            let encoded = base64_encode(TransferEncodingHint::UseBase64WithLineLength(64));
+            assert_eq!(line_lengths(&encoded), vec![64, 64, 8]);
+            assert!(encoded.ends_with("qw=="));
+        }
+
+        #[test]
+        fn clamps_invalid_line_lengths_to_76_chars() {
+            let expected = base64_encode(TransferEncodingHint::UseBase64);
+            for &line_length in &[0, 77, 1000] {
+                let hint = TransferEncodingHint::UseBase64WithLineLength(line_length);
+                assert_eq!(base64_encode(hint), expected);
+            }
+        }
+    }
+
+    mod quoted_printable_encoding {
+        use super::super::*;
+
+        fn qp_encode(text: &str) -> String {
+            let data = Data::plain_text(text, ContentId::from_unchecked("a@b.c".into()))
+                .with_transfer_encoding_hint(TransferEncodingHint::UseQuotedPrintable);
+            let enc_data = data.transfer_encode(data.transfer_encoding_hint());
+            assert_eq!(enc_data.encoding(), TransferEncoding::QuotedPrintable);
+            String::from_utf8(enc_data.transfer_encoded_buffer().to_vec()).unwrap()
+        }
+
+        #[test]
+        fn keeps_crlf_as_hard_line_break() {
+            assert_eq!(qp_encode("abc\r\ndef\r\n"), "abc\r\ndef\r\n");
+        }
+
+        #[test]
+        fn encodes_trailing_whitespace_before_line_break() {
+            assert_eq!(qp_encode("abc \r\ndef\t"), "abc=20\r\ndef=09");
+        }
+
+        #[test]
+        fn encodes_equal_sign_control_chars_and_non_ascii() {
+            assert_eq!(qp_encode("a=b\x07c ä"), "a=3Db=07c =C3=A4");
+        }
+
+        #[test]
+        fn wraps_long_lines_with_soft_line_breaks() {
+            let text = "a".repeat(100);
+            let encoded = qp_encode(&text);
+            assert!(encoded.split("\r\n").all(|line| line.len() <= 76));
+            assert_eq!(encoded.replace("=\r\n", ""), text);
+        }
+
+        #[test]
+        fn round_trips() {
+            let text = "Grüße, = \r\nzweite Zeile \t\r\n";
+            let data = Data::plain_text(text, ContentId::from_unchecked("a@b.c".into()));
+            let enc_data = data.transfer_encode(TransferEncodingHint::UseQuotedPrintable);
+            assert_eq!(
+                &*enc_data.transfer_decoded_buffer().unwrap(),
+                text.as_bytes()
+            );
+        }
+    }
+
+    mod auto_transfer_encoding {
+        use super::super::*;
+
+        fn auto_encoding(buffer: &[u8], hint: TransferEncodingHint) -> TransferEncoding {
+            let data = Data::new(
+                buffer,
+                Metadata {
+                    file_meta: Default::default(),
+                    media_type: MediaType::parse("text/plain; charset=utf-8").unwrap(),
+                    content_id: ContentId::from_unchecked("a@b.c".into()),
+                },
+            );
+            data.transfer_encode(hint).encoding()
+        }
+
+        fn encoding(buffer: &[u8]) -> TransferEncoding {
+            auto_encoding(buffer, TransferEncodingHint::Auto)
+        }
+
+        #[test]
+        fn is_the_default_hint() {
+            let data = Data::plain_text("abc", ContentId::from_unchecked("a@b.c".into()));
+            assert_eq!(data.transfer_encoding_hint(), TransferEncodingHint::Auto);
+        }
+
+        #[test]
+        fn uses_7bit_for_ascii_text() {
+            assert_eq!(encoding(b"abc\r\ndef = 12\r\n"), TransferEncoding::_7Bit);
+            assert_eq!(encoding(b""), TransferEncoding::_7Bit);
+            assert_eq!(encoding(&[b'a'; 998]), TransferEncoding::_7Bit);
+        }
+
+        #[test]
+        fn does_not_use_7bit_for_long_lines() {
+            assert_eq!(encoding(&[b'a'; 999]), TransferEncoding::QuotedPrintable);
+        }
+
+        #[test]
+        fn does_not_use_7bit_for_bare_line_breaks_or_nul() {
+            assert_eq!(encoding(b"abc\ndef"), TransferEncoding::QuotedPrintable);
+            assert_eq!(encoding(b"abc\rdef"), TransferEncoding::QuotedPrintable);
+            assert_eq!(encoding(b"abc\0def"), TransferEncoding::QuotedPrintable);
+        }
+
+        #[test]
+        fn uses_8bit_for_utf8_text_only_if_allowed() {
+            let text = "Viele Grüße aus Berlin\r\n".as_bytes();
+            assert_eq!(encoding(text), TransferEncoding::QuotedPrintable);
+            assert_eq!(
+                auto_encoding(text, TransferEncodingHint::AutoAllow8Bit),
+                TransferEncoding::_8Bit
+            );
+            assert_eq!(
+                auto_encoding(b"\xFF\xFE", TransferEncodingHint::AutoAllow8Bit),
+                TransferEncoding::Base64
+            );
+        }
+
+        #[test]
+        fn uses_base64_if_many_bytes_need_escaping() {
+            assert_eq!(
+                encoding(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+                TransferEncoding::Base64
+            );
+            assert_eq!(encoding("ÄÖÜ".as_bytes()), TransferEncoding::Base64);
+        }
+
+        #[test]
+        fn uses_base64_for_non_text_data() {
+            let data = Data::new(
+                &b"mostly ascii\r\nwith a CRLF\r\n"[..],
+                Metadata {
+                    file_meta: Default::default(),
+                    media_type: MediaType::parse("application/octet-stream").unwrap(),
+                    content_id: ContentId::from_unchecked("a@b.c".into()),
+                },
+            );
+            let enc_data = data.transfer_encode(TransferEncodingHint::AutoAllow8Bit);
+            assert_eq!(enc_data.encoding(), TransferEncoding::Base64);
+            assert_eq!(
+                &*enc_data.transfer_decoded_buffer().unwrap(),
+                &**data.data_buffer()
+            );
+        }
+
+        #[test]
+        fn explicit_hints_override_detection() {
+            assert_eq!(
+                auto_encoding(b"abc", TransferEncodingHint::UseBase64),
+                TransferEncoding::Base64
+            );
+            assert_eq!(
+                auto_encoding(b"abc", TransferEncodingHint::UseQuotedPrintable),
+                TransferEncoding::QuotedPrintable
+            );
+        }
+    }
+
+    mod with_refined_media_type {
+        use super::super::*;
+
+        fn octet_stream_data(buffer: &[u8]) -> Data {
+            Data::new(
+                buffer,
+                Metadata {
+                    file_meta: Default::default(),
+                    media_type: MediaType::parse("application/octet-stream").unwrap(),
+                    content_id: ContentId::from_unchecked("a@b.c".into()),
+                },
+            )
+        }
+
+        #[test]
+        fn refines_png() {
+            let data =
+                octet_stream_data(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").with_refined_media_type();
+            assert_eq!(data.media_type().as_str_repr(), "image/png");
+        }
+
+        #[test]
+        fn refines_pdf() {
+            let data = octet_stream_data(b"%PDF-1.4\n%\xE2\xE3").with_refined_media_type();
+            assert_eq!(data.media_type().as_str_repr(), "application/pdf");
+        }
+
+        #[test]
+        fn keeps_octet_stream_for_unknown_bytes() {
+            let data = octet_stream_data(b"\0\x01\x02unknown").with_refined_media_type();
+            assert_eq!(data.media_type().as_str_repr(), "application/octet-stream");
+        }
+
+        #[test]
+        fn does_not_touch_specific_media_types() {
+            let data = Data::plain_text("%PDF-1.4", ContentId::from_unchecked("a@b.c".into()))
+                .with_refined_media_type();
+            assert_eq!(data.media_type().as_str_repr(), "text/plain; charset=utf-8");
+        }
+    }
+}
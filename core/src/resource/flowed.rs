@@ -0,0 +1,110 @@
+//! Formatting of plain text as `text/plain; format=flowed` (rfc3676).
+
+/// The default maximal line width used for `format=flowed` text.
+pub const DEFAULT_FLOWED_LINE_WIDTH: usize = 72;
+
+/// The signature delimiter, which is never treated as flowed line.
+const SIGNATURE_DELIMITER: &str = "-- ";
+
+/// Formats the text as `format=flowed` text with CRLF line breaks.
+///
+/// - trailing spaces of lines are removed, except for the signature delimiter
+/// - lines longer than `width` (in chars) are wrapped at spaces, the
+///   space is kept at the end of the wrapped line marking it as soft
+///   line break, words longer than `width` are not split
+/// - lines starting with a space, `>` or `From ` are space-stuffed
+/// - existing line breaks are kept as hard line breaks
+pub(crate) fn format_flowed(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / width.max(1) * 3);
+    let mut lines = text.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let is_last = lines.peek().is_none();
+        if is_last && line.is_empty() {
+            break;
+        }
+
+        if line == SIGNATURE_DELIMITER {
+            out.push_str(line);
+        } else {
+            let mut remaining = line.trim_end_matches(' ');
+            while let Some(split_idx) = soft_break_idx(remaining, width) {
+                push_stuffed(&mut out, &remaining[..=split_idx]);
+                out.push_str("\r\n");
+                remaining = &remaining[split_idx + 1..];
+            }
+            push_stuffed(&mut out, remaining);
+        }
+
+        if !is_last {
+            out.push_str("\r\n");
+        }
+    }
+    out
+}
+
+/// Returns the index of the space after which the line should be (soft) broken.
+fn soft_break_idx(line: &str, width: usize) -> Option<usize> {
+    let limit = match line.char_indices().nth(width) {
+        Some((idx, _)) => idx,
+        None => return None,
+    };
+
+    // the space at `limit` can still be used as it's the trailing space
+    line[..=limit]
+        .rfind(' ')
+        .filter(|&idx| idx > 0)
+        .or_else(|| line[limit..].find(' ').map(|idx| idx + limit))
+        .filter(|&idx| idx + 1 < line.len())
+}
+
+fn push_stuffed(out: &mut String, line: &str) {
+    if line.starts_with(' ') || line.starts_with('>') || line.starts_with("From ") {
+        out.push(' ');
+    }
+    out.push_str(line);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wraps_long_lines_with_trailing_space() {
+        let flowed = format_flowed("aaa bbb ccc ddd", 8);
+        assert_eq!(flowed, "aaa bbb \r\nccc ddd");
+    }
+
+    #[test]
+    fn does_not_split_long_words() {
+        let flowed = format_flowed("abcdefghijkl mn", 5);
+        assert_eq!(flowed, "abcdefghijkl \r\nmn");
+    }
+
+    #[test]
+    fn keeps_hard_line_breaks() {
+        let flowed = format_flowed("abc  \r\ndef\n\nghi\n", 72);
+        assert_eq!(flowed, "abc\r\ndef\r\n\r\nghi\r\n");
+    }
+
+    #[test]
+    fn space_stuffs_lines() {
+        let flowed = format_flowed("> quote\n indented\nFrom me\nx >y", 3);
+        assert_eq!(
+            flowed,
+            " > \r\nquote\r\n  indented\r\n From \r\nme\r\nx \r\n >y"
+        );
+    }
+
+    #[test]
+    fn preserves_the_signature_delimiter() {
+        let flowed = format_flowed("text\n-- \nsignature ", 72);
+        assert_eq!(flowed, "text\r\n-- \r\nsignature");
+    }
+
+    #[test]
+    fn counts_chars_not_bytes() {
+        let flowed = format_flowed("äöü äöü", 7);
+        assert_eq!(flowed, "äöü äöü");
+    }
+}
@@ -2,6 +2,7 @@
 use std::{
     borrow::{Borrow, ToOwned},
     collections::HashMap,
+    fmt::Display,
     mem,
 };
 
@@ -13,7 +14,10 @@ use futures::{
 use crate::{error::ResourceLoadingError, utils::SendBoxFuture, Context, MaybeEncData, Resource};
 
 pub trait ContainedResourcesAccess {
-    type Key: ToOwned + ?Sized;
+    /// The key used to access a resource.
+    ///
+    /// It's displayed as part of the error if loading a resource fails.
+    type Key: ToOwned + Display + ?Sized;
 
     /// Visit all resources.
     ///
@@ -100,6 +104,13 @@ impl<S: std::hash::BuildHasher> ContainedResourcesAccess for HashMap<String, Res
 //TODO[feat] impl. where applicable in std (Box, BTreeMap, other HashMap, etc.)
 
 impl Resource {
+    /// Loads all `Resource::Source` resources in the container.
+    ///
+    /// The returned future resolves to the container with all loaded
+    /// resources replaced. If loading any resource fails the future
+    /// fails with the `ResourceLoadingError` of it, which has the key
+    /// of the resource in the container (e.g. the map key or vector
+    /// index) set as `container_key`.
     pub fn load_container<CO>(
         container: CO,
         ctx: &impl Context,
@@ -137,8 +148,11 @@ where
 
         container.visit_resources(&mut |key, resource| {
             if let Resource::Source(ref source) = *resource {
-                let fut = ctx.load_resource(source);
-                futs.push(fut);
+                let container_key = key.to_string();
+                let fut = ctx.load_resource(source).map_err(move |err| {
+                    err.with_container_key_or_else(move || Some(container_key))
+                });
+                futs.push(Box::new(fut) as SendBoxFuture<_, _>);
                 keys.push(key.to_owned());
             }
         });
@@ -189,3 +203,42 @@ where
         Ok(Async::Ready(container))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use futures::Future;
+
+    use crate::{default_impl::test_context, Resource};
+
+    const MISSING_FILE: &str = "./this/file/does/not.exist";
+
+    #[test]
+    fn loading_errors_contain_the_map_key() {
+        let ctx = test_context();
+        let mut resources = HashMap::new();
+        resources.insert("text".to_owned(), Resource::plain_text("abc", &ctx));
+        resources.insert("logo".to_owned(), Resource::from_path(MISSING_FILE));
+
+        let err = Resource::load_container(resources, &ctx)
+            .wait()
+            .unwrap_err();
+        assert_eq!(err.container_key(), Some("logo"));
+        assert!(err.to_string().ends_with(" (resource: logo)"));
+    }
+
+    #[test]
+    fn loading_errors_contain_the_vec_index() {
+        let ctx = test_context();
+        let resources = vec![
+            Resource::plain_text("abc", &ctx),
+            Resource::from_path(MISSING_FILE),
+        ];
+
+        let err = Resource::load_container(resources, &ctx)
+            .wait()
+            .unwrap_err();
+        assert_eq!(err.container_key(), Some("1"));
+    }
+}
@@ -1,6 +1,7 @@
 // a module level circ. dep. but fine as only
 // used for more ergonomic helper constructors
 use context::Context;
+use mail::Mail;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -114,15 +115,29 @@ Source->|    |                     |
     - Use QuotedPrintable
     - NotHint
 */
-use headers::header_components::ContentId;
+use std::borrow::Cow;
+use std::path::Path;
+use std::str;
 
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use headers::header_components::{ContentId, MediaType};
+
+#[cfg(feature = "compression")]
+mod compression;
 mod data;
+mod flowed;
 mod loading;
 mod source;
+mod transcode;
 
+#[cfg(feature = "compression")]
+pub use self::compression::*;
 pub use self::data::*;
+pub use self::flowed::DEFAULT_FLOWED_LINE_WIDTH;
 pub use self::loading::*;
 pub use self::source::*;
+pub use self::transcode::*;
 
 /// A enum specifying a "resource" for a mail.
 ///
@@ -130,10 +145,11 @@ pub use self::source::*;
 /// It can be anything from a html body of a mail over a embedded
 /// image to a attached spread sheet.
 ///
-/// A resource can be specified in 3 ways:
+/// A resource can be specified in 4 ways:
 /// 1. As a source specifying what to get and how to handle it.
 /// 2. Data (and Metadata) representing a resource.
 /// 3. Data (and Metadata) representing a transfer encoded resource.
+/// 4. A mail which is embedded as `message/rfc822` body.
 ///
 /// Normally auto generated content will be provided as `Data`, embeddings
 /// and attachments will be provided as `Source` (potentially referring to
@@ -168,6 +184,16 @@ pub enum Resource {
     /// This can not be created by a consumer of the library and will be
     /// created when turning a mail into a transfer encoded mail.
     EncData(EncData),
+
+    /// Provide a mail which is embedded as `message/rfc822` body.
+    ///
+    /// This can e.g. be used to forward a mail as attachment. When the
+    /// outer mail is turned into an encodable mail the embedded mail is
+    /// turned into an encodable mail, too (i.e. it is validated and gets
+    /// `Date`/`Message-Id` headers if it has none) and then encoded and
+    /// replaced by a `EncData` instance.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Message(Box<Mail>),
 }
 
 impl Resource {
@@ -178,12 +204,455 @@ impl Resource {
         Resource::Data(Data::plain_text(content, ctx.generate_content_id()))
     }
 
+    /// Creates a new text `Resource` with `text/html; charset=utf-8` media type.
+    ///
+    /// The `Context` is used to generate a `ContentId`.
+    pub fn html(content: impl Into<String>, ctx: &impl Context) -> Resource {
+        Resource::Data(Data::html(content, ctx.generate_content_id()))
+    }
+
+    /// Creates a new text `Resource` with `text/plain; charset=utf-8; format=flowed` media type.
+    ///
+    /// The text is wrapped at `DEFAULT_FLOWED_LINE_WIDTH`, see `Data::flowed_text`.
+    ///
+    /// The `Context` is used to generate a `ContentId`.
+    pub fn flowed_text(content: impl AsRef<str>, ctx: &impl Context) -> Resource {
+        Resource::flowed_text_with_width(content, DEFAULT_FLOWED_LINE_WIDTH, ctx)
+    }
+
+    /// Like `Resource::flowed_text` but wrapping lines at given width.
+    pub fn flowed_text_with_width(
+        content: impl AsRef<str>,
+        width: usize,
+        ctx: &impl Context,
+    ) -> Resource {
+        Resource::Data(Data::flowed_text(content, width, ctx.generate_content_id()))
+    }
+
+    /// Creates a new `Resource` from a `Bytes` instance with given media type.
+    ///
+    /// The `Bytes` are stored as is, i.e. without copying them,
+    /// and are read directly from it when transfer encoding.
+    ///
+    /// The `Context` is used to generate a `ContentId`.
+    #[cfg(feature = "bytes")]
+    pub fn from_bytes(bytes: Bytes, media_type: MediaType, ctx: &impl Context) -> Resource {
+        let meta = Metadata {
+            file_meta: Default::default(),
+            media_type,
+            content_id: ctx.generate_content_id(),
+        };
+        Resource::Data(Data::new(bytes, meta))
+    }
+
+    /// Creates a new `message/rfc822` `Resource` embedding the given mail.
+    ///
+    /// See `Resource::Message`.
+    pub fn message(mail: Mail) -> Resource {
+        Resource::Message(Box::new(mail))
+    }
+
+    /// Creates a new `Resource` for the file at given path.
+    ///
+    /// The file is read lazily when the resource is loaded, e.g. when
+    /// turning a mail into an encodable mail. See `Source::from_path`.
+    pub fn from_path(path: impl AsRef<Path>) -> Resource {
+        Resource::Source(Source::from_path(path))
+    }
+
+    /// Sets the hint used when this resource is transfer encoded.
+    ///
+    /// This only affects `Resource::Data`, a `Resource::EncData` already
+    /// is transfer encoded and for a `Resource::Source` the hint is decided
+    /// by the resource loader of the context (see `Data::with_transfer_encoding_hint`).
+    pub fn with_transfer_encoding_hint(self, hint: TransferEncodingHint) -> Resource {
+        match self {
+            Resource::Data(data) => Resource::Data(data.with_transfer_encoding_hint(hint)),
+            other => other,
+        }
+    }
+
+    /// Makes the data be compressed with given content coding when it's transfer encoded.
+    ///
+    /// Like `Resource::with_transfer_encoding_hint` this only affects
+    /// `Resource::Data`, see `Data::with_content_coding`.
+    #[cfg(feature = "compression")]
+    pub fn with_content_coding(self, coding: ContentCoding) -> Resource {
+        match self {
+            Resource::Data(data) => Resource::Data(data.with_content_coding(coding)),
+            other => other,
+        }
+    }
+
+    /// Replaces the media type of this resource.
+    ///
+    /// For a `Resource::Source` this sets `use_media_type` to
+    /// `UseMediaType::Default(media_type)`, i.e. the media type
+    /// is used if the loaded resource doesn't come with one.
+    ///
+    /// A `Resource::Message` is returned unchanged as it always
+    /// has the `message/rfc822` media type.
+    pub fn with_media_type(self, media_type: MediaType) -> Resource {
+        match self {
+            Resource::Source(source) => Resource::Source(Source {
+                use_media_type: UseMediaType::Default(media_type),
+                ..source
+            }),
+            Resource::Data(data) => Resource::Data(data.with_media_type(media_type)),
+            Resource::EncData(enc_data) => Resource::EncData(enc_data.with_media_type(media_type)),
+            message @ Resource::Message(..) => message,
+        }
+    }
+
     /// Return the content id, if there is any.
     pub fn content_id(&self) -> Option<&ContentId> {
         match *self {
-            Resource::Source(..) => None,
+            Resource::Source(..) | Resource::Message(..) => None,
             Resource::Data(ref data) => Some(data.content_id()),
             Resource::EncData(ref enc_data) => Some(enc_data.content_id()),
         }
     }
+
+    /// Returns the text of a loaded text resource without copying it.
+    ///
+    /// This is only available for `Resource::Data` with a `text/*` media
+    /// type and a `utf-8` (or `us-ascii`) charset, for any other resource
+    /// (including not yet loaded ones) `None` is returned.
+    pub fn as_text(&self) -> Option<Cow<'_, str>> {
+        let data = match *self {
+            Resource::Data(ref data) => data,
+            _ => return None,
+        };
+
+        let media_type = data.media_type();
+        if media_type.type_() != "text" {
+            return None;
+        }
+        let charset = media_type.get_param("charset")?.to_content();
+        if !charset.eq_ignore_ascii_case("utf-8") && !charset.eq_ignore_ascii_case("us-ascii") {
+            return None;
+        }
+
+        str::from_utf8(data.data_buffer()).ok().map(Cow::Borrowed)
+    }
+
+    /// Returns the SHA-256 digest of the raw (not transfer encoded) data, if there is any.
+    ///
+    /// The digest is only computed for `Data` instances for which digest
+    /// computation was enabled using `Data::with_digest_computation` and
+    /// only once they got transfer encoded, i.e. it's only available on
+    /// `Resource::EncData` (e.g. the resources of a `EncodableMail`).
+    ///
+    /// This is only available with the `digest` feature.
+    #[cfg(feature = "digest")]
+    pub fn computed_digest(&self) -> Option<&[u8; 32]> {
+        match *self {
+            Resource::Source(..) | Resource::Data(..) | Resource::Message(..) => None,
+            Resource::EncData(ref enc_data) => enc_data.computed_digest(),
+        }
+    }
+}
+
+/// Compares resources by their content.
+///
+/// Loaded resources (`Data` and `EncData`) are equal if they have the
+/// same media type and the same (transfer decoded) data, independent of
+/// their content id and transfer encoding. Not yet loaded resources are
+/// equal if they have the same IRI and embedded mails are compared as mails.
+impl PartialEq for Resource {
+    fn eq(&self, other: &Resource) -> bool {
+        match (self, other) {
+            (Resource::Source(source), Resource::Source(other)) => source.iri == other.iri,
+            (Resource::Message(mail), Resource::Message(other)) => mail == other,
+            _ => match (self.loaded_data(), other.loaded_data()) {
+                (Some((media_type, data)), Some((other_media_type, other_data))) => {
+                    media_type.eq_ignoring_params(other_media_type, &[]) && data == other_data
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Resource {
+    fn loaded_data(&self) -> Option<(&MediaType, Cow<'_, [u8]>)> {
+        match *self {
+            Resource::Data(ref data) => Some((data.media_type(), Cow::Borrowed(&**data.data_buffer()))),
+            Resource::EncData(ref enc_data) => enc_data
+                .transfer_decoded_buffer()
+                .ok()
+                .map(|buffer| (enc_data.media_type(), buffer)),
+            Resource::Source(..) | Resource::Message(..) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #[cfg(feature = "bytes")]
+    mod from_bytes {
+        use bytes::Bytes;
+        use futures::Future;
+
+        use default_impl::test_context;
+        use headers::header_components::{MediaType, TransferEncoding};
+        use internals::MailType;
+
+        use super::super::*;
+        use {Context, Mail};
+
+        test!(stores_bytes_and_encodes_them, {
+            let ctx = test_context();
+            // long enough to not be stored inline by `Bytes`
+            let text: &'static [u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+            let bytes = Bytes::from_static(text);
+            let media_type = MediaType::parse("text/plain; charset=utf-8")?;
+            let resource = Resource::from_bytes(bytes, media_type, &ctx);
+
+            if let Resource::Data(ref data) = resource {
+                assert_eq!(&**data.buffer(), text);
+                assert_eq!(data.data_buffer().as_ptr(), text.as_ptr());
+            } else {
+                panic!("unexpected resource: {:?}", resource);
+            }
+
+            let enc_data = ctx.load_transfer_encoded_resource(&resource).wait()?;
+            // us-ascii text doesn't need to be encoded
+            assert_eq!(enc_data.encoding(), TransferEncoding::_7Bit);
+            let expected = text;
+            assert_eq!(&**enc_data.transfer_encoded_data_buffer(), expected);
+            // not encoded data is used as is
+            assert_eq!(
+                enc_data.transfer_encoded_data_buffer().as_ptr(),
+                text.as_ptr()
+            );
+
+            let mut mail = Mail::new_singlepart_mail(resource);
+            mail.insert_headers(headers! {
+                ::headers::headers::_From: ["random@this.is.no.mail"]
+            }?);
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+
+            let mut expected_body = b"\r\n\r\n".to_vec();
+            expected_body.extend(expected);
+            expected_body.extend(b"\r\n");
+            assert!(encoded.ends_with(&expected_body));
+        });
+    }
+    mod text {
+        use futures::Future;
+
+        use default_impl::test_context;
+        use internals::MailType;
+
+        use super::super::*;
+        use Mail;
+
+        fn media_type_of(resource: &Resource) -> String {
+            match *resource {
+                Resource::Data(ref data) => data.media_type().as_str_repr().to_owned(),
+                ref other => panic!("unexpected resource: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn plain_text_has_utf8_charset() {
+            let resource = Resource::plain_text("abc", &test_context());
+            assert_eq!(media_type_of(&resource), "text/plain; charset=utf-8");
+        }
+
+        #[test]
+        fn html_has_utf8_charset() {
+            let resource = Resource::html("<p>abc</p>", &test_context());
+            assert_eq!(media_type_of(&resource), "text/html; charset=utf-8");
+        }
+
+        #[test]
+        fn as_text_borrows_utf8_text() {
+            let resource = Resource::plain_text("Grüße", &test_context());
+            let text = resource.as_text().unwrap();
+            assert_eq!(text, "Grüße");
+
+            let text = match text {
+                Cow::Borrowed(text) => text,
+                Cow::Owned(_) => panic!("text was copied"),
+            };
+            match resource {
+                Resource::Data(ref data) => assert_eq!(text.as_ptr(), data.buffer().as_ptr()),
+                ref other => panic!("unexpected resource: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn as_text_is_none_for_non_text_resources() {
+            let ctx = test_context();
+            let image = Resource::plain_text("abc", &ctx)
+                .with_media_type(MediaType::parse("image/png").unwrap());
+            assert_eq!(image.as_text(), None);
+
+            let latin1 = Resource::plain_text("abc", &ctx)
+                .with_media_type(MediaType::parse("text/plain; charset=latin1").unwrap());
+            assert_eq!(latin1.as_text(), None);
+
+            assert_eq!(Resource::from_path("./test.txt").as_text(), None);
+        }
+
+        test!(resources_are_compared_by_media_type_and_data, {
+            let ctx = test_context();
+            let resource = Resource::plain_text("Grüße", &ctx);
+            let enc_data = ctx.load_transfer_encoded_resource(&resource).wait()?;
+
+            assert_eq!(resource, Resource::plain_text("Grüße", &ctx));
+            assert_eq!(resource, Resource::EncData(enc_data));
+            assert_ne!(resource, Resource::plain_text("Grüse", &ctx));
+            assert_ne!(resource, Resource::html("Grüße", &ctx));
+            assert_ne!(resource, Resource::from_path("./text.txt"));
+            assert_eq!(
+                Resource::from_path("./text.txt"),
+                Resource::from_path("./text.txt")
+            );
+        });
+
+        test!(flowed_text_is_encoded_with_format_parameter, {
+            let ctx = test_context();
+            let text = "Hy there, this line is a bit too long.\n-- \nsignature";
+            let resource = Resource::flowed_text_with_width(text, 20, &ctx);
+            assert_eq!(
+                media_type_of(&resource),
+                "text/plain; charset=utf-8; format=flowed"
+            );
+
+            let mut mail = Mail::new_singlepart_mail(resource);
+            mail.insert_headers(headers! {
+                ::headers::headers::_From: ["random@this.is.no.mail"]
+            }?);
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            let content_type = "Content-Type: text/plain; charset=utf-8; format=flowed\r\n";
+            assert!(encoded.contains(content_type));
+            let body = "Hy there, this line \r\nis a bit too long.\r\n-- \r\nsignature\r\n";
+            assert!(encoded.ends_with(&format!("\r\n\r\n{}", body)));
+        });
+
+        test!(html_charset_is_encoded_in_content_type, {
+            let ctx = test_context();
+            let mut mail = Mail::new_singlepart_mail(Resource::html("<p>Grüße</p>", &ctx));
+            mail.insert_headers(headers! {
+                ::headers::headers::_From: ["random@this.is.no.mail"]
+            }?);
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+            assert!(encoded.contains("Content-Type: text/html; charset=utf-8\r\n"));
+        });
+    }
+    mod with_transfer_encoding_hint {
+        use futures::Future;
+
+        use default_impl::test_context;
+        use internals::MailType;
+
+        use super::super::*;
+        use Mail;
+
+        test!(quoted_printable_is_used_when_encoding_the_mail, {
+            let ctx = test_context();
+            let resource = Resource::plain_text("Grüße\r\n", &ctx)
+                .with_transfer_encoding_hint(TransferEncodingHint::UseQuotedPrintable);
+
+            let mut mail = Mail::new_singlepart_mail(resource);
+            mail.insert_headers(headers! {
+                ::headers::headers::_From: ["random@this.is.no.mail"]
+            }?);
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+
+            assert!(encoded.contains("Content-Transfer-Encoding: quoted-printable\r\n"));
+            assert!(encoded.ends_with("\r\n\r\nGr=C3=BC=C3=9Fe\r\n"));
+        });
+    }
+
+    #[cfg(feature = "compression")]
+    mod with_content_coding {
+        use futures::Future;
+
+        use default_impl::test_context;
+        use internals::{bind::base64, MailType};
+
+        use super::super::*;
+        use Mail;
+
+        test!(compressed_data_is_marked_with_content_encoding, {
+            let ctx = test_context();
+            let text = "a long, very repetitive text\r\n".repeat(100);
+            let resource =
+                Resource::plain_text(text.as_str(), &ctx).with_content_coding(ContentCoding::Gzip);
+
+            let mut mail = Mail::new_singlepart_mail(resource);
+            mail.insert_headers(headers! {
+                ::headers::headers::_From: ["random@this.is.no.mail"]
+            }?);
+            let encoded = mail
+                .into_encodable_mail(ctx)
+                .wait()?
+                .encode_into_bytes(MailType::Ascii)?;
+            let encoded = String::from_utf8(encoded).unwrap();
+
+            assert!(encoded.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+            assert!(encoded.contains("Content-Transfer-Encoding: base64\r\n"));
+            assert!(encoded.contains("Content-Encoding: gzip\r\n"));
+
+            let body = &encoded[encoded.find("\r\n\r\n").unwrap() + 4..];
+            let compressed = base64::normal_decode(body)?;
+            assert!(compressed.starts_with(&[0x1f, 0x8b]));
+            assert!(compressed.len() < text.len());
+        });
+    }
+
+    #[cfg(feature = "digest")]
+    mod computed_digest {
+        use futures::Future;
+
+        use default_impl::test_context;
+
+        use super::super::*;
+        use {Context, Mail, MailBody};
+
+        test!(is_available_after_turning_mail_into_encodable_mail, {
+            let ctx = test_context();
+            let data =
+                Data::plain_text("abc", ctx.generate_content_id()).with_digest_computation(true);
+            let resource = Resource::Data(data);
+            assert_eq!(resource.computed_digest(), None);
+
+            let mut mail = Mail::new_singlepart_mail(resource);
+            mail.insert_headers(headers! {
+                ::headers::headers::_From: ["random@this.is.no.mail"]
+            }?);
+            let encodable = mail.into_encodable_mail(ctx).wait()?;
+
+            let digest = match *encodable.body() {
+                MailBody::SingleBody { ref body } => body.computed_digest().cloned(),
+                _ => panic!("unexpected multipart body"),
+            };
+            let expected = [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad,
+            ];
+            assert_eq!(digest, Some(expected));
+        });
+    }
 }
@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use headers::header_components::MediaType;
 use iri::IRI;
 
@@ -36,6 +38,28 @@ pub struct Source {
     pub use_file_name: Option<String>,
 }
 
+impl Source {
+    /// Creates a `Source` for the file at given path using the `path` scheme.
+    ///
+    /// The file is not accessed until the resource is loaded, which for
+    /// the default `FsResourceLoader` happens on the contexts cpu pool.
+    /// Relative paths are resolved relative to the root of the loader.
+    ///
+    /// The media type is detected when loading (`UseMediaType::Auto`) and
+    /// the file name is inferred from the path. Paths which are not valid
+    /// unicode are converted lossily. The path is percent-encoded as tail
+    /// of the IRI (see `IRI::with_scheme_and_tail`).
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_string_lossy();
+        let iri = IRI::with_scheme_and_tail("path", &path).expect("\"path\" is a valid scheme");
+        Source {
+            iri,
+            use_media_type: UseMediaType::Auto,
+            use_file_name: None,
+        }
+    }
+}
+
 /// Specifies how the content type should be handled when loading the data.
 ///
 /// Depending on how the context implementation handles the loading it might
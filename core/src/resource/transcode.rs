@@ -0,0 +1,251 @@
+//! Transcoding of text in legacy charsets to utf-8.
+use std::str;
+
+use headers::header_components::MediaType;
+
+use context::Context;
+use error::TranscodingError;
+
+use super::{Data, Metadata, Resource};
+
+/// The charsets supported by `transcode_to_utf8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Utf8,
+    UsAscii,
+    Windows1252,
+}
+
+impl Charset {
+    fn from_label(label: &str) -> Option<Charset> {
+        use self::Charset::*;
+        let label = label.trim().to_ascii_lowercase();
+        let charset = match label.as_str() {
+            "utf-8" | "utf8" => Utf8,
+            "us-ascii" | "ascii" => UsAscii,
+            // like browsers do (see the WHATWG encoding standard) latin1 is
+            // decoded as windows-1252, as mislabeled windows-1252 text is common
+            "windows-1252" | "cp1252" | "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1"
+            | "l1" => Windows1252,
+            _ => return None,
+        };
+        Some(charset)
+    }
+
+    fn name(self) -> &'static str {
+        use self::Charset::*;
+        match self {
+            Utf8 => "utf-8",
+            UsAscii => "us-ascii",
+            Windows1252 => "windows-1252",
+        }
+    }
+}
+
+/// Maps the bytes `0x80`-`0x9F` of windows-1252 to chars, `None` marks undefined bytes.
+const WINDOWS_1252_C1: [Option<char>; 32] = [
+    Some('\u{20AC}'),
+    None,
+    Some('\u{201A}'),
+    Some('\u{0192}'),
+    Some('\u{201E}'),
+    Some('\u{2026}'),
+    Some('\u{2020}'),
+    Some('\u{2021}'),
+    Some('\u{02C6}'),
+    Some('\u{2030}'),
+    Some('\u{0160}'),
+    Some('\u{2039}'),
+    Some('\u{0152}'),
+    None,
+    Some('\u{017D}'),
+    None,
+    None,
+    Some('\u{2018}'),
+    Some('\u{2019}'),
+    Some('\u{201C}'),
+    Some('\u{201D}'),
+    Some('\u{2022}'),
+    Some('\u{2013}'),
+    Some('\u{2014}'),
+    Some('\u{02DC}'),
+    Some('\u{2122}'),
+    Some('\u{0161}'),
+    Some('\u{203A}'),
+    Some('\u{0153}'),
+    None,
+    Some('\u{017E}'),
+    Some('\u{0178}'),
+];
+
+/// Transcodes text in the charset with the given label to utf-8.
+///
+/// Supported are `utf-8`, `us-ascii` and `windows-1252` (including some common
+/// aliases), labels are matched case-insensitive. The `iso-8859-1` (latin1)
+/// labels are treated as aliases for `windows-1252`, which is a superset of
+/// the printable part of latin1.
+///
+/// # Error
+///
+/// Fails if the charset is not supported or if the text contains a byte
+/// sequence which is not valid in the charset, in which case the error
+/// contains the byte offset of the first invalid byte.
+pub fn transcode_to_utf8(bytes: &[u8], charset: &str) -> Result<String, TranscodingError> {
+    let charset = Charset::from_label(charset)
+        .ok_or_else(|| TranscodingError::UnsupportedCharset(charset.to_owned()))?;
+
+    let invalid_at = |offset| TranscodingError::InvalidByteSequence {
+        charset: charset.name(),
+        offset,
+    };
+
+    match charset {
+        Charset::Utf8 => str::from_utf8(bytes)
+            .map(ToOwned::to_owned)
+            .map_err(|err| invalid_at(err.valid_up_to())),
+        Charset::UsAscii => match bytes.iter().position(|bch| !bch.is_ascii()) {
+            Some(offset) => Err(invalid_at(offset)),
+            None => Ok(bytes.iter().map(|&bch| bch as char).collect()),
+        },
+        Charset::Windows1252 => bytes
+            .iter()
+            .enumerate()
+            .map(|(offset, &bch)| match bch {
+                0x80..=0x9F => WINDOWS_1252_C1[(bch - 0x80) as usize].ok_or(invalid_at(offset)),
+                _ => Ok(bch as char),
+            })
+            .collect(),
+    }
+}
+
+impl Resource {
+    /// Creates a new text `Resource` from text in the given charset.
+    ///
+    /// The text is transcoded to utf-8 (see `transcode_to_utf8`) and
+    /// the `charset` parameter of the media type (e.g. `text/plain`)
+    /// is set to `utf-8`.
+    ///
+    /// The `Context` is used to generate a `ContentId`.
+    ///
+    /// # Error
+    ///
+    /// Fails if the charset is not supported or the text is not valid
+    /// in the given charset.
+    pub fn transcoded_text(
+        bytes: &[u8],
+        charset: &str,
+        mut media_type: MediaType,
+        ctx: &impl Context,
+    ) -> Result<Resource, TranscodingError> {
+        let text = transcode_to_utf8(bytes, charset)?;
+        media_type.set_param("charset", "utf-8");
+        let meta = Metadata {
+            file_meta: Default::default(),
+            media_type,
+            content_id: ctx.generate_content_id(),
+        };
+        Ok(Resource::Data(Data::new(text.into_bytes(), meta)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use default_impl::test_context;
+
+    #[test]
+    fn transcodes_latin1() {
+        let text = assert_ok!(transcode_to_utf8(b"Gr\xFC\xDFe", "ISO-8859-1"));
+        assert_eq!(text, "Grüße");
+    }
+
+    #[test]
+    fn decodes_latin1_as_windows_1252() {
+        let text = assert_ok!(transcode_to_utf8(b"\x93Gr\xFC\xDFe\x94", "latin1"));
+        assert_eq!(text, "“Grüße”");
+
+        let err = assert_err!(transcode_to_utf8(b"a\x81", "iso-8859-1"));
+        assert_eq!(
+            err,
+            TranscodingError::InvalidByteSequence {
+                charset: "windows-1252",
+                offset: 1
+            }
+        );
+    }
+
+    #[test]
+    fn transcodes_windows_1252() {
+        let text = assert_ok!(transcode_to_utf8(
+            b"\x93Gr\xFC\xDFe\x94 \x80",
+            "windows-1252"
+        ));
+        assert_eq!(text, "“Grüße” €");
+    }
+
+    #[test]
+    fn reports_offset_of_invalid_bytes() {
+        let err = assert_err!(transcode_to_utf8(b"abc\x81", "cp1252"));
+        assert_eq!(
+            err,
+            TranscodingError::InvalidByteSequence {
+                charset: "windows-1252",
+                offset: 3
+            }
+        );
+
+        let err = assert_err!(transcode_to_utf8(b"ab\xFCc", "us-ascii"));
+        assert_eq!(
+            err,
+            TranscodingError::InvalidByteSequence {
+                charset: "us-ascii",
+                offset: 2
+            }
+        );
+
+        let err = assert_err!(transcode_to_utf8(b"a\xC3\xBCb\xC3", "utf-8"));
+        assert_eq!(
+            err,
+            TranscodingError::InvalidByteSequence {
+                charset: "utf-8",
+                offset: 4
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_charsets() {
+        let err = assert_err!(transcode_to_utf8(b"abc", "koi8-r"));
+        assert_eq!(
+            err,
+            TranscodingError::UnsupportedCharset("koi8-r".to_owned())
+        );
+    }
+
+    #[test]
+    fn creates_utf8_resource() {
+        let ctx = test_context();
+        let media_type = MediaType::parse("text/html; charset=windows-1252").unwrap();
+        let resource = assert_ok!(Resource::transcoded_text(
+            b"<p>Gr\xFC\xDFe</p>",
+            "windows-1252",
+            media_type,
+            &ctx
+        ));
+
+        match resource {
+            Resource::Data(data) => {
+                assert_eq!(&**data.buffer(), "<p>Grüße</p>".as_bytes());
+                assert_eq!(
+                    data.media_type().get_param("charset").unwrap().to_content(),
+                    "utf-8"
+                );
+                assert_eq!(
+                    data.media_type().as_str_repr().split(';').next(),
+                    Some("text/html")
+                );
+            }
+            other => panic!("unexpected resource: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! This module provides a way to create `multipart/signed` bodies (rfc1847).
+//!
+//! A `multipart/signed` body consists of exactly two body parts, the
+//! signed part followed by a signature over it, e.g. a OpenPGP signature
+//! (rfc3156). As the signature is computed over the exact bytes of the
+//! encoded signed part, the signed part is encoded (with CRLF line endings)
+//! when the `multipart/signed` body is created and is used as is afterwards,
+//! i.e. it is never encoded again.
+use failure::Fail;
+use futures::{future, Future};
+
+use headers::{
+    error::ComponentCreationError,
+    header_components::{MediaType, TransferEncoding},
+};
+use internals::{
+    encoder::EncodingBuffer,
+    error::{EncodingError, EncodingErrorKind, Place},
+    MailType,
+};
+
+use crate::{
+    context::Context,
+    encode::encode_body_part,
+    error::MailError,
+    mail::{assume_encoded, load_body_part, Mail},
+    resource::{Data, EncData, Metadata, Resource},
+    utils::SendBoxFuture,
+};
+
+/// The `protocol` of a OpenPGP signed body (rfc3156).
+pub const PGP_SIGNATURE_PROTOCOL: &str = "application/pgp-signature";
+
+/// Builder for a `multipart/signed` mail.
+///
+/// # Example
+///
+/// ```
+/// # extern crate futures;
+/// # extern crate mail_core;
+/// # use futures::Future;
+/// # use mail_core::{default_impl::simple_context, signed::MultipartSigned, Mail};
+/// # fn main() {
+/// # let ctx = simple_context::from_str_parts("example.com", "xm3r2u").unwrap();
+/// let signed_part = Mail::plain_text("I really wrote this.", &ctx);
+/// let mail = MultipartSigned::pgp("pgp-sha256")
+///     .sign(signed_part, &ctx, |data: &[u8]| {
+///         // call into a OpenPGP implementation here
+///         Ok::<_, std::io::Error>(create_armored_signature(data))
+///     })
+///     .wait()
+///     .unwrap();
+/// # }
+/// # fn create_armored_signature(_data: &[u8]) -> Vec<u8> {
+/// #     b"-----BEGIN PGP SIGNATURE-----\r\n...\r\n-----END PGP SIGNATURE-----\r\n".to_vec()
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultipartSigned {
+    protocol: MediaType,
+    micalg: String,
+}
+
+impl MultipartSigned {
+    /// Creates a builder for a `multipart/signed` body with given protocol and micalg.
+    ///
+    /// The `protocol` is the media type of the signature body part, the
+    /// `micalg` names the message integrity check algorithm used for the
+    /// signature (e.g. `"pgp-sha256"`).
+    ///
+    /// # Error
+    ///
+    /// Fails if `protocol` is not a valid media type.
+    pub fn new(protocol: &str, micalg: impl Into<String>) -> Result<Self, ComponentCreationError> {
+        Ok(MultipartSigned {
+            protocol: MediaType::parse(protocol)?,
+            micalg: micalg.into(),
+        })
+    }
+
+    /// Creates a builder for a OpenPGP signed body (rfc3156).
+    ///
+    /// The `micalg` has to be `"pgp-<hash-algorithm>"`, e.g. `"pgp-sha256"`.
+    pub fn pgp(micalg: impl Into<String>) -> Self {
+        MultipartSigned {
+            //UNWRAP_SAFE: it's a valid media type
+            protocol: MediaType::parse(PGP_SIGNATURE_PROTOCOL).unwrap(),
+            micalg: micalg.into(),
+        }
+    }
+
+    /// Creates the `multipart/signed` mail with `signed_part` as the signed body part.
+    ///
+    /// This loads all resources of `signed_part` and encodes it as a body part
+    /// with `MailType::Ascii`. The `sign` function is called with the encoded
+    /// body part (excluding the CRLF in front of the following boundary, which
+    /// belongs to the boundary) and has to return the signature, which is
+    /// placed into the second body part using the `protocol` as media type.
+    ///
+    /// The returned mail has a non multipart body, containing the already
+    /// encoded `multipart/signed` body with the `Content-Type` parameters
+    /// `protocol`, `micalg` and `boundary`. It can be used like any other
+    /// mail, e.g. as a top-level mail or as a body of a `multipart/mixed`
+    /// body. As the signed part is only encoded once the signature stays valid.
+    ///
+    /// # Error
+    ///
+    /// The future fails if loading a resource of `signed_part` fails, if the
+    /// `signed_part` can not be encoded as a `7bit` body part (rfc1847 requires
+    /// this, so bodies using the `8bit` or `binary` transfer encoding are
+    /// rejected) or with `MailError::Signing` if `sign` fails.
+    pub fn sign<C, F, E>(
+        self,
+        signed_part: Mail,
+        ctx: &C,
+        sign: F,
+    ) -> SendBoxFuture<Mail, MailError>
+    where
+        C: Context,
+        F: FnOnce(&[u8]) -> Result<Vec<u8>, E> + Send + 'static,
+        E: Fail,
+    {
+        let ctx = ctx.clone();
+        let fut = load_body_part(signed_part, &ctx).and_then(move |signed_part| {
+            let encoded_part = match encode_signed_part(&signed_part) {
+                Ok(encoded_part) => encoded_part,
+                Err(err) => return future::Either::A(future::err(err)),
+            };
+
+            let signed_data = if encoded_part.ends_with(b"\r\n") {
+                &encoded_part[..encoded_part.len() - 2]
+            } else {
+                &encoded_part[..]
+            };
+            let signature = match sign(signed_data) {
+                Ok(signature) => signature,
+                Err(err) => return future::Either::A(future::err(MailError::Signing(err.into()))),
+            };
+
+            let meta = Metadata {
+                file_meta: Default::default(),
+                media_type: self.protocol.clone(),
+                content_id: ctx.generate_content_id(),
+            };
+            let signature_part =
+                Mail::new_singlepart_mail(Resource::Data(Data::new(signature, meta)));
+
+            let fut = load_body_part(signature_part, &ctx).and_then(move |signature_part| {
+                let encoded_signature = encode_signed_part(&signature_part)?;
+                self.assemble(&encoded_part, &encoded_signature, &ctx)
+            });
+            future::Either::B(fut)
+        });
+        Box::new(fut)
+    }
+
+    /// Creates the mail with the already encoded `multipart/signed` body.
+    fn assemble(
+        self,
+        encoded_part: &[u8],
+        encoded_signature: &[u8],
+        ctx: &impl Context,
+    ) -> Result<Mail, MailError> {
+        let boundary = ctx.generate_boundary_absent_from(&[encoded_part, encoded_signature]);
+        let delimiter = format!("--{}\r\n", boundary);
+
+        let mut body = Vec::with_capacity(
+            encoded_part.len() + encoded_signature.len() + 3 * delimiter.len() + 2,
+        );
+        body.extend_from_slice(delimiter.as_bytes());
+        body.extend_from_slice(encoded_part);
+        body.extend_from_slice(delimiter.as_bytes());
+        body.extend_from_slice(encoded_signature);
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+        let media_type = MediaType::builder("multipart", "signed")
+            .param("boundary", boundary)
+            .param("protocol", self.protocol.full_type().as_str())
+            .param("micalg", self.micalg)
+            .build()?;
+
+        let meta = Metadata {
+            file_meta: Default::default(),
+            media_type,
+            content_id: ctx.generate_content_id(),
+        };
+        let enc_data = EncData::new(body, meta, TransferEncoding::_7Bit);
+        Ok(Mail::new_singlepart_mail(Resource::EncData(enc_data)))
+    }
+}
+
+/// Encodes a loaded body part, failing if it contains non `7bit` bodies.
+fn encode_signed_part(mail: &Mail) -> Result<Vec<u8>, MailError> {
+    let has_non_7bit_body = mail.bodies().any(|body| {
+        let encoding = assume_encoded(body).encoding();
+        encoding == TransferEncoding::_8Bit || encoding == TransferEncoding::Binary
+    });
+    if has_non_7bit_body {
+        return Err(
+            EncodingError::from(EncodingErrorKind::NotEncodable { encoding: "7bit" })
+                .with_place_or_else(|| Some(Place::Body))
+                .into(),
+        );
+    }
+
+    let mut encoder = EncodingBuffer::new(MailType::Ascii);
+    encode_body_part(mail, &mut encoder)?;
+    Ok(encoder.into())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use futures::Future;
+
+    use headers::headers::{_From, Subject};
+
+    use super::*;
+    use crate::{default_impl::test_context, mail::MailBody, resource::TransferEncodingHint};
+
+    const SIGNATURE: &str =
+        "-----BEGIN PGP SIGNATURE-----\r\n\r\niQEzBAEBCAAdFiEE\r\n-----END PGP SIGNATURE-----\r\n";
+
+    test!(signs_the_encoded_body_part, {
+        let ctx = test_context();
+        let signed_data = Arc::new(Mutex::new(Vec::new()));
+        let signed_data2 = signed_data.clone();
+
+        let signed_part = Mail::plain_text("Hy there,\r\nthis is signed.", &ctx);
+        let mut mail = MultipartSigned::pgp("pgp-sha256")
+            .sign(signed_part, &ctx, move |data: &[u8]| {
+                signed_data2.lock().unwrap().extend_from_slice(data);
+                Ok::<_, io::Error>(SIGNATURE.as_bytes().to_vec())
+            })
+            .wait()?;
+
+        let media_type = match *mail.body() {
+            MailBody::SingleBody {
+                body: Resource::EncData(ref enc_data),
+            } => enc_data.media_type().clone(),
+            _ => panic!("expected a single already encoded body"),
+        };
+        assert_eq!(media_type.full_type(), "multipart/signed");
+        assert_eq!(
+            media_type.get_param("protocol").unwrap().to_content(),
+            "application/pgp-signature"
+        );
+        assert_eq!(
+            media_type.get_param("micalg").unwrap().to_content(),
+            "pgp-sha256"
+        );
+        let boundary = media_type.get_param("boundary").unwrap().to_content();
+
+        mail.insert_headers(headers! {
+            _From: ["alice@example.test"],
+            Subject: "signed"
+        }?);
+        let enc_mail = mail.into_encodable_mail(ctx).wait()?;
+
+        let encoded = String::from_utf8(enc_mail.encode_into_bytes(MailType::Ascii)?).unwrap();
+        let signed_data = String::from_utf8(signed_data.lock().unwrap().clone()).unwrap();
+        assert!(signed_data.starts_with("Content-Type: text/plain"));
+        assert!(signed_data.ends_with("\r\n\r\nHy there,\r\nthis is signed."));
+
+        let expected = format!(
+            "\r\n\r\n--{b}\r\n{data}\r\n--{b}\r\nContent-Type: application/pgp-signature\r\n",
+            b = boundary,
+            data = signed_data
+        );
+        assert!(encoded.contains(&expected), "{}", encoded);
+        // the signature is not text, so it's base64 encoded
+        let encoded_signature = ::internals::bind::base64::normal_encode(SIGNATURE);
+        assert!(encoded.contains(
+            "Content-Type: application/pgp-signature\r\nContent-Transfer-Encoding: base64\r\n"
+        ));
+        assert!(encoded.ends_with(&format!(
+            "\r\n\r\n{}\r\n--{}--\r\n",
+            encoded_signature, boundary
+        )));
+    });
+
+    #[test]
+    fn signing_errors_are_returned() {
+        let ctx = test_context();
+        let signed_part = Mail::plain_text("abc", &ctx);
+        let res = MultipartSigned::pgp("pgp-sha256")
+            .sign(signed_part, &ctx, |_: &[u8]| {
+                Err(io::Error::other("no key"))
+            })
+            .wait();
+
+        match res {
+            Err(MailError::Signing(err)) => assert_eq!(err.to_string(), "no key"),
+            Err(other) => panic!("unexpected error: {}", other),
+            Ok(_) => panic!("expected signing to fail"),
+        }
+    }
+
+    #[test]
+    fn rejects_binary_bodies() {
+        let ctx = test_context();
+        let data = Data::plain_text("abc", ctx.generate_content_id())
+            .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+        let signed_part = Mail::new_singlepart_mail(Resource::Data(data));
+        let res = MultipartSigned::pgp("pgp-sha256")
+            .sign(signed_part, &ctx, |_: &[u8]| {
+                Ok::<_, io::Error>(SIGNATURE.as_bytes().to_vec())
+            })
+            .wait();
+
+        match res {
+            Err(MailError::Encoding(_)) => {}
+            Err(other) => panic!("unexpected error: {}", other),
+            Ok(_) => panic!("expected signing to fail"),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Context wrappers making the output of the encoder predictable.
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use futures::Future;
+
+use headers::header_components::{ContentId, DateTime, Mailbox, MailboxList, MessageId};
+
+use crate::{
+    context::{Context, MaybeEncData},
+    error::ResourceLoadingError,
+    utils::SendBoxFuture,
+    EncData, Resource, Source,
+};
+
+/// Wraps a context replacing the random multipart boundaries with predictable ones.
+///
+/// The n-th generated boundary (starting with 0) is `=_^<n>.boundary`,
+/// the counter is shared between all clones of the context. Everything
+/// else is delegated to the wrapped context.
+///
+/// This is meant for golden-file tests, it should never be used in
+/// production as the boundaries are neither random nor unique.
+#[derive(Debug, Clone)]
+pub struct SequentialBoundaries<C: Context> {
+    inner: C,
+    counter: Arc<AtomicUsize>,
+}
+
+impl<C> SequentialBoundaries<C>
+where
+    C: Context,
+{
+    /// Wraps the given context.
+    pub fn new(inner: C) -> Self {
+        SequentialBoundaries {
+            inner,
+            counter: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns a reference to the wrapped context.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C> Context for SequentialBoundaries<C>
+where
+    C: Context,
+{
+    fn load_resource(&self, source: &Source) -> SendBoxFuture<MaybeEncData, ResourceLoadingError> {
+        self.inner.load_resource(source)
+    }
+
+    fn load_transfer_encoded_resource(
+        &self,
+        resource: &Resource,
+    ) -> SendBoxFuture<EncData, ResourceLoadingError> {
+        self.inner.load_transfer_encoded_resource(resource)
+    }
+
+    fn generate_message_id(&self) -> MessageId {
+        self.inner.generate_message_id()
+    }
+
+    fn generate_content_id(&self) -> ContentId {
+        self.inner.generate_content_id()
+    }
+
+    fn generate_content_ids(&self, n: usize) -> Vec<ContentId> {
+        self.inner.generate_content_ids(n)
+    }
+
+    fn generate_boundary(&self) -> String {
+        let count = self.counter.fetch_add(1, Ordering::SeqCst);
+        format!("=_^{}.boundary", count)
+    }
+
+    fn default_from(&self) -> Option<MailboxList> {
+        self.inner.default_from()
+    }
+
+    fn default_sender(&self) -> Option<Mailbox> {
+        self.inner.default_sender()
+    }
+
+    fn auto_sender(&self) -> bool {
+        self.inner.auto_sender()
+    }
+
+    fn require_message_id(&self) -> bool {
+        self.inner.require_message_id()
+    }
+
+    fn reject_auto_replied_list_mail(&self) -> bool {
+        self.inner.reject_auto_replied_list_mail()
+    }
+
+    fn now(&self) -> DateTime {
+        self.inner.now()
+    }
+
+    fn offload<F>(&self, fut: F) -> SendBoxFuture<F::Item, F::Error>
+    where
+        F: Future + Send + 'static,
+        F::Item: Send + 'static,
+        F::Error: Send + 'static,
+    {
+        self.inner.offload(fut)
+    }
+}
+
+/// Wraps a context making it always return the same time from `Context::now`.
+///
+/// This makes the auto-generated `Date` header predictable, so tests
+/// don't have to set a `Date` header themself. Everything else is
+/// delegated to the wrapped context.
+#[derive(Debug, Clone)]
+pub struct FixedClock<C: Context> {
+    inner: C,
+    now: DateTime,
+}
+
+impl<C> FixedClock<C>
+where
+    C: Context,
+{
+    /// Wraps the given context, using `now` as the current time.
+    pub fn new(inner: C, now: DateTime) -> Self {
+        FixedClock { inner, now }
+    }
+
+    /// Returns a reference to the wrapped context.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C> Context for FixedClock<C>
+where
+    C: Context,
+{
+    fn load_resource(&self, source: &Source) -> SendBoxFuture<MaybeEncData, ResourceLoadingError> {
+        self.inner.load_resource(source)
+    }
+
+    fn load_transfer_encoded_resource(
+        &self,
+        resource: &Resource,
+    ) -> SendBoxFuture<EncData, ResourceLoadingError> {
+        self.inner.load_transfer_encoded_resource(resource)
+    }
+
+    fn generate_message_id(&self) -> MessageId {
+        self.inner.generate_message_id()
+    }
+
+    fn generate_content_id(&self) -> ContentId {
+        self.inner.generate_content_id()
+    }
+
+    fn generate_content_ids(&self, n: usize) -> Vec<ContentId> {
+        self.inner.generate_content_ids(n)
+    }
+
+    fn generate_boundary(&self) -> String {
+        self.inner.generate_boundary()
+    }
+
+    fn default_from(&self) -> Option<MailboxList> {
+        self.inner.default_from()
+    }
+
+    fn default_sender(&self) -> Option<Mailbox> {
+        self.inner.default_sender()
+    }
+
+    fn auto_sender(&self) -> bool {
+        self.inner.auto_sender()
+    }
+
+    fn require_message_id(&self) -> bool {
+        self.inner.require_message_id()
+    }
+
+    fn reject_auto_replied_list_mail(&self) -> bool {
+        self.inner.reject_auto_replied_list_mail()
+    }
+
+    fn now(&self) -> DateTime {
+        self.now.clone()
+    }
+
+    fn offload<F>(&self, fut: F) -> SendBoxFuture<F::Item, F::Error>
+    where
+        F: Future + Send + 'static,
+        F::Item: Send + 'static,
+        F::Error: Send + 'static,
+    {
+        self.inner.offload(fut)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use futures::Future;
+
+    use headers::{
+        header_components::MediaType,
+        headers::{ContentType, Date, _From},
+    };
+    use internals::MailType;
+
+    use super::*;
+    use crate::{default_impl::test_context, Mail};
+
+    #[test]
+    fn generates_sequential_boundaries() {
+        let ctx = SequentialBoundaries::new(test_context());
+        let bodies = vec![Mail::plain_text("abc", &ctx), Mail::plain_text("def", &ctx)];
+        let mut mail =
+            Mail::new_multipart_mail(MediaType::parse("multipart/mixed").unwrap(), bodies);
+        mail.insert_headers(headers! { _From: ["alice@example.test"] }.unwrap());
+
+        let encodable = mail.into_encodable_mail(ctx.clone()).wait().unwrap();
+        let content_type = encodable
+            .headers()
+            .get_single(ContentType)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            content_type.get_param("boundary").unwrap().to_content(),
+            "=_^0.boundary"
+        );
+
+        let encoded = encodable.encode_into_bytes(MailType::Ascii).unwrap();
+        let encoded = String::from_utf8(encoded).unwrap();
+        assert!(encoded.contains("\r\n--=_^0.boundary\r\n"));
+        assert!(encoded.ends_with("\r\n--=_^0.boundary--\r\n"));
+
+        assert_eq!(ctx.generate_boundary(), "=_^1.boundary");
+    }
+
+    #[test]
+    fn skips_boundaries_contained_in_bodies() {
+        let ctx = SequentialBoundaries::new(test_context());
+        let boundary = ctx.generate_boundary_absent_from(&[b"xx=_^0.boundaryxx"]);
+        assert_eq!(boundary, "=_^1.boundary");
+    }
+
+    #[test]
+    fn fixed_clock_is_used_for_the_date_header() {
+        let now = DateTime::new(Utc.with_ymd_and_hms(2013, 8, 6, 7, 11, 0).unwrap());
+        let ctx = FixedClock::new(test_context(), now.clone());
+        let mut mail = Mail::plain_text("abc", &ctx);
+        mail.insert_headers(headers! { _From: ["alice@example.test"] }.unwrap());
+
+        let encodable = mail.into_encodable_mail(ctx.clone()).wait().unwrap();
+        let date = encodable.headers().get_single(Date).unwrap().unwrap();
+        assert_eq!(date.body(), &now);
+
+        let encoded = encodable.encode_into_bytes(MailType::Ascii).unwrap();
+        let encoded = String::from_utf8(encoded).unwrap();
+        assert!(encoded.contains("Date: Tue, 6 Aug 2013 07:11:00 +0000\r\n"));
+    }
+}
@@ -0,0 +1,325 @@
+//! Utilities to compare two mails, e.g. to test mail transformations.
+//!
+//! Instead of comparing the encoded form of two mails `diff_mails`
+//! reports the differences in a structured way, so that tests can
+//! assert that exactly the intended changes happened.
+use headers::{HeaderMap, HeaderName};
+
+use crate::mail::{Mail, MailBody};
+
+/// The differences between two mails as returned by `diff_mails`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MailDiff {
+    /// Headers which were added, removed or changed.
+    pub header_diffs: Vec<HeaderDiff>,
+
+    /// Differences in the body (structure).
+    pub body_diffs: Vec<BodyDiff>,
+}
+
+impl MailDiff {
+    /// Returns true if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.header_diffs.is_empty() && self.body_diffs.is_empty()
+    }
+}
+
+/// A difference wrt. a single header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderDiff {
+    /// The indices of the (sub-)bodies leading to the mail containing the header.
+    ///
+    /// This is empty for headers of the outer most mail.
+    pub path: Vec<usize>,
+
+    /// The name of the header.
+    pub name: HeaderName,
+
+    /// The kind of change.
+    pub change: HeaderChange,
+}
+
+/// The kind of change of a header.
+///
+/// Header values are represented by their `Debug` representation
+/// as header bodies neither implement `PartialEq` nor can they all
+/// be encoded for every mail type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderChange {
+    /// The header with given value was added.
+    Added(String),
+
+    /// The header with given value was removed.
+    Removed(String),
+
+    /// The value of the header was changed.
+    Changed { old: String, new: String },
+}
+
+/// A difference wrt. the body of a mail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyDiff {
+    /// The indices of the (sub-)bodies leading to the differing body.
+    ///
+    /// This is empty if the body of the outer most mail differs.
+    pub path: Vec<usize>,
+
+    /// The kind of change.
+    pub change: BodyChange,
+}
+
+/// The kind of change of a body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyChange {
+    /// A singlepart body was replaced by a multipart body.
+    SinglepartToMultipart,
+
+    /// A multipart body was replaced by a singlepart body.
+    MultipartToSinglepart,
+
+    /// The number of sub-bodies of a multipart body changed.
+    ///
+    /// Only the first `min(old, new)` sub-bodies are compared further.
+    BodyCountChanged { old: usize, new: usize },
+
+    /// The resource of a singlepart body changed.
+    ///
+    /// Resources are compared with their `PartialEq` implementation,
+    /// i.e. by content and not by content id or transfer encoding.
+    ResourceChanged,
+
+    /// The hidden text of a multipart body changed.
+    HiddenTextChanged,
+}
+
+/// Returns the differences between mail `a` and mail `b`.
+///
+/// Changes are always reported from the perspective of `a`, e.g. a
+/// header which is in `b` but not in `a` is reported as added.
+///
+/// Headers are compared by name and the position of the value in the
+/// list of values associated with that name, but not by the position
+/// in the header map. Sub-bodies of multipart bodies are compared
+/// recursively.
+pub fn diff_mails(a: &Mail, b: &Mail) -> MailDiff {
+    let mut diff = MailDiff::default();
+    diff_mails_at(&mut Vec::new(), a, b, &mut diff);
+    diff
+}
+
+fn diff_mails_at(path: &mut Vec<usize>, a: &Mail, b: &Mail, diff: &mut MailDiff) {
+    diff_headers(path, a.headers(), b.headers(), &mut diff.header_diffs);
+
+    let change = match (a.body(), b.body()) {
+        (MailBody::SingleBody { body: a_body }, MailBody::SingleBody { body: b_body }) => {
+            if a_body == b_body {
+                None
+            } else {
+                Some(BodyChange::ResourceChanged)
+            }
+        }
+        (MailBody::SingleBody { .. }, MailBody::MultipleBodies { .. }) => {
+            Some(BodyChange::SinglepartToMultipart)
+        }
+        (MailBody::MultipleBodies { .. }, MailBody::SingleBody { .. }) => {
+            Some(BodyChange::MultipartToSinglepart)
+        }
+        (
+            MailBody::MultipleBodies {
+                bodies: a_bodies,
+                hidden_text: a_hidden_text,
+            },
+            MailBody::MultipleBodies {
+                bodies: b_bodies,
+                hidden_text: b_hidden_text,
+            },
+        ) => {
+            if a_hidden_text != b_hidden_text {
+                diff.body_diffs.push(BodyDiff {
+                    path: path.clone(),
+                    change: BodyChange::HiddenTextChanged,
+                });
+            }
+
+            for (idx, (a_sub, b_sub)) in a_bodies.iter().zip(b_bodies.iter()).enumerate() {
+                path.push(idx);
+                diff_mails_at(path, a_sub, b_sub, diff);
+                path.pop();
+            }
+
+            if a_bodies.len() != b_bodies.len() {
+                Some(BodyChange::BodyCountChanged {
+                    old: a_bodies.len(),
+                    new: b_bodies.len(),
+                })
+            } else {
+                None
+            }
+        }
+    };
+
+    if let Some(change) = change {
+        diff.body_diffs.push(BodyDiff {
+            path: path.clone(),
+            change,
+        });
+    }
+}
+
+fn diff_headers(path: &[usize], a: &HeaderMap, b: &HeaderMap, out: &mut Vec<HeaderDiff>) {
+    let mut names = Vec::new();
+    for (name, _) in a.iter().chain(b.iter()) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    for name in names {
+        let a_values = header_values(a, name);
+        let b_values = header_values(b, name);
+
+        let mut push = |change| {
+            out.push(HeaderDiff {
+                path: path.to_vec(),
+                name,
+                change,
+            })
+        };
+
+        for (old, new) in a_values.iter().zip(b_values.iter()) {
+            if old != new {
+                push(HeaderChange::Changed {
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+
+        for removed in a_values.iter().skip(b_values.len()) {
+            push(HeaderChange::Removed(removed.clone()));
+        }
+
+        for added in b_values.iter().skip(a_values.len()) {
+            push(HeaderChange::Added(added.clone()));
+        }
+    }
+}
+
+fn header_values(headers: &HeaderMap, name: HeaderName) -> Vec<String> {
+    headers
+        .get_untyped(name)
+        .map(|header| format!("{:?}", header))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use headers::{
+        headers::{Comments, Subject},
+        HeaderKind,
+    };
+
+    use super::*;
+    use crate::{resource::Resource, test_utils::CTX};
+
+    #[test]
+    fn same_mail_has_empty_diff() {
+        let mail = Mail::plain_text("hy", CTX.unwrap());
+        assert!(diff_mails(&mail, &mail).is_empty());
+    }
+
+    #[test]
+    fn adding_a_header_is_one_addition() {
+        let a = Mail::plain_text("hy", CTX.unwrap());
+        let mut b = a.clone();
+        b.insert_header(Subject::auto_body("new subject").unwrap());
+
+        let diff = diff_mails(&a, &b);
+
+        assert!(diff.body_diffs.is_empty());
+        assert_eq!(diff.header_diffs.len(), 1);
+        let header_diff = &diff.header_diffs[0];
+        assert_eq!(header_diff.name, Subject::name());
+        assert!(header_diff.path.is_empty());
+        match header_diff.change {
+            HeaderChange::Added(ref value) => assert!(value.contains("new subject")),
+            ref other => panic!("unexpected change: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_changed_and_removed_headers() {
+        let mut a = Mail::plain_text("hy", CTX.unwrap());
+        a.insert_header(Subject::auto_body("old").unwrap());
+        a.insert_header(Comments::auto_body("some comment").unwrap());
+        let mut b = Mail::plain_text("hy there", CTX.unwrap());
+        b.insert_header(Subject::auto_body("new").unwrap());
+
+        let diff = diff_mails(&a, &b);
+
+        let changes = diff
+            .header_diffs
+            .iter()
+            .map(|diff| (diff.name.as_str(), &diff.change))
+            .collect::<Vec<_>>();
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].0, "Subject");
+        assert!(matches!(changes[0].1, HeaderChange::Changed { .. }));
+        assert_eq!(changes[1].0, "Comments");
+        assert!(matches!(changes[1].1, HeaderChange::Removed(..)));
+
+        // different texts in the plain text bodies
+        assert_eq!(
+            diff.body_diffs,
+            vec![BodyDiff {
+                path: vec![],
+                change: BodyChange::ResourceChanged
+            }]
+        );
+    }
+
+    #[test]
+    fn ignores_content_ids_and_compares_embedded_mails() {
+        let a = Mail::plain_text("hy", CTX.unwrap());
+        let b = Mail::plain_text("hy", CTX.unwrap());
+        assert!(diff_mails(&a, &b).is_empty());
+
+        let a = Mail::new_singlepart_mail(Resource::message(a));
+        let mut inner = Mail::plain_text("hy", CTX.unwrap());
+        inner.insert_header(Subject::auto_body("changed").unwrap());
+        let b = Mail::new_singlepart_mail(Resource::message(inner));
+        assert_eq!(
+            diff_mails(&a, &b).body_diffs,
+            vec![BodyDiff {
+                path: vec![],
+                change: BodyChange::ResourceChanged
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_body_structure_changes() {
+        let a = Mail::plain_text("hy", CTX.unwrap());
+        let b = a.clone().wrap_with_mixed(vec![a.clone()]);
+        let c = a.clone().wrap_with_mixed(vec![]);
+
+        let diff = diff_mails(&a, &b);
+        assert_eq!(
+            diff.body_diffs,
+            vec![BodyDiff {
+                path: vec![],
+                change: BodyChange::SinglepartToMultipart
+            }]
+        );
+
+        let diff = diff_mails(&b, &c);
+        assert_eq!(
+            diff.body_diffs,
+            vec![BodyDiff {
+                path: vec![],
+                change: BodyChange::BodyCountChanged { old: 2, new: 1 }
+            }]
+        );
+    }
+}
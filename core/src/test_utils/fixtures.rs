@@ -0,0 +1,175 @@
+//! Representative mails, e.g. for benchmarking the encoder.
+//!
+//! All fixtures produce deterministic content (except for the
+//! content ids generated through the passed in context) and
+//! return a `Mail` which can directly be turned into an
+//! `EncodableMail`.
+use headers::{
+    header_components::{FileMeta, MediaType},
+    headers::{Subject, _From, _To},
+    HeaderKind,
+};
+use vec1::Vec1;
+
+use crate::{
+    compose::{BodyPart, MailParts},
+    context::Context,
+    Data, Mail, Metadata, Resource,
+};
+
+/// The size of the attachment in `large_attachment_mail` (1MiB).
+pub const LARGE_ATTACHMENT_SIZE: usize = 1024 * 1024;
+
+/// The number of recipients in `many_recipient_mail`.
+pub const RECIPIENT_COUNT: usize = 500;
+
+/// Creates a mail with a text body and a large binary attachment.
+///
+/// The attachment has a size of `LARGE_ATTACHMENT_SIZE` bytes
+/// and a `application/octet-stream` media type, so it will be
+/// base64 encoded.
+pub fn large_attachment_mail(ctx: &impl Context) -> Mail {
+    let buffer = (0..LARGE_ATTACHMENT_SIZE)
+        .map(|idx| (idx * 31 % 251) as u8)
+        .collect::<Vec<_>>();
+
+    let attachment = Resource::Data(Data::new(
+        buffer,
+        Metadata {
+            file_meta: FileMeta {
+                file_name: Some("data.bin".to_owned()),
+                size: Some(LARGE_ATTACHMENT_SIZE),
+                ..Default::default()
+            },
+            media_type: MediaType::parse("application/octet-stream").unwrap(),
+            content_id: ctx.generate_content_id(),
+        },
+    ));
+
+    let mut mail = MailParts {
+        alternative_bodies: Vec1::new(text_body("See the attached data.", ctx)),
+        inline_embeddings: Vec::new(),
+        attachments: vec![attachment],
+    }
+    .compose();
+
+    insert_envelope_headers(&mut mail, "Large attachment", vec!["bob@example.com"]);
+    mail
+}
+
+/// Creates a simple text mail with `RECIPIENT_COUNT` recipients in the `To` header.
+pub fn many_recipient_mail(ctx: &impl Context) -> Mail {
+    let recipients = (0..RECIPIENT_COUNT)
+        .map(|idx| format!("recipient{}@example.com", idx))
+        .collect::<Vec<_>>();
+
+    let mut mail = Mail::plain_text("Hy everyone.", ctx);
+    insert_envelope_headers(&mut mail, "Many recipients", recipients);
+    mail
+}
+
+/// Creates a mail with non us-ascii display names, subject and body.
+///
+/// When encoded for a non-internationalized mail this requires
+/// encoded words for the headers and a transfer encoding for the body.
+pub fn unicode_header_mail(ctx: &impl Context) -> Mail {
+    let mut mail = Mail::plain_text(
+        "Grüße aus Köln, привет из Москвы, 東京からこんにちは。\r\n".repeat(20),
+        ctx,
+    );
+
+    mail.insert_header(_From::auto_body([("Jürgen Müller", "juergen@example.com")]).unwrap());
+    mail.insert_header(
+        _To::auto_body([
+            ("Дмитрий Иванов", "dmitri@example.com"),
+            ("山田 太郎", "taro@example.com"),
+        ])
+        .unwrap(),
+    );
+    mail.insert_header(
+        Subject::auto_body(
+            "Ünïcödé Sübjéct — 日本語の件名 — тема письма, which is long enough to be folded",
+        )
+        .unwrap(),
+    );
+    mail
+}
+
+/// Creates a mail with `depth` levels of nested `multipart/mixed` bodies.
+///
+/// Each level contains a text body and the next level.
+pub fn deeply_nested_mail(depth: usize, ctx: &impl Context) -> Mail {
+    let mut mail = Mail::plain_text("Innermost body.", ctx);
+    for level in 0..depth {
+        let text = Mail::plain_text(format!("Body of level {}.", depth - level), ctx);
+        mail = mail.wrap_with_mixed(vec![text]);
+    }
+
+    insert_envelope_headers(&mut mail, "Deeply nested", vec!["bob@example.com"]);
+    mail
+}
+
+fn text_body(text: &str, ctx: &impl Context) -> BodyPart {
+    BodyPart {
+        resource: Resource::plain_text(text, ctx),
+        inline_embeddings: Vec::new(),
+        attachments: Vec::new(),
+    }
+}
+
+fn insert_envelope_headers<T>(mail: &mut Mail, subject: &str, recipients: Vec<T>)
+where
+    T: AsRef<str>,
+{
+    let recipients = recipients
+        .iter()
+        .map(|recipient| recipient.as_ref())
+        .collect::<Vec<_>>();
+
+    mail.insert_header(_From::auto_body(["alice@example.com"]).unwrap());
+    mail.insert_header(_To::auto_body(recipients).unwrap());
+    mail.insert_header(Subject::auto_body(subject).unwrap());
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+
+    use internals::MailType;
+
+    use super::*;
+    use crate::test_utils::CTX;
+
+    fn assert_encodes(mail: Mail, mail_type: MailType) -> Vec<u8> {
+        let ctx = CTX.unwrap().clone();
+        let enc_mail = mail.into_encodable_mail(ctx).wait().unwrap();
+        enc_mail.encode_into_bytes(mail_type).unwrap()
+    }
+
+    #[test]
+    fn large_attachment_mail_encodes() {
+        let encoded = assert_encodes(large_attachment_mail(CTX.unwrap()), MailType::Ascii);
+        assert!(encoded.len() > LARGE_ATTACHMENT_SIZE);
+    }
+
+    #[test]
+    fn many_recipient_mail_encodes() {
+        let encoded = assert_encodes(many_recipient_mail(CTX.unwrap()), MailType::Ascii);
+        let encoded = String::from_utf8(encoded).unwrap();
+        assert!(encoded.contains(&format!("recipient{}@example.com", RECIPIENT_COUNT - 1)));
+    }
+
+    #[test]
+    fn unicode_header_mail_encodes() {
+        assert_encodes(unicode_header_mail(CTX.unwrap()), MailType::Ascii);
+        assert_encodes(
+            unicode_header_mail(CTX.unwrap()),
+            MailType::Internationalized,
+        );
+    }
+
+    #[test]
+    fn deeply_nested_mail_encodes() {
+        assert_encodes(deeply_nested_mail(20, CTX.unwrap()), MailType::Ascii);
+    }
+}
@@ -4,6 +4,10 @@ use headers::header_components::Domain;
 
 use crate::default_impl::simple_context::{self, Context, ContextSetupError};
 
+pub mod context;
+pub mod diff;
+pub mod fixtures;
+
 pub struct CtxHolder {
     inner: Result<Context, ContextSetupError>,
 }
@@ -87,6 +87,11 @@ pub enum BuildInValidationError {
 
     #[fail(display = "Resent-From field in resent block without a Resent-Sender field")]
     MultiMailboxResentFromWithoutResentSender,
+
+    #[fail(
+        display = "List-Unsubscribe-Post requested one-click unsubscription but List-Unsubscribe has no https uri"
+    )]
+    OneClickUnsubscribeWithoutHttpsUri,
 }
 
 macro_rules! header_validation_bail {
@@ -133,6 +138,7 @@ pub struct ComponentCreationError {
     component: &'static str,
     backtrace: ChainTail,
     str_context: Option<String>,
+    span: Option<(usize, usize)>,
 }
 
 impl ComponentCreationError {
@@ -147,6 +153,7 @@ impl ComponentCreationError {
             component,
             backtrace: ChainTail::Error(parent.into()),
             str_context: None,
+            span: None,
         }
     }
 
@@ -158,6 +165,7 @@ impl ComponentCreationError {
             component,
             backtrace: ChainTail::Backtrace(Backtrace::new()),
             str_context: None,
+            span: None,
         }
     }
 
@@ -175,6 +183,7 @@ impl ComponentCreationError {
             component,
             backtrace: ChainTail::Backtrace(Backtrace::new()),
             str_context: Some(str_context.into()),
+            span: None,
         }
     }
 
@@ -196,6 +205,34 @@ impl ComponentCreationError {
         self.set_str_context(ctx);
         self
     }
+
+    /// Returns the byte range `(start, end)` of the offending part of the input, if known.
+    ///
+    /// The offsets are relative to the input the component was created
+    /// from, e.g. the whole email address when creating an `Email`.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        self.span
+    }
+
+    pub fn set_span(&mut self, start: usize, end: usize) {
+        self.span = Some((start, end));
+    }
+
+    pub fn with_span(mut self, start: usize, end: usize) -> Self {
+        self.set_span(start, end);
+        self
+    }
+
+    /// Moves the span (if there is one) by `offset` bytes.
+    ///
+    /// This is used when a component is created from a sub-slice of
+    /// the input of the component containing it.
+    pub(crate) fn with_span_offset(mut self, offset: usize) -> Self {
+        if let Some((start, end)) = self.span {
+            self.span = Some((start + offset, end + offset));
+        }
+        self
+    }
 }
 
 impl Fail for ComponentCreationError {
@@ -209,6 +246,10 @@ impl Fail for ComponentCreationError {
 
 impl Display for ComponentCreationError {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
-        write!(fter, "creating component {} failed", self.component)
+        write!(fter, "creating component {} failed", self.component)?;
+        if let Some((start, end)) = self.span {
+            write!(fter, " at byte {}..{}", start, end)?;
+        }
+        Ok(())
     }
 }
@@ -2,9 +2,13 @@ use std::any::TypeId;
 use std::fmt::{self, Debug};
 use std::ops::{Deref, DerefMut};
 
+use soft_ascii_string::SoftAsciiString;
+
 use internals::{
     encoder::{EncodableInHeader, EncodingWriter},
-    error::EncodingError,
+    error::{EncodingError, EncodingErrorKind},
+    grammar::{is_vchar, is_ws},
+    MailType,
 };
 
 use convert::HeaderTryInto;
@@ -258,3 +262,76 @@ impl HeaderObjTraitBoxExt for Box<dyn HeaderObjTrait> {
         }
     }
 }
+
+/// A header whose (already folded) body is written byte by byte as given.
+///
+/// This is an escape hatch for headers which have to be byte-exact, e.g.
+/// for interoperability with systems expecting a specific folding, see
+/// `HeaderMap::insert_raw`.
+#[derive(Debug, Clone)]
+pub struct VerbatimHeader {
+    name: HeaderName,
+    value: SoftAsciiString,
+    max_one: bool,
+}
+
+impl VerbatimHeader {
+    /// Creates a new verbatim header, line breaks in `value` have to be `"\r\n"` + WSP.
+    ///
+    /// If `name` is the name of a header defined by this crate which
+    /// can appear at most one time the verbatim header is "max one", too.
+    pub fn new(name: HeaderName, value: SoftAsciiString) -> Self {
+        let max_one = name.is_builtin_max_one();
+        VerbatimHeader {
+            name,
+            value,
+            max_one,
+        }
+    }
+
+    /// Returns the body exactly as it will be written.
+    pub fn value(&self) -> &str {
+        self.value.as_str()
+    }
+}
+
+impl HeaderObjTrait for VerbatimHeader {
+    fn name(&self) -> HeaderName {
+        self.name
+    }
+
+    fn is_max_one(&self) -> bool {
+        self.max_one
+    }
+
+    fn validator(&self) -> Option<HeaderMapValidator> {
+        None
+    }
+
+    /// Writes the body without any transformation.
+    ///
+    /// Fails if the body contains control characters, a line break which is
+    /// not followed by a continuation line (i.e. a line starting with WSP and
+    /// containing non WSP chars) or a line reaching the hard line length limit.
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let value = self.value.as_str();
+
+        let valid_chars = value
+            .chars()
+            .all(|ch| is_vchar(ch, MailType::Ascii) || is_ws(ch) || ch == '\r' || ch == '\n');
+        let valid_folding = value
+            .split("\r\n")
+            .skip(1)
+            .all(|line| line.starts_with(is_ws) && !line.chars().all(is_ws));
+
+        if !valid_chars || !valid_folding {
+            return Err(EncodingError::from(EncodingErrorKind::Malformed).with_str_context(value));
+        }
+
+        handle.write_str_verbatim(value)
+    }
+
+    fn boxed_clone(&self) -> Box<HeaderObj> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,370 @@
+//! Components for the `Authentication-Results` header (rfc8601).
+use quoted_string;
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+
+use internals::bind::quoted_string::{InternationalizedMailQsSpec, MailQsSpec};
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::{EncodingError, EncodingErrorKind};
+use internals::grammar::{is_token, is_token_char};
+
+use error::ComponentCreationError;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The body of a `Authentication-Results` header (rfc8601).
+///
+/// It consists of the authserv-id of the server which did the
+/// authentication checks and the results of the checks, e.g.:
+///
+/// ```text
+/// mx.example.com; spf=pass smtp.mailfrom=example.net; dkim=fail header.d=example.org
+/// ```
+///
+/// If there are no results it's encoded as `<authserv-id>; none`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuthenticationResults {
+    authserv_id: String,
+    results: Vec<AuthenticationResult>,
+}
+
+impl AuthenticationResults {
+    /// Creates a new instance without any results.
+    ///
+    /// # Error
+    ///
+    /// Fails if the `authserv_id` is not a valid token (e.g. a domain).
+    pub fn new(authserv_id: impl Into<String>) -> Result<Self, ComponentCreationError> {
+        let authserv_id = authserv_id.into();
+        if !is_token(&authserv_id) {
+            return Err(ComponentCreationError::new_with_str(
+                "AuthenticationResults",
+                authserv_id,
+            ));
+        }
+        Ok(AuthenticationResults {
+            authserv_id,
+            results: Vec::new(),
+        })
+    }
+
+    /// Adds a result and returns self.
+    pub fn with_result(mut self, result: AuthenticationResult) -> Self {
+        self.results.push(result);
+        self
+    }
+
+    /// Returns the authserv-id.
+    pub fn authserv_id(&self) -> &str {
+        &self.authserv_id
+    }
+
+    /// Returns the results in the order they were added.
+    pub fn results(&self) -> &[AuthenticationResult] {
+        &self.results
+    }
+
+    /// Returns the first result for the given method (ignoring its letter case).
+    pub fn get_result(&self, method: &str) -> Option<&AuthenticationResult> {
+        self.results
+            .iter()
+            .find(|result| result.method.eq_ignore_ascii_case(method))
+    }
+}
+
+impl EncodableInHeader for AuthenticationResults {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(&self.authserv_id))?;
+
+        if self.results.is_empty() {
+            handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+            handle.write_fws();
+            handle.write_str(SoftAsciiStr::from_unchecked("none"))?;
+        }
+
+        for result in self.results.iter() {
+            handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+            handle.write_fws();
+            result.encode(handle)?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// The result of a single authentication method, e.g. `spf=pass smtp.mailfrom=example.net`.
+///
+/// Method and result have to be keywords (letters, digits and `-`),
+/// the reason and the property values are encoded as quoted string
+/// if they are not a token (property values can also be a email
+/// address or domain).
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuthenticationResult {
+    method: String,
+    result: String,
+    reason: Option<String>,
+    properties: Vec<AuthenticationProperty>,
+}
+
+impl AuthenticationResult {
+    /// Creates a new instance for the given method and result, e.g. `dkim` and `pass`.
+    ///
+    /// # Error
+    ///
+    /// Fails if method or result is not a keyword.
+    pub fn new(
+        method: impl Into<String>,
+        result: impl Into<String>,
+    ) -> Result<Self, ComponentCreationError> {
+        let method = method.into();
+        let result = result.into();
+        if !is_keyword(&method) || !is_keyword(&result) {
+            return Err(ComponentCreationError::new_with_str(
+                "AuthenticationResult",
+                format!("{}={}", method, result),
+            ));
+        }
+        Ok(AuthenticationResult {
+            method,
+            result,
+            reason: None,
+            properties: Vec::new(),
+        })
+    }
+
+    /// Sets the reason (a human readable explanation of the result) and returns self.
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// Adds a property, e.g. `header.d=example.com`, and returns self.
+    ///
+    /// # Error
+    ///
+    /// Fails if `ptype` is not one of `smtp`, `header`, `body` or
+    /// `policy` or if the `property` is not a keyword.
+    pub fn with_property(
+        mut self,
+        ptype: &str,
+        property: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Self, ComponentCreationError> {
+        let ptype = ptype.to_ascii_lowercase();
+        let property = property.into();
+        let valid_ptype = ["smtp", "header", "body", "policy"].contains(&ptype.as_str());
+        if !valid_ptype || !is_keyword(&property) {
+            return Err(ComponentCreationError::new_with_str(
+                "AuthenticationProperty",
+                format!("{}.{}", ptype, property),
+            ));
+        }
+        self.properties.push(AuthenticationProperty {
+            ptype,
+            property,
+            value: value.into(),
+        });
+        Ok(self)
+    }
+
+    /// Returns the method, e.g. `spf`.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the result, e.g. `pass`.
+    pub fn result(&self) -> &str {
+        &self.result
+    }
+
+    /// Returns the reason, if there is one.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Returns the properties in the order they were added.
+    pub fn properties(&self) -> &[AuthenticationProperty] {
+        &self.properties
+    }
+}
+
+impl EncodableInHeader for AuthenticationResult {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(&self.method))?;
+        handle.write_char(SoftAsciiChar::from_unchecked('='))?;
+        handle.write_str(SoftAsciiStr::from_unchecked(&self.result))?;
+
+        if let Some(reason) = self.reason.as_ref() {
+            handle.write_fws();
+            handle.write_str(SoftAsciiStr::from_unchecked("reason="))?;
+            encode_value(reason, false, handle)?;
+        }
+
+        for property in self.properties.iter() {
+            handle.write_fws();
+            handle.write_str(SoftAsciiStr::from_unchecked(&property.ptype))?;
+            handle.write_char(SoftAsciiChar::from_unchecked('.'))?;
+            handle.write_str(SoftAsciiStr::from_unchecked(&property.property))?;
+            handle.write_char(SoftAsciiChar::from_unchecked('='))?;
+            encode_value(&property.value, true, handle)?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// A property of a `AuthenticationResult`, e.g. `smtp.mailfrom=example.net`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuthenticationProperty {
+    ptype: String,
+    property: String,
+    value: String,
+}
+
+impl AuthenticationProperty {
+    /// Returns the property type, i.e. one of `smtp`, `header`, `body` or `policy`.
+    pub fn ptype(&self) -> &str {
+        &self.ptype
+    }
+
+    /// Returns the property, e.g. `mailfrom`.
+    pub fn property(&self) -> &str {
+        &self.property
+    }
+
+    /// Returns the (unquoted) value.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Returns true if the input is a non empty string of letters, digits and `-`.
+fn is_keyword(input: &str) -> bool {
+    !input.is_empty()
+        && input
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+}
+
+/// Writes the value as is if it's a token (or a address if `allow_at`) else as quoted string.
+fn encode_value(
+    value: &str,
+    allow_at: bool,
+    handle: &mut EncodingWriter,
+) -> Result<(), EncodingError> {
+    let is_plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|ch| is_token_char(ch) || (allow_at && ch == '@'));
+
+    if is_plain {
+        return handle.write_str(SoftAsciiStr::from_unchecked(value));
+    }
+
+    let res = if handle.mail_type().is_internationalized() {
+        quoted_string::quote::<InternationalizedMailQsSpec>(value)
+    } else {
+        quoted_string::quote::<MailQsSpec>(value)
+    };
+    let quoted = res.map_err(|_err| {
+        EncodingError::from(EncodingErrorKind::Malformed).with_str_context(value)
+    })?;
+    handle.write_str_unchecked(&quoted)
+}
+
+#[cfg(test)]
+mod test {
+    use internals::encoder::EncodingBuffer;
+    use internals::MailType;
+
+    use super::*;
+
+    fn spf_pass() -> AuthenticationResult {
+        AuthenticationResult::new("spf", "pass")
+            .unwrap()
+            .with_property("smtp", "mailfrom", "sender@example.net")
+            .unwrap()
+    }
+
+    #[test]
+    fn rejects_invalid_parts() {
+        assert_err!(AuthenticationResults::new("mx example"));
+        assert_err!(AuthenticationResult::new("spf", "pass fail"));
+        assert_err!(AuthenticationResult::new("", "pass"));
+        assert_err!(spf_pass().with_property("envelope", "from", "a"));
+        assert_err!(spf_pass().with_property("smtp", "mail from", "a"));
+    }
+
+    #[test]
+    fn finds_results_by_method() {
+        let results = AuthenticationResults::new("mx.example.com")
+            .unwrap()
+            .with_result(spf_pass());
+        assert_eq!(results.get_result("SPF").unwrap().result(), "pass");
+        assert!(results.get_result("dkim").is_none());
+    }
+
+    ec_test! { encodes_no_result, {
+        AuthenticationResults::new("mx.example.com")?
+    } => ascii => [
+        Text "mx.example.com;",
+        MarkFWS,
+        Text " none"
+    ]}
+
+    ec_test! { encodes_results_with_reason_and_properties, {
+        AuthenticationResults::new("mx.example.com")?
+            .with_result(spf_pass())
+            .with_result(
+                AuthenticationResult::new("dkim", "fail")?
+                    .with_reason("bad signature")
+                    .with_property("header", "d", "example.org")?
+            )
+    } => ascii => [
+        Text "mx.example.com;",
+        MarkFWS,
+        Text " spf=pass",
+        MarkFWS,
+        Text " smtp.mailfrom=sender@example.net;",
+        MarkFWS,
+        Text " dkim=fail",
+        MarkFWS,
+        Text " reason=\"bad signature\"",
+        MarkFWS,
+        Text " header.d=example.org"
+    ]}
+
+    #[test]
+    fn folds_long_results() {
+        let mut results = AuthenticationResults::new("mx.example.com").unwrap();
+        for _ in 0..4 {
+            results = results.with_result(spf_pass());
+        }
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        encoder
+            .write_header_line(|handle| {
+                handle.write_str(SoftAsciiStr::from_unchecked("Authentication-Results:"))?;
+                handle.write_fws();
+                results.encode(handle)
+            })
+            .unwrap();
+        let encoded = encoder.as_str().unwrap();
+
+        assert!(encoded.contains("\r\n "));
+        for line in encoded.split("\r\n") {
+            assert!(line.len() <= 78, "too long line: {:?}", line);
+        }
+        let unfolded = encoded.replace("\r\n", "");
+        assert_eq!(unfolded.matches("; spf=pass smtp.mailfrom=").count(), 4);
+    }
+}
@@ -0,0 +1,266 @@
+//! Component for the `Auto-Submitted` header (rfc3834).
+use std::fmt::{self, Display};
+
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::{EncodingError, EncodingErrorKind};
+use internals::grammar::is_token;
+
+use error::ComponentCreationError;
+use HeaderTryFrom;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The keyword of a `Auto-Submitted` header.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AutoSubmittedKind {
+    /// The mail was send by a human (`no`).
+    No,
+    /// The mail was generated by an automatic process (`auto-generated`).
+    AutoGenerated,
+    /// The mail is an automatic response to another mail (`auto-replied`).
+    AutoReplied,
+    /// Any other (extension) keyword.
+    ///
+    /// The keyword has to be a valid token, it is encoded as given.
+    Other(String),
+}
+
+impl AutoSubmittedKind {
+    /// Returns the kind for the given keyword (ignoring its letter case).
+    ///
+    /// # Error
+    ///
+    /// Fails if the keyword is not a valid token.
+    pub fn from_keyword(keyword: &str) -> Result<Self, ComponentCreationError> {
+        let kind = match keyword.to_ascii_lowercase().as_str() {
+            "no" => AutoSubmittedKind::No,
+            "auto-generated" => AutoSubmittedKind::AutoGenerated,
+            "auto-replied" => AutoSubmittedKind::AutoReplied,
+            _ if is_token(keyword) => AutoSubmittedKind::Other(keyword.to_owned()),
+            _ => {
+                return Err(ComponentCreationError::new_with_str(
+                    "AutoSubmittedKind",
+                    keyword,
+                ))
+            }
+        };
+        Ok(kind)
+    }
+
+    /// Returns the keyword used to encode this kind.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            AutoSubmittedKind::No => "no",
+            AutoSubmittedKind::AutoGenerated => "auto-generated",
+            AutoSubmittedKind::AutoReplied => "auto-replied",
+            AutoSubmittedKind::Other(ref keyword) => keyword,
+        }
+    }
+}
+
+impl Display for AutoSubmittedKind {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+/// The body of a `Auto-Submitted` header, e.g. `auto-replied` (rfc3834).
+///
+/// Parameters are encoded as `; <name>=<value>` after the keyword,
+/// both name and value have to be tokens.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AutoSubmitted {
+    kind: AutoSubmittedKind,
+    params: Vec<(String, String)>,
+}
+
+impl AutoSubmitted {
+    /// Creates a new instance without any parameters.
+    pub fn new(kind: AutoSubmittedKind) -> Self {
+        AutoSubmitted {
+            kind,
+            params: Vec::new(),
+        }
+    }
+
+    /// Creates a new `auto-generated` instance.
+    pub fn auto_generated() -> Self {
+        AutoSubmitted::new(AutoSubmittedKind::AutoGenerated)
+    }
+
+    /// Creates a new `auto-replied` instance.
+    pub fn auto_replied() -> Self {
+        AutoSubmitted::new(AutoSubmittedKind::AutoReplied)
+    }
+
+    /// Adds a parameter and returns self.
+    ///
+    /// # Error
+    ///
+    /// Fails if the name or the value is not a valid token.
+    pub fn with_param(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Self, ComponentCreationError> {
+        let name = name.into();
+        let value = value.into();
+        if !is_token(&name) || !is_token(&value) {
+            return Err(ComponentCreationError::new_with_str(
+                "AutoSubmitted",
+                format!("{}={}", name, value),
+            ));
+        }
+        self.params.push((name, value));
+        Ok(self)
+    }
+
+    /// Returns the kind (keyword) of this instance.
+    pub fn kind(&self) -> &AutoSubmittedKind {
+        &self.kind
+    }
+
+    /// Returns the parameters in the order they were added.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// Returns the value of the parameter with the given name (ignoring its letter case).
+    pub fn get_param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(pname, _)| pname.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns true if this is anything but `no`, i.e. the mail was send automatically.
+    pub fn is_automatic(&self) -> bool {
+        self.kind != AutoSubmittedKind::No
+    }
+}
+
+impl From<AutoSubmittedKind> for AutoSubmitted {
+    fn from(kind: AutoSubmittedKind) -> Self {
+        AutoSubmitted::new(kind)
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for AutoSubmitted {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        let mut parts = value.split(';');
+        //UNWRAP_SAFE: split always returns at last one element
+        let keyword = parts.next().unwrap().trim();
+        let mut auto_submitted = AutoSubmitted::new(AutoSubmittedKind::from_keyword(keyword)?);
+        for param in parts {
+            let mut name_value = param.splitn(2, '=');
+            //UNWRAP_SAFE: splitn always returns at last one element
+            let name = name_value.next().unwrap().trim();
+            let value = name_value
+                .next()
+                .ok_or_else(|| ComponentCreationError::new_with_str("AutoSubmitted", value))?;
+            auto_submitted = auto_submitted.with_param(name, value.trim())?;
+        }
+        Ok(auto_submitted)
+    }
+}
+
+impl HeaderTryFrom<String> for AutoSubmitted {
+    fn try_from(value: String) -> Result<Self, ComponentCreationError> {
+        AutoSubmitted::try_from(value.as_str())
+    }
+}
+
+impl HeaderTryFrom<AutoSubmittedKind> for AutoSubmitted {
+    fn try_from(kind: AutoSubmittedKind) -> Result<Self, ComponentCreationError> {
+        Ok(AutoSubmitted::new(kind))
+    }
+}
+
+impl EncodableInHeader for AutoSubmitted {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let keyword = self.kind.as_str();
+        if !is_token(keyword) {
+            return Err(EncodingError::from(EncodingErrorKind::Malformed).with_str_context(keyword));
+        }
+        handle.write_str(SoftAsciiStr::from_unchecked(keyword))?;
+
+        for (name, value) in self.params.iter() {
+            handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+            handle.write_fws();
+            handle.write_str(SoftAsciiStr::from_unchecked(name))?;
+            handle.write_char(SoftAsciiChar::from_unchecked('='))?;
+            handle.write_str(SoftAsciiStr::from_unchecked(value))?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use internals::encoder::EncodingBuffer;
+    use internals::MailType;
+
+    use super::*;
+
+    #[test]
+    fn parses_keywords_ignoring_case() {
+        let auto_submitted = AutoSubmitted::try_from("Auto-Replied").unwrap();
+        assert_eq!(auto_submitted.kind(), &AutoSubmittedKind::AutoReplied);
+        assert!(auto_submitted.is_automatic());
+
+        let auto_submitted = AutoSubmitted::try_from("no").unwrap();
+        assert!(!auto_submitted.is_automatic());
+
+        let auto_submitted = AutoSubmitted::try_from("auto-notified").unwrap();
+        assert_eq!(
+            auto_submitted.kind(),
+            &AutoSubmittedKind::Other("auto-notified".to_owned())
+        );
+    }
+
+    #[test]
+    fn parses_parameters() {
+        let auto_submitted = AutoSubmitted::try_from("auto-generated; type=digest").unwrap();
+        assert_eq!(auto_submitted.kind(), &AutoSubmittedKind::AutoGenerated);
+        assert_eq!(auto_submitted.get_param("Type"), Some("digest"));
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert_err!(AutoSubmitted::try_from("auto replied"));
+        assert_err!(AutoSubmitted::try_from("auto-replied; type"));
+        assert_err!(AutoSubmitted::auto_replied().with_param("type", "a b"));
+    }
+
+    ec_test! { encodes_keyword, {
+        AutoSubmitted::auto_replied()
+    } => ascii => [
+        Text "auto-replied"
+    ]}
+
+    ec_test! { encodes_params, {
+        AutoSubmitted::auto_generated().with_param("type", "digest")?
+    } => ascii => [
+        Text "auto-generated;",
+        MarkFWS,
+        Text " type=digest"
+    ]}
+
+    #[test]
+    fn fails_for_invalid_other_keyword() {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        let mut handle = encoder.writer();
+        let input = AutoSubmitted::new(AutoSubmittedKind::Other("a b".to_owned()));
+        assert_err!(input.encode(&mut handle));
+        handle.undo_header();
+    }
+}
@@ -0,0 +1,210 @@
+//! Components for the `Content-Language` header (rfc3282).
+use std::fmt::{self, Display};
+
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+use vec1::Vec1;
+
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::EncodingError;
+
+use error::ComponentCreationError;
+use {HeaderTryFrom, HeaderTryInto};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A language tag like `de`, `en-US` or `zh-Hant-TW` (rfc5646).
+///
+/// The tag has to consist of subtags separated by `-`. The primary
+/// subtag has to have 2-8 letters (or be `x`/`i` for private use and
+/// grandfathered tags), all other subtags 1-8 letters or digits. The
+/// registry of subtags is not checked.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LanguageTag {
+    tag: String,
+}
+
+impl LanguageTag {
+    /// Creates a new instance from the given language tag.
+    ///
+    /// # Error
+    ///
+    /// Fails if the tag doesn't match the language tag grammar described above.
+    pub fn new(tag: impl AsRef<str>) -> Result<Self, ComponentCreationError> {
+        let tag = tag.as_ref().trim();
+        if !is_language_tag(tag) {
+            return Err(ComponentCreationError::new_with_str("LanguageTag", tag));
+        }
+        Ok(LanguageTag {
+            tag: tag.to_owned(),
+        })
+    }
+
+    /// Returns the language tag.
+    pub fn as_str(&self) -> &str {
+        &self.tag
+    }
+
+    /// Returns the primary subtag, e.g. `en` for `en-US`.
+    pub fn primary_subtag(&self) -> &str {
+        self.tag.split('-').next().unwrap_or("")
+    }
+}
+
+fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or("");
+    let primary_ok = match primary.len() {
+        1 => primary.eq_ignore_ascii_case("x") || primary.eq_ignore_ascii_case("i"),
+        2..=8 => primary.chars().all(|ch| ch.is_ascii_alphabetic()),
+        _ => false,
+    };
+    // `x`/`i` can not be used on their own
+    let needs_subtags = primary.len() == 1;
+
+    let mut has_subtags = false;
+    let subtags_ok = subtags.all(|subtag| {
+        has_subtags = true;
+        (1..=8).contains(&subtag.len()) && subtag.chars().all(|ch| ch.is_ascii_alphanumeric())
+    });
+
+    primary_ok && subtags_ok && (has_subtags || !needs_subtags)
+}
+
+impl Display for LanguageTag {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+impl HeaderTryFrom<&str> for LanguageTag {
+    fn try_from(tag: &str) -> Result<Self, ComponentCreationError> {
+        LanguageTag::new(tag)
+    }
+}
+
+impl HeaderTryFrom<String> for LanguageTag {
+    fn try_from(tag: String) -> Result<Self, ComponentCreationError> {
+        LanguageTag::new(tag)
+    }
+}
+
+impl EncodableInHeader for LanguageTag {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// The language tags of a `Content-Language` header, encoded as `de, en-US`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LanguageTagList(pub Vec1<LanguageTag>);
+
+deref0! { +mut LanguageTagList => Vec1<LanguageTag> }
+
+impl LanguageTagList {
+    pub fn from_single(tag: LanguageTag) -> Self {
+        LanguageTagList(Vec1::new(tag))
+    }
+}
+
+impl EncodableInHeader for LanguageTagList {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        for (idx, tag) in self.iter().enumerate() {
+            if idx > 0 {
+                handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                handle.write_fws();
+            }
+            tag.encode(handle)?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+impl HeaderTryFrom<&str> for LanguageTagList {
+    fn try_from(tag: &str) -> Result<Self, ComponentCreationError> {
+        Ok(LanguageTagList::from_single(LanguageTag::new(tag)?))
+    }
+}
+
+impl<T> HeaderTryFrom<Vec<T>> for LanguageTagList
+where
+    T: HeaderTryInto<LanguageTag>,
+{
+    fn try_from(vec: Vec<T>) -> Result<Self, ComponentCreationError> {
+        let mut tags = Vec::with_capacity(vec.len());
+        for tag in vec {
+            tags.push(tag.try_into()?);
+        }
+        Vec1::try_from_vec(tags)
+            .map(LanguageTagList)
+            .map_err(|_| ComponentCreationError::new("LanguageTagList"))
+    }
+}
+
+macro_rules! impl_header_try_from_array {
+    ($($len:tt)*) => ($(
+        impl<T> HeaderTryFrom<[T; $len]> for LanguageTagList
+            where T: HeaderTryInto<LanguageTag>
+        {
+            fn try_from(tags: [T; $len]) -> Result<Self, ComponentCreationError> {
+                let heapified: Box<[T]> = Box::new(tags);
+                let vecified: Vec<_> = heapified.into();
+                LanguageTagList::try_from(vecified)
+            }
+        }
+    )*);
+}
+
+impl_header_try_from_array! { 1 2 3 4 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_tags() {
+        assert_ok!(LanguageTag::new("de"));
+        assert_ok!(LanguageTag::new("en-US"));
+        assert_ok!(LanguageTag::new("zh-Hant-TW"));
+        assert_ok!(LanguageTag::new("es-419"));
+        assert_ok!(LanguageTag::new("x-klingon"));
+        assert_ok!(LanguageTag::new("i-navajo"));
+    }
+
+    #[test]
+    fn rejects_invalid_tags() {
+        assert_err!(LanguageTag::new(""));
+        assert_err!(LanguageTag::new("d"));
+        assert_err!(LanguageTag::new("x"));
+        assert_err!(LanguageTag::new("toolongtag"));
+        assert_err!(LanguageTag::new("de-"));
+        assert_err!(LanguageTag::new("de--DE"));
+        assert_err!(LanguageTag::new("d3"));
+        assert_err!(LanguageTag::new("de DE"));
+        assert_err!(LanguageTag::new("dé"));
+    }
+
+    #[test]
+    fn returns_the_primary_subtag() {
+        let tag = LanguageTag::new("en-US").unwrap();
+        assert_eq!(tag.primary_subtag(), "en");
+    }
+
+    ec_test! { encodes_list, {
+        LanguageTagList::try_from(["de", "en-US"])?
+    } => ascii => [
+        Text "de,",
+        MarkFWS,
+        Text " en-US"
+    ]}
+}
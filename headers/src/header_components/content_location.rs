@@ -0,0 +1,118 @@
+//! Component for the `Content-Location` header (rfc2557).
+use std::fmt::{self, Display, Write};
+
+use soft_ascii_string::SoftAsciiStr;
+
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::EncodingError;
+
+use error::ComponentCreationError;
+use HeaderTryFrom;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The (absolute or relative) uri a body part can be referenced with, e.g. from a html body.
+///
+/// IRIs can be used, too. Non us-ascii characters are percent encoded
+/// (as utf-8) when creating the instance, so it always contains a uri.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ContentLocation {
+    uri: String,
+}
+
+impl ContentLocation {
+    /// Creates a new instance from the given uri or iri.
+    ///
+    /// # Error
+    ///
+    /// Fails if the uri is empty or contains (us-ascii) whitespace,
+    /// control characters or any of `<`, `>`, `"`.
+    pub fn new(uri: impl AsRef<str>) -> Result<Self, ComponentCreationError> {
+        let iri = uri.as_ref().trim();
+
+        let valid_chars = iri
+            .chars()
+            .all(|ch| !ch.is_ascii() || (ch.is_ascii_graphic() && !['<', '>', '"'].contains(&ch)));
+
+        if iri.is_empty() || !valid_chars {
+            return Err(ComponentCreationError::new_with_str("ContentLocation", iri));
+        }
+
+        let mut uri = String::with_capacity(iri.len());
+        for byte in iri.bytes() {
+            if byte.is_ascii() {
+                uri.push(byte as char);
+            } else {
+                // OK: writing to a string can not fail
+                let _ = write!(uri, "%{:02X}", byte);
+            }
+        }
+
+        Ok(ContentLocation { uri })
+    }
+
+    /// Returns the uri.
+    pub fn as_str(&self) -> &str {
+        &self.uri
+    }
+}
+
+impl Display for ContentLocation {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+impl HeaderTryFrom<&str> for ContentLocation {
+    fn try_from(uri: &str) -> Result<Self, ComponentCreationError> {
+        ContentLocation::new(uri)
+    }
+}
+
+impl HeaderTryFrom<String> for ContentLocation {
+    fn try_from(uri: String) -> Result<Self, ComponentCreationError> {
+        ContentLocation::new(uri)
+    }
+}
+
+impl EncodableInHeader for ContentLocation {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accepts_absolute_and_relative_uris() {
+        assert_ok!(ContentLocation::new("https://example.com/logo.png"));
+        assert_ok!(ContentLocation::new("images/logo.png"));
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        assert_err!(ContentLocation::new(""));
+        assert_err!(ContentLocation::new("images/my logo.png"));
+        assert_err!(ContentLocation::new("<images/logo.png>"));
+    }
+
+    #[test]
+    fn percent_encodes_iris() {
+        let location = ContentLocation::new("https://example.com/grüße.png").unwrap();
+        assert_eq!(location.as_str(), "https://example.com/gr%C3%BC%C3%9Fe.png");
+    }
+
+    ec_test! { encodes_uri, {
+        ContentLocation::new("https://example.com/logo.png")?
+    } => ascii => [
+        Text "https://example.com/logo.png"
+    ]}
+}
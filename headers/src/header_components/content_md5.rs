@@ -0,0 +1,157 @@
+use soft_ascii_string::SoftAsciiStr;
+
+use internals::bind::base64;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::EncodingError;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The `Content-MD5` header component (rfc1864).
+///
+/// It contains the 128-bit MD5 digest of a body (before it got transfer
+/// encoded) and is encoded as base64 in the header. While the header
+/// is considered obsolete some systems still use it to verify the
+/// integrity of a body.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ContentMd5 {
+    digest: [u8; 16],
+}
+
+impl ContentMd5 {
+    /// Create a new instance from an already computed MD5 digest.
+    pub fn from_digest(digest: [u8; 16]) -> Self {
+        ContentMd5 { digest }
+    }
+
+    /// Create a new instance by computing the MD5 digest of given data.
+    pub fn of(data: impl AsRef<[u8]>) -> Self {
+        ContentMd5::from_digest(md5_digest(data.as_ref()))
+    }
+
+    /// Returns the MD5 digest.
+    pub fn digest(&self) -> &[u8; 16] {
+        &self.digest
+    }
+}
+
+impl EncodableInHeader for ContentMd5 {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let encoded = base64::normal_encode(self.digest);
+        handle.write_str(SoftAsciiStr::from_unchecked(encoded.as_str()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+const CONSTANTS: [u32; 64] = [
+    0xd76a_a478, 0xe8c7_b756, 0x2420_70db, 0xc1bd_ceee, 0xf57c_0faf, 0x4787_c62a, 0xa830_4613,
+    0xfd46_9501, 0x6980_98d8, 0x8b44_f7af, 0xffff_5bb1, 0x895c_d7be, 0x6b90_1122, 0xfd98_7193,
+    0xa679_438e, 0x49b4_0821, 0xf61e_2562, 0xc040_b340, 0x265e_5a51, 0xe9b6_c7aa, 0xd62f_105d,
+    0x0244_1453, 0xd8a1_e681, 0xe7d3_fbc8, 0x21e1_cde6, 0xc337_07d6, 0xf4d5_0d87, 0x455a_14ed,
+    0xa9e3_e905, 0xfcef_a3f8, 0x676f_02d9, 0x8d2a_4c8a, 0xfffa_3942, 0x8771_f681, 0x6d9d_6122,
+    0xfde5_380c, 0xa4be_ea44, 0x4bde_cfa9, 0xf6bb_4b60, 0xbebf_bc70, 0x289b_7ec6, 0xeaa1_27fa,
+    0xd4ef_3085, 0x0488_1d05, 0xd9d4_d039, 0xe6db_99e5, 0x1fa2_7cf8, 0xc4ac_5665, 0xf429_2244,
+    0x432a_ff97, 0xab94_23a7, 0xfc93_a039, 0x655b_59c3, 0x8f0c_cc92, 0xffef_f47d, 0x8584_5dd1,
+    0x6fa8_7e4f, 0xfe2c_e6e0, 0xa301_4314, 0x4e08_11a1, 0xf753_7e82, 0xbd3a_f235, 0x2ad7_d2bb,
+    0xeb86_d391,
+];
+
+/// Computes the MD5 digest (rfc1321) of the given data.
+///
+/// MD5 is only needed for the `Content-MD5` header, so this small
+/// implementation is used instead of pulling in an additional dependency.
+fn md5_digest(data: &[u8]) -> [u8; 16] {
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+
+    let bit_len = (data.len() as u64).wrapping_mul(8);
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&bit_len.to_le_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut words = [0u32; 16];
+        for (word, bytes) in words.iter_mut().zip(chunk.chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        let [mut a, mut b, mut c, mut d] = state;
+        for idx in 0..64 {
+            let (f, word_idx) = match idx / 16 {
+                0 => ((b & c) | (!b & d), idx),
+                1 => ((d & b) | (!d & c), (5 * idx + 1) % 16),
+                2 => (b ^ c ^ d, (3 * idx + 5) % 16),
+                _ => (c ^ (b | !d), (7 * idx) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(CONSTANTS[idx])
+                .wrapping_add(words[word_idx])
+                .rotate_left(SHIFTS[idx]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hex(digest: &[u8; 16]) -> String {
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn md5_of_rfc1321_test_suite() {
+        let suite: &[(&str, &str)] = &[
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("a", "0cc175b9c0f1b6a831c399e269772661"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                "abcdefghijklmnopqrstuvwxyz",
+                "c3fcd3d76192e4007dfb496cca67e13b",
+            ),
+            (
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+
+        for &(input, expected) in suite {
+            assert_eq!(hex(&md5_digest(input.as_bytes())), expected, "input: {:?}", input);
+        }
+    }
+
+    ec_test! {encodes_digest_as_base64, {
+        ContentMd5::of("abc")
+    } => ascii => [
+        Text "kAFQmDzST7DWlj99KOF/cg=="
+    ]}
+}
@@ -0,0 +1,299 @@
+//! Components for the fields of a `message/delivery-status` body (rfc3464).
+//!
+//! The body of a `message/delivery-status` part consists of header like
+//! fields, some of which have a structured value like e.g.
+//! `Final-Recipient: rfc822; user@example.com` or `Status: 5.1.1`.
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::EncodingError;
+
+use error::ComponentCreationError;
+use header_components::Email;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A address prefixed with its address type, e.g. `rfc822; user@example.com`.
+///
+/// This is used for the `Original-Recipient` and `Final-Recipient` fields
+/// (with address types like `rfc822`) as well as the `Reporting-MTA` and
+/// `Remote-MTA` fields (with name types like `dns`).
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TypedAddress {
+    address_type: String,
+    address: String,
+}
+
+impl TypedAddress {
+    /// Creates a new instance from the address type and the address.
+    ///
+    /// # Error
+    ///
+    /// Fails if the address type is not an atom consisting of us-ascii
+    /// letters, digits and `-`, or if the address is empty or contains
+    /// non us-ascii or control characters.
+    ///
+    /// If the address type is `rfc822` (case insensitive) this also
+    /// fails if the address is not a valid email address.
+    pub fn new(address_type: &str, address: &str) -> Result<Self, ComponentCreationError> {
+        let valid_type = !address_type.is_empty()
+            && address_type
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-');
+
+        if !valid_type {
+            return Err(ComponentCreationError::new_with_str(
+                "TypedAddress",
+                address_type,
+            ));
+        }
+
+        let address = address.trim();
+        let valid_address = !address.is_empty()
+            && address
+                .chars()
+                .all(|ch| ch.is_ascii() && !ch.is_ascii_control());
+
+        if !valid_address {
+            return Err(ComponentCreationError::new_with_str(
+                "TypedAddress",
+                address,
+            ));
+        }
+
+        if address_type.eq_ignore_ascii_case("rfc822") {
+            Email::new(address)
+                .map_err(|err| ComponentCreationError::from_parent(err, "TypedAddress"))?;
+        }
+
+        Ok(TypedAddress {
+            address_type: address_type.to_owned(),
+            address: address.to_owned(),
+        })
+    }
+
+    /// Creates a new instance with the `rfc822` address type.
+    pub fn rfc822(email: &str) -> Result<Self, ComponentCreationError> {
+        TypedAddress::new("rfc822", email)
+    }
+
+    /// Creates a new instance with the `dns` name type, e.g. for the `Reporting-MTA`.
+    pub fn dns(host: &str) -> Result<Self, ComponentCreationError> {
+        TypedAddress::new("dns", host)
+    }
+
+    /// Returns the address type, e.g. `rfc822`.
+    pub fn address_type(&self) -> &str {
+        &self.address_type
+    }
+
+    /// Returns the address.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+impl EncodableInHeader for TypedAddress {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        // both are validated to be us-ascii on creation
+        handle.write_str(SoftAsciiStr::from_unchecked(&self.address_type))?;
+        handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+        handle.write_fws();
+        handle.write_str(SoftAsciiStr::from_unchecked(&self.address))?;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// The action performed by the reporting MTA for a recipient.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DsnAction {
+    /// The message could not be delivered.
+    #[cfg_attr(feature = "serde", serde(rename = "failed"))]
+    Failed,
+    /// The delivery was delayed, but will be retried.
+    #[cfg_attr(feature = "serde", serde(rename = "delayed"))]
+    Delayed,
+    /// The message was successfully delivered.
+    #[cfg_attr(feature = "serde", serde(rename = "delivered"))]
+    Delivered,
+    /// The message was relayed to an environment not supporting DSNs.
+    #[cfg_attr(feature = "serde", serde(rename = "relayed"))]
+    Relayed,
+    /// The message was delivered and forwarded to multiple addresses.
+    #[cfg_attr(feature = "serde", serde(rename = "expanded"))]
+    Expanded,
+}
+
+impl DsnAction {
+    pub fn repr(&self) -> &SoftAsciiStr {
+        use self::DsnAction::*;
+        SoftAsciiStr::from_unchecked(match *self {
+            Failed => "failed",
+            Delayed => "delayed",
+            Delivered => "delivered",
+            Relayed => "relayed",
+            Expanded => "expanded",
+        })
+    }
+}
+
+impl EncodableInHeader for DsnAction {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(self.repr())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+/// A enhanced mail system status code (rfc3463), e.g. `5.1.1`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DsnStatus {
+    class: u8,
+    subject: u16,
+    detail: u16,
+}
+
+impl DsnStatus {
+    /// Creates a new status code.
+    ///
+    /// # Error
+    ///
+    /// Fails if the class is not `2` (success), `4` (persistent transient
+    /// failure) or `5` (permanent failure), or if the subject or detail
+    /// has more than three digits.
+    pub fn new(class: u8, subject: u16, detail: u16) -> Result<Self, ComponentCreationError> {
+        let valid = (class == 2 || class == 4 || class == 5) && subject <= 999 && detail <= 999;
+        if !valid {
+            return Err(ComponentCreationError::new_with_str(
+                "DsnStatus",
+                format!("{}.{}.{}", class, subject, detail),
+            ));
+        }
+        Ok(DsnStatus {
+            class,
+            subject,
+            detail,
+        })
+    }
+
+    pub fn class(&self) -> u8 {
+        self.class
+    }
+
+    pub fn subject(&self) -> u16 {
+        self.subject
+    }
+
+    pub fn detail(&self) -> u16 {
+        self.detail
+    }
+}
+
+impl FromStr for DsnStatus {
+    type Err = ComponentCreationError;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        let err = || ComponentCreationError::new_with_str("DsnStatus", status);
+
+        let mut parts = status.split('.');
+        let mut next_part = || -> Result<u16, ComponentCreationError> {
+            let part = parts.next().ok_or_else(err)?;
+            if part.is_empty() || part.len() > 3 || !part.bytes().all(|bch| bch.is_ascii_digit()) {
+                return Err(err());
+            }
+            part.parse().map_err(|_| err())
+        };
+
+        let class = next_part()?;
+        let subject = next_part()?;
+        let detail = next_part()?;
+
+        if parts.next().is_some() || class > 9 {
+            return Err(err());
+        }
+
+        DsnStatus::new(class as u8, subject, detail)
+    }
+}
+
+impl Display for DsnStatus {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{}.{}.{}", self.class, self.subject, self.detail)
+    }
+}
+
+impl EncodableInHeader for DsnStatus {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(&self.to_string()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    ec_test! {final_recipient, {
+        TypedAddress::rfc822("user@example.com")?
+    } => ascii => [
+        Text "rfc822;",
+        MarkFWS,
+        Text " user@example.com"
+    ]}
+
+    ec_test! {status, {
+        "5.1.1".parse::<DsnStatus>()?
+    } => ascii => [
+        Text "5.1.1"
+    ]}
+
+    ec_test! {action, {
+        DsnAction::Failed
+    } => ascii => [
+        Text "failed"
+    ]}
+
+    #[test]
+    fn typed_address_rejects_invalid_address_type() {
+        assert_err!(TypedAddress::new("rfc 822", "user@example.com"));
+        assert_err!(TypedAddress::new("", "user@example.com"));
+    }
+
+    #[test]
+    fn typed_address_validates_rfc822_addresses() {
+        assert_err!(TypedAddress::rfc822("not an email"));
+        assert_err!(TypedAddress::new("RFC822", "user.example.com"));
+        assert_ok!(TypedAddress::new("x-custom", "not an email"));
+    }
+
+    #[test]
+    fn status_parsing() {
+        let status = assert_ok!("4.7.12".parse::<DsnStatus>());
+        assert_eq!(
+            (status.class(), status.subject(), status.detail()),
+            (4, 7, 12)
+        );
+
+        assert_err!("3.1.1".parse::<DsnStatus>());
+        assert_err!("5.1".parse::<DsnStatus>());
+        assert_err!("5.1.1.1".parse::<DsnStatus>());
+        assert_err!("5.1.1000".parse::<DsnStatus>());
+        assert_err!("5.a.1".parse::<DsnStatus>());
+    }
+}
@@ -5,7 +5,7 @@ use std::fmt;
 use failure::Fail;
 use media_type::push_params_to_buffer;
 use media_type::spec::{Ascii, Internationalized, MimeSpec, Modern};
-use soft_ascii_string::SoftAsciiStr;
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -13,6 +13,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use error::ComponentCreationError;
 use internals::encoder::{EncodableInHeader, EncodingWriter};
 use internals::error::{EncodingError, EncodingErrorKind};
+use internals::grammar::is_token_char;
 use HeaderTryFrom;
 
 use super::FileMeta;
@@ -146,7 +147,11 @@ impl EncodableInHeader for DispositionParameters {
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
         let mut params = Vec::<(&str, Cow<str>)>::new();
         if let Some(filename) = self.file_name.as_ref() {
-            params.push(("filename", Cow::Borrowed(filename)));
+            if needs_extended_encoding(filename) {
+                encode_extended_param(handle, "filename", filename)?;
+            } else {
+                params.push(("filename", Cow::Borrowed(filename)));
+            }
         }
         if let Some(creation_date) = self.creation_date.as_ref() {
             params.push(("creation-date", Cow::Owned(creation_date.to_rfc2822())));
@@ -206,6 +211,84 @@ impl EncodableInHeader for Disposition {
 
 deref0! {+mut DispositionParameters => FileMeta }
 
+/// The maximal length of the value of a single section of a rfc2231 encoded parameter.
+const MAX_EXTENDED_SECTION_LEN: usize = 48;
+
+/// The charset prefix of a rfc2231 encoded parameter value (without language).
+const EXTENDED_VALUE_PREFIX: &str = "UTF-8''";
+
+/// Returns true if the value can't be represented as token or quoted-string in a us-ascii mail.
+fn needs_extended_encoding(value: &str) -> bool {
+    value
+        .chars()
+        .any(|ch| !(ch.is_ascii_graphic() || ch == ' '))
+}
+
+/// Percent encodes the value as `ext-value` (rfc2231), i.e. all bytes which are not attribute-chars.
+fn percent_encode_ext_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for bch in value.bytes() {
+        let ch = bch as char;
+        if is_token_char(ch) && !['*', '\'', '%'].contains(&ch) {
+            encoded.push(ch);
+        } else {
+            encoded.push_str(&format!("%{:02X}", bch));
+        }
+    }
+    encoded
+}
+
+/// Splits the `UTF-8''` prefixed, percent encoded value into sections.
+///
+/// Sections are at most `MAX_EXTENDED_SECTION_LEN` bytes long (except if
+/// the charset prefix plus the first encoded byte is longer) and are never
+/// split within a percent encoded byte.
+fn extended_value_sections(value: &str) -> Vec<String> {
+    let encoded = percent_encode_ext_value(value);
+    let mut sections = Vec::new();
+    let mut current = EXTENDED_VALUE_PREFIX.to_owned();
+    let mut rest = encoded.as_str();
+    while !rest.is_empty() {
+        let unit_len = if rest.starts_with('%') { 3 } else { 1 };
+        if current.len() + unit_len > MAX_EXTENDED_SECTION_LEN && !current.is_empty() {
+            sections.push(current);
+            current = String::new();
+        }
+        current.push_str(&rest[..unit_len]);
+        rest = &rest[unit_len..];
+    }
+    if !current.is_empty() {
+        sections.push(current);
+    }
+    sections
+}
+
+/// Writes the parameter encoded as specified in rfc2231.
+///
+/// If the encoded value is too long for a single section it is split
+/// using parameter continuations (`name*0*=..; name*1*=..`) with the
+/// possibility to fold the line between the sections.
+fn encode_extended_param(
+    handle: &mut EncodingWriter,
+    name: &str,
+    value: &str,
+) -> Result<(), EncodingError> {
+    let sections = extended_value_sections(value);
+    let single_section = sections.len() == 1;
+    for (idx, section) in sections.iter().enumerate() {
+        handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+        handle.write_fws();
+        let param_name = if single_section {
+            format!("{}*=", name)
+        } else {
+            format!("{}*{}*=", name, idx)
+        };
+        handle.write_str(SoftAsciiStr::from_unchecked(&param_name))?;
+        handle.write_str(SoftAsciiStr::from_unchecked(section))?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use chrono;
@@ -264,7 +347,124 @@ mod test {
     } => ascii => [
         Text "inline; filename=logo.png"
     ]}
-    //TODO: (1 allow FWS or so in parameters) (2 utf8 file names)
+    //TODO: allow FWS or so in parameters
+
+    ec_test! { attachment_non_ascii_file_name, {
+        Disposition::new(DispositionKind::Attachment, FileMeta {
+            file_name: Some("رسالة.pdf".to_owned()),
+            ..Default::default()
+        })
+    } => ascii => [
+        Text "attachment;",
+        MarkFWS,
+        Text " filename*=UTF-8''%D8%B1%D8%B3%D8%A7%D9%84%D8%A9.pdf"
+    ]}
+
+    mod extended_file_name {
+        use internals::encoder::EncodingBuffer;
+        use internals::MailType;
+
+        use super::*;
+
+        fn encode_header(file_name: &str) -> String {
+            let disposition = Disposition::new(
+                DispositionKind::Attachment,
+                FileMeta {
+                    file_name: Some(file_name.to_owned()),
+                    size: Some(12),
+                    ..Default::default()
+                },
+            );
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            encoder
+                .write_header_line(|handle| {
+                    handle.write_str(SoftAsciiStr::from_unchecked("Content-Disposition:"))?;
+                    handle.write_fws();
+                    disposition.encode(handle)
+                })
+                .unwrap();
+            encoder.to_string().unwrap()
+        }
+
+        /// Decodes the (potentially continued) rfc2231 encoded `filename*` parameter.
+        fn decode_file_name(header: &str) -> String {
+            let unfolded = header.replace("\r\n", "");
+            let mut sections = unfolded
+                .split(';')
+                .map(str::trim)
+                .filter(|param| param.starts_with("filename*"))
+                .map(|param| {
+                    let (name, value) = param.split_at(param.find('=').unwrap());
+                    let idx = name
+                        .trim_start_matches("filename*")
+                        .trim_end_matches('*')
+                        .parse::<usize>()
+                        .unwrap_or(0);
+                    (idx, value[1..].to_owned())
+                })
+                .collect::<Vec<_>>();
+            sections.sort();
+
+            let joined = sections
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect::<String>();
+            let encoded = joined.trim_start_matches("UTF-8''").as_bytes();
+
+            let mut decoded = Vec::new();
+            let mut idx = 0;
+            while idx < encoded.len() {
+                if encoded[idx] == b'%' {
+                    let hex = ::std::str::from_utf8(&encoded[idx + 1..idx + 3]).unwrap();
+                    decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                    idx += 3;
+                } else {
+                    decoded.push(encoded[idx]);
+                    idx += 1;
+                }
+            }
+            String::from_utf8(decoded).unwrap()
+        }
+
+        #[test]
+        fn round_trips_arabic_file_name() {
+            let file_name = "رسالة.pdf";
+            let header = encode_header(file_name);
+
+            assert!(header.contains("filename*=UTF-8''"));
+            assert!(header.ends_with("; size=12\r\n"));
+            assert_eq!(decode_file_name(&header), file_name);
+        }
+
+        #[test]
+        fn splits_long_file_names_into_continuations() {
+            let file_name = "رسالة طويلة جدا تحتاج إلى تقسيم في عدة أجزاء.pdf";
+            let header = encode_header(file_name);
+
+            assert!(header.contains("filename*0*=UTF-8''"));
+            assert!(header.contains("filename*1*="));
+            assert!(!header.contains("filename*="));
+            for line in header.split_terminator("\r\n") {
+                assert!(line.len() <= 78, "line too long: {:?}", line);
+            }
+            assert_eq!(decode_file_name(&header), file_name);
+        }
+
+        #[test]
+        fn encodes_rfc2231_special_chars() {
+            let file_name = "a*b'c%d ä.txt";
+            let header = encode_header(file_name);
+
+            assert!(header.contains("filename*=UTF-8''a%2Ab%27c%25d%20%C3%A4.txt"));
+            assert_eq!(decode_file_name(&header), file_name);
+        }
+
+        #[test]
+        fn keeps_quoting_ascii_file_names() {
+            let header = encode_header("this is nice.txt");
+            assert!(header.contains("; filename=\"this is nice.txt\""));
+        }
+    }
 
     #[test]
     fn test_from_str() {
@@ -48,23 +48,66 @@ impl Email {
                 //2. get 2 sub shares split befor/after @
                 let index = shared.find('@').ok_or_else(|| {
                     ComponentCreationError::new_with_str("Email", shared.to_string())
+                        .with_span(0, shared.len())
                 })?;
 
                 let left = shared.clone().map(|all| &all[..index]);
                 let local_part = LocalPart::try_from(Input(InnerUtf8::Shared(left)))?;
                 //index+1 is ok as '@'.utf8_len() == 1
                 let right = shared.map(|all| &all[index + 1..]);
-                let domain = Domain::try_from(Input(InnerUtf8::Shared(right)))?;
+                let domain = Domain::try_from(Input(InnerUtf8::Shared(right)))
+                    .map_err(|err| err.with_span_offset(index + 1))?;
                 Ok(Email { local_part, domain })
             }
         }
     }
+
+    /// Returns a canonical form of this email, e.g. for deduplicating recipients.
+    ///
+    /// The domain is normalized (see `Domain::normalize`) while the local part
+    /// is kept as is, as it is (technically) case-sensitive.
+    pub fn normalize(&self) -> Email {
+        Email {
+            local_part: self.local_part.clone(),
+            domain: self.domain.normalize(),
+        }
+    }
+
+    /// Like `normalize` but additionally lowercases the local part.
+    ///
+    /// This is meant for mail providers which treat the local part as
+    /// case-insensitive. Local parts which have to be quoted (i.e. which
+    /// are not a `dot-atom`) are not altered.
+    pub fn normalize_local_lowercase(&self) -> Email {
+        let local_part = if self.local_part.needs_quoting() {
+            self.local_part.clone()
+        } else {
+            LocalPart(Input::from(self.local_part.as_str().to_lowercase()))
+        };
+
+        Email {
+            local_part,
+            domain: self.domain.normalize(),
+        }
+    }
 }
 
 impl LocalPart {
     pub fn check_if_internationalized(&self) -> bool {
         self.0.as_str().bytes().any(|b| b > 0x7f)
     }
+
+    /// Returns true if the local part is not a `dot-atom` and as such is encoded as quoted string.
+    fn needs_quoting(&self) -> bool {
+        let local_part = self.0.as_str();
+        local_part.is_empty()
+            || local_part.starts_with('.')
+            || local_part.ends_with('.')
+            || local_part.contains("..")
+            || !local_part
+                .chars()
+                .all(|ch| ch == '.' || is_atext(ch, MailType::Internationalized))
+    }
 }
 
 impl<'a> HeaderTryFrom<&'a str> for Email {
@@ -187,10 +230,11 @@ impl Domain {
     fn check_domain(domain: &str) -> Result<MailType, ComponentCreationError> {
         if domain.starts_with('[') && domain.ends_with(']') {
             //TODO improved support for domain literals, e.g. internationalized ones? CRLF? etc.
-            for ch in domain.chars() {
+            for (idx, ch) in domain.char_indices() {
                 if !(is_dtext(ch, MailType::Ascii) || is_ws(ch)) {
                     let mut err = ComponentCreationError::new("Domain");
                     err.set_str_context(domain);
+                    err.set_span(idx, idx + ch.len_utf8());
                     return Err(err);
                 }
             }
@@ -198,7 +242,7 @@ impl Domain {
         } else {
             let mut ascii = true;
             let mut dot_alowed = false;
-            for char in domain.chars() {
+            for (idx, char) in domain.char_indices() {
                 if ascii {
                     ascii = is_ascii(char)
                 }
@@ -207,6 +251,7 @@ impl Domain {
                 } else if !is_atext(char, MailType::Internationalized) {
                     let mut err = ComponentCreationError::new("Domain");
                     err.set_str_context(domain);
+                    err.set_span(idx, idx + char.len_utf8());
                     return Err(err);
                 } else {
                     dot_alowed = true;
@@ -237,6 +282,19 @@ impl Domain {
             SimpleItem::Utf8(ref utf8) => Cow::Owned(idna::puny_code_domain(utf8)?),
         })
     }
+
+    /// Returns a canonical form of this domain.
+    ///
+    /// Domains are case-insensitive, so the domain is lowercased. Internationalized
+    /// domains are converted to their ascii (punycode) form using IDNA, if this
+    /// fails they are only lowercased.
+    pub fn normalize(&self) -> Domain {
+        let normalized = match self.to_ascii_string() {
+            Ok(ascii) => ascii.as_str().to_ascii_lowercase(),
+            Err(_) => self.as_str().to_lowercase(),
+        };
+        Domain::from_unchecked(normalized)
+    }
 }
 
 impl EncodableInHeader for Domain {
@@ -392,4 +450,55 @@ mod test {
         let res: Result<Domain, _> = "...".parse();
         assert!(res.is_err());
     }
+
+    #[test]
+    fn normalize_lowercases_domain_only() {
+        let email = Email::try_from("Hans.Peter@Example.COM").unwrap();
+        let normalized = email.normalize();
+        assert_eq!(normalized.local_part.as_str(), "Hans.Peter");
+        assert_eq!(normalized.domain.as_str(), "example.com");
+        assert_eq!(
+            normalized,
+            Email::try_from("Hans.Peter@example.com").unwrap()
+        );
+    }
+
+    #[test]
+    fn normalize_puny_codes_internationalized_domains() {
+        let email = Email::try_from("abc@HÖ.test").unwrap();
+        let normalized = email.normalize();
+        assert_eq!(normalized.domain.as_str(), "xn--h-1ga.test");
+        assert_eq!(
+            normalized,
+            Email::try_from("abc@hö.Test").unwrap().normalize()
+        );
+    }
+
+    #[test]
+    fn normalize_local_lowercase() {
+        let email = Email::try_from("Hans.Peter@Example.COM").unwrap();
+        let normalized = email.normalize_local_lowercase();
+        assert_eq!(normalized.local_part.as_str(), "hans.peter");
+        assert_eq!(normalized.domain.as_str(), "example.com");
+    }
+
+    #[test]
+    fn normalize_local_lowercase_keeps_quoted_local_parts() {
+        let email = Email::try_from("Ha Ns@example.com").unwrap();
+        let normalized = email.normalize_local_lowercase();
+        assert_eq!(normalized.local_part.as_str(), "Ha Ns");
+
+        let email = Email::try_from("Hans..Peter@example.com").unwrap();
+        let normalized = email.normalize_local_lowercase();
+        assert_eq!(normalized.local_part.as_str(), "Hans..Peter");
+    }
+
+    #[test]
+    fn invalid_email_reports_span() {
+        let err = Email::try_from("abc@de(f.gh").unwrap_err();
+        assert_eq!(err.span(), Some((6, 7)));
+
+        let err = Email::try_from("abc.de").unwrap_err();
+        assert_eq!(err.span(), Some((0, 6)));
+    }
 }
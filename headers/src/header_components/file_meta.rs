@@ -68,6 +68,35 @@ macro_rules! impl_replace_none {
 }
 
 impl FileMeta {
+    /// Returns a builder for a `FileMeta` instance.
+    ///
+    /// All fields which are not set on the builder will be `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate mail_headers;
+    /// use chrono::{TimeZone, Utc};
+    /// use mail_headers::header_components::FileMeta;
+    ///
+    /// # fn main() {
+    /// let file_meta = FileMeta::builder()
+    ///     .file_name("report.pdf")
+    ///     .modification_date(Utc.with_ymd_and_hms(2013, 8, 6, 7, 11, 0).unwrap())
+    ///     .size(4096)
+    ///     .build();
+    ///
+    /// assert_eq!(file_meta.file_name, Some("report.pdf".to_owned()));
+    /// assert_eq!(file_meta.creation_date, None);
+    /// # }
+    /// ```
+    pub fn builder() -> FileMetaBuilder {
+        FileMetaBuilder {
+            file_meta: FileMeta::default(),
+        }
+    }
+
     /// Replaces all fields which are `None` with the value of the field in `other_meta`.
     pub fn replace_empty_fields_with(&mut self, other_meta: &Self) {
         impl_replace_none! {
@@ -76,3 +105,46 @@ impl FileMeta {
         }
     }
 }
+
+/// Builder for a `FileMeta`, created with `FileMeta::builder`.
+#[derive(Debug, Clone)]
+pub struct FileMetaBuilder {
+    file_meta: FileMeta,
+}
+
+impl FileMetaBuilder {
+    /// Sets the file name.
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_meta.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Sets the creation date.
+    pub fn creation_date(mut self, date: DateTime<Utc>) -> Self {
+        self.file_meta.creation_date = Some(date);
+        self
+    }
+
+    /// Sets the last modification date.
+    pub fn modification_date(mut self, date: DateTime<Utc>) -> Self {
+        self.file_meta.modification_date = Some(date);
+        self
+    }
+
+    /// Sets the date the file was read.
+    pub fn read_date(mut self, date: DateTime<Utc>) -> Self {
+        self.file_meta.read_date = Some(date);
+        self
+    }
+
+    /// Sets the size.
+    pub fn size(mut self, size: usize) -> Self {
+        self.file_meta.size = Some(size);
+        self
+    }
+
+    /// Creates the `FileMeta`.
+    pub fn build(self) -> FileMeta {
+        self.file_meta
+    }
+}
@@ -0,0 +1,194 @@
+//! Components for the `Importance` (rfc2156) and the non-standard `X-Priority` header.
+use std::fmt::{self, Display};
+
+use soft_ascii_string::{SoftAsciiStr, SoftAsciiString};
+
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::EncodingError;
+
+use error::ComponentCreationError;
+use HeaderTryFrom;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The body of a `Importance` header, i.e. `low`, `normal` or `high` (rfc2156).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Importance {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Importance {
+    /// Returns the importance for the given keyword (ignoring its letter case).
+    ///
+    /// # Error
+    ///
+    /// Fails if the keyword is not one of `low`, `normal` or `high`.
+    pub fn from_keyword(keyword: &str) -> Result<Self, ComponentCreationError> {
+        let importance = match keyword.trim().to_ascii_lowercase().as_str() {
+            "low" => Importance::Low,
+            "normal" => Importance::Normal,
+            "high" => Importance::High,
+            _ => return Err(ComponentCreationError::new_with_str("Importance", keyword)),
+        };
+        Ok(importance)
+    }
+
+    /// Returns the keyword used to encode the importance.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Importance::Low => "low",
+            Importance::Normal => "normal",
+            Importance::High => "high",
+        }
+    }
+}
+
+impl Display for Importance {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+impl HeaderTryFrom<&str> for Importance {
+    fn try_from(keyword: &str) -> Result<Self, ComponentCreationError> {
+        Importance::from_keyword(keyword)
+    }
+}
+
+impl EncodableInHeader for Importance {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+/// The body of a `X-Priority` header, a number from `1` (highest) to `5` (lowest).
+///
+/// The header isn't standardized but used by many clients instead of
+/// (or additionally to) `Importance`. Priorities `1`/`2` correspond to
+/// a `high`, `3` to a `normal` and `4`/`5` to a `low` importance.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct XPriority(u8);
+
+impl XPriority {
+    /// Creates a new instance.
+    ///
+    /// # Error
+    ///
+    /// Fails if the priority is not in the range `1..=5`.
+    pub fn new(priority: u8) -> Result<Self, ComponentCreationError> {
+        if !(1..=5).contains(&priority) {
+            return Err(ComponentCreationError::new_with_str(
+                "XPriority",
+                priority.to_string(),
+            ));
+        }
+        Ok(XPriority(priority))
+    }
+
+    /// Returns the priority, `1` is the highest and `5` the lowest priority.
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the importance corresponding to this priority.
+    pub fn importance(self) -> Importance {
+        match self.0 {
+            1 | 2 => Importance::High,
+            3 => Importance::Normal,
+            _ => Importance::Low,
+        }
+    }
+}
+
+impl From<Importance> for XPriority {
+    fn from(importance: Importance) -> Self {
+        match importance {
+            Importance::High => XPriority(1),
+            Importance::Normal => XPriority(3),
+            Importance::Low => XPriority(5),
+        }
+    }
+}
+
+impl Display for XPriority {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{}", self.0)
+    }
+}
+
+impl HeaderTryFrom<u8> for XPriority {
+    fn try_from(priority: u8) -> Result<Self, ComponentCreationError> {
+        XPriority::new(priority)
+    }
+}
+
+impl HeaderTryFrom<&str> for XPriority {
+    fn try_from(priority: &str) -> Result<Self, ComponentCreationError> {
+        match priority.trim().parse::<u8>() {
+            Ok(priority) => XPriority::new(priority),
+            Err(_) => Err(ComponentCreationError::new_with_str("XPriority", priority)),
+        }
+    }
+}
+
+impl EncodableInHeader for XPriority {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(&SoftAsciiString::from_unchecked(self.0.to_string()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_keywords_ignoring_case() {
+        assert_eq!(Importance::from_keyword("HIGH").unwrap(), Importance::High);
+        assert_eq!(Importance::from_keyword("low").unwrap(), Importance::Low);
+        assert_err!(Importance::from_keyword("urgent"));
+    }
+
+    #[test]
+    fn rejects_out_of_range_priorities() {
+        assert_err!(XPriority::new(0));
+        assert_err!(XPriority::new(6));
+        assert_err!(XPriority::try_from("7"));
+        assert_err!(XPriority::try_from("high"));
+        assert_eq!(XPriority::try_from("2").unwrap().value(), 2);
+    }
+
+    #[test]
+    fn maps_importance_to_priority_and_back() {
+        for &importance in &[Importance::Low, Importance::Normal, Importance::High] {
+            assert_eq!(XPriority::from(importance).importance(), importance);
+        }
+        assert_eq!(XPriority::new(2).unwrap().importance(), Importance::High);
+        assert_eq!(XPriority::new(4).unwrap().importance(), Importance::Low);
+    }
+
+    ec_test! { encodes_importance, {
+        Importance::High
+    } => ascii => [
+        Text "high"
+    ]}
+
+    ec_test! { encodes_priority, {
+        XPriority::new(1)?
+    } => ascii => [
+        Text "1"
+    ]}
+}
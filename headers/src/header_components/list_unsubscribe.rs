@@ -0,0 +1,225 @@
+//! Components for the `List-Unsubscribe` header (rfc2369, rfc8058).
+use std::fmt::{self, Display};
+
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+use vec1::Vec1;
+
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::EncodingError;
+
+use error::ComponentCreationError;
+use {HeaderTryFrom, HeaderTryInto};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The value `List-Unsubscribe-Post` has to have to request one-click unsubscription (rfc8058).
+pub const ONE_CLICK_UNSUBSCRIBE: &str = "List-Unsubscribe=One-Click";
+
+/// A single uri which can be used to unsubscribe from a mailing list.
+///
+/// Only `mailto:`, `http:` and `https:` uris are supported. The
+/// uri is encoded in angle brackets, e.g. `<mailto:leave@example.com>`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnsubscribeUri {
+    uri: String,
+}
+
+impl UnsubscribeUri {
+    /// Creates a new instance from the given uri.
+    ///
+    /// # Error
+    ///
+    /// Fails if the uri doesn't start with one of the supported schemes,
+    /// has nothing after the scheme or contains non us-ascii characters,
+    /// whitespace or any of `<`, `>`, `"`.
+    pub fn new(uri: impl AsRef<str>) -> Result<Self, ComponentCreationError> {
+        let uri = uri.as_ref().trim();
+
+        let has_scheme = ["mailto:", "http:", "https:"]
+            .iter()
+            .any(|scheme| uri.len() > scheme.len() && has_scheme(uri, scheme));
+
+        let valid_chars = uri
+            .chars()
+            .all(|ch| ch.is_ascii_graphic() && !['<', '>', '"'].contains(&ch));
+
+        if !has_scheme || !valid_chars {
+            return Err(ComponentCreationError::new_with_str("UnsubscribeUri", uri));
+        }
+
+        Ok(UnsubscribeUri {
+            uri: uri.to_owned(),
+        })
+    }
+
+    /// Returns the uri (without the angle brackets).
+    pub fn as_str(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns true if this is a `https:` uri.
+    pub fn is_https(&self) -> bool {
+        has_scheme(&self.uri, "https:")
+    }
+
+    /// Returns true if this is a `mailto:` uri.
+    pub fn is_mailto(&self) -> bool {
+        has_scheme(&self.uri, "mailto:")
+    }
+}
+
+fn has_scheme(uri: &str, scheme: &str) -> bool {
+    uri.get(..scheme.len())
+        .map(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        .unwrap_or(false)
+}
+
+impl Display for UnsubscribeUri {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+impl HeaderTryFrom<&str> for UnsubscribeUri {
+    fn try_from(uri: &str) -> Result<Self, ComponentCreationError> {
+        UnsubscribeUri::new(uri)
+    }
+}
+
+impl HeaderTryFrom<String> for UnsubscribeUri {
+    fn try_from(uri: String) -> Result<Self, ComponentCreationError> {
+        UnsubscribeUri::new(uri)
+    }
+}
+
+impl EncodableInHeader for UnsubscribeUri {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_char(SoftAsciiChar::from_unchecked('<'))?;
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))?;
+        handle.write_char(SoftAsciiChar::from_unchecked('>'))?;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// The uris of a `List-Unsubscribe` header, encoded as `<uri>, <uri>`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnsubscribeUriList(pub Vec1<UnsubscribeUri>);
+
+deref0! { +mut UnsubscribeUriList => Vec1<UnsubscribeUri> }
+
+impl UnsubscribeUriList {
+    pub fn from_single(uri: UnsubscribeUri) -> Self {
+        UnsubscribeUriList(Vec1::new(uri))
+    }
+
+    /// Returns true if any of the uris is a `https:` uri.
+    ///
+    /// A `https:` uri is required for one-click unsubscription (rfc8058).
+    pub fn has_https_uri(&self) -> bool {
+        self.iter().any(UnsubscribeUri::is_https)
+    }
+}
+
+impl EncodableInHeader for UnsubscribeUriList {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        for (idx, uri) in self.iter().enumerate() {
+            if idx > 0 {
+                handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                handle.write_fws();
+            }
+            uri.encode(handle)?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+impl HeaderTryFrom<&str> for UnsubscribeUriList {
+    fn try_from(uri: &str) -> Result<Self, ComponentCreationError> {
+        Ok(UnsubscribeUriList::from_single(UnsubscribeUri::new(uri)?))
+    }
+}
+
+impl<T> HeaderTryFrom<Vec<T>> for UnsubscribeUriList
+where
+    T: HeaderTryInto<UnsubscribeUri>,
+{
+    fn try_from(vec: Vec<T>) -> Result<Self, ComponentCreationError> {
+        let mut uris = Vec::with_capacity(vec.len());
+        for uri in vec {
+            uris.push(uri.try_into()?);
+        }
+        Vec1::try_from_vec(uris)
+            .map(UnsubscribeUriList)
+            .map_err(|_| ComponentCreationError::new("UnsubscribeUriList"))
+    }
+}
+
+macro_rules! impl_header_try_from_array {
+    ($($len:tt)*) => ($(
+        impl<T> HeaderTryFrom<[T; $len]> for UnsubscribeUriList
+            where T: HeaderTryInto<UnsubscribeUri>
+        {
+            fn try_from(uris: [T; $len]) -> Result<Self, ComponentCreationError> {
+                let heapified: Box<[T]> = Box::new(uris);
+                let vecified: Vec<_> = heapified.into();
+                UnsubscribeUriList::try_from(vecified)
+            }
+        }
+    )*);
+}
+
+impl_header_try_from_array! { 1 2 3 4 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accepts_supported_schemes() {
+        assert_ok!(UnsubscribeUri::new("mailto:leave@example.com"));
+        assert_ok!(UnsubscribeUri::new("https://example.com/leave?id=12"));
+        assert_ok!(UnsubscribeUri::new("HTTP://example.com/leave"));
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        assert_err!(UnsubscribeUri::new("ftp://example.com/leave"));
+        assert_err!(UnsubscribeUri::new("https:"));
+        assert_err!(UnsubscribeUri::new("https://example.com/le ave"));
+        assert_err!(UnsubscribeUri::new("https://example.com/<leave>"));
+        assert_err!(UnsubscribeUri::new("https://exämple.com/leave"));
+    }
+
+    #[test]
+    fn detects_https_uris() {
+        let list = UnsubscribeUriList::try_from(["mailto:leave@example.com"]).unwrap();
+        assert!(!list.has_https_uri());
+
+        let list =
+            UnsubscribeUriList::try_from(["mailto:leave@example.com", "HTTPS://example.com/leave"])
+                .unwrap();
+        assert!(list.has_https_uri());
+    }
+
+    ec_test! { encodes_list, {
+        UnsubscribeUriList::try_from([
+            "mailto:leave@example.com",
+            "https://example.com/leave"
+        ])?
+    } => ascii => [
+        Text "<mailto:leave@example.com>,",
+        MarkFWS,
+        Text " <https://example.com/leave>"
+    ]}
+}
@@ -17,6 +17,17 @@ pub struct Mailbox {
 }
 
 impl Mailbox {
+    /// Creates a new mailbox from an already created display name and email.
+    ///
+    /// As both parts are already validated this doesn't do any parsing,
+    /// making it the cheapest way to create a mailbox from structured data.
+    pub fn new(display_name: Option<Phrase>, email: Email) -> Self {
+        Mailbox {
+            display_name,
+            email,
+        }
+    }
+
     pub fn auto_gen_name<F>(&mut self, default_fn: F) -> Result<(), ComponentCreationError>
     where
         F: FnOnce(&Email) -> Result<Option<Phrase>, ComponentCreationError>,
@@ -39,20 +50,14 @@ impl Mailbox {
 
 impl From<Email> for Mailbox {
     fn from(email: Email) -> Self {
-        Mailbox {
-            email,
-            display_name: None,
-        }
+        Mailbox::new(None, email)
     }
 }
 
 impl From<(Option<Phrase>, Email)> for Mailbox {
     fn from(pair: (Option<Phrase>, Email)) -> Self {
         let (display_name, email) = pair;
-        Mailbox {
-            display_name,
-            email,
-        }
+        Mailbox::new(display_name, email)
     }
 }
 
@@ -71,10 +76,7 @@ where
 {
     fn try_from(pair: (NoDisplayName, E)) -> Result<Self, ComponentCreationError> {
         let email = pair.1.try_into()?;
-        Ok(Mailbox {
-            display_name: None,
-            email,
-        })
+        Ok(Mailbox::new(None, email))
     }
 }
 impl<P, E> HeaderTryFrom<(Option<P>, E)> for Mailbox
@@ -89,10 +91,7 @@ where
             None
         };
         let email = pair.1.try_into()?;
-        Ok(Mailbox {
-            display_name,
-            email,
-        })
+        Ok(Mailbox::new(display_name, email))
     }
 }
 
@@ -104,10 +103,7 @@ where
     fn try_from(pair: (P, E)) -> Result<Self, ComponentCreationError> {
         let display_name = Some(pair.0.try_into()?);
         let email = pair.1.try_into()?;
-        Ok(Mailbox {
-            display_name,
-            email,
-        })
+        Ok(Mailbox::new(display_name, email))
     }
 }
 
@@ -170,6 +166,22 @@ mod test {
         Text ">"
     ]}
 
+    #[test]
+    fn new_is_equivalent_to_try_from_a_pair() {
+        let display_name = Phrase::try_from("ay ya").unwrap();
+        let email = Email::try_from("affen@haus").unwrap();
+
+        let mailbox = Mailbox::new(Some(display_name.clone()), email.clone());
+        assert_eq!(mailbox.display_name, Some(display_name));
+        assert_eq!(mailbox.email, email);
+
+        let parsed = Mailbox::try_from(("ay ya", "affen@haus")).unwrap();
+        assert_eq!(mailbox, parsed);
+
+        let without_name = Mailbox::new(None, email.clone());
+        assert_eq!(without_name, Mailbox::from(email));
+    }
+
     mod with_default_name {
         use super::*;
 
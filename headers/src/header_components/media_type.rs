@@ -2,7 +2,7 @@
 
 #[cfg(feature = "serde")]
 use std::fmt;
-use std::{ops::Deref, str::FromStr};
+use std::{ops::Deref, path::Path, str::FromStr};
 
 #[cfg(feature = "serde")]
 use serde::{
@@ -16,6 +16,7 @@ use internals::{
     error::EncodingError,
 };
 use media_type::{
+    error::{ErrorKind, ParserErrorRef},
     spec::{Ascii, Internationalized, MimeSpec, Modern},
     AnyMediaType, MediaType as _MediaType, Name,
 };
@@ -31,8 +32,13 @@ pub struct MediaType {
 impl MediaType {
     pub fn parse(media_type: &str) -> Result<Self, ComponentCreationError> {
         let media_type = InternationalizedMediaType::parse(media_type).map_err(|e| {
-            ComponentCreationError::from_parent(e.to_owned(), "MediaType")
-                .with_str_context(media_type)
+            let span = parser_error_span(media_type, &e);
+            let err = ComponentCreationError::from_parent(e.to_owned(), "MediaType")
+                .with_str_context(media_type);
+            match span {
+                Some((start, end)) => err.with_span(start, end),
+                None => err,
+            }
         })?;
 
         Ok(media_type.into())
@@ -79,6 +85,18 @@ impl MediaType {
         Ok(media_type.into())
     }
 
+    /// Returns a builder for a media type with given type and subtype.
+    ///
+    /// This allows adding parameters without having to format (and
+    /// correctly quote) them into a string which then is parsed again.
+    pub fn builder(type_: impl Into<String>, subtype: impl Into<String>) -> MediaTypeBuilder {
+        MediaTypeBuilder {
+            type_: type_.into(),
+            subtype: subtype.into(),
+            params: Vec::new(),
+        }
+    }
+
     pub fn remove_param<N>(&mut self, name: N) -> bool
     where
         N: for<'a> PartialEq<Name<'a>>,
@@ -93,6 +111,196 @@ impl MediaType {
     {
         self.media_type.set_param(name, value)
     }
+
+    /// Compares two media types ignoring parameters which don't change the meaning of the content.
+    ///
+    /// The type, subtype and all parameters are compared except the
+    /// parameters listed in `INSIGNIFICANT_MEDIA_TYPE_PARAMS`, i.e. `boundary` and
+    /// `name`. So `text/plain; charset=utf-8; name=a.txt` and
+    /// `text/plain; charset=utf-8` are "essentially" equal while
+    /// `text/plain; charset=utf-8` and `text/plain; charset=us-ascii`
+    /// are not.
+    ///
+    /// Use `eq_ignoring_params` to use a different set of ignored parameters.
+    pub fn essentially_eq(&self, other: &MediaType) -> bool {
+        self.eq_ignoring_params(other, INSIGNIFICANT_MEDIA_TYPE_PARAMS)
+    }
+
+    /// Compares two media types ignoring all parameters with a name in `ignored_params`.
+    ///
+    /// Parameter names are compared case insensitive, as are the values
+    /// of the `charset` parameter (rfc2046, section 4.1.2).
+    pub fn eq_ignoring_params(&self, other: &MediaType, ignored_params: &[&str]) -> bool {
+        if self.full_type() != other.full_type() {
+            return false;
+        }
+
+        let is_significant = |name: &Name| !ignored_params.iter().any(|ignored| name == ignored);
+
+        let own_params = self.params().filter(|(name, _)| is_significant(name));
+        let other_params = other.params().filter(|(name, _)| is_significant(name));
+
+        let mut count = 0;
+        for (name, value) in own_params {
+            count += 1;
+            let matches = other
+                .get_param(name.as_str())
+                .map(|other_value| {
+                    if name == "charset" {
+                        other_value
+                            .to_content()
+                            .eq_ignore_ascii_case(&value.to_content())
+                    } else {
+                        other_value == value
+                    }
+                })
+                .unwrap_or(false);
+
+            if !matches {
+                return false;
+            }
+        }
+
+        other_params.count() == count
+    }
+
+    /// Guesses the media type of a file based on its file extension.
+    ///
+    /// Only a small set of common extensions is known, if the extension
+    /// is unknown (or there is none) `application/octet-stream` is returned.
+    /// Use `guess_from_extension` to detect unknown extensions.
+    pub fn guess_from_path(path: &Path) -> MediaType {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(MediaType::guess_from_extension)
+            .unwrap_or_else(|| MediaType::from_static("application", "octet-stream"))
+    }
+
+    /// Returns the media type for a (common) file extension like `"png"` or `"PDF"`.
+    ///
+    /// The extension is passed without the leading `.`, `None` is returned for
+    /// unknown extensions.
+    pub fn guess_from_extension(extension: &str) -> Option<MediaType> {
+        let extension = extension.to_ascii_lowercase();
+        let (type_, subtype) = match &*extension {
+            "txt" => ("text", "plain"),
+            "htm" | "html" => ("text", "html"),
+            "css" => ("text", "css"),
+            "csv" => ("text", "csv"),
+            "ics" => ("text", "calendar"),
+            "md" => ("text", "markdown"),
+            "xml" => ("application", "xml"),
+            "json" => ("application", "json"),
+            "pdf" => ("application", "pdf"),
+            "zip" => ("application", "zip"),
+            "gz" => ("application", "gzip"),
+            "eml" => ("message", "rfc822"),
+            "png" => ("image", "png"),
+            "jpg" | "jpeg" => ("image", "jpeg"),
+            "gif" => ("image", "gif"),
+            "svg" => ("image", "svg+xml"),
+            "webp" => ("image", "webp"),
+            _ => return None,
+        };
+        Some(MediaType::from_static(type_, subtype))
+    }
+
+    /// Guesses the media type of some data based on the "magic bytes" it starts with.
+    ///
+    /// This only detects a few (binary) formats: PNG, JPEG, GIF, WebP,
+    /// PDF, ZIP and gzip. For all other data `None` is returned.
+    pub fn guess_from_bytes(bytes: &[u8]) -> Option<MediaType> {
+        let (type_, subtype) = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            ("image", "png")
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            ("image", "jpeg")
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            ("image", "gif")
+        } else if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+            ("image", "webp")
+        } else if bytes.starts_with(b"%PDF-") {
+            ("application", "pdf")
+        } else if bytes.starts_with(b"PK\x03\x04") {
+            ("application", "zip")
+        } else if bytes.starts_with(b"\x1f\x8b") {
+            ("application", "gzip")
+        } else {
+            return None;
+        };
+        Some(MediaType::from_static(type_, subtype))
+    }
+
+    fn from_static(type_: &'static str, subtype: &'static str) -> MediaType {
+        MediaType::new(type_, subtype).expect("[BUG] static media types are valid")
+    }
+}
+
+/// Builder for a `MediaType`, created with `MediaType::builder`.
+#[derive(Debug, Clone)]
+pub struct MediaTypeBuilder {
+    type_: String,
+    subtype: String,
+    params: Vec<(String, String)>,
+}
+
+impl MediaTypeBuilder {
+    /// Adds a parameter to the media type.
+    ///
+    /// The value is quoted when the media type is encoded if it
+    /// is not a valid token (e.g. if it contains spaces or `;`).
+    pub fn param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+
+    /// Creates the `MediaType`.
+    ///
+    /// # Error
+    ///
+    /// Fails if the type, subtype or a parameter name is not a valid
+    /// token or if a parameter value can not be represented.
+    pub fn build(self) -> Result<MediaType, ComponentCreationError> {
+        MediaType::new_with_params(self.type_, self.subtype, self.params)
+    }
+}
+
+/// The parameters ignored by `MediaType::essentially_eq`.
+///
+/// - `boundary`: is regenerated when encoding a multipart body
+/// - `name`: is just (obsolete) metadata, `Content-Disposition` should be used for it
+pub const INSIGNIFICANT_MEDIA_TYPE_PARAMS: &[&str] = &["boundary", "name"];
+
+/// Returns the span of the offending part of `input` for a error from parsing `input`.
+///
+/// The parser reports positions relative to the (sub-)slice of the input
+/// it was parsing when it failed, so the offset of that slice in `input`
+/// is added to it.
+fn parser_error_span(input: &str, err: &ParserErrorRef) -> Option<(usize, usize)> {
+    let err_input = err.input();
+    let offset = (err_input.as_ptr() as usize).checked_sub(input.as_ptr() as usize)?;
+    if offset + err_input.len() > input.len() {
+        return None;
+    }
+
+    let (pos, len) = match err.kind() {
+        ErrorKind::UnexpectedChar { pos, .. } => {
+            let len = err_input
+                .get(pos..)
+                .and_then(|tail| tail.chars().next())
+                .map(char::len_utf8)
+                .unwrap_or(0);
+            (pos, len)
+        }
+        ErrorKind::QuotedParamValue { pos, .. } | ErrorKind::UnquotedParamValue { pos, .. } => {
+            (pos, 0)
+        }
+        // a "\r\n " or "\r\n\t" sequence
+        ErrorKind::IllegalCrNlSeq { pos } => (pos, 3),
+        ErrorKind::UnexpectedEof => (err_input.len(), 0),
+    };
+
+    let start = offset + pos;
+    Some((start, (start + len).min(input.len())))
 }
 
 impl FromStr for MediaType {
@@ -406,6 +614,144 @@ impl<'de> Deserialize<'de> for MediaType {
 mod test {
     use super::*;
 
+    #[test]
+    fn parse_error_reports_span() {
+        let err = MediaType::parse("text/pl@in").unwrap_err();
+        assert_eq!(err.span(), Some((7, 8)));
+        assert!(err.to_string().ends_with("at byte 7..8"));
+    }
+
+    mod builder {
+        use super::*;
+
+        test!(builds_media_type_with_params {
+            let media_type = MediaType::builder("text", "plain")
+                .param("charset", "utf-8")
+                .param("format", "flowed")
+                .build()?;
+
+            let expected = MediaType::parse("text/plain; charset=utf-8; format=flowed")?;
+            assert!(media_type.essentially_eq(&expected));
+        });
+
+        test!(quotes_values_if_needed {
+            let media_type = MediaType::builder("application", "octet-stream")
+                .param("name", "a file; \"quoted\".txt")
+                .build()?;
+
+            let expected = "a file; \"quoted\".txt";
+            assert_eq!(media_type.get_param("name").unwrap().to_content(), expected);
+            let reparsed = MediaType::parse(&media_type.to_string())?;
+            assert_eq!(reparsed.get_param("name").unwrap().to_content(), expected);
+        });
+
+        #[test]
+        fn rejects_invalid_type_or_subtype() {
+            assert!(MediaType::builder("te xt", "plain").build().is_err());
+            assert!(MediaType::builder("text", "pl/ain").build().is_err());
+            assert!(MediaType::builder("text", "plain")
+                .param("char set", "utf-8")
+                .build()
+                .is_err());
+        }
+    }
+
+    mod essentially_eq {
+        use super::*;
+
+        test!(ignores_name_param {
+            let left = MediaType::parse("text/plain; charset=utf-8; name=a.txt")?;
+            let right = MediaType::parse("text/plain; charset=utf-8; name=\"b.txt\"")?;
+            assert!(left.essentially_eq(&right));
+
+            let right = MediaType::parse("text/plain; charset=utf-8")?;
+            assert!(left.essentially_eq(&right));
+            assert!(right.essentially_eq(&left));
+        });
+
+        test!(ignores_boundary_param {
+            let left = MediaType::parse("multipart/mixed; boundary=abc")?;
+            let right = MediaType::parse("multipart/mixed; boundary=def")?;
+            assert!(left.essentially_eq(&right));
+        });
+
+        test!(respects_charset_param {
+            let left = MediaType::parse("text/plain; charset=utf-8; name=a.txt")?;
+            let right = MediaType::parse("text/plain; charset=us-ascii; name=a.txt")?;
+            assert!(!left.essentially_eq(&right));
+
+            let right = MediaType::parse("text/plain; name=a.txt")?;
+            assert!(!left.essentially_eq(&right));
+            assert!(!right.essentially_eq(&left));
+        });
+
+        test!(compares_charset_values_case_insensitive {
+            let left = MediaType::parse("text/plain; charset=utf-8")?;
+            let right = MediaType::parse("text/plain; charset=\"UTF-8\"")?;
+            assert!(left.essentially_eq(&right));
+            assert!(right.essentially_eq(&left));
+
+            let left = MediaType::parse("text/plain; format=flowed")?;
+            let right = MediaType::parse("text/plain; format=Flowed")?;
+            assert!(!left.essentially_eq(&right));
+        });
+
+        test!(respects_type_and_subtype {
+            let left = MediaType::parse("text/plain; charset=utf-8")?;
+            let right = MediaType::parse("text/html; charset=utf-8")?;
+            assert!(!left.essentially_eq(&right));
+        });
+
+        test!(can_use_custom_ignored_params {
+            let left = MediaType::parse("text/plain; charset=utf-8; format=flowed")?;
+            let right = MediaType::parse("text/plain; charset=us-ascii; format=flowed")?;
+            assert!(left.eq_ignoring_params(&right, &["charset"]));
+            assert!(!left.eq_ignoring_params(&right, &["format"]));
+        });
+    }
+
+    mod guess {
+        use super::*;
+
+        #[test]
+        fn uses_the_file_extension() {
+            let media_type = MediaType::guess_from_path(Path::new("a/b/logo.PNG"));
+            assert_eq!(media_type.as_str_repr(), "image/png");
+            let media_type = MediaType::guess_from_path(Path::new("report.pdf"));
+            assert_eq!(media_type.as_str_repr(), "application/pdf");
+        }
+
+        #[test]
+        fn falls_back_to_octet_stream() {
+            let media_type = MediaType::guess_from_path(Path::new("Cargo.toml"));
+            assert_eq!(media_type.as_str_repr(), "application/octet-stream");
+            let media_type = MediaType::guess_from_path(Path::new("README"));
+            assert_eq!(media_type.as_str_repr(), "application/octet-stream");
+            assert!(MediaType::guess_from_extension("toml").is_none());
+        }
+
+        #[test]
+        fn sniffs_magic_bytes() {
+            let guess = |bytes: &[u8]| {
+                MediaType::guess_from_bytes(bytes).map(|mt| mt.as_str_repr().to_owned())
+            };
+            assert_eq!(
+                guess(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").as_deref(),
+                Some("image/png")
+            );
+            assert_eq!(guess(b"%PDF-1.7\n").as_deref(), Some("application/pdf"));
+            assert_eq!(guess(b"GIF89a\x01\0").as_deref(), Some("image/gif"));
+            assert_eq!(
+                guess(b"RIFF\0\0\0\0WEBPVP8 ").as_deref(),
+                Some("image/webp")
+            );
+            assert_eq!(guess(b"PK\x03\x04").as_deref(), Some("application/zip"));
+            assert_eq!(guess(b"RIFF\0\0\0\0WAVE"), None);
+            assert_eq!(guess(b"hy there"), None);
+            assert_eq!(guess(b""), None);
+        }
+    }
+
     ec_test! { writing_encoded, {
         MediaType::try_from("text/plain; arbitrary*=utf8''this%20is%it")?
     } => ascii => [
@@ -149,15 +149,23 @@ where
     }
 }
 
-impl EncodableInHeader for MessageId {
-    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
-        handle.mark_fws_pos();
+impl MessageId {
+    /// writes `<id>` without marking any place to fold the line
+    fn encode_bracketed(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
         handle.write_char(SoftAsciiChar::from_unchecked('<'))?;
         match self.message_id {
             SimpleItem::Ascii(ref ascii) => handle.write_str(ascii)?,
             SimpleItem::Utf8(ref utf8) => handle.write_utf8(utf8)?,
         }
         handle.write_char(SoftAsciiChar::from_unchecked('>'))?;
+        Ok(())
+    }
+}
+
+impl EncodableInHeader for MessageId {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.mark_fws_pos();
+        self.encode_bracketed(handle)?;
         handle.mark_fws_pos();
         Ok(())
     }
@@ -175,9 +183,16 @@ deref0! { +mut MessageIdList => Vec1<MessageId> }
 
 impl EncodableInHeader for MessageIdList {
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
-        for msg_id in self.iter() {
-            msg_id.encode(handle)?;
+        handle.mark_fws_pos();
+        for (idx, msg_id) in self.iter().enumerate() {
+            // the ids are separated by a space, which is the place the
+            // line is folded at if the list is too long for one line
+            if idx > 0 {
+                handle.write_fws();
+            }
+            msg_id.encode_bracketed(handle)?;
         }
+        handle.mark_fws_pos();
         Ok(())
     }
 
@@ -339,8 +354,44 @@ mod test {
         MarkFWS,
         Text "<affen@haus>",
         MarkFWS,
-        MarkFWS,
-        Text "<obst@salat>",
+        Text " <obst@salat>",
         MarkFWS,
     ]}
+
+    #[test]
+    fn long_lists_fold_between_message_ids() {
+        let ids = (0..5)
+            .map(|idx| MessageId::try_from(format!("{}.thread-reply@mail.example.test", idx)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let list = MessageIdList(Vec1::try_from_vec(ids).unwrap());
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        encoder
+            .write_header_line(|handle| {
+                handle.write_str(SoftAsciiStr::from_unchecked("References:"))?;
+                handle.write_fws();
+                list.encode(handle)
+            })
+            .unwrap();
+        let encoded = encoder.to_string().unwrap();
+
+        let lines = encoded.split_terminator("\r\n").collect::<Vec<_>>();
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(line.len() <= 78, "line too long: {:?}", line);
+            assert!(line.ends_with('>'), "not folded at boundary: {:?}", line);
+        }
+        for line in &lines[1..] {
+            assert!(line.starts_with(" <"), "not folded at boundary: {:?}", line);
+        }
+
+        let unfolded = encoded.replace("\r\n", "");
+        assert_eq!(
+            unfolded,
+            "References: <0.thread-reply@mail.example.test> \
+             <1.thread-reply@mail.example.test> <2.thread-reply@mail.example.test> \
+             <3.thread-reply@mail.example.test> <4.thread-reply@mail.example.test>"
+        );
+    }
 }
@@ -50,6 +50,9 @@ pub use self::path::Path;
 mod received_token;
 pub use self::received_token::ReceivedToken;
 
+mod received;
+pub use self::received::{Received, ReceivedBuilder};
+
 pub mod word;
 pub use self::word::Word;
 
@@ -64,3 +67,29 @@ pub use self::disposition::*;
 
 mod raw_unstructured;
 pub use self::raw_unstructured::*;
+
+mod content_md5;
+pub use self::content_md5::ContentMd5;
+
+mod delivery_status;
+pub use self::delivery_status::{DsnAction, DsnStatus, TypedAddress};
+
+mod list_unsubscribe;
+pub use self::list_unsubscribe::{UnsubscribeUri, UnsubscribeUriList, ONE_CLICK_UNSUBSCRIBE};
+
+mod content_language;
+pub use self::content_language::{LanguageTag, LanguageTagList};
+
+mod content_location;
+pub use self::content_location::ContentLocation;
+
+mod importance;
+pub use self::importance::{Importance, XPriority};
+
+mod auto_submitted;
+pub use self::auto_submitted::{AutoSubmitted, AutoSubmittedKind};
+
+mod authentication_results;
+pub use self::authentication_results::{
+    AuthenticationProperty, AuthenticationResult, AuthenticationResults,
+};
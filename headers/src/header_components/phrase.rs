@@ -9,7 +9,7 @@ use error::ComponentCreationError;
 use {HeaderTryFrom, HeaderTryInto};
 
 use super::utils::text_partition::{partition, Partition};
-use super::word::{do_encode_word, Word};
+use super::word::{do_encode_word, needs_encoding, Word};
 use super::{CFWS, FWS};
 
 /// Represent a "phrase" as it for example is used in the `Mailbox` type for the display name.
@@ -86,6 +86,42 @@ impl Phrase {
 
         Ok(Phrase(words))
     }
+
+    /// Creates a `Phrase` from some input, truncating it to at most `max_len` chars.
+    ///
+    /// If the input has more than `max_len` chars it's cut off, trailing
+    /// whitespace is removed and an ellipsis (`'…'`) is appended, with the
+    /// ellipsis counting towards `max_len`.
+    ///
+    /// The input is truncated on `char` boundaries _before_ it's split into
+    /// words, so if the phrase needs to be encoded using encoded words
+    /// (RFC 2047) each of them still encodes complete characters. This is
+    /// mainly useful for pathologically long display names, e.g. from user input.
+    ///
+    /// # Error
+    ///
+    /// Fails in the same cases as `Phrase::new`, this includes a `max_len` of 0.
+    pub fn with_max_len<T: HeaderTryInto<Input>>(
+        input: T,
+        max_len: usize,
+    ) -> Result<Self, ComponentCreationError> {
+        let input = input.try_into()?;
+        let text = input.as_str();
+        if text.chars().count() <= max_len {
+            return Phrase::new(input);
+        }
+
+        let end = text
+            .char_indices()
+            .nth(max_len.saturating_sub(1))
+            .map(|(idx, _)| idx)
+            .unwrap_or_else(|| text.len());
+        let mut truncated = text[..end].trim_end().to_owned();
+        if max_len > 0 {
+            truncated.push('…');
+        }
+        Phrase::new(truncated)
+    }
 }
 
 impl<'a> HeaderTryFrom<&'a str> for Phrase {
@@ -110,8 +146,20 @@ impl EncodableInHeader for Phrase {
     //FEATURE_TODO(warn_on_bad_phrase): warn if the phrase contains chars it should not
     //  but can contain due to encoding, e.g. ascii CTL's
     fn encode(&self, heandle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let mail_type = heandle.mail_type();
+        let mut last_was_encoded = false;
         for word in self.0.iter() {
-            do_encode_word(&*word, heandle, Some(EncodedWordContext::Phrase))?;
+            let needs_encoding = needs_encoding(&word.input, mail_type);
+            if needs_encoding && last_was_encoded && word.left_padding.is_some() {
+                // whitespace between two encoded words is ignored when decoding them
+                // (RFC 2047, section 6.2), so it has to be part of the encoded word
+                let mut word = word.clone();
+                word.input = Input::from(format!(" {}", word.input.as_str()));
+                do_encode_word(&word, heandle, Some(EncodedWordContext::Phrase))?;
+            } else {
+                do_encode_word(&*word, heandle, Some(EncodedWordContext::Phrase))?;
+            }
+            last_was_encoded = needs_encoding;
         }
 
         Ok(())
@@ -124,6 +172,13 @@ impl EncodableInHeader for Phrase {
 
 #[cfg(test)]
 mod test {
+    use soft_ascii_string::SoftAsciiStr;
+
+    use internals::bind::quoted_printable::encoded_word_decode;
+    use internals::encoder::{EncodableInHeader, EncodingBuffer};
+    use internals::grammar::encoded_word::MAX_ECW_LEN;
+    use internals::MailType;
+
     use super::Phrase;
     use HeaderTryFrom;
 
@@ -145,4 +200,99 @@ mod test {
         MarkFWS,
         Text " encoding"
     ]}
+
+    fn encode(phrase: &Phrase) -> String {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        encoder
+            .write_header_line(|handle| {
+                handle.write_str(SoftAsciiStr::from_unchecked("To:"))?;
+                handle.write_fws();
+                phrase.encode(handle)
+            })
+            .unwrap();
+        encoder.as_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn with_max_len_keeps_short_input() {
+        let phrase = Phrase::with_max_len("simple think", 12).unwrap();
+        assert_eq!(phrase, Phrase::new("simple think").unwrap());
+    }
+
+    #[test]
+    fn with_max_len_truncates_long_input() {
+        let phrase = Phrase::with_max_len("simple think", 8).unwrap();
+        assert_eq!(phrase, Phrase::new("simple…").unwrap());
+        assert!(Phrase::with_max_len("simple think", 0).is_err());
+    }
+
+    #[test]
+    fn with_max_len_does_not_split_encoded_words() {
+        let name = "Jörg Änderßön ".repeat(36);
+        let name = &name[..name.char_indices().nth(500).unwrap().0];
+        assert_eq!(name.chars().count(), 500);
+
+        let phrase = Phrase::with_max_len(name, 40).unwrap();
+        let expected = format!("{}…", &name[..name.char_indices().nth(39).unwrap().0]);
+        assert_eq!(phrase, Phrase::new(expected).unwrap());
+
+        let encoded = encode(&phrase);
+        for line in encoded.split("\r\n") {
+            assert!(line.len() <= 78, "too long line: {:?}", line);
+        }
+        let unfolded = encoded.replace("\r\n", "");
+        for word in unfolded.split(' ').filter(|word| word.starts_with("=?")) {
+            assert!(word.ends_with("?="), "split encoded word: {:?}", word);
+        }
+        assert!(unfolded.contains("=E2=80=A6?="));
+    }
+
+    /// Decodes a encoded phrase consisting only of encoded words.
+    fn decode_encoded_words(encoded: &str) -> String {
+        let unfolded = encoded.replace("\r\n", "");
+        let mut decoded = Vec::new();
+        for word in unfolded["To:".len()..].split_whitespace() {
+            assert!(
+                word.len() <= MAX_ECW_LEN,
+                "too long encoded word: {:?}",
+                word
+            );
+            let payload = word
+                .strip_prefix("=?utf8?Q?")
+                .and_then(|rest| rest.strip_suffix("?="))
+                .unwrap_or_else(|| panic!("not a encoded word: {:?}", word));
+            let bytes = encoded_word_decode(payload).unwrap();
+            assert!(
+                ::std::str::from_utf8(&bytes).is_ok(),
+                "encoded word splits a char: {:?}",
+                word
+            );
+            decoded.extend(bytes);
+        }
+        String::from_utf8(decoded).unwrap()
+    }
+
+    #[test]
+    fn keeps_whitespace_between_encoded_words() {
+        let encoded = encode(&Phrase::new("Jörg Änderßön").unwrap());
+        assert_eq!(
+            encoded,
+            "To: =?utf8?Q?J=C3=B6rg?= =?utf8?Q?=20=C3=84nder=C3=9F=C3=B6n?=\r\n"
+        );
+        assert_eq!(decode_encoded_words(&encoded), "Jörg Änderßön");
+    }
+
+    #[test]
+    fn long_names_are_split_into_multiple_encoded_words() {
+        let word = "e\u{301}🦀👩\u{200d}💻".repeat(12);
+        let name = format!("{} {}", word, word);
+
+        let encoded = encode(&Phrase::new(name.as_str()).unwrap());
+
+        for line in encoded.split("\r\n") {
+            assert!(line.len() <= 78, "too long line: {:?}", line);
+        }
+        assert!(encoded.matches("=?utf8?Q?").count() > 4);
+        assert_eq!(decode_encoded_words(&encoded), name);
+    }
 }
@@ -0,0 +1,320 @@
+//! Component for the `Received` trace header (rfc5321, rfc5322).
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::error::EncodingError;
+use internals::grammar::is_atext;
+use internals::MailType;
+
+use error::ComponentCreationError;
+use HeaderTryFrom;
+
+use super::{DateTime, Domain, Email, MessageId};
+
+/// The body of a `Received` header as added by a MTA relaying a mail, e.g.:
+///
+/// ```text
+/// from mail.example.net by mx.example.com with ESMTP id 4Bx2k; Tue, 6 Aug 2013 04:11:45 +0000
+/// ```
+///
+/// All clauses are optional, but the timestamp is always present. Use
+/// `Received::builder` to create it.
+///
+/// As each server relaying the mail adds a `Received` header in front of
+/// the previous ones, it should be added using `HeaderMap::prepend`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Received {
+    from: Option<Domain>,
+    by: Option<Domain>,
+    via: Option<String>,
+    with: Option<String>,
+    id: Option<String>,
+    for_recipient: Option<Email>,
+    date_time: DateTime,
+}
+
+impl Received {
+    /// Creates a builder for a `Received` header body.
+    pub fn builder() -> ReceivedBuilder {
+        ReceivedBuilder::default()
+    }
+
+    /// Returns the domain of the server the mail was received from.
+    pub fn from(&self) -> Option<&Domain> {
+        self.from.as_ref()
+    }
+
+    /// Returns the domain of the server which received the mail.
+    pub fn by(&self) -> Option<&Domain> {
+        self.by.as_ref()
+    }
+
+    /// Returns the link over which the mail was received (e.g. `TCP`).
+    pub fn via(&self) -> Option<&str> {
+        self.via.as_deref()
+    }
+
+    /// Returns the protocol used to receive the mail (e.g. `ESMTP`).
+    pub fn with(&self) -> Option<&str> {
+        self.with.as_deref()
+    }
+
+    /// Returns the id the receiving server assigned to the mail.
+    ///
+    /// This is either an atom or a message id including the surrounding `<`, `>`.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Returns the recipient the mail was received for.
+    pub fn for_recipient(&self) -> Option<&Email> {
+        self.for_recipient.as_ref()
+    }
+
+    /// Returns the time the mail was received.
+    pub fn date_time(&self) -> &DateTime {
+        &self.date_time
+    }
+}
+
+impl EncodableInHeader for Received {
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let mut first = true;
+        let mut write_keyword = |handle: &mut EncodingWriter, keyword: &str| {
+            if !first {
+                handle.write_fws();
+            }
+            first = false;
+            handle.write_str(SoftAsciiStr::from_unchecked(keyword))?;
+            handle.write_fws();
+            Ok::<_, EncodingError>(())
+        };
+
+        if let Some(from) = self.from.as_ref() {
+            write_keyword(handle, "from")?;
+            from.encode(handle)?;
+        }
+        if let Some(by) = self.by.as_ref() {
+            write_keyword(handle, "by")?;
+            by.encode(handle)?;
+        }
+        let atoms = [("via", &self.via), ("with", &self.with), ("id", &self.id)];
+        for &(keyword, atom) in atoms.iter() {
+            if let Some(atom) = atom.as_ref() {
+                write_keyword(handle, keyword)?;
+                handle.write_str(SoftAsciiStr::from_unchecked(atom))?;
+            }
+        }
+        if let Some(for_recipient) = self.for_recipient.as_ref() {
+            write_keyword(handle, "for")?;
+            handle.write_char(SoftAsciiChar::from_unchecked('<'))?;
+            for_recipient.encode(handle)?;
+            handle.write_char(SoftAsciiChar::from_unchecked('>'))?;
+        }
+
+        handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+        handle.write_fws();
+        self.date_time.encode(handle)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// Builder for a `Received` header body, created with `Received::builder`.
+#[derive(Debug, Clone, Default)]
+pub struct ReceivedBuilder {
+    from: Option<String>,
+    by: Option<String>,
+    via: Option<String>,
+    with: Option<String>,
+    id: Option<String>,
+    for_recipient: Option<String>,
+    date_time: Option<DateTime>,
+}
+
+impl ReceivedBuilder {
+    /// Sets the domain of the server the mail was received from (`from` clause).
+    pub fn from(mut self, domain: impl Into<String>) -> Self {
+        self.from = Some(domain.into());
+        self
+    }
+
+    /// Sets the domain of the server receiving the mail (`by` clause).
+    pub fn by(mut self, domain: impl Into<String>) -> Self {
+        self.by = Some(domain.into());
+        self
+    }
+
+    /// Sets the link over which the mail was received, e.g. `TCP` (`via` clause).
+    pub fn via(mut self, link: impl Into<String>) -> Self {
+        self.via = Some(link.into());
+        self
+    }
+
+    /// Sets the protocol used to receive the mail, e.g. `ESMTPS` (`with` clause).
+    pub fn with(mut self, protocol: impl Into<String>) -> Self {
+        self.with = Some(protocol.into());
+        self
+    }
+
+    /// Sets the id the receiving server assigned to the mail (`id` clause).
+    ///
+    /// The id is either an atom (e.g. `4Bx2k`) or a message id in angle
+    /// brackets (e.g. `<4Bx2k@mx.example.com>`) as allowed by rfc5321.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the recipient the mail was received for (`for` clause).
+    pub fn for_recipient(mut self, email: impl Into<String>) -> Self {
+        self.for_recipient = Some(email.into());
+        self
+    }
+
+    /// Sets the time the mail was received, if not set the current time is used.
+    pub fn date_time(mut self, date_time: impl Into<DateTime>) -> Self {
+        self.date_time = Some(date_time.into());
+        self
+    }
+
+    /// Creates the `Received` header body.
+    ///
+    /// # Error
+    ///
+    /// Fails if `from`/`by` is not a valid domain, `for` is not a valid
+    /// email address, `via`/`with` is not an (us-ascii) atom or `id` is
+    /// neither an (us-ascii) atom nor an (us-ascii) message id.
+    pub fn build(self) -> Result<Received, ComponentCreationError> {
+        Ok(Received {
+            from: self.from.map(Domain::try_from).transpose()?,
+            by: self.by.map(Domain::try_from).transpose()?,
+            via: self.via.map(check_atom).transpose()?,
+            with: self.with.map(check_atom).transpose()?,
+            id: self.id.map(check_id).transpose()?,
+            for_recipient: self.for_recipient.map(Email::try_from).transpose()?,
+            date_time: self.date_time.unwrap_or_else(DateTime::now),
+        })
+    }
+}
+
+fn check_atom(atom: String) -> Result<String, ComponentCreationError> {
+    if atom.is_empty() || !atom.chars().all(|ch| is_atext(ch, MailType::Ascii)) {
+        return Err(ComponentCreationError::new_with_str("Received", atom));
+    }
+    Ok(atom)
+}
+
+fn check_id(id: String) -> Result<String, ComponentCreationError> {
+    if id.starts_with('<') && id.ends_with('>') && id.len() > 2 {
+        let inner = &id[1..id.len() - 1];
+        if !inner.is_ascii() || MessageId::try_from(inner).is_err() {
+            return Err(ComponentCreationError::new_with_str("Received", id));
+        }
+        return Ok(id);
+    }
+    check_atom(id)
+}
+
+#[cfg(test)]
+mod test {
+    use soft_ascii_string::SoftAsciiStr;
+
+    use internals::encoder::{EncodableInHeader, EncodingBuffer};
+    use internals::MailType;
+
+    use super::*;
+
+    fn encode(received: &Received) -> String {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        encoder
+            .write_header_line(|handle| {
+                handle.write_str(SoftAsciiStr::from_unchecked("Received:"))?;
+                handle.write_fws();
+                received.encode(handle)
+            })
+            .unwrap();
+        encoder.as_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn encodes_all_clauses() {
+        let received = Received::builder()
+            .from("a.example")
+            .by("b.example")
+            .with("ESMTP")
+            .id("4Bx2k")
+            .for_recipient("bob@c.example")
+            .date_time(DateTime::test_time(45))
+            .build()
+            .unwrap();
+
+        let unfolded = encode(&received).replace("\r\n", "");
+        let expected_start =
+            "Received: from a.example by b.example with ESMTP id 4Bx2k for <bob@c.example>; ";
+        assert!(unfolded.starts_with(expected_start), "{:?}", unfolded);
+        assert!(
+            unfolded.ends_with("Aug 2013 04:11:45 +0000"),
+            "{:?}",
+            unfolded
+        );
+    }
+
+    #[test]
+    fn only_needs_the_timestamp() {
+        let received = Received::builder()
+            .date_time(DateTime::test_time(45))
+            .build()
+            .unwrap();
+        let encoded = encode(&received);
+        assert!(encoded.starts_with("Received: ; Tue, "), "{:?}", encoded);
+    }
+
+    #[test]
+    fn folds_long_values() {
+        let received = Received::builder()
+            .from("a-very-long-subdomain-name.of-a-mail-server.example.net")
+            .by("another-quite-long-subdomain.mx.example.com")
+            .with("ESMTPSA")
+            .id("5F2B9A1C7D3E")
+            .for_recipient("some.recipient@example.com")
+            .build()
+            .unwrap();
+
+        let encoded = encode(&received);
+        assert!(encoded.contains("\r\n "));
+        for line in encoded.trim_end().split("\r\n") {
+            assert!(line.len() <= 78, "too long line: {:?}", line);
+        }
+    }
+
+    #[test]
+    fn accepts_message_ids_as_id() {
+        let received = Received::builder()
+            .by("b.example")
+            .id("<4Bx2k@b.example>")
+            .date_time(DateTime::test_time(45))
+            .build()
+            .unwrap();
+
+        assert_eq!(received.id(), Some("<4Bx2k@b.example>"));
+        let unfolded = encode(&received).replace("\r\n", "");
+        assert!(
+            unfolded.starts_with("Received: by b.example id <4Bx2k@b.example>; "),
+            "{:?}",
+            unfolded
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_clauses() {
+        assert!(Received::builder().with("E SMTP").build().is_err());
+        assert!(Received::builder().id("").build().is_err());
+        assert!(Received::builder().id("<>").build().is_err());
+        assert!(Received::builder().id("<no-at-sign>").build().is_err());
+        assert!(Received::builder().from("not a domain").build().is_err());
+        assert!(Received::builder().for_recipient("bob").build().is_err());
+    }
+}
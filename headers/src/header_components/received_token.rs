@@ -6,6 +6,10 @@ use internals::error::EncodingError;
 use super::word::{do_encode_word, Word};
 use super::{Domain, Email};
 
+/// A single token of a `Received` header body.
+///
+/// This is no longer the component of the `Received` header, which
+/// uses the structured `Received` component instead.
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum ReceivedToken {
     Word(Word),
@@ -8,6 +8,7 @@ use internals::encoder::{EncodableInHeader, EncodingWriter};
 use internals::error::{EncodingError, EncodingErrorKind};
 use internals::grammar::encoded_word::EncodedWordContext;
 use internals::grammar::is_atext;
+use internals::MailType;
 use {HeaderTryFrom, HeaderTryInto};
 
 use super::CFWS;
@@ -52,6 +53,11 @@ impl Word {
     }
 }
 
+/// Returns true if the word can not be written as is (i.e. it has to be quoted or encoded).
+pub(crate) fn needs_encoding(input: &str, mail_type: MailType) -> bool {
+    input.contains("=?") || !input.chars().all(|ch| is_atext(ch, mail_type))
+}
+
 /// As word has to be differently encoded, depending on the context it
 /// appears in it cannot implement EncodableInHeader, instead we have
 /// a function which can be used by type containing it which (should)
@@ -74,9 +80,7 @@ pub fn do_encode_word<'a, 'b: 'a>(
     let input: &str = &*word.input;
     let mail_type = handle.mail_type();
     handle
-        .write_if(input, |input| {
-            (!input.contains("=?")) && input.chars().all(|ch| is_atext(ch, mail_type))
-        })
+        .write_if(input, |input| !needs_encoding(input, mail_type))
         .handle_condition_failure(|handle| {
             if let Some(_ecw_ctx) = ecw_ctx {
                 //FIXME actually use the EncodedWordContext
@@ -107,11 +107,25 @@ macro_rules! def_headers {
         //  type names)
 
 
-        #[cfg(test)]
-        const HEADER_NAMES: &[ &str ] = &[ $(
+        /// The names of all headers defined in this `def_headers!` call.
+        #[allow(dead_code)]
+        pub(crate) const HEADER_NAMES: &[ &str ] = &[ $(
             $hname
         ),+ ];
 
+        /// Looks up a header defined in this `def_headers!` call by its (canonical) name.
+        ///
+        /// Returns the `&'static` name of the header and its `MAX_ONE` value.
+        #[allow(dead_code)]
+        pub(crate) fn lookup_header_name(name: &str) -> Option<(&'static str, bool)> {
+            match name {
+                $(
+                    $hname => Some(($hname, def_headers!{ _PRIV_mk_max_one $maxOne })),
+                )+
+                _ => None
+            }
+        }
+
         #[test]
         fn $tn() {
             use std::collections::HashSet;
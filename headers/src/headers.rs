@@ -1,4 +1,7 @@
-use self::validators::{from as validator_from, resent_any as validator_resent_any};
+use self::validators::{
+    from as validator_from, list_unsubscribe_post as validator_list_unsubscribe_post,
+    resent_any as validator_resent_any,
+};
 use header_components;
 
 def_headers! {
@@ -46,8 +49,50 @@ def_headers! {
     ResentMsgId,  unchecked { "Resent-Msg-Id" },  MessageId,      multi,    validator_resent_any,
     /// (rfc5322)
     ReturnPath,   unchecked { "Return-Path"   },  Path,           multi,    None,
-    /// (rfc5322)
-    Received,     unchecked { "Received"      },  ReceivedToken,  multi,    None,
+    /// Trace information added by each server relaying the mail (rfc5321, rfc5322)
+    ///
+    /// Each server prepends its own header (see `HeaderMap::prepend`),
+    /// so the newest one comes first.
+    ///
+    /// Note: the component used to be a single `ReceivedToken`, it is now
+    /// the structured `Received` component created with `Received::builder`.
+    Received,     unchecked { "Received"      },  Received,       multi,    None,
+
+    /// Requests a disposition notification (read receipt) to be send to the given mailboxes (rfc8098)
+    DispositionNotificationTo, unchecked { "Disposition-Notification-To" }, MailboxList, maxOne, None,
+
+    /// Non-standard predecessor of `Disposition-Notification-To` still used by some clients
+    ReturnReceiptTo, unchecked { "Return-Receipt-To" }, MailboxList, maxOne, None,
+
+    /// Uris which can be used to unsubscribe from a mailing list (rfc2369)
+    ListUnsubscribe, unchecked { "List-Unsubscribe" }, UnsubscribeUriList, maxOne, None,
+
+    /// Requests one-click unsubscription using the `https:` uri of `List-Unsubscribe` (rfc8058)
+    ///
+    /// The only defined value is `List-Unsubscribe=One-Click`, if it is used
+    /// the `List-Unsubscribe` header has to contain a `https:` uri.
+    ListUnsubscribePost, unchecked { "List-Unsubscribe-Post" }, Unstructured, maxOne, validator_list_unsubscribe_post,
+
+    /// Marks a mail as send by an automatic process, e.g. `auto-replied` (rfc3834)
+    ///
+    /// Automatic responders should not reply to mails with a `Auto-Submitted`
+    /// header other than `no`, which prevents mail loops.
+    AutoSubmitted, unchecked { "Auto-Submitted" }, AutoSubmitted, maxOne, None,
+
+    /// The importance of the mail, i.e. `low`, `normal` or `high` (rfc2156)
+    ///
+    /// Some clients only read `X-Priority`, use `Mail::set_importance`
+    /// to set both headers consistently.
+    Importance, unchecked { "Importance" }, Importance, maxOne, None,
+
+    /// Non-standard priority of the mail from `1` (highest) to `5` (lowest)
+    XPriority, unchecked { "X-Priority" }, XPriority, maxOne, None,
+
+    /// The results of the authentication checks (e.g. SPF, DKIM) done by a mail server (rfc8601)
+    ///
+    /// Each server doing checks prepends its own header (see `HeaderMap::prepend`),
+    /// so the newest results come first.
+    AuthenticationResults, unchecked { "Authentication-Results" }, AuthenticationResults, multi, None,
 
     /// (rfc2045)
     ContentType,  unchecked { "Content-Type"  }, MediaType,       maxOne,   None,
@@ -55,6 +100,12 @@ def_headers! {
     /// (rfc2045)
     ContentId,    unchecked { "Content-Id"    }, ContentId,       maxOne,   None,
 
+    /// The MD5 digest of the (not yet transfer encoded) body (rfc1864)
+    ///
+    /// This header is obsolete, but some systems still use it to
+    /// verify the integrity of a body.
+    ContentMd5,   unchecked { "Content-Md5"   }, ContentMd5,      maxOne,   None,
+
     /// The transfer encoding used to (transfer) encode the body (rfc2045)
     ///
     /// This should either be:
@@ -87,6 +138,17 @@ def_headers! {
     /// to add an description to a inlined/attached image.
     ContentDescription,   unchecked { "Content-Description"       }, Unstructured, maxOne, None,
 
+    /// The language(s) of the intended audience of the body (rfc3282)
+    ///
+    /// E.g. `de` or `en-US, fr` for a body containing the same text in two languages.
+    ContentLanguage,      unchecked { "Content-Language"          }, LanguageTagList, maxOne, None,
+
+    /// The uri with which the body can be referenced (rfc2557)
+    ///
+    /// This is mainly used to resolve references from a html body to
+    /// other parts of a `multipart/related` body, e.g. images.
+    ContentLocation,      unchecked { "Content-Location"          }, ContentLocation, maxOne, None,
+
     /// Defines the disposition of a multipart part it is used on (rfc2183)
     ///
     /// This is meant to be used as a header for a multipart body part, which
@@ -114,7 +176,11 @@ mod validators {
     use error::HeaderValidationError;
     use {HeaderKind, HeaderMap, HeaderName, HeaderObj};
 
-    use super::{ResentDate, ResentFrom, ResentSender, Sender, _From};
+    use header_components::ONE_CLICK_UNSUBSCRIBE;
+
+    use super::{
+        ListUnsubscribe, ListUnsubscribePost, ResentDate, ResentFrom, ResentSender, Sender, _From,
+    };
 
     pub fn from(map: &HeaderMap) -> Result<(), HeaderValidationError> {
         // Note: we do not care about the quantity of From bodies,
@@ -173,12 +239,36 @@ mod validators {
         }
         validate_resent_block(&block)
     }
+
+    pub fn list_unsubscribe_post(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+        let one_click = map
+            .get(ListUnsubscribePost)
+            .filter_map(|res| res.ok())
+            .any(|value| value.as_str().trim().eq_ignore_ascii_case(ONE_CLICK_UNSUBSCRIBE));
+
+        if !one_click {
+            return Ok(());
+        }
+
+        let has_https_uri = map
+            .get(ListUnsubscribe)
+            .filter_map(|res| res.ok())
+            .any(|list| list.has_https_uri());
+
+        if !has_https_uri {
+            header_validation_bail!(kind: OneClickUnsubscribeWithoutHttpsUri);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use header_components::DateTime;
-    use headers::{ResentDate, ResentFrom, ResentSender, ResentTo, Sender, Subject, _From};
+    use header_components::{AuthenticationResult, DateTime};
+    use headers::{
+        AuthenticationResults, ListUnsubscribe, ListUnsubscribePost, Received, ResentDate,
+        ResentFrom, ResentSender, ResentTo, Sender, Subject, _From,
+    };
     use {HeaderKind, HeaderMap};
 
     test!(from_validation_normal {
@@ -262,4 +352,65 @@ mod test {
         map.insert(ResentSender ::auto_body( "a@b.c"           )?);
         assert_ok!(map.use_contextual_validators());
     });
+
+    test!(one_click_unsubscribe_with_https_uri {
+        let mut map = HeaderMap::new();
+        map.insert(ListUnsubscribe::auto_body(
+            ["mailto:leave@example.com", "https://example.com/leave"]
+        )?);
+        map.insert(ListUnsubscribePost::auto_body("List-Unsubscribe=One-Click")?);
+        assert_ok!(map.use_contextual_validators());
+    });
+
+    test!(one_click_unsubscribe_without_https_uri {
+        let mut map = HeaderMap::new();
+        map.insert(ListUnsubscribe::auto_body(["mailto:leave@example.com"])?);
+        map.insert(ListUnsubscribePost::auto_body("List-Unsubscribe=One-Click")?);
+        assert_err!(map.use_contextual_validators());
+    });
+
+    test!(one_click_unsubscribe_without_list_unsubscribe {
+        let mut map = HeaderMap::new();
+        map.insert(ListUnsubscribePost::auto_body("List-Unsubscribe=One-Click")?);
+        assert_err!(map.use_contextual_validators());
+    });
+
+    test!(authentication_results_are_prepended_in_order {
+        let mut map = HeaderMap::new();
+        map.insert(Subject::auto_body("Ok")?);
+        for &authserv_id in &["mx1.example.com", "mx2.example.com"] {
+            let results = ::header_components::AuthenticationResults::new(authserv_id)?
+                .with_result(AuthenticationResult::new("spf", "pass")?);
+            map.prepend(AuthenticationResults::body(results));
+        }
+
+        let ids = map
+            .get(AuthenticationResults)
+            .map(|res| res.unwrap().authserv_id().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["mx2.example.com", "mx1.example.com"]);
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["Authentication-Results", "Authentication-Results", "Subject"]
+        );
+        assert_ok!(map.use_contextual_validators());
+    });
+
+    test!(received_headers_are_prepended_in_order {
+        let mut map = HeaderMap::new();
+        map.insert(Subject::auto_body("Ok")?);
+        for &by in &["mx1.example.com", "mx2.example.com"] {
+            let received = ::header_components::Received::builder().by(by).build()?;
+            map.prepend(Received::body(received));
+        }
+
+        let by = map
+            .get(Received)
+            .map(|res| res.unwrap().by().unwrap().as_str().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(by, vec!["mx2.example.com", "mx1.example.com"]);
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Received", "Received", "Subject"]);
+    });
 }
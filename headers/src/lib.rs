@@ -136,6 +136,8 @@ pub extern crate mail_internals as __internals;
 #[cfg(feature = "serde")]
 extern crate serde;
 #[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+#[cfg(all(test, feature = "serde"))]
 extern crate serde_test;
 
 #[cfg(all(test, not(feature = "traceing")))]
@@ -156,6 +158,7 @@ mod header;
 mod header_macro;
 #[macro_use]
 pub mod map;
+pub mod parse;
 pub mod header_components;
 pub mod headers;
 
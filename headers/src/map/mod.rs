@@ -9,15 +9,28 @@ use std::hash::{Hash, Hasher};
 use std::iter::ExactSizeIterator;
 use std::marker::PhantomData;
 
+use failure::Fail;
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiString};
 use total_order_multi_map::{self, EntryValues, EntryValuesMut, TotalOrderMultiMap};
 
+use internals::{
+    encoder::{EncodingBuffer, LINE_LEN_HARD_LIMIT},
+    error::{EncodingError, EncodingErrorKind, Place},
+    MailType,
+};
+
 use error::{BuildInValidationError, HeaderTypeError, HeaderValidationError};
 
 use name::{HasHeaderName, HeaderName};
 
-use header::{Header, HeaderKind, HeaderObj, HeaderObjTrait, MaxOneMarker};
+use header::{
+    Header, HeaderKind, HeaderObj, HeaderObjTrait, HeaderObjTraitBoxExt, MaxOneMarker,
+    VerbatimHeader,
+};
 
 mod into_iter;
+#[cfg(feature = "serde")]
+mod serde_impl;
 pub use self::into_iter::*;
 
 /// The type of an validator used to check more complex header contraints.
@@ -314,6 +327,42 @@ impl HeaderMap {
         })
     }
 
+    /// Applies `update` to the header associated with the given header kind.
+    ///
+    /// Returns `Ok(false)` without calling `update` if the header isn't
+    /// in the map and `Ok(true)` if it was updated.
+    ///
+    /// # Error
+    ///
+    /// Fails with a `HeaderTypeError` if there are multiple headers with
+    /// the same name (e.g. inserted as untyped header) or if the header
+    /// has a different type than `H`. `update` isn't called in this case.
+    #[inline]
+    pub fn update_single<H, F>(&mut self, _type_hint: H, update: F) -> Result<bool, HeaderTypeError>
+    where
+        H: MaxOneMarker,
+        F: FnOnce(&mut Header<H>),
+    {
+        self._update_single::<H, F>(update)
+    }
+
+    /// A variation of `update_single` which doesn't require passing in a type hint.
+    ///
+    /// See `HeaderMap::_get_single` for more details.
+    pub fn _update_single<H, F>(&mut self, update: F) -> Result<bool, HeaderTypeError>
+    where
+        H: MaxOneMarker,
+        F: FnOnce(&mut Header<H>),
+    {
+        match self._get_single_mut::<H>() {
+            Some(header) => {
+                update(header?);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     /// Returns all header bodies for a given header name, without trying to cast them to a concrete type
     ///
     /// Accepts both `HeaderName` or a type implementing `HeaderKind`.
@@ -390,12 +439,68 @@ impl HeaderMap {
         self._insert(name, H::MAX_ONE, obj)
     }
 
+    /// Inserts the given header, returning the header it replaced.
+    ///
+    /// This behaves like `insert` for a header with `MAX_ONE == true`, but
+    /// instead of discarding the previously stored header it is returned.
+    /// If there was no header with the same name `None` is returned.
+    ///
+    /// If the previously stored header has the same name but a different
+    /// type than `H` (e.g. a custom header type using the same name) it can
+    /// not be returned as `Header<H>` and is discarded, so `None` is
+    /// returned in that case, too.
+    pub fn replace<H>(&mut self, header: Header<H>) -> Option<Header<H>>
+    where
+        H: MaxOneMarker,
+    {
+        let name = header.name();
+        let obj: Box<HeaderObj> = Box::new(header);
+        let old = self.inner_map.set(name, obj).pop()?;
+        old.downcast::<H>().ok().map(|header| *header)
+    }
+
     /// Insert a HeaderObj into the header map.
     #[doc(hidden)]
     pub fn insert_untyped(&mut self, obj: Box<HeaderObj>) {
         self._insert(obj.name(), obj.is_max_one(), obj)
     }
 
+    /// Inserts a header whose body is written exactly as given when encoding.
+    ///
+    /// This is an escape hatch for headers which the header components
+    /// would encode differently or reject, e.g. a header with a precomputed,
+    /// already folded body. Line breaks in `raw_value` have to be `"\r\n"`
+    /// followed by at least one space or tab.
+    ///
+    /// Like `insert` this replaces all existing headers with the same name
+    /// if the header can appear at most one time, i.e. if it's the name of
+    /// a "max one" header defined by this crate or of a "max one" header
+    /// already in the map. Else the header is added to the existing ones.
+    ///
+    /// The value is only validated when encoding, which fails if it
+    /// contains a orphan `'\r'`/`'\n'`, a line break not followed by a
+    /// (non blank) continuation line or a too long line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// # extern crate soft_ascii_string;
+    /// use soft_ascii_string::SoftAsciiString;
+    /// use mail_headers::{HeaderMap, HeaderName};
+    ///
+    /// let mut map = HeaderMap::new();
+    /// let name = HeaderName::parse("X-Legacy-Token").unwrap();
+    /// let value = SoftAsciiString::from_unchecked("abc;\r\n\tdef");
+    /// map.insert_raw(name, value);
+    /// assert!(map.contains(name));
+    /// ```
+    pub fn insert_raw(&mut self, name: HeaderName, raw_value: SoftAsciiString) {
+        let obj: Box<HeaderObj> = Box::new(VerbatimHeader::new(name, raw_value));
+        let max_one = obj.is_max_one() || self.get_untyped(name).any(|header| header.is_max_one());
+        self._insert(name, max_one, obj)
+    }
+
     #[inline(always)]
     fn _insert(&mut self, name: HeaderName, max_one: bool, obj: Box<HeaderObj>) {
         if max_one {
@@ -405,6 +510,38 @@ impl HeaderMap {
         }
     }
 
+    /// Inserts the given header in front of all other headers in the map.
+    ///
+    /// This behaves like `insert` wrt. the "max one" behavior, i.e. if
+    /// `H::MAX_ONE` is `true` all headers previously associated with the
+    /// given header name are removed, but instead of placing the header
+    /// after all other headers it's placed before them.
+    ///
+    /// This is mainly useful to make sure structural headers like e.g.
+    /// `Content-Type` appear before any other headers in the header section.
+    pub fn prepend<H>(&mut self, header: Header<H>)
+    where
+        H: HeaderKind,
+    {
+        let name = header.name();
+        let obj: Box<HeaderObj> = Box::new(header);
+        self._prepend(name, H::MAX_ONE, obj)
+    }
+
+    /// Prepend a HeaderObj to the header map.
+    #[doc(hidden)]
+    pub fn prepend_untyped(&mut self, obj: Box<HeaderObj>) {
+        self._prepend(obj.name(), obj.is_max_one(), obj)
+    }
+
+    fn _prepend(&mut self, name: HeaderName, max_one: bool, obj: Box<HeaderObj>) {
+        // the inner map only supports appending, but reversing it
+        // twice around an insert has the same effect as prepending
+        self.inner_map.reverse();
+        self._insert(name, max_one, obj);
+        self.inner_map.reverse();
+    }
+
     /// Insert all given headers in order into this header map.
     ///
     /// The insertion order of the given headers into this map
@@ -451,6 +588,51 @@ impl HeaderMap {
         }
     }
 
+    /// Insert all given headers, replacing all headers with the same name in this map.
+    ///
+    /// Different to `insert_all` this ignores the "max one" behavior: For
+    /// each header name contained in `other` all headers with that name are
+    /// removed from this map, then all headers of `other` are added in the
+    /// order they have in `other`. This includes multiple headers with the
+    /// same name, e.g. two `Comments` headers in `other` replace all
+    /// `Comments` headers in this map.
+    ///
+    /// This is useful to apply a set of overrides to a set of default headers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers!{
+    ///     Comments: "default comment",
+    ///     Subject: "default subject",
+    ///     Comments: "another default comment"
+    /// }.unwrap();
+    ///
+    /// map.merge_replacing(headers! {
+    ///     Comments: "comment override"
+    /// }.unwrap());
+    ///
+    /// assert_eq!(map.len(), 2);
+    /// let comments = map.get(Comments)
+    ///     .map(|comment| comment.unwrap().as_str().to_owned())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(comments, &["comment override"]);
+    /// # }
+    /// ```
+    pub fn merge_replacing(&mut self, other: HeaderMap) {
+        for (name, _header) in other.iter() {
+            self.inner_map.remove_all(name);
+        }
+        for (name, header) in other.into_iter() {
+            self.inner_map.add(name, header);
+        }
+    }
+
     /// Remove all headers with the given header name.
     ///
     /// Returns true, if at last one header was removed.
@@ -458,10 +640,174 @@ impl HeaderMap {
         self.inner_map.remove_all(name.get_name())
     }
 
+    /// Remove all headers for which the predicate returns false.
+    ///
+    /// The predicate is called once for each header in insertion order,
+    /// the relative order of the remaining headers is kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # use mail_headers::headers::*;
+    /// # fn main() {
+    /// let mut map = headers! {
+    ///     Comments: "internal",
+    ///     Subject: "hy there",
+    ///     Comments: "internal, too"
+    /// }.unwrap();
+    ///
+    /// map.retain(|name, _header| name.as_str() != "Comments");
+    ///
+    /// assert!(!map.contains(Comments));
+    /// assert!(map.contains(Subject));
+    /// # }
+    /// ```
+    pub fn retain(&mut self, mut f: impl FnMut(&HeaderName, &HeaderObj) -> bool) {
+        self.inner_map.retain(|name, header| f(&name, header))
+    }
+
     /// iterate over all (header name, boxed body) pairs in this map
     pub fn iter(&self) -> Iter {
         self.inner_map.iter()
     }
+
+    /// Returns the byte length the header section would have once encoded.
+    ///
+    /// This runs the encoder over all headers in this map, so the length
+    /// is exact and includes any line folding, the CRLF ending each header
+    /// line and the blank line terminating the header section.
+    ///
+    /// This is useful to enforce limits on the size of the header section
+    /// which some mail gateways impose.
+    ///
+    /// Only the headers in this map are counted, headers generated when
+    /// encoding a mail (like `MIME-Version`) are not. Use
+    /// `EncodableMail::encoded_header_len` from `mail-core` to get the
+    /// length of the header section of an encoded mail.
+    ///
+    /// # Error
+    ///
+    /// Fails if any of the headers can not be encoded for the given mail type,
+    /// e.g. if it contains utf-8 which can't be represented in an ascii mail.
+    pub fn encoded_len(&self, mail_type: MailType) -> Result<usize, EncodingError> {
+        let mut encoder = EncodingBuffer::new(mail_type);
+        self.encode_into_unvalidated(&mut encoder)?;
+        Ok(encoder.len())
+    }
+
+    /// Returns true if some header can only be encoded with `MailType::Internationalized`.
+    ///
+    /// This is the case for headers containing non us-ascii text which has
+    /// no us-ascii representation, e.g. a mailbox with a non us-ascii local
+    /// part. Non us-ascii text which can be written as encoded words (e.g. in
+    /// a `Subject`) doesn't count, neither do headers which can't be encoded
+    /// with any mail type.
+    pub fn requires_internationalized(&self) -> bool {
+        self.iter().any(|(name, header)| {
+            let encodes_with = |mail_type| {
+                let mut encoder = EncodingBuffer::new(mail_type);
+                encode_header_line(&mut encoder, name, header).is_ok()
+            };
+            !encodes_with(MailType::Ascii) && encodes_with(MailType::Internationalized)
+        })
+    }
+
+    /// Encodes all headers in order followed by the blank line terminating the header section.
+    ///
+    /// The headers are encoded with the mail type of the buffer. Before
+    /// anything is written the contextual validators are run (see
+    /// `use_contextual_validators`), use `encode_into_unvalidated` to skip
+    /// them. This allows encoding just the header section, e.g. to hash it
+    /// or to prepend it to a separately encoded body.
+    ///
+    /// # Error
+    ///
+    /// Fails with a `EncodingErrorKind::Malformed` error if the validation
+    /// fails, in which case nothing is written to the buffer.
+    ///
+    /// Fails if any of the headers can not be encoded for the mail type of the
+    /// buffer. In this case the headers before the failing header have already
+    /// been written to the buffer.
+    pub fn encode_into(&self, encoder: &mut EncodingBuffer) -> Result<(), EncodingError> {
+        self.use_contextual_validators().map_err(|err| {
+            EncodingError::from(err.context(EncodingErrorKind::Malformed))
+                .with_mail_type_or_else(|| Some(encoder.mail_type()))
+        })?;
+        self.encode_into_unvalidated(encoder)
+    }
+
+    /// Like `encode_into` but without running the contextual validators.
+    pub fn encode_into_unvalidated(
+        &self,
+        encoder: &mut EncodingBuffer,
+    ) -> Result<(), EncodingError> {
+        for (name, header) in self.iter() {
+            encode_header_line(encoder, name, header)?;
+        }
+        encoder.write_blank_line()
+    }
+}
+
+/// Compares the names and bodies of all headers in order.
+///
+/// As the header bodies are type erased they are compared by their
+/// unfolded encoded representation (with `MailType::Internationalized`).
+/// Headers which can not be encoded are compared by their type and their
+/// `Debug` representation instead, so the comparison stays reflexive. A
+/// header which can be encoded never equals one which can not be encoded.
+impl PartialEq for HeaderMap {
+    fn eq(&self, other: &HeaderMap) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .zip(other.iter())
+                .all(|((name, header), (other_name, other_header))| {
+                    if name != other_name {
+                        return false;
+                    }
+                    match (encode_header_body(header), encode_header_body(other_header)) {
+                        (Ok(body), Ok(other_body)) => body == other_body,
+                        (Err(_), Err(_)) => {
+                            HeaderObjTrait::type_id(header) == HeaderObjTrait::type_id(other_header)
+                                && format!("{:?}", header) == format!("{:?}", other_header)
+                        }
+                        _ => false,
+                    }
+                })
+    }
+}
+
+/// Encodes the body of the header into a single (unfolded) line.
+pub(crate) fn encode_header_body(header: &HeaderObj) -> Result<String, EncodingError> {
+    let mut encoder =
+        EncodingBuffer::new(MailType::Internationalized).with_soft_line_limit(LINE_LEN_HARD_LIMIT);
+    encoder.write_header_line(|handle| header.encode(handle))?;
+    let line = encoder.as_str()?;
+    Ok(line.trim_end_matches("\r\n").replace("\r\n", ""))
+}
+
+/// Encodes a single header line including the header name.
+fn encode_header_line(
+    encoder: &mut EncodingBuffer,
+    name: HeaderName,
+    header: &HeaderObj,
+) -> Result<(), EncodingError> {
+    encoder
+        .write_header_line(|handle| {
+            handle.write_str(name.as_ascii_str())?;
+            handle.write_char(SoftAsciiChar::from_unchecked(':'))?;
+            handle.write_fws();
+            header.encode(handle)
+        })
+        .map_err(|err| {
+            err.with_place_or_else(|| {
+                Some(Place::Header {
+                    name: name.as_str(),
+                })
+            })
+        })
 }
 
 /// Iterator over all boxed bodies for a given header name
@@ -840,6 +1186,90 @@ mod test {
         assert_err!(res.expect("where did the header go?"));
     }
 
+    test!(update_single_updates_the_header {
+        let mut headers = headers! {
+            Subject: "abc"
+        }?;
+
+        let updated = headers.update_single(Subject, |subject| {
+            *subject = Subject::auto_body("def").unwrap();
+        })?;
+
+        assert!(updated);
+        assert_eq!("def", headers.get_single(Subject).unwrap()?.as_str());
+    });
+
+    test!(update_single_is_noop_if_header_is_absent {
+        let mut headers = headers! {
+            Comments: "abc"
+        }?;
+
+        let updated = headers.update_single(Subject, |_| panic!("update called"))?;
+
+        assert!(!updated);
+        assert_eq!(1, headers.len());
+    });
+
+    #[test]
+    fn update_single_cast_error() {
+        let mut headers = headers! {
+            Subject: "abc"
+        }
+        .unwrap();
+
+        let res = headers.update_single(BadSubject, |_| panic!("update called"));
+        assert_err!(res);
+    }
+
+    #[test]
+    fn update_single_fails_for_multiple_headers() {
+        let mut headers = headers! {
+            Comments: "abc",
+            Comments: "def"
+        }
+        .unwrap();
+
+        let res = headers.update_single(BadComments2, |_| panic!("update called"));
+        assert_err!(res);
+    }
+
+    #[test]
+    fn replace_returns_old_header() {
+        let mut headers = headers! {
+            Subject: "old subject"
+        }
+        .unwrap();
+
+        let old = headers.replace(Subject::auto_body("new subject").unwrap());
+
+        assert_eq!(old.unwrap().as_str(), "old subject");
+        assert_eq!(
+            headers.get_single(Subject).unwrap().unwrap().as_str(),
+            "new subject"
+        );
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn replace_without_old_header() {
+        let mut headers = HeaderMap::new();
+        let old = headers.replace(Subject::auto_body("new subject").unwrap());
+        assert!(old.is_none());
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn replace_discards_old_header_of_other_type() {
+        let mut headers = headers! {
+            BadSubject: ()
+        }
+        .unwrap();
+
+        let old = headers.replace(Subject::auto_body("new subject").unwrap());
+        assert!(old.is_none());
+        assert_eq!(headers.len(), 1);
+    }
+
     #[test]
     fn get() {
         let headers = headers! {
@@ -940,6 +1370,38 @@ mod test {
         );
     });
 
+    test!(merge_replacing_replaces_all_headers_with_the_same_name {
+        let mut headers = headers! {
+            Comments: "a",
+            Subject: "b",
+            XComment: "x",
+            Comments: "c"
+        }?;
+
+        headers.merge_replacing(headers! {
+            Comments: "d",
+            Subject: "e",
+            Comments: "f"
+        }?);
+
+        assert_eq!(
+            &[ "X-Comment", "Comments", "Subject", "Comments" ],
+            headers.iter()
+                .map(|(name, _val)| name.as_str())
+                .collect::<Vec<_>>()
+                .as_slice()
+        );
+
+        let values = headers.get(Comments)
+            .map(|comp| comp.unwrap().as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            &[ "d", "f" ],
+            values.as_slice()
+        );
+    });
+
     test!(remove_1 {
         let mut headers = headers!{
             Comments: "a",
@@ -986,6 +1448,63 @@ mod test {
         );
     });
 
+    test!(retain_keeps_order {
+        let mut headers = headers!{
+            Comments: "a",
+            XComment: "x",
+            Subject: "b",
+            Comments: "c",
+            XComment: "y",
+            Comments: "d"
+        }?;
+
+        headers.retain(|name, _header| !name.as_str().starts_with("X-"));
+
+        assert_eq!(
+            &[ "Comments", "Subject", "Comments", "Comments" ],
+            headers.iter()
+                .map(|(name, _val)| name.as_str())
+                .collect::<Vec<_>>()
+                .as_slice()
+        );
+        assert!(!headers.contains(XComment));
+
+        let values = headers.get(Comments)
+            .map(|comp| comp.unwrap().as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            &[ "a", "c", "d" ],
+            values.as_slice()
+        );
+    });
+
+    test!(retain_can_inspect_bodies {
+        let mut headers = headers!{
+            Comments: "a",
+            Subject: "b",
+            Comments: "c",
+            Comments: "d"
+        }?;
+
+        headers.retain(|_name, header| {
+            header.downcast_ref::<Comments>()
+                .map(|comments| comments.as_str() != "c")
+                .unwrap_or(true)
+        });
+
+        assert_eq!(3, headers.len());
+        let values = headers.get(Comments)
+            .map(|comp| comp.unwrap().as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            &[ "a", "d" ],
+            values.as_slice()
+        );
+        assert!(headers.contains(Subject));
+    });
+
     #[derive(Default, Copy, Clone)]
     struct XComment;
     impl HeaderKind for XComment {
@@ -1051,6 +1570,217 @@ mod test {
         assert_eq!(3, map.len());
     });
 
+    test!(prepend_places_header_first {
+        let mut headers = headers! {
+            Comments: "a",
+            XComment: "b",
+            Comments: "c"
+        }?;
+
+        headers.prepend(Subject::auto_body("d")?);
+        headers.prepend(Comments::auto_body("e")?);
+
+        assert_eq!(
+            &["Comments", "Subject", "Comments", "X-Comment", "Comments"],
+            headers.iter()
+                .map(|(name, _val)| name.as_str())
+                .collect::<Vec<_>>()
+                .as_slice()
+        );
+
+        let values = headers.get(Comments)
+            .map(|comp| comp.unwrap().as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(&["e", "a", "c"], values.as_slice());
+    });
+
+    test!(prepend_replaces_max_one_headers {
+        let mut headers = headers! {
+            Comments: "a",
+            Subject: "b"
+        }?;
+
+        headers.prepend(Subject::auto_body("c")?);
+
+        assert_eq!(2, headers.len());
+        assert_eq!(
+            &["Subject", "Comments"],
+            headers.iter()
+                .map(|(name, _val)| name.as_str())
+                .collect::<Vec<_>>()
+                .as_slice()
+        );
+        assert_eq!("c", headers.get_single(Subject).unwrap()?.as_str());
+    });
+
+    test!(encoded_len_matches_encoded_header_section {
+        let long_text = "this is a quite long subject which will need to be folded \
+                         as it goes well beyond the soft line length limit";
+        let map = headers! {
+            ::headers::Subject: long_text,
+            Comments: "short"
+        }?;
+
+        let expected = concat!(
+            "Subject: this is a quite long subject which will need to be folded as it goes\r\n",
+            "  well beyond the soft line length limit\r\n",
+            "Comments: short\r\n",
+            "\r\n"
+        );
+        assert_eq!(map.encoded_len(MailType::Ascii)?, expected.len());
+    });
+
+    test!(encoded_len_of_empty_map_is_blank_line {
+        let map = HeaderMap::new();
+        assert_eq!(map.encoded_len(MailType::Ascii)?, 2);
+    });
+
+    test!(requires_internationalized_only_for_non_ascii_without_ascii_form {
+        let map = headers! {
+            ::headers::Subject: "Grüße",
+            ::headers::_From: [("Grüße", "alice@example.test")]
+        }?;
+        assert!(!map.requires_internationalized());
+
+        let map = headers! { ::headers::_From: ["jörg@example.test"] }?;
+        assert!(map.requires_internationalized());
+
+        let map = headers! { BadComments: () }?;
+        assert!(!map.requires_internationalized());
+    });
+
+    test!(encode_into_writes_headers_in_order {
+        let map = headers! {
+            Comments: "first",
+            ::headers::Subject: "hy there",
+            Comments: "second"
+        }?;
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        map.encode_into(&mut encoder)?;
+
+        assert_eq!(
+            encoder.to_string()?,
+            "Comments: first\r\nSubject: hy there\r\nComments: second\r\n\r\n"
+        );
+    });
+
+    test!(encode_into_validates_first {
+        let map = headers! {
+            BadComments2: (),
+            BadComments: ()
+        }?;
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        let err = map.encode_into(&mut encoder).unwrap_err();
+        assert_eq!(err.kind(), EncodingErrorKind::Malformed);
+        assert_eq!(err.mail_type(), Some(MailType::Ascii));
+        assert_eq!(encoder.len(), 0);
+    });
+
+    test!(encode_into_unvalidated_writes_headers {
+        let map = headers! {
+            ::headers::Subject: "a",
+            Comments: "b"
+        }?;
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        map.encode_into_unvalidated(&mut encoder)?;
+        assert_eq!(encoder.to_string()?, "Subject: a\r\nComments: b\r\n\r\n");
+    });
+
+    test!(insert_raw_writes_the_value_verbatim {
+        let value = format!("{};\r\n\t{}", "a".repeat(90), "b");
+        let mut map = HeaderMap::new();
+        map.insert_raw(
+            HeaderName::parse("X-Legacy")?,
+            SoftAsciiString::from_unchecked(value.clone()),
+        );
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        map.encode_into(&mut encoder)?;
+        assert_eq!(encoder.to_string()?, format!("X-Legacy: {}\r\n\r\n", value));
+    });
+
+    test!(insert_raw_replaces_max_one_headers {
+        let mut map = headers! {
+            Subject: "typed",
+            Comments: "first"
+        }?;
+        map.insert_raw(Subject::name(), SoftAsciiString::from_unchecked("raw"));
+        map.insert_raw(Subject::name(), SoftAsciiString::from_unchecked("raw again"));
+        map.insert_raw(Comments::name(), SoftAsciiString::from_unchecked("second"));
+
+        assert_eq!(map.get_untyped(Subject::name()).len(), 1);
+        assert_eq!(map.get_untyped(Comments::name()).len(), 2);
+        map.use_contextual_validators()?;
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        map.encode_into(&mut encoder)?;
+        assert_eq!(
+            encoder.to_string()?,
+            "Comments: first\r\nSubject: raw again\r\nComments: second\r\n\r\n"
+        );
+    });
+
+    #[test]
+    fn insert_raw_values_are_validated_when_encoding() {
+        let invalid_values = ["a\r\nb", "a\nb", "a\r\n \r\n b", "a\r\n", "a\x00b"];
+        for value in invalid_values.iter() {
+            let mut map = HeaderMap::new();
+            map.insert_raw(
+                HeaderName::parse("X-Legacy").unwrap(),
+                SoftAsciiString::from_unchecked(*value),
+            );
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            assert!(map.encode_into(&mut encoder).is_err(), "{:?}", value);
+        }
+    }
+
+    test!(maps_are_compared_by_names_and_encoded_bodies_in_order {
+        let map = headers! {
+            Comments: "first",
+            ::headers::Subject: "hy there"
+        }?;
+        let same = headers! {
+            Comments: "first",
+            ::headers::Subject: "hy there"
+        }?;
+        let reordered = headers! {
+            ::headers::Subject: "hy there",
+            Comments: "first"
+        }?;
+        let other_body = headers! {
+            Comments: "first",
+            ::headers::Subject: "hy"
+        }?;
+
+        assert_eq!(map, same);
+        assert_ne!(map, reordered);
+        assert_ne!(map, other_body);
+        assert_ne!(map, HeaderMap::new());
+    });
+
+    #[test]
+    fn maps_with_unencodable_headers_are_compared_consistently() {
+        fn raw_map(value: &str) -> HeaderMap {
+            let mut map = HeaderMap::new();
+            map.insert_raw(
+                HeaderName::parse("X-Legacy").unwrap(),
+                SoftAsciiString::from_unchecked(value),
+            );
+            map
+        }
+
+        let map = raw_map("a\r\nb");
+        assert_eq!(map, map);
+        assert_eq!(map, map.clone());
+        assert_eq!(map, raw_map("a\r\nb"));
+        assert_ne!(map, raw_map("a\nb"));
+        assert_ne!(map, raw_map("ab"));
+    }
+
     test!(does_not_conflic_with_custom_result_type {
         #[allow(unused)]
         type Result<T> = ::std::result::Result<T, ()>;
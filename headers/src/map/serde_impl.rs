@@ -0,0 +1,147 @@
+//! `Serialize`/`Deserialize` implementations for `HeaderMap`.
+//!
+//! The map is represented as a list of `{ "name": .., "value": .. }`
+//! objects in the order of the headers in the map. The value is the
+//! unfolded header body as it would be encoded (with `MailType::Internationalized`).
+//! When deserializing the values are parsed using `parse::parse_header_strict`.
+//!
+//! As encoded words are not decoded when parsing, values which need to be
+//! encoded using encoded words (e.g. a display name containing a `,`) do
+//! not round trip, all other values keep their order and multiplicity.
+use serde::{
+    de::{Deserializer, Error as DeError},
+    ser::{Error as SerError, SerializeSeq, Serializer},
+    Deserialize, Serialize,
+};
+
+use name::HeaderName;
+use parse::parse_header_strict;
+
+use super::{encode_header_body, HeaderMap};
+
+#[derive(Serialize)]
+struct SerializeField<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[derive(Deserialize)]
+struct DeserializeField {
+    name: String,
+    value: String,
+}
+
+impl Serialize for HeaderMap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for (name, header) in self.iter() {
+            let value = encode_header_body(header).map_err(S::Error::custom)?;
+            seq.serialize_element(&SerializeField {
+                name: name.as_str(),
+                value: &value,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for HeaderMap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let fields = Vec::<DeserializeField>::deserialize(deserializer)?;
+        let mut map = HeaderMap::new();
+        for field in fields {
+            let name = HeaderName::parse(&field.name).map_err(D::Error::custom)?;
+            let header = parse_header_strict(name, &field.value).map_err(D::Error::custom)?;
+            map.insert_untyped(header);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json;
+    use serde_test::{assert_ser_tokens, Token};
+
+    use headers::{Subject, _From, _To};
+    use {HeaderKind, HeaderMap};
+
+    use super::*;
+
+    const FIXTURE: &str = r#"[
+        { "name": "From", "value": "Alice <alice@example.test>" },
+        { "name": "x-custom", "value": "first" },
+        { "name": "To", "value": "<bob@example.test>, Carl Jr <carl@example.test>" },
+        { "name": "X-Custom", "value": "second" },
+        { "name": "Subject", "value": "Grüße" }
+    ]"#;
+
+    #[test]
+    fn deserializes_into_typed_headers_preserving_order() {
+        let map: HeaderMap = serde_json::from_str(FIXTURE).unwrap();
+        assert_eq!(map.len(), 5);
+
+        let names = map
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["From", "X-Custom", "To", "X-Custom", "Subject"]);
+
+        let subject = map.get_single(Subject).unwrap().unwrap();
+        assert_eq!(subject.as_str(), "Grüße");
+        let to = map.get_single(_To).unwrap().unwrap();
+        assert_eq!(to.0.len(), 2);
+        assert!(map.get_single(_From).unwrap().is_ok());
+        let name = HeaderName::parse("X-Custom").unwrap();
+        assert_eq!(map.get_untyped(name).count(), 2);
+    }
+
+    #[test]
+    fn serializes_as_list_of_name_value_pairs() {
+        let mut map = HeaderMap::new();
+        map.insert(Subject::auto_body("hy there").unwrap());
+
+        assert_ser_tokens(
+            &map,
+            &[
+                Token::Seq { len: Some(1) },
+                Token::Struct {
+                    name: "SerializeField",
+                    len: 2,
+                },
+                Token::Str("name"),
+                Token::Str("Subject"),
+                Token::Str("value"),
+                Token::Str("hy there"),
+                Token::StructEnd,
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let map: HeaderMap = serde_json::from_str(FIXTURE).unwrap();
+        let json = serde_json::to_string(&map).unwrap();
+        let map2: HeaderMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&map2).unwrap(), json);
+
+        let names = map2
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["From", "X-Custom", "To", "X-Custom", "Subject"]);
+    }
+
+    #[test]
+    fn fails_for_invalid_header_bodies() {
+        let json = r#"[{ "name": "Message-Id", "value": "no angle brackets" }]"#;
+        assert!(serde_json::from_str::<HeaderMap>(json).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use soft_ascii_string::SoftAsciiStr;
+use std::collections::HashSet;
 use std::fmt;
+use std::sync::{Mutex, OnceLock};
 
 use internals::grammar::is_ftext;
 
@@ -22,11 +24,44 @@ impl HeaderName {
     /// This frees us from doing either case insensitive comparison/hash wrt. hash map
     /// lookups, or converting all names to upper/lower case.
     ///
+    /// Use `HeaderName::parse` to create a header name from a name with arbitrary
+    /// letter case.
+    ///
     pub fn new(name: &'static SoftAsciiStr) -> Result<Self, InvalidHeaderName> {
         HeaderName::validate_name(name)?;
         Ok(HeaderName { name })
     }
 
+    /// Creates a header name from a name with arbitrary letter case.
+    ///
+    /// The name is converted into the letter case used by this library
+    /// (see `HeaderName::new`), e.g. `X-ID`, `x-id` and `X-Id` all
+    /// become `X-Id` and as such compare and hash equal.
+    ///
+    /// As `HeaderName` only stores `&'static` strings the names of
+    /// headers not defined by this crate are interned, i.e. each distinct
+    /// name is leaked exactly once. Parsing many distinct names (e.g. from
+    /// untrusted input) therefore permanently uses memory for each of them.
+    ///
+    /// # Error
+    ///
+    /// Fails if the name is empty or contains characters not allowed in
+    /// header names (i.e. any char which is not `ftext`).
+    pub fn parse(name: &str) -> Result<Self, InvalidHeaderName> {
+        let canonical = canonical_name(name);
+        HeaderName::validate_name(SoftAsciiStr::from_unchecked(&canonical)).map_err(|_| {
+            InvalidHeaderName {
+                invalid_name: name.to_owned(),
+            }
+        })?;
+
+        if let Some((builtin, _)) = crate::headers::lookup_header_name(&canonical) {
+            return Ok(HeaderName::from_ascii_unchecked(builtin));
+        }
+
+        Ok(HeaderName::from_ascii_unchecked(intern_name(canonical)))
+    }
+
     pub fn from_ascii_unchecked<B: ?Sized>(name: &'static B) -> HeaderName
     where
         B: AsRef<str>,
@@ -44,6 +79,22 @@ impl HeaderName {
     pub fn as_str(&self) -> &'static str {
         self.name.as_str()
     }
+
+    /// Returns the canonical name if this is the name of a header defined by this crate.
+    ///
+    /// The returned `&'static str` comes from the table of the headers
+    /// defined in `mail_headers::headers`, for the name of any custom
+    /// header `None` is returned.
+    pub fn as_static_str(&self) -> Option<&'static str> {
+        crate::headers::lookup_header_name(self.as_str()).map(|(name, _)| name)
+    }
+
+    /// Returns true if this is the name of a header defined by this crate which has `MAX_ONE` set.
+    pub(crate) fn is_builtin_max_one(&self) -> bool {
+        crate::headers::lookup_header_name(self.as_str())
+            .map(|(_, max_one)| max_one)
+            .unwrap_or(false)
+    }
 }
 
 impl fmt::Display for HeaderName {
@@ -118,6 +169,43 @@ impl HeaderName {
     }
 }
 
+/// Converts a header name into the letter case used by this library.
+///
+/// The first letter of each alphanumeric part is uppercase, all other
+/// letters are lowercase. Non ascii chars are kept as they are.
+fn canonical_name(name: &str) -> String {
+    let mut canonical = String::with_capacity(name.len());
+    let mut begin_of_word = true;
+    for ch in name.chars() {
+        if ch.is_ascii_alphabetic() {
+            if begin_of_word {
+                canonical.push(ch.to_ascii_uppercase());
+            } else {
+                canonical.push(ch.to_ascii_lowercase());
+            }
+            begin_of_word = false;
+        } else {
+            canonical.push(ch);
+            begin_of_word = !ch.is_ascii_digit();
+        }
+    }
+    canonical
+}
+
+static INTERNED_NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+fn intern_name(name: String) -> &'static str {
+    let names = INTERNED_NAMES.get_or_init(Default::default);
+    //UNWRAP_SAFE: we never panic while holding the lock
+    let mut names = names.lock().unwrap();
+    if let Some(interned) = names.get(&*name) {
+        return interned;
+    }
+    let leaked: &'static str = Box::leak(name.into_boxed_str());
+    names.insert(leaked);
+    leaked
+}
+
 #[derive(Clone, Debug, Fail)]
 #[fail(display = "given name is not a valid header name: {:?}", invalid_name)]
 pub struct InvalidHeaderName {
@@ -139,6 +227,56 @@ impl HasHeaderName for HeaderName {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{headers::Subject, HeaderKind};
+
+    #[test]
+    fn as_static_str_of_builtin_header() {
+        assert_eq!(Subject::name().as_static_str(), Some("Subject"));
+    }
+
+    #[test]
+    fn as_static_str_of_custom_header() {
+        let name = HeaderName::from_ascii_unchecked("X-Custom-Header");
+        assert_eq!(name.as_static_str(), None);
+    }
+
+    #[test]
+    fn parse_normalizes_letter_case() {
+        let names = ["X-ID", "x-id", "X-Id", "x-iD"]
+            .iter()
+            .map(|name| HeaderName::parse(name).unwrap())
+            .collect::<Vec<_>>();
+
+        for name in &names {
+            assert_eq!(name.as_str(), "X-Id");
+            assert_eq!(*name, names[0]);
+        }
+        // interned only once
+        assert_eq!(names[0].as_str().as_ptr(), names[1].as_str().as_ptr());
+    }
+
+    #[test]
+    fn parse_uses_names_of_builtin_headers() {
+        let name = HeaderName::parse("MESSAGE-ID").unwrap();
+        assert_eq!(name.as_static_str(), Some("Message-Id"));
+        assert_eq!(
+            name.as_str().as_ptr(),
+            name.as_static_str().unwrap().as_ptr()
+        );
+    }
+
+    #[test]
+    fn parse_handles_digits_and_other_separators() {
+        assert_eq!(HeaderName::parse("x-4d_FOO").unwrap().as_str(), "X-4d_Foo");
+        assert_eq!(HeaderName::parse("3*4=12").unwrap().as_str(), "3*4=12");
+    }
+
+    #[test]
+    fn parse_rejects_invalid_names() {
+        for case in &["", "a:b", "Message Id", "Null\0Msg", "Ümlaut"] {
+            assert_err!(HeaderName::parse(case), case);
+        }
+    }
 
     #[test]
     fn valide_header_names() {
@@ -0,0 +1,321 @@
+//! Module implementing the parsing of (unfolded) header field bodies.
+//!
+//! Following headers are parsed into their typed representation: `Date`,
+//! `From`, `Sender`, `Reply-To`, `To`, `Cc`, `Bcc`, `Message-Id`,
+//! `In-Reply-To`, `References`, `Subject`, `Comments` and `Auto-Submitted`.
+//! All other headers are kept as `RawHeader`, i.e. as raw (unstructured) text.
+//!
+//! `parse_header` also keeps headers as `RawHeader` if their body can't be
+//! parsed into the typed representation, e.g. address lists using the group
+//! syntax (`undisclosed-recipients:;`) or containing comments. Use
+//! `parse_header_strict` to reject such headers instead.
+//!
+//! Encoded words (rfc2047) are not decoded.
+use chrono;
+use soft_ascii_string::SoftAsciiChar;
+use vec1::Vec1;
+
+use internals::{
+    encoder::EncodingWriter,
+    error::{EncodingError, EncodingErrorKind},
+    grammar::{is_vchar, is_ws},
+};
+
+use error::ComponentCreationError;
+use header_components::{
+    ContentId, DateTime, Mailbox, MailboxList, MessageIdList, RawUnstructured, Unstructured,
+};
+use headers::{
+    AutoSubmitted, Bcc, Cc, Comments, Date, InReplyTo, MessageId, References, ReplyTo, Sender,
+    Subject, _From, _To,
+};
+use map::HeaderMapValidator;
+use {HeaderKind, HeaderName, HeaderObj, HeaderObjTrait, HeaderTryFrom};
+
+/// Parses the unfolded body of the header with the given name.
+///
+/// Headers without a parser (see the module documentation) and headers
+/// whose body is not valid for their parser are returned as `RawHeader`,
+/// so they are still encoded (mostly) as they were parsed.
+pub fn parse_header(name: HeaderName, value: &str) -> Box<HeaderObj> {
+    parse_header_strict(name, value)
+        .unwrap_or_else(|_| Box::new(RawHeader::new(name, value.trim_matches(is_ws))))
+}
+
+/// Like `parse_header` but fails if the body isn't valid for the headers parser.
+///
+/// Headers without a parser are still returned as `RawHeader`.
+pub fn parse_header_strict(
+    name: HeaderName,
+    value: &str,
+) -> Result<Box<HeaderObj>, ComponentCreationError> {
+    let value = value.trim_matches(is_ws);
+    let obj: Box<HeaderObj> = match name.as_str() {
+        "Date" => Box::new(Date::body(parse_date_time(value)?)),
+        "From" => Box::new(_From::body(parse_mailbox_list(value)?)),
+        "Sender" => Box::new(Sender::body(parse_mailbox(value)?)),
+        "Reply-To" => Box::new(ReplyTo::body(parse_mailbox_list(value)?)),
+        "To" => Box::new(_To::body(parse_mailbox_list(value)?)),
+        "Cc" => Box::new(Cc::body(parse_mailbox_list(value)?)),
+        "Bcc" => Box::new(Bcc::body(parse_mailbox_list(value)?)),
+        "Message-Id" => Box::new(MessageId::body(parse_message_id(value)?)),
+        "In-Reply-To" => Box::new(InReplyTo::body(parse_message_id_list(value)?)),
+        "References" => Box::new(References::body(parse_message_id_list(value)?)),
+        "Subject" => Box::new(Subject::body(Unstructured::try_from(value)?)),
+        "Comments" => Box::new(Comments::body(Unstructured::try_from(value)?)),
+        "Auto-Submitted" => Box::new(AutoSubmitted::auto_body(value)?),
+        _ => Box::new(RawHeader::new(name, value)),
+    };
+    Ok(obj)
+}
+
+fn parse_date_time(value: &str) -> Result<DateTime, ComponentCreationError> {
+    chrono::DateTime::parse_from_rfc2822(value)
+        .map(DateTime::new)
+        .map_err(|_| ComponentCreationError::new_with_str("DateTime", value))
+}
+
+/// Parses a message id including the surrounding `<`, `>`.
+pub fn parse_message_id(value: &str) -> Result<ContentId, ComponentCreationError> {
+    let value = value.trim();
+    if value.starts_with('<') && value.ends_with('>') && value.len() >= 2 {
+        ContentId::try_from(&value[1..value.len() - 1])
+    } else {
+        Err(ComponentCreationError::new_with_str("MessageId", value))
+    }
+}
+
+fn parse_message_id_list(value: &str) -> Result<MessageIdList, ComponentCreationError> {
+    let ids = value
+        .split_whitespace()
+        .map(parse_message_id)
+        .collect::<Result<Vec<_>, _>>()?;
+    Vec1::try_from_vec(ids)
+        .map(MessageIdList)
+        .map_err(|_| ComponentCreationError::new_with_str("MessageIdList", value))
+}
+
+fn parse_mailbox_list(value: &str) -> Result<MailboxList, ComponentCreationError> {
+    let mailboxes = split_address_list(value)
+        .into_iter()
+        .map(parse_mailbox)
+        .collect::<Result<Vec<_>, _>>()?;
+    Vec1::try_from_vec(mailboxes)
+        .map(MailboxList)
+        .map_err(|_| ComponentCreationError::new_with_str("MailboxList", value))
+}
+
+/// Parses a mailbox of the form `addr-spec` or `[display-name] "<" addr-spec ">"`.
+fn parse_mailbox(value: &str) -> Result<Mailbox, ComponentCreationError> {
+    let value = value.trim();
+    if !value.ends_with('>') {
+        return Mailbox::try_from(value);
+    }
+
+    let open_idx = value
+        .rfind('<')
+        .ok_or_else(|| ComponentCreationError::new_with_str("Mailbox", value))?;
+    let email = &value[open_idx + 1..value.len() - 1];
+    let display_name = value[..open_idx].trim();
+    if display_name.is_empty() {
+        return Mailbox::try_from(email);
+    }
+
+    let display_name = if display_name.len() >= 2
+        && display_name.starts_with('"')
+        && display_name.ends_with('"')
+    {
+        unquote(&display_name[1..display_name.len() - 1])
+    } else {
+        display_name.to_owned()
+    };
+    Mailbox::try_from((display_name, email))
+}
+
+/// Removes the quoted-pair escapes of the content of a quoted string.
+fn unquote(quoted: &str) -> String {
+    let mut unquoted = String::with_capacity(quoted.len());
+    let mut escaped = false;
+    for ch in quoted.chars() {
+        if ch == '\\' && !escaped {
+            escaped = true;
+        } else {
+            unquoted.push(ch);
+            escaped = false;
+        }
+    }
+    unquoted
+}
+
+/// Splits a address list at all `,` which are not in a quoted string or angle brackets.
+fn split_address_list(value: &str) -> Vec<&str> {
+    let mut addresses = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut in_angle_brackets = false;
+    let mut escaped = false;
+    for (idx, ch) in value.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_angle_brackets = true,
+            '>' if !in_quotes => in_angle_brackets = false,
+            ',' if !in_quotes && !in_angle_brackets => {
+                addresses.push(value[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    addresses.push(value[start..].trim());
+    addresses.retain(|address| !address.is_empty());
+    addresses
+}
+
+/// A header which is kept as is, used for all headers without a (successful) parser.
+#[derive(Debug, Clone)]
+pub struct RawHeader {
+    name: HeaderName,
+    body: RawUnstructured,
+}
+
+impl RawHeader {
+    /// Creates a new raw header, whitespace in `body` is used for folding when encoding.
+    pub fn new(name: HeaderName, body: impl Into<String>) -> Self {
+        RawHeader {
+            name,
+            body: RawUnstructured::from(body.into()),
+        }
+    }
+
+    /// Returns the (unfolded) body of the header.
+    pub fn body(&self) -> &str {
+        self.body.as_str()
+    }
+}
+
+impl HeaderObjTrait for RawHeader {
+    fn name(&self) -> HeaderName {
+        self.name
+    }
+
+    fn is_max_one(&self) -> bool {
+        false
+    }
+
+    fn validator(&self) -> Option<HeaderMapValidator> {
+        None
+    }
+
+    /// Writes the unfolded header body, marking all whitespace as possible fold positions.
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let mail_type = handle.mail_type();
+        let text = self.body.as_str();
+
+        if !text.chars().all(|ch| is_ws(ch) || is_vchar(ch, mail_type)) {
+            return Err(EncodingError::from(EncodingErrorKind::Malformed).with_str_context(text));
+        }
+
+        for (idx, ch) in text.char_indices() {
+            if ch == ' ' {
+                handle.write_fws();
+            } else if ch == '\t' {
+                handle.mark_fws_pos();
+                handle.write_char(SoftAsciiChar::from_unchecked('\t'))?;
+            } else if ch.is_ascii() {
+                handle.write_char(SoftAsciiChar::from_unchecked(ch))?;
+            } else {
+                handle.write_utf8(&text[idx..idx + ch.len_utf8()])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<HeaderObj> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+
+    use super::*;
+    use header_components::{Email, Phrase};
+    use map::encode_header_body;
+
+    fn assert_raw_header(obj: &HeaderObj, body: &str) {
+        assert_eq!(HeaderObjTrait::type_id(obj), TypeId::of::<RawHeader>());
+        assert_eq!(encode_header_body(obj).unwrap(), body);
+    }
+
+    #[test]
+    fn parses_known_headers_into_their_components() {
+        let name = HeaderName::parse("to").unwrap();
+        let obj = parse_header(name, " bob@example.test, \"Carl, Jr.\" <carl@example.test>");
+        let to = obj.downcast_ref::<_To>().unwrap();
+        assert_eq!(to.body().0.len(), 2);
+        assert_eq!(
+            to.body().last().email,
+            Email::new("carl@example.test").unwrap()
+        );
+        assert_eq!(
+            to.body().last().display_name,
+            Some(Phrase::try_from("Carl, Jr.").unwrap())
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_headers_as_raw_headers() {
+        let name = HeaderName::parse("x-custom").unwrap();
+        let obj = parse_header(name, "some\tvalue ");
+        assert_eq!(obj.name().as_str(), "X-Custom");
+        assert!(!obj.is_max_one());
+        assert!(!obj.is::<Subject>());
+
+        let raw = RawHeader::new(name, "some\tvalue");
+        assert_eq!(raw.body(), "some\tvalue");
+    }
+
+    #[test]
+    fn keeps_empty_groups_as_raw_headers() {
+        let name = HeaderName::parse("To").unwrap();
+        let obj = parse_header(name, " undisclosed-recipients:;");
+        assert_raw_header(&*obj, "undisclosed-recipients:;");
+
+        assert!(parse_header_strict(name, "undisclosed-recipients:;").is_err());
+    }
+
+    #[test]
+    fn keeps_groups_with_members_as_raw_headers() {
+        let name = HeaderName::parse("Cc").unwrap();
+        let value = "friends: alice@example.test, bob@example.test;";
+        let obj = parse_header(name, value);
+        assert_raw_header(&*obj, value);
+    }
+
+    #[test]
+    fn keeps_address_lists_with_comments_as_raw_headers() {
+        let name = HeaderName::parse("From").unwrap();
+        let value = "alice@example.test (Alice), Bob <bob@example.test> (work)";
+        let obj = parse_header(name, value);
+        assert_raw_header(&*obj, value);
+    }
+
+    #[test]
+    fn keeps_invalid_bodies_of_known_headers_as_raw_headers() {
+        let name = HeaderName::parse("Message-Id").unwrap();
+        let obj = parse_header(name, "no-angle-brackets@example.test");
+        assert_raw_header(&*obj, "no-angle-brackets@example.test");
+    }
+
+    #[test]
+    fn strict_parsing_rejects_invalid_bodies_of_known_headers() {
+        let name = HeaderName::parse("Message-Id").unwrap();
+        assert!(parse_header_strict(name, "no-angle-brackets@example.test").is_err());
+    }
+}
@@ -10,21 +10,36 @@ use super::encoded_word::EncodedWordWriter;
 const CHARSET: extern_base64::CharacterSet = extern_base64::CharacterSet::Standard;
 const NO_LINE_WRAP: extern_base64::LineWrap = extern_base64::LineWrap::NoWrap;
 const LINE_WRAP: extern_base64::LineWrap =
-    extern_base64::LineWrap::Wrap(78, extern_base64::LineEnding::CRLF);
+    extern_base64::LineWrap::Wrap(DEFAULT_LINE_LENGTH, extern_base64::LineEnding::CRLF);
 const USE_PADDING: bool = true;
 const ECW_STRIP_WHITESPACE: bool = false;
 const NON_ECW_STRIP_WHITESPACE: bool = true;
 
+/// The length of the lines produced by `normal_encode` (excluding the CRLF), see rfc2045.
+pub const DEFAULT_LINE_LENGTH: usize = 76;
+
 #[inline]
 pub fn normal_encode<R: AsRef<[u8]>>(input: R) -> SoftAsciiString {
+    normal_encode_with_line_length(input, DEFAULT_LINE_LENGTH)
+}
+
+/// Base64 encodes the input wrapping lines after `line_length` chars.
+///
+/// Lines are separated by CRLF, the last line is not followed by a
+/// CRLF and includes the `=` padding. A `line_length` of 0 disables
+/// line wrapping.
+#[inline]
+pub fn normal_encode_with_line_length<R: AsRef<[u8]>>(
+    input: R,
+    line_length: usize,
+) -> SoftAsciiString {
     let res = extern_base64::encode_config(
         input.as_ref(),
         extern_base64::Config::new(
-            //FIXME: check if line wrap should be used here, I thinks it should
             CHARSET,
             USE_PADDING,
             NON_ECW_STRIP_WHITESPACE,
-            LINE_WRAP,
+            extern_base64::LineWrap::Wrap(line_length, extern_base64::LineEnding::CRLF),
         ),
     );
     SoftAsciiString::from_unchecked(res)
@@ -152,7 +167,7 @@ mod test {
 
         assert_eq!(
             res.as_str(),
-            "MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2Nzg5MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2Nz\r\ng5"
+            "MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2Nzg5MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2\r\nNzg5"
         );
 
         let dec = normal_decode(res).unwrap();
@@ -160,6 +175,41 @@ mod test {
         assert_eq!(dec, input.as_bytes());
     }
 
+    #[test]
+    fn encoding_with_custom_line_length() {
+        let input = [0xABu8; 100];
+
+        let res = normal_encode_with_line_length(&input[..], 64);
+        let lines = res.as_str().split("\r\n").collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 64);
+        assert_eq!(lines[1].len(), 64);
+        assert_eq!(lines[2].len(), 8);
+        assert!(lines[2].ends_with("qw=="));
+        assert_eq!(normal_decode(res).unwrap(), &input[..]);
+    }
+
+    #[test]
+    fn encoding_does_not_end_with_newline() {
+        // 96 bytes encode to exactly 128 chars, i.e. two full lines
+        let input = [0x12u8; 96];
+
+        let res = normal_encode_with_line_length(&input[..], 64);
+
+        assert!(!res.as_str().ends_with("\r\n"));
+        assert_eq!(res.as_str().split("\r\n").count(), 2);
+        assert_eq!(normal_decode(res).unwrap(), &input[..]);
+    }
+
+    #[test]
+    fn line_length_zero_disables_wrapping() {
+        let input = [0x12u8; 100];
+        let res = normal_encode_with_line_length(&input[..], 0);
+        assert!(!res.as_str().contains("\r\n"));
+        assert!(res.as_str().ends_with("="));
+    }
+
     #[test]
     fn calc_max_input_len_from_max_output_len() {
         assert!(USE_PADDING, "algorithm is specific to the usage of padding");
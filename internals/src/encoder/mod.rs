@@ -15,6 +15,7 @@
 //! writing tests easier. (Through it should _only_ be enabled
 //! for testing and maybe debugging in some cases).
 use std::borrow::Cow;
+use std::io;
 use std::str;
 
 use failure::Fail;
@@ -47,6 +48,10 @@ pub const NEWLINE_WITH_SPACE: &str = "\r\n ";
 pub struct EncodingBuffer {
     mail_type: MailType,
     buffer: Vec<u8>,
+    size_limit: Option<usize>,
+    soft_line_limit: usize,
+    binary_bodies: bool,
+    flushed_len: usize,
     #[cfg(feature = "traceing")]
     pub trace: Vec<TraceToken>,
 }
@@ -57,27 +62,131 @@ impl EncodingBuffer {
         EncodingBuffer {
             mail_type,
             buffer: Vec::new(),
+            size_limit: None,
+            soft_line_limit: LINE_LEN_SOFT_LIMIT,
+            binary_bodies: false,
+            flushed_len: 0,
             #[cfg(feature = "traceing")]
             trace: Vec::new(),
         }
     }
 
+    /// Create a new buffer which fails writes making the encoded data exceed `size_limit` bytes.
+    ///
+    /// Writes which would exceed the limit fail with `EncodingErrorKind::SizeLimitExceeded`.
+    /// Data flushed using `flush_to` still counts against the limit.
+    pub fn with_size_limit(mail_type: MailType, size_limit: usize) -> Self {
+        let mut buffer = EncodingBuffer::new(mail_type);
+        buffer.size_limit = Some(size_limit);
+        buffer
+    }
+
+    /// Overrides the soft line length limit at which header lines are folded.
+    ///
+    /// By default header lines are folded at `LINE_LEN_SOFT_LIMIT` (78).
+    /// Using `LINE_LEN_HARD_LIMIT` disables folding as lines then only
+    /// get folded if they would breach the hard limit anyway. The hard
+    /// limit can not be changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `soft_line_limit` is `0` or larger than `LINE_LEN_HARD_LIMIT`.
+    pub fn with_soft_line_limit(mut self, soft_line_limit: usize) -> Self {
+        assert!(
+            soft_line_limit > 0 && soft_line_limit <= LINE_LEN_HARD_LIMIT,
+            "soft line limit has to be in 1..={}, got {}",
+            LINE_LEN_HARD_LIMIT,
+            soft_line_limit
+        );
+        self.soft_line_limit = soft_line_limit;
+        self
+    }
+
+    /// Allows bodies with the `binary` transfer encoding to be written.
+    ///
+    /// Such bodies contain raw bytes without any line structure, sending
+    /// them requires the smtp `BINARYMIME` extension (rfc3030), which in
+    /// turn requires the `CHUNKING` extension (i.e. `BDAT` instead of `DATA`).
+    /// This is independent of the mail type, which only restricts headers
+    /// and (non-binary) bodies.
+    pub fn with_binary_bodies(mut self) -> Self {
+        self.binary_bodies = true;
+        self
+    }
+
     /// Returns the mail type for which the buffer was created.
     pub fn mail_type(&self) -> MailType {
         self.mail_type
     }
 
+    /// Returns true if bodies with the `binary` transfer encoding can be written.
+    pub fn allows_binary_bodies(&self) -> bool {
+        self.binary_bodies
+    }
+
+    /// Returns the size limit of the buffer, if there is one.
+    pub fn size_limit(&self) -> Option<usize> {
+        self.size_limit
+    }
+
+    /// Returns the soft line length limit at which header lines are folded.
+    pub fn soft_line_limit(&self) -> usize {
+        self.soft_line_limit
+    }
+
+    /// Returns the number of bytes in the buffer.
+    ///
+    /// This doesn't include bytes which were flushed using `flush_to`.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns true if there are no bytes in the buffer.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Returns the maximal length the underlying buffer can have without exceeding the size limit.
+    fn max_buffer_len(&self) -> Option<usize> {
+        self.size_limit
+            .map(|limit| limit.saturating_sub(self.flushed_len))
+    }
+
+    fn check_size_limit(&self, additional_len: usize) -> Result<(), EncodingError> {
+        let exceeds_limit = self
+            .max_buffer_len()
+            .map(|max_len| self.buffer.len() + additional_len > max_len)
+            .unwrap_or(false);
+
+        if exceeds_limit {
+            ec_bail!(mail_type: self.mail_type, kind: SizeLimitExceeded);
+        }
+        Ok(())
+    }
+
     /// returns a new EncodingWriter which contains
     /// a mutable reference to the current string buffer
     ///
     pub fn writer(&mut self) -> EncodingWriter {
+        let max_buffer_len = self.max_buffer_len();
         #[cfg(not(feature = "traceing"))]
         {
-            EncodingWriter::new(self.mail_type, &mut self.buffer)
+            EncodingWriter::new(
+                self.mail_type,
+                &mut self.buffer,
+                max_buffer_len,
+                self.soft_line_limit,
+            )
         }
         #[cfg(feature = "traceing")]
         {
-            EncodingWriter::new(self.mail_type, &mut self.buffer, &mut self.trace)
+            EncodingWriter::new(
+                self.mail_type,
+                &mut self.buffer,
+                max_buffer_len,
+                self.soft_line_limit,
+                &mut self.trace,
+            )
         }
     }
 
@@ -105,22 +214,68 @@ impl EncodingBuffer {
         }
     }
 
-    pub fn write_blank_line(&mut self) {
+    /// writes a blank line (i.e. CRLF) to the internal buffer
+    ///
+    /// # Error
+    ///
+    /// Fails if this would exceed the size limit.
+    pub fn write_blank_line(&mut self) -> Result<(), EncodingError> {
+        self.check_size_limit(NEWLINE.len())?;
         //TODO/BENCH push_str vs. extends(&[u8])
         self.buffer.extend(NEWLINE.as_bytes());
         #[cfg(feature = "traceing")]
         {
             self.trace.push(TraceToken::BlankLine);
         }
+        Ok(())
     }
 
     /// writes a body to the internal buffer, without verifying it's correctness
-    pub fn write_body_unchecked(&mut self, body: &impl AsRef<[u8]>) {
+    ///
+    /// # Error
+    ///
+    /// Fails if this would exceed the size limit, in which case nothing is written.
+    pub fn write_body_unchecked(&mut self, body: &impl AsRef<[u8]>) -> Result<(), EncodingError> {
         let slice = body.as_ref();
+        let needs_newline = !slice.ends_with(NEWLINE.as_bytes());
+        let newline_len = if needs_newline { NEWLINE.len() } else { 0 };
+        self.check_size_limit(slice.len() + newline_len)?;
         self.buffer.extend(slice);
-        if !slice.ends_with(NEWLINE.as_bytes()) {
+        if needs_newline {
             self.buffer.extend(NEWLINE.as_bytes());
         }
+        Ok(())
+    }
+
+    /// writes a body to the internal buffer exactly as given
+    ///
+    /// Unlike `write_body_unchecked` no newline is appended if the
+    /// body doesn't end with one. This is needed for bodies with the
+    /// `binary` transfer encoding, which have no line structure.
+    ///
+    /// # Error
+    ///
+    /// Fails if this would exceed the size limit, in which case nothing is written.
+    pub fn write_body_verbatim(&mut self, body: &impl AsRef<[u8]>) -> Result<(), EncodingError> {
+        let slice = body.as_ref();
+        self.check_size_limit(slice.len())?;
+        self.buffer.extend(slice);
+        Ok(())
+    }
+
+    /// writes the content of the buffer to `out` and clears the buffer
+    ///
+    /// As a `EncodingWriter` borrows the buffer this can only be called
+    /// between writing headers, i.e. it only ever flushes complete lines.
+    /// This allows encoding a mail into a `io::Write` sink without keeping
+    /// the whole encoded mail in memory.
+    ///
+    /// If `out` fails the buffer is not cleared.
+    pub fn flush_to(&mut self, out: &mut dyn io::Write) -> io::Result<()> {
+        out.write_all(&self.buffer)?;
+        self.flushed_len += self.buffer.len();
+        self.buffer.clear();
+        Ok(())
     }
 
     //TODO impl. a alt. `write_body(body,  boundaries)` which:
@@ -181,6 +336,7 @@ impl Into<(MailType, Vec<u8>, Vec<TraceToken>)> for EncodingBuffer {
             mail_type,
             buffer,
             trace,
+            ..
         } = self;
         (mail_type, buffer, trace)
     }
@@ -189,18 +345,23 @@ impl Into<(MailType, Vec<u8>, Vec<TraceToken>)> for EncodingBuffer {
 /// A handle providing method to write to the underlying buffer
 /// keeping track of newlines the current line length and places
 /// where the line can be broken so that the soft line length
-/// limit (78) and the hard length limit (998) can be kept.
+/// limit (78 by default, see `EncodingBuffer::with_soft_line_limit`)
+/// and the hard length limit (998) can be kept.
 ///
 /// It's basically a string buffer which know how to brake
 /// lines at the right place.
 ///
 /// Note any act of writing a header through `EncodingWriter`
-/// has to be concluded by either calling `finish_header` or `undo_header`.
-/// If not this handle will panic in _test_ builds when being dropped
-/// (and the thread is not already panicing) as writes through the handle are directly
-/// writes to the underlying buffer which now contains malformed/incomplete
-/// data. (Note that this Handle does not own any Drop types so if
-/// needed `forget`-ing it won't leak any memory)
+/// has to be concluded by either calling `finish_header`, `undo_header`
+/// or `abort`. If not this handle will panic in builds with debug assertions
+/// when being dropped (and the thread is not already panicing) as writes through
+/// the handle are directly writes to the underlying buffer which now contains
+/// malformed/incomplete data. Builds without debug assertions never panic,
+/// instead the partially written header is silently undone, so that e.g. a
+/// `?` early-return in a header closure can not crash a released service.
+/// (Note that this Handle does not own any Drop types so if needed `forget`-ing
+/// it won't leak any memory, but it will leave the partially written header
+/// in the buffer)
 ///
 ///
 pub struct EncodingWriter<'a> {
@@ -208,6 +369,10 @@ pub struct EncodingWriter<'a> {
     #[cfg(feature = "traceing")]
     trace: &'a mut Vec<TraceToken>,
     mail_type: MailType,
+    /// the maximal length of `buffer` allowed by the size limit
+    max_buffer_len: Option<usize>,
+    /// the line length at which lines are folded
+    soft_line_limit: usize,
     line_start_idx: usize,
     last_fws_idx: usize,
     skipped_cr: bool,
@@ -226,25 +391,41 @@ pub struct EncodingWriter<'a> {
     trace_start_idx: usize,
 }
 
-#[cfg(feature = "traceing")]
 impl<'a> Drop for EncodingWriter<'a> {
     fn drop(&mut self) {
-        use std::thread;
-        if !thread::panicking() && self.has_unfinished_parts() {
-            // we really should panic as the back buffer i.e. the mail will contain
-            // some partially written header which definitely is a bug
-            panic!("dropped Handle which partially wrote header to back buffer (use `finish_header` or `discard`)")
+        if !self.has_unfinished_parts() {
+            return;
+        }
+
+        // with debug assertions we really should panic as the back buffer i.e. the
+        // mail would contain some partially written header which definitely is a bug
+        #[cfg(debug_assertions)]
+        {
+            use std::thread;
+            if !thread::panicking() {
+                panic!("dropped Handle which partially wrote header to back buffer (use `finish_header`, `undo_header` or `abort`)")
+            }
         }
+
+        // without (or if we already panic) we just remove the partially written header
+        self.undo_header();
     }
 }
 
 impl<'inner> EncodingWriter<'inner> {
     #[cfg(not(feature = "traceing"))]
-    fn new(mail_type: MailType, buffer: &'inner mut Vec<u8>) -> Self {
+    fn new(
+        mail_type: MailType,
+        buffer: &'inner mut Vec<u8>,
+        max_buffer_len: Option<usize>,
+        soft_line_limit: usize,
+    ) -> Self {
         let start_idx = buffer.len();
         EncodingWriter {
             buffer,
             mail_type,
+            max_buffer_len,
+            soft_line_limit,
             line_start_idx: start_idx,
             last_fws_idx: start_idx,
             skipped_cr: false,
@@ -259,6 +440,8 @@ impl<'inner> EncodingWriter<'inner> {
     fn new(
         mail_type: MailType,
         buffer: &'inner mut Vec<u8>,
+        max_buffer_len: Option<usize>,
+        soft_line_limit: usize,
         trace: &'inner mut Vec<TraceToken>,
     ) -> Self {
         let start_idx = buffer.len();
@@ -267,6 +450,8 @@ impl<'inner> EncodingWriter<'inner> {
             buffer,
             trace,
             mail_type,
+            max_buffer_len,
+            soft_line_limit,
             line_start_idx: start_idx,
             last_fws_idx: start_idx,
             skipped_cr: false,
@@ -316,6 +501,25 @@ impl<'inner> EncodingWriter<'inner> {
         self.buffer.len() - self.line_start_idx
     }
 
+    /// Returns the number of bytes which can be written before the current line
+    /// reaches the soft line length limit.
+    ///
+    /// This can be used to decide whether to fold (e.g. by writing a
+    /// fws) before writing the next token. It is `0` if the current line
+    /// already reached the soft limit.
+    #[inline]
+    pub fn remaining_soft_budget(&self) -> usize {
+        self.soft_line_limit
+            .saturating_sub(self.current_line_byte_length())
+    }
+
+    /// Returns the number of bytes which can be written before the current line
+    /// reaches the hard line length limit.
+    #[inline]
+    pub fn remaining_hard_budget(&self) -> usize {
+        LINE_LEN_HARD_LIMIT.saturating_sub(self.current_line_byte_length())
+    }
+
     /// marks the current position a a place where a soft
     /// line break (i.e. "\r\n ") can be inserted
     ///
@@ -527,6 +731,29 @@ impl<'inner> EncodingWriter<'inner> {
         self.internal_write_str(s)
     }
 
+    /// writes the given str as is, without inserting any soft line breaks
+    ///
+    /// This is meant for writing already folded header bodies, which
+    /// must not be changed. Line breaks have to be given as `"\r\n"` and
+    /// any fws positions marked before calling this method are ignored.
+    ///
+    /// # Error
+    /// - fails if a line reaches the hard line length limit
+    /// - buffer would contain a orphan '\r' or '\n' after the write
+    ///
+    /// # Trace (test build only)
+    /// does push `NowUnchecked` and then can push `Text`,`CRLF`
+    pub fn write_str_verbatim(&mut self, s: &str) -> Result<(), EncodingError> {
+        #[cfg(feature = "traceing")]
+        {
+            self.trace.push(TraceToken::NowUnchecked)
+        }
+        // without content before the last fws the line is never broken there
+        self.content_since_fws |= self.content_before_fws;
+        self.content_before_fws = false;
+        self.internal_write_str(s)
+    }
+
     /// like finish_header, but won't start a new line
     ///
     /// This is meant to be used when _miss-using_ the
@@ -592,6 +819,14 @@ impl<'inner> EncodingWriter<'inner> {
         self.reinit();
     }
 
+    /// explicitly discards any unfinished header and consumes the handle
+    ///
+    /// This is the same as calling `undo_header` before dropping
+    /// the handle, i.e. it never panics.
+    pub fn abort(mut self) {
+        self.undo_header();
+    }
+
     //---------------------------------------------------------------------------------------------/
     //-/////////////////////////// methods only using the public iface   /////////////////////////-/
 
@@ -678,7 +913,13 @@ impl<'inner> EncodingWriter<'inner> {
     fn break_line_on_fws(&mut self) -> bool {
         if self.content_before_fws && self.last_fws_idx > self.line_start_idx {
             let newline = if self.last_fws_has_char {
-                debug_assert!([b' ', b'\t'].contains(&self.buffer[self.last_fws_idx]));
+                // the fws char is not written yet if writing it is what
+                // made the line reach the soft limit
+                debug_assert!(self
+                    .buffer
+                    .get(self.last_fws_idx)
+                    .map(|bch| [b' ', b'\t'].contains(bch))
+                    .unwrap_or(true));
                 NEWLINE
             } else {
                 NEWLINE_WITH_SPACE
@@ -738,7 +979,7 @@ impl<'inner> EncodingWriter<'inner> {
             }
         }
 
-        if self.current_line_byte_length() >= LINE_LEN_SOFT_LIMIT {
+        if self.current_line_byte_length() >= self.soft_line_limit {
             self.break_line_on_fws();
 
             if self.current_line_byte_length() >= LINE_LEN_HARD_LIMIT {
@@ -749,6 +990,17 @@ impl<'inner> EncodingWriter<'inner> {
             }
         }
 
+        if let Some(max_buffer_len) = self.max_buffer_len {
+            // the CRLF ending the header has to fit in, too
+            let new_len = self.buffer.len() + unchecked_utf8_char.len() + NEWLINE.len();
+            if new_len > max_buffer_len {
+                ec_bail!(
+                    mail_type: self.mail_type(),
+                    kind: SizeLimitExceeded
+                );
+            }
+        }
+
         self.buffer.extend(unchecked_utf8_char.as_bytes());
         #[cfg(feature = "traceing")]
         {
@@ -930,15 +1182,102 @@ mod test {
             let body1 = "una body\r\n";
             let body2 = "another body";
 
-            encoder.write_body_unchecked(&body1);
-            encoder.write_blank_line();
-            encoder.write_body_unchecked(&body2);
+            assert_ok!(encoder.write_body_unchecked(&body1));
+            assert_ok!(encoder.write_blank_line());
+            assert_ok!(encoder.write_body_unchecked(&body2));
 
             assert_eq!(
                 encoder.as_slice(),
                 concat!("una body\r\n", "\r\n", "another body\r\n").as_bytes()
             )
         }
+
+        #[test]
+        fn binary_bodies_are_opt_in() {
+            let encoder = EncodingBuffer::new(MailType::Internationalized);
+            assert!(!encoder.allows_binary_bodies());
+
+            let encoder = encoder.with_binary_bodies();
+            assert!(encoder.allows_binary_bodies());
+            assert_eq!(encoder.mail_type(), MailType::Internationalized);
+        }
+
+        #[test]
+        fn write_body_verbatim() {
+            let mut encoder = EncodingBuffer::new(MailType::Ascii).with_binary_bodies();
+            let body = b"\0bin\rary\n";
+
+            assert_ok!(encoder.write_body_verbatim(&body));
+
+            assert_eq!(encoder.as_slice(), body);
+        }
+
+        #[test]
+        fn len_is_the_number_of_buffered_bytes() {
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            assert!(encoder.is_empty());
+
+            assert_ok!(encoder.write_body_unchecked(&"body"));
+            assert_eq!(encoder.len(), 6);
+
+            let mut out = Vec::new();
+            assert_ok!(encoder.flush_to(&mut out));
+            assert_eq!(encoder.len(), 0);
+        }
+
+        #[test]
+        fn size_limit_allows_writes_up_to_the_limit() {
+            let mut encoder = EncodingBuffer::with_size_limit(MailType::Ascii, 14);
+            assert_eq!(encoder.size_limit(), Some(14));
+
+            assert_ok!(encoder.write_header_line(|handle| {
+                handle.write_str(SoftAsciiStr::from_unchecked("H: yay"))
+            }));
+            assert_ok!(encoder.write_blank_line());
+            assert_ok!(encoder.write_body_unchecked(&"ab"));
+
+            assert_eq!(encoder.as_slice(), b"H: yay\r\n\r\nab\r\n" as &[u8]);
+        }
+
+        #[test]
+        fn size_limit_fails_header_writes_exceeding_it() {
+            let mut encoder = EncodingBuffer::with_size_limit(MailType::Ascii, 8);
+
+            let err = encoder
+                .write_header_line(|handle| {
+                    handle.write_str(SoftAsciiStr::from_unchecked("H: yay!"))
+                })
+                .unwrap_err();
+
+            assert_eq!(err.kind(), EncodingErrorKind::SizeLimitExceeded);
+            assert!(encoder.is_empty());
+        }
+
+        #[test]
+        fn size_limit_fails_body_writes_exceeding_it() {
+            let mut encoder = EncodingBuffer::with_size_limit(MailType::Ascii, 5);
+
+            let err = encoder.write_body_unchecked(&"abcd").unwrap_err();
+            assert_eq!(err.kind(), EncodingErrorKind::SizeLimitExceeded);
+            assert!(encoder.is_empty());
+
+            let err = encoder.write_body_verbatim(&"abcdef").unwrap_err();
+            assert_eq!(err.kind(), EncodingErrorKind::SizeLimitExceeded);
+            assert_ok!(encoder.write_body_verbatim(&"abcde"));
+            assert!(encoder.write_blank_line().is_err());
+        }
+
+        #[test]
+        fn size_limit_counts_flushed_data() {
+            let mut encoder = EncodingBuffer::with_size_limit(MailType::Ascii, 10);
+            assert_ok!(encoder.write_body_unchecked(&"abcd"));
+
+            let mut out = Vec::new();
+            assert_ok!(encoder.flush_to(&mut out));
+
+            assert!(encoder.write_body_unchecked(&"abc").is_err());
+            assert_ok!(encoder.write_body_unchecked(&"ab"));
+        }
     }
 
     mod EncodingWriter {
@@ -948,6 +1287,7 @@ mod test {
 
         use super::_Encoder as EncodingBuffer;
         use super::*;
+        use encoder::LINE_LEN_HARD_LIMIT;
 
         #[test]
         fn commit_partial_and_drop_does_not_panic() {
@@ -1113,6 +1453,36 @@ mod test {
             }
         }
 
+        #[test]
+        fn write_str_verbatim_does_not_break_lines() {
+            let value = format!("{}\r\n {}", "a".repeat(100), "b".repeat(100));
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            {
+                let mut handle = encoder.writer();
+                assert_ok!(handle.write_str(SoftAsciiStr::from_unchecked("X-Raw:")));
+                handle.write_fws();
+                assert_ok!(handle.write_str_verbatim(&value));
+                handle.finish_header();
+            }
+            let expected = format!("X-Raw: {}\r\n", value);
+            assert_eq!(encoder.as_str().unwrap(), expected);
+        }
+
+        #[test]
+        fn write_str_verbatim_validates_the_input() {
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            {
+                let mut handle = encoder.writer();
+                assert_err!(handle.write_str_verbatim("a\nb"));
+                handle.undo_header();
+                assert_err!(handle.write_str_verbatim("a\rb"));
+                handle.undo_header();
+                assert_err!(handle.write_str_verbatim(&"a".repeat(LINE_LEN_HARD_LIMIT + 1)));
+                handle.undo_header();
+            }
+            assert_eq!(encoder.as_slice(), b"");
+        }
+
         #[test]
         fn break_line_on_fws() {
             let mut encoder = EncodingBuffer::new(MailType::Ascii);
@@ -1149,6 +1519,60 @@ mod test {
             );
         }
 
+        #[test]
+        fn remaining_budget_decreases_and_resets_after_fold() {
+            use encoder::{LINE_LEN_HARD_LIMIT, LINE_LEN_SOFT_LIMIT};
+
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            let mut handle = encoder.writer();
+            assert_eq!(handle.remaining_soft_budget(), LINE_LEN_SOFT_LIMIT);
+            assert_eq!(handle.remaining_hard_budget(), LINE_LEN_HARD_LIMIT);
+
+            assert_ok!(handle.write_str(SoftAsciiStr::from_str("A23456789:").unwrap()));
+            assert_eq!(handle.remaining_soft_budget(), LINE_LEN_SOFT_LIMIT - 10);
+            assert_eq!(handle.remaining_hard_budget(), LINE_LEN_HARD_LIMIT - 10);
+
+            handle.mark_fws_pos();
+            assert_ok!(handle.write_str(
+                SoftAsciiStr::from_str(concat!(
+                    "20_3456789",
+                    "30_3456789",
+                    "40_3456789",
+                    "50_3456789",
+                    "60_3456789",
+                    "70_3456789",
+                    "12345678"
+                ))
+                .unwrap()
+            ));
+            assert_eq!(handle.remaining_soft_budget(), 0);
+
+            // the next char doesn't fit and makes the line fold at the fws
+            assert_ok!(handle.write_str(SoftAsciiStr::from_str("X").unwrap()));
+            assert_eq!(handle.remaining_soft_budget(), LINE_LEN_SOFT_LIMIT - 70);
+            assert_eq!(handle.remaining_hard_budget(), LINE_LEN_HARD_LIMIT - 70);
+            handle.finish_header();
+        }
+
+        #[test]
+        fn break_line_on_fws_which_is_not_written_yet() {
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            {
+                let mut handle = encoder.writer();
+                assert_ok!(handle.write_str(SoftAsciiStr::from_str("A23456789:").unwrap()));
+                handle.write_fws();
+                for _ in 0..67 {
+                    assert_ok!(handle.write_char(SoftAsciiChar::from_unchecked('x')));
+                }
+                handle.write_fws();
+                assert_ok!(handle.write_str(SoftAsciiStr::from_str("abc").unwrap()));
+                handle.finish_header();
+            }
+
+            let expected = format!("A23456789: {}\r\n abc\r\n", "x".repeat(67));
+            assert_eq!(encoder.as_str().unwrap(), expected);
+        }
+
         #[test]
         fn break_line_on_fws_does_not_insert_unessesary_space() {
             let mut encoder = EncodingBuffer::new(MailType::Ascii);
@@ -1186,6 +1610,52 @@ mod test {
             );
         }
 
+        #[test]
+        fn soft_line_limit_equal_to_hard_limit_disables_folding() {
+            let mut encoder =
+                EncodingBuffer::new(MailType::Ascii).with_soft_line_limit(LINE_LEN_HARD_LIMIT);
+            assert_eq!(encoder.soft_line_limit(), LINE_LEN_HARD_LIMIT);
+            {
+                let mut handle = encoder.writer();
+                assert_ok!(handle.write_str(SoftAsciiStr::from_str("X-Long:").unwrap()));
+                for _ in 0..20 {
+                    handle.write_fws();
+                    assert_ok!(handle.write_str(SoftAsciiStr::from_str("abcdefghi").unwrap()));
+                }
+                handle.finish_header();
+            }
+
+            let expected = format!("X-Long:{}\r\n", " abcdefghi".repeat(20));
+            assert_eq!(encoder.as_str().unwrap(), expected);
+        }
+
+        #[test]
+        fn lower_soft_line_limit_folds_earlier() {
+            let mut encoder = EncodingBuffer::new(MailType::Ascii).with_soft_line_limit(20);
+            {
+                let mut handle = encoder.writer();
+                assert_eq!(handle.remaining_soft_budget(), 20);
+                assert_ok!(handle.write_str(SoftAsciiStr::from_str("X-Short:").unwrap()));
+                for _ in 0..3 {
+                    handle.write_fws();
+                    assert_ok!(handle.write_str(SoftAsciiStr::from_str("abcdefghi").unwrap()));
+                }
+                handle.finish_header();
+            }
+
+            assert_eq!(
+                encoder.as_str().unwrap(),
+                "X-Short: abcdefghi\r\n abcdefghi abcdefghi\r\n"
+            );
+        }
+
+        #[test]
+        #[should_panic]
+        fn soft_line_limit_can_not_exceed_hard_limit() {
+            let _ =
+                EncodingBuffer::new(MailType::Ascii).with_soft_line_limit(LINE_LEN_HARD_LIMIT + 1);
+        }
+
         #[test]
         fn to_long_unbreakable_line() {
             let mut encoder = EncodingBuffer::new(MailType::Ascii);
@@ -1431,7 +1901,7 @@ mod test {
                 assert_ok!(handle.write_utf8("H: yay"));
                 handle.finish_header();
             }
-            encoder.write_body_unchecked(&"da body");
+            assert_ok!(encoder.write_body_unchecked(&"da body"));
             {
                 let mut handle = encoder.writer();
                 assert_ok!(handle.write_utf8("❤"));
@@ -1484,6 +1954,20 @@ mod test {
             mem::drop(handle);
         }
 
+        #[test]
+        fn abort_discards_unfinished_header() {
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            {
+                let mut handle = encoder.writer();
+                assert_ok!(handle.write_str(SoftAsciiStr::from_str("Header-One: 12").unwrap()));
+                handle.finish_header();
+                assert_ok!(handle.write_str(SoftAsciiStr::from_str("Header-Two:").unwrap()));
+                handle.abort();
+            }
+            assert_eq!(encoder.as_slice(), b"Header-One: 12\r\n");
+        }
+
+        #[cfg(debug_assertions)]
         #[should_panic]
         #[test]
         fn drop_unfinished_panics() {
@@ -1751,6 +2235,202 @@ mod test {
         ]
     }
 
+    /// Randomized tests checking invariants of the line folding for many generated headers.
+    mod folding_properties {
+        use super::_Encoder as EncodingBuffer;
+        use super::*;
+        use encoder::LINE_LEN_HARD_LIMIT;
+        use error::EncodingError;
+
+        const WORD_CHARS: &[u8] =
+            b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.-_=?!";
+
+        /// A xorshift prng, so that failures can be reproduced using the reported seed.
+        struct Rng(u64);
+
+        impl Rng {
+            fn below(&mut self, max: usize) -> usize {
+                let mut x = self.0;
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                self.0 = x;
+                (x % max as u64) as usize
+            }
+        }
+
+        #[derive(Debug)]
+        enum Op {
+            Word(String),
+            Fws,
+            MarkFws,
+            Space(char),
+        }
+
+        fn random_word(rng: &mut Rng) -> String {
+            let len = match rng.below(30) {
+                0 => 900 + rng.below(200),
+                1 | 2 => 60 + rng.below(300),
+                _ => 1 + rng.below(15),
+            };
+            (0..len)
+                .map(|_| WORD_CHARS[rng.below(WORD_CHARS.len())] as char)
+                .collect()
+        }
+
+        /// Generates a header body, words are always separated by some (potential) whitespace.
+        fn random_ops(rng: &mut Rng) -> Vec<Op> {
+            let mut ops = Vec::new();
+            let mut last_was_word = false;
+            for _ in 0..rng.below(80) {
+                let op = match rng.below(10) {
+                    0..=4 if !last_was_word => Op::Word(random_word(rng)),
+                    0..=5 => Op::Fws,
+                    6 | 7 => Op::MarkFws,
+                    8 => Op::Space('\t'),
+                    _ => Op::Space(' '),
+                };
+                last_was_word = matches!(op, Op::Word(_));
+                ops.push(op);
+            }
+            ops
+        }
+
+        fn encode_ops(ops: &[Op]) -> Result<String, EncodingError> {
+            let mut encoder = EncodingBuffer::new(MailType::Ascii);
+            encoder.write_header_line(|handle| {
+                handle.write_str(SoftAsciiStr::from_unchecked("X-Test:"))?;
+                for op in ops {
+                    match *op {
+                        Op::Word(ref word) => {
+                            handle.write_str(SoftAsciiStr::from_unchecked(word))?
+                        }
+                        Op::Fws => handle.write_fws(),
+                        Op::MarkFws => handle.mark_fws_pos(),
+                        Op::Space(ch) => handle.write_char(SoftAsciiChar::from_unchecked(ch))?,
+                    }
+                }
+                Ok(())
+            })?;
+            Ok(encoder.as_str().unwrap().to_owned())
+        }
+
+        /// Returns the length of the longest line needed to fit the parts between the fws.
+        ///
+        /// Every part but the first is preceded by the space of the fws
+        /// (or the one inserted when breaking the line at a fws mark).
+        fn longest_unbreakable_len(ops: &[Op]) -> usize {
+            let mut longest = 0;
+            let mut current = "X-Test:".len();
+            for op in ops {
+                match *op {
+                    Op::Word(ref word) => current += word.len(),
+                    Op::Space(_) => current += 1,
+                    Op::Fws | Op::MarkFws => {
+                        longest = longest.max(current);
+                        current = 1;
+                    }
+                }
+            }
+            longest.max(current)
+        }
+
+        /// Checks that unfolding `encoded` results in the written content.
+        ///
+        /// Besides removing the line breaks the only allowed differences are
+        /// a space inserted at a fws mark which was used to break the line
+        /// and whitespace at the end of the header.
+        fn assert_unfolds_to_input(ops: &[Op], encoded: &str) {
+            let unfolded = encoded.replace("\r\n", "");
+            let rest = unfolded.trim_end_matches([' ', '\t']);
+            let rest = rest.trim_start_matches("X-Test:");
+            assert!(
+                unfolds_to(ops, rest),
+                "{:?} does not unfold to the input",
+                encoded
+            );
+        }
+
+        fn unfolds_to(ops: &[Op], rest: &str) -> bool {
+            let (op, ops) = match ops.split_first() {
+                Some(split) => split,
+                None => return rest.is_empty(),
+            };
+            match *op {
+                Op::Word(ref word) => {
+                    rest.starts_with(word.as_str()) && unfolds_to(ops, &rest[word.len()..])
+                }
+                // trailing whitespace might have been removed
+                Op::Fws | Op::Space(_) if rest.is_empty() => unfolds_to(ops, rest),
+                Op::Fws => rest.starts_with(' ') && unfolds_to(ops, &rest[1..]),
+                Op::Space(ch) => rest.starts_with(ch) && unfolds_to(ops, &rest[1..]),
+                Op::MarkFws => {
+                    (rest.starts_with(' ') && unfolds_to(ops, &rest[1..])) || unfolds_to(ops, rest)
+                }
+            }
+        }
+
+        fn assert_valid_folding(encoded: &str) {
+            assert!(encoded.ends_with("\r\n"));
+            let bytes = encoded.as_bytes();
+            for (idx, &bch) in bytes.iter().enumerate() {
+                if bch == b'\r' {
+                    assert_eq!(bytes.get(idx + 1), Some(&b'\n'), "orphan CR");
+                } else if bch == b'\n' {
+                    assert!(idx > 0 && bytes[idx - 1] == b'\r', "orphan LF");
+                }
+            }
+
+            let lines = encoded[..encoded.len() - 2].split("\r\n");
+            for (idx, line) in lines.enumerate() {
+                assert!(
+                    line.len() <= LINE_LEN_HARD_LIMIT,
+                    "too long line: {:?}",
+                    line
+                );
+                assert!(
+                    line.bytes().any(|bch| bch != b' ' && bch != b'\t'),
+                    "blank line in header"
+                );
+                if idx > 0 {
+                    assert!(
+                        line.starts_with([' ', '\t']),
+                        "line is not a continuation: {:?}",
+                        line
+                    );
+                }
+            }
+        }
+
+        /// Headers which can be folded (i.e. there is no part between two
+        /// fws longer than the hard line length limit) are folded correctly,
+        /// all others fail with `HardLineLengthLimitBreached`.
+        #[test]
+        fn folded_headers_are_valid_and_unfold_to_the_input() {
+            for seed in 1..3000u64 {
+                let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                let ops = random_ops(&mut rng);
+                let result = encode_ops(&ops).map_err(|err| err.kind());
+                let foldable = longest_unbreakable_len(&ops) <= LINE_LEN_HARD_LIMIT;
+
+                let check = ::std::panic::catch_unwind(|| match result {
+                    Ok(ref encoded) => {
+                        assert!(foldable, "header should not be foldable");
+                        assert_valid_folding(encoded);
+                        assert_unfolds_to_input(&ops, encoded);
+                    }
+                    Err(kind) => {
+                        assert!(!foldable, "folding failed: {}", kind);
+                        assert_eq!(kind, EncodingErrorKind::HardLineLengthLimitBreached);
+                    }
+                });
+                if check.is_err() {
+                    panic!("seed {} failed: {:?} => {:?}", seed, ops, result);
+                }
+            }
+        }
+    }
+
     mod trait_object {
         use super::super::*;
 
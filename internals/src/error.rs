@@ -33,6 +33,9 @@ pub enum EncodingErrorKind {
     #[fail(display = "the mail body data cannot be accessed")]
     AccessingMailBodyFailed,
 
+    #[fail(display = "size limit of the encoding buffer exceeded")]
+    SizeLimitExceeded,
+
     #[fail(display = "{}", kind)]
     Other { kind: &'static str }, //ErrorKinds potentially needed when using this wrt. to decoding the mail encoding
                                   //UnsupportedEncoding { encoding: &'static str }
@@ -127,13 +127,16 @@ pub fn vec_insert_bytes(target: &mut Vec<u8>, idx: usize, source: &[u8]) {
     let old_len = target.len();
     let insertion_len = source.len();
     let source_ptr = source.as_ptr();
+    let moved_data_len = old_len - idx;
+
+    // this has to be done before getting the insertion point
+    // as it might reallocate the buffer
+    target.reserve(insertion_len);
+
     let insertion_point = unsafe {
         // SAFE: we panic if idx > target.len(), through idx == target.len() is fine
         target.as_mut_ptr().add(idx)
     };
-    let moved_data_len = old_len - idx;
-
-    target.reserve(insertion_len);
 
     unsafe {
         // SAFE 1: we reserved insertion_len and insertion_point is at most old_len
@@ -5,7 +5,7 @@ use rpassword::read_password_with_reader;
 
 use mail::{
     header_components::{Phrase, Unstructured},
-    smtp::misc::Domain,
+    smtp::{misc::Domain, Security},
     Email, HeaderTryFrom, Mailbox,
 };
 
@@ -139,10 +139,26 @@ impl<'a> ClDialog<'a> {
         writeln!(self.stdout, "Mail Submission Agent (MSA) Information:")?;
         self.prompt("MSA domain name")?;
         let domain = self.read_domain()?;
-        writeln!(self.stdout, "[using port 587 and STARTTLS]")?;
+        self.prompt("Use implicit TLS on port 465 instead of STARTTLS on port 587? [y/N]")?;
+        let security = self.read_security()?;
         writeln!(self.stdout, "MSA Authentication data")?;
         let auth = self.read_auth_data()?;
-        Ok(MsaInfo { domain, auth })
+        Ok(MsaInfo {
+            domain,
+            security,
+            auth,
+        })
+    }
+
+    pub fn read_security(&mut self) -> Result<Security, io::Error> {
+        loop {
+            let line = self.read_line()?;
+            match line.trim() {
+                "" | "n" | "N" => return Ok(Security::StartTls),
+                "y" | "Y" => return Ok(Security::ImplicitTls),
+                _ => self.prompt("[syntax error] y/N")?,
+            }
+        }
     }
 
     pub fn read_simple_mail(&mut self) -> Result<SimpleMail, io::Error> {
@@ -189,6 +205,7 @@ pub struct AuthData {
 #[derive(Debug)]
 pub struct MsaInfo {
     pub domain: Domain,
+    pub security: Security,
     pub auth: AuthData,
 }
 
@@ -58,10 +58,15 @@ fn main() {
 }
 
 fn create_connection_config(msa_info: cli::MsaInfo) -> ConnectionConfig<AuthPlain> {
-    let cli::MsaInfo { domain, auth } = msa_info;
+    let cli::MsaInfo {
+        domain,
+        security,
+        auth,
+    } = msa_info;
 
     ConnectionBuilder::new(domain)
         .expect("could not resolve domain/host name of MSA")
+        .security(security)
         .auth(
             AuthPlain::from_username(auth.username, auth.password)
                 .expect("used \\0 in username or password"),
@@ -0,0 +1,342 @@
+//! Module containing a builder for the `ConnectionConfig` used to connect to a server.
+
+use std::{
+    io as std_io,
+    net::{SocketAddr, ToSocketAddrs},
+};
+
+use futures::Future;
+use new_tokio_smtp::{
+    command::Noop, error::ConnectingFailed, ClientId, Cmd, Connection, ConnectionConfig,
+    DefaultTlsSetup, Domain, Security as SmtpSecurity, SetupTls, TlsConfig,
+};
+
+/// The port used for mail submission with `STARTTLS` (rfc6409).
+pub const DEFAULT_SUBMISSION_PORT: u16 = 587;
+
+/// The port used for mail submission with implicit TLS (rfc8314).
+pub const DEFAULT_SUBMISSIONS_PORT: u16 = 465;
+
+/// The security mechanism used for a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Security {
+    /// Connect with plain TCP and then start TLS with the `STARTTLS` command.
+    #[default]
+    StartTls,
+
+    /// Connect with TLS directly (also known as "wrapped" mode or `SMTPS`).
+    ImplicitTls,
+
+    /// Use a plain, unencrypted TCP connection.
+    ///
+    /// This should only be used for test setups, as e.g. the
+    /// authentication data is sent unencrypted.
+    Plaintext,
+}
+
+impl Security {
+    /// Returns the port used if no port is set explicitly.
+    ///
+    /// This is 465 for `ImplicitTls` and 587 else.
+    pub fn default_port(&self) -> u16 {
+        match *self {
+            Security::ImplicitTls => DEFAULT_SUBMISSIONS_PORT,
+            Security::StartTls | Security::Plaintext => DEFAULT_SUBMISSION_PORT,
+        }
+    }
+}
+
+/// Builder for a `ConnectionConfig`.
+///
+/// This has the same API as the `ConnectionBuilder` of `new-tokio-smtp`
+/// but additionally allows changing the port and using a plain text
+/// connection (see `port` and `security`).
+///
+/// Unless specified otherwise the connection uses `STARTTLS` on port 587,
+/// the hostname as `ClientId`, no authentication and the `DefaultTlsSetup`.
+#[derive(Debug)]
+pub struct ConnectionBuilder<A = Noop, S = DefaultTlsSetup>
+where
+    A: Cmd,
+    S: SetupTls,
+{
+    addr: SocketAddr,
+    domain: Domain,
+    port: Option<u16>,
+    uses_default_port: bool,
+    security: Security,
+    client_id: Option<ClientId>,
+    setup_tls: S,
+    auth_cmd: A,
+}
+
+impl ConnectionBuilder<Noop, DefaultTlsSetup> {
+    /// Create a new `ConnectionBuilder` based on a domain name/host name.
+    ///
+    /// The address is resolved using `std::net::ToSocketAddrs` with the
+    /// default port of the used security (see `Security::default_port`),
+    /// i.e. 587 unless `security` is used to change it.
+    ///
+    /// # Error
+    ///
+    /// Fails if no socket address can be resolved for the host name.
+    pub fn new(host: Domain) -> Result<Self, std_io::Error> {
+        let mut builder = Self::new_with_port(host, DEFAULT_SUBMISSION_PORT)?;
+        builder.uses_default_port = true;
+        Ok(builder)
+    }
+
+    /// Create a new `ConnectionBuilder` based on a domain name/host name and port.
+    ///
+    /// # Error
+    ///
+    /// Fails if no socket address can be resolved for the host name.
+    pub fn new_with_port(host: Domain, port: u16) -> Result<Self, std_io::Error> {
+        let addr = resolve_addr(&host, port)?;
+        Ok(Self::new_with_addr(addr, host))
+    }
+
+    /// Creates a new builder connecting to the given socket address.
+    ///
+    /// The domain is used for Server Name Identification (SNI) and TLS
+    /// hostname verification. The port of the address is used unless
+    /// `port` is called.
+    pub fn new_with_addr(addr: SocketAddr, domain: Domain) -> Self {
+        ConnectionBuilder {
+            addr,
+            domain,
+            port: None,
+            uses_default_port: false,
+            security: Security::default(),
+            client_id: None,
+            setup_tls: DefaultTlsSetup,
+            auth_cmd: Noop,
+        }
+    }
+}
+
+impl<A, S> ConnectionBuilder<A, S>
+where
+    A: Cmd,
+    S: SetupTls,
+{
+    /// Sets the port to connect to.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Sets the security mechanism to use.
+    ///
+    /// If the builder was created with `new` and no port is set explicitly
+    /// this also changes the port used (see `Security::default_port`).
+    pub fn security(mut self, security: Security) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Make the builder use `STARTTLS` security when building.
+    ///
+    /// This is the same as `security(Security::StartTls)`.
+    pub fn use_start_tls(self) -> Self {
+        self.security(Security::StartTls)
+    }
+
+    /// Make the builder use direct tls security when building.
+    ///
+    /// This is the same as `security(Security::ImplicitTls)`.
+    pub fn use_direct_tls(self) -> Self {
+        self.security(Security::ImplicitTls)
+    }
+
+    /// Sets the client identity (default: `ClientId::hostname()`).
+    pub fn client_id(mut self, id: ClientId) -> Self {
+        self.client_id = Some(id);
+        self
+    }
+
+    /// Sets the command used for authentication (default: `Noop`, i.e. no authentication).
+    pub fn auth<NA: Cmd>(self, auth_cmd: NA) -> ConnectionBuilder<NA, S> {
+        let ConnectionBuilder {
+            addr,
+            domain,
+            port,
+            uses_default_port,
+            security,
+            client_id,
+            setup_tls,
+            auth_cmd: _,
+        } = self;
+
+        ConnectionBuilder {
+            addr,
+            domain,
+            port,
+            uses_default_port,
+            security,
+            client_id,
+            setup_tls,
+            auth_cmd,
+        }
+    }
+
+    /// Uses a different `SetupTls` implementation, e.g. to use client certificates.
+    pub fn use_tls_setup<NS: SetupTls>(self, setup_tls: NS) -> ConnectionBuilder<A, NS> {
+        let ConnectionBuilder {
+            addr,
+            domain,
+            port,
+            uses_default_port,
+            security,
+            client_id,
+            setup_tls: _,
+            auth_cmd,
+        } = self;
+
+        ConnectionBuilder {
+            addr,
+            domain,
+            port,
+            uses_default_port,
+            security,
+            client_id,
+            setup_tls,
+            auth_cmd,
+        }
+    }
+
+    /// Returns the port which will be used.
+    pub fn get_port(&self) -> u16 {
+        match self.port {
+            Some(port) => port,
+            None if self.uses_default_port => self.security.default_port(),
+            None => self.addr.port(),
+        }
+    }
+
+    /// Creates the connection config.
+    pub fn build(self) -> ConnectionConfig<A, S> {
+        let port = self.get_port();
+        let ConnectionBuilder {
+            mut addr,
+            domain,
+            security,
+            client_id,
+            setup_tls,
+            auth_cmd,
+            ..
+        } = self;
+
+        addr.set_port(port);
+
+        let tls_config = TlsConfig {
+            domain,
+            setup: setup_tls,
+        };
+        #[allow(deprecated)]
+        let security = match security {
+            Security::StartTls => SmtpSecurity::StartTls(tls_config),
+            Security::ImplicitTls => SmtpSecurity::DirectTls(tls_config),
+            Security::Plaintext => SmtpSecurity::None,
+        };
+
+        ConnectionConfig {
+            addr,
+            auth_cmd,
+            security,
+            client_id: client_id.unwrap_or_else(ClientId::hostname),
+        }
+    }
+
+    /// Calls `Connection::connect(self.build())`.
+    pub fn connect(self) -> impl Future<Item = Connection, Error = ConnectingFailed> + Send {
+        Connection::connect(self.build())
+    }
+}
+
+fn resolve_addr(domain: &Domain, port: u16) -> Result<SocketAddr, std_io::Error> {
+    (domain.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| {
+            std_io::Error::new(
+                std_io::ErrorKind::AddrNotAvailable,
+                format!(
+                    "{}:{} is not associated with any socket address",
+                    domain.as_str(),
+                    port
+                ),
+            )
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+
+    fn builder() -> ConnectionBuilder {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 2525);
+        ConnectionBuilder::new_with_addr(addr, Domain::from_unchecked("mx.example.test"))
+    }
+
+    fn localhost_builder() -> ConnectionBuilder {
+        ConnectionBuilder::new(Domain::from_unchecked("localhost")).unwrap()
+    }
+
+    #[test]
+    fn defaults_to_starttls_on_587() {
+        let builder = localhost_builder();
+        assert_eq!(builder.get_port(), 587);
+        assert_eq!(builder.security, Security::StartTls);
+    }
+
+    #[test]
+    fn implicit_tls_defaults_to_465() {
+        let builder = localhost_builder().security(Security::ImplicitTls);
+        assert_eq!(builder.get_port(), 465);
+        assert_eq!(builder.port(2465).get_port(), 2465);
+
+        let builder = localhost_builder().use_direct_tls();
+        assert_eq!(builder.get_port(), 465);
+        assert_eq!(builder.use_start_tls().get_port(), 587);
+    }
+
+    #[test]
+    fn explicit_ports_are_kept_when_changing_security() {
+        let with_port = ConnectionBuilder::new_with_port(Domain::from_unchecked("localhost"), 2587)
+            .unwrap()
+            .security(Security::ImplicitTls);
+        assert_eq!(with_port.get_port(), 2587);
+        assert_eq!(with_port.build().addr.port(), 2587);
+
+        let with_addr = builder().use_direct_tls();
+        assert_eq!(with_addr.get_port(), 2525);
+        assert_eq!(with_addr.build().addr.port(), 2525);
+    }
+
+    #[test]
+    fn builds_config_with_port_and_security() {
+        let config = builder().build();
+        assert_eq!(config.addr.port(), 2525);
+        match config.security {
+            SmtpSecurity::StartTls(ref tls) => assert_eq!(tls.domain.as_str(), "mx.example.test"),
+            ref other => panic!("unexpected security: {:?}", other),
+        }
+
+        let config = builder().port(465).security(Security::ImplicitTls).build();
+        assert_eq!(config.addr.port(), 465);
+        match config.security {
+            SmtpSecurity::DirectTls(_) => {}
+            ref other => panic!("unexpected security: {:?}", other),
+        }
+
+        let config = builder().security(Security::Plaintext).build();
+        #[allow(deprecated)]
+        match config.security {
+            SmtpSecurity::None => {}
+            ref other => panic!("unexpected security: {:?}", other),
+        }
+    }
+}
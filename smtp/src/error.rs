@@ -6,6 +6,8 @@ use new_tokio_smtp::error::{ConnectingFailed, GeneralError, LogicError};
 use headers::error::HeaderValidationError;
 use mail::error::MailError;
 
+use xoauth2::XOauth2Error;
+
 /// Error used when sending a mail fails.
 ///
 /// Failing to encode a mail before sending
@@ -53,6 +55,61 @@ pub enum MailSendError {
     /// commands.
     #[fail(display = "{}", _0)]
     Io(std_io::Error),
+
+    /// The mail can't be sent to the server as it lacks required capabilities.
+    ///
+    /// E.g. the mail needs `SMTPUTF8` but the server doesn't support it.
+    #[fail(display = "{}", _0)]
+    MailTypeNegotiation(MailTypeNegotiationError),
+
+    /// The encoded mail is larger than the maximal message size advertised by the server.
+    ///
+    /// This is checked before the mail transaction is started (i.e. before
+    /// `MAIL FROM` is sent), using the limit of the `SIZE` extension (rfc1870).
+    #[fail(
+        display = "mail has {} bytes but the server only accepts mails up to {} bytes",
+        size, max_size
+    )]
+    SizeLimitExceeded {
+        /// The size of the encoded mail in bytes.
+        size: u64,
+        /// The maximal message size advertised by the server.
+        max_size: u64,
+    },
+}
+
+impl MailSendError {
+    /// Returns the `XOauth2Error` if the `AUTH XOAUTH2` authentication was rejected.
+    ///
+    /// This can e.g. be used to detect that the access token expired and
+    /// has to be refreshed (see `XOauth2Error::status`).
+    pub fn xoauth2_error(&self) -> Option<&XOauth2Error> {
+        match *self {
+            MailSendError::Connecting(ConnectingFailed::Auth(ref err)) => {
+                XOauth2Error::from_logic_error(err)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if sending the mail again later might succeed.
+    ///
+    /// This is the case for transient (4xx) smtp failures, e.g. because
+    /// the server is overloaded or greylisting is used, and for
+    /// I/O errors, e.g. because the connection broke. Permanent (5xx)
+    /// failures and errors with the mail itself are not transient.
+    pub fn is_transient(&self) -> bool {
+        match *self {
+            MailSendError::Smtp(ref err)
+            | MailSendError::Connecting(ConnectingFailed::Setup(ref err))
+            | MailSendError::Connecting(ConnectingFailed::Auth(ref err)) => match *err {
+                LogicError::Code(ref response) => response.code().is_transient_failure(),
+                _ => false,
+            },
+            MailSendError::Connecting(ConnectingFailed::Io(_)) | MailSendError::Io(_) => true,
+            _ => false,
+        }
+    }
 }
 
 impl From<MailError> for MailSendError {
@@ -73,6 +130,12 @@ impl From<std_io::Error> for MailSendError {
     }
 }
 
+impl From<MailTypeNegotiationError> for MailSendError {
+    fn from(err: MailTypeNegotiationError) -> Self {
+        MailSendError::MailTypeNegotiation(err)
+    }
+}
+
 impl From<ConnectingFailed> for MailSendError {
     fn from(err: ConnectingFailed) -> Self {
         MailSendError::Connecting(err)
@@ -90,9 +153,27 @@ impl From<GeneralError> for MailSendError {
     }
 }
 
+/// Error returned if no `MailType` usable with the servers capabilities can represent a mail.
+#[derive(Debug, Fail)]
+pub enum MailTypeNegotiationError {
+    /// The mail needs `SMTPUTF8`, which the server doesn't support.
+    #[fail(display = "mail requires SMTPUTF8 which is not supported by the server")]
+    Smtputf8NotSupported,
+
+    /// The mail contains 8bit bodies but the server doesn't support `8BITMIME`.
+    #[fail(display = "mail contains 8bit bodies but 8BITMIME is not supported by the server")]
+    EightBitMimeNotSupported,
+
+    /// The mail contains binary bodies but the server doesn't support `BINARYMIME` and `CHUNKING`.
+    #[fail(
+        display = "mail contains binary bodies but BINARYMIME/CHUNKING is not supported by the server"
+    )]
+    BinaryMimeNotSupported,
+}
+
 #[derive(Debug, Fail)]
 pub enum OtherValidationError {
-    #[fail(display = "no To header was present")]
+    #[fail(display = "no recipient (To, Cc or Bcc header) was present")]
     NoTo,
 }
 
@@ -6,9 +6,13 @@
 //! for reliable usage which is not part of this crate.
 //!
 //! For ease of use this crate re-exports some of the most commonly used
-//! parts from `new-tokio-smtp` including `ConnectionConfig`,
-//! `ConnectionBuilder`, all authentication commands/methods (the
-//! `auth` module) as well as useful types (in the `misc` module).
+//! parts from `new-tokio-smtp` including `ConnectionConfig`, all
+//! authentication commands/methods (the `auth` module) as well as
+//! useful types (in the `misc` module). The `ConnectionBuilder` used
+//! to create a `ConnectionConfig` has the same API as the one of
+//! `new-tokio-smtp` but additionally allows choosing the port and
+//! between `STARTTLS` (the default, on port 587), implicit TLS (on
+//! port 465) and plain text connections.
 //!
 //! The `send_mails` function is the simplest way to send a batch
 //! of mails. Nevertheless it doesn't directly accept `Mail` instances,
@@ -64,33 +68,51 @@ extern crate mail_core as mail;
 extern crate mail_headers as headers;
 extern crate mail_internals;
 extern crate new_tokio_smtp;
+extern crate tokio_timer;
+#[cfg(test)]
+extern crate tokio;
+extern crate vec1;
 #[macro_use]
 extern crate failure;
 
 mod resolve_all;
 
+mod connect;
 pub mod error;
+mod negotiate;
 mod request;
+mod retry;
 mod send_mail;
+mod xoauth2;
 
+pub use self::negotiate::{
+    check_mail_size, negotiate_mail_type, negotiate_mail_type_for_encodable, EhloCaps,
+    On8BitUnsupported,
+};
 #[cfg(feature = "extended-api")]
 pub use self::request::derive_envelop_data_from_mail;
 pub use self::request::MailRequest;
+pub use self::retry::{send_batch_with_retry, RetryPolicy, RetryResult};
 
 #[cfg(feature = "extended-api")]
 pub use self::send_mail::encode;
-pub use self::send_mail::{send, send_batch};
+pub use self::send_mail::{send, send_batch, send_negotiated};
 
-pub use new_tokio_smtp::{ConnectionBuilder, ConnectionConfig};
+pub use self::connect::{
+    ConnectionBuilder, Security, DEFAULT_SUBMISSIONS_PORT, DEFAULT_SUBMISSION_PORT,
+};
+pub use new_tokio_smtp::ConnectionConfig;
 
 pub mod auth {
     //! Module containing authentification commands/methods.
     //!
-    //! This Module is re-exported from `new-tokio-smtp` for
-    //! ease of use.
+    //! Except for `XOauth2` this Module is re-exported from
+    //! `new-tokio-smtp` for ease of use.
 
     pub use new_tokio_smtp::command::auth::*;
 
+    pub use xoauth2::{SeparatorCodePointError, XOauth2, XOauth2Error};
+
     /// Auth command for not doing anything on auth.
     //FIXME: this currently still sends the noop cmd,
     // replace it with some new "NoCommand" command.
@@ -99,5 +121,7 @@ pub mod auth {
 
 pub mod misc {
     //! A small collection of usefull types re-exported from `new-tokio-smtp`.
-    pub use new_tokio_smtp::{AddressLiteral, ClientId, Cmd, DefaultTlsSetup, Domain, SetupTls};
+    pub use new_tokio_smtp::{
+        AddressLiteral, ClientId, Cmd, DefaultTlsSetup, Domain, EhloData, SetupTls,
+    };
 }
@@ -0,0 +1,457 @@
+//! Module implementing the negotiation of the `MailType` used to encode a mail.
+
+use new_tokio_smtp::EhloData;
+
+use headers::header_components::TransferEncoding;
+use mail::{EncodableMail, Mail, MailBody, Resource, TransferEncodingHint};
+use mail_internals::MailType;
+
+use error::{MailSendError, MailTypeNegotiationError};
+
+/// The capabilities advertised by a server in its EHLO response.
+///
+/// Only the capabilities relevant for choosing the `MailType` a mail
+/// is encoded with are included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EhloCaps {
+    /// The server supports the `8BITMIME` extension (rfc6152).
+    pub eight_bit_mime: bool,
+
+    /// The server supports the `SMTPUTF8` extension (rfc6531).
+    pub smtputf8: bool,
+
+    /// The server supports the `BINARYMIME` extension (rfc3030).
+    pub binary_mime: bool,
+
+    /// The server supports the `CHUNKING` extension (rfc3030).
+    pub chunking: bool,
+
+    /// The server supports the `SIZE` extension (rfc1870).
+    pub size: bool,
+
+    /// The maximal message size advertised with the `SIZE` extension (rfc1870).
+    ///
+    /// This is `None` if the extension isn't supported or no limit is given.
+    pub max_size: Option<u64>,
+}
+
+impl EhloCaps {
+    /// Extracts the relevant capabilities from the data of a EHLO response.
+    pub fn from_ehlo_data(ehlo_data: &EhloData) -> Self {
+        let max_size = ehlo_data
+            .get_capability_params("SIZE")
+            .and_then(|params| params.first())
+            .and_then(|size| size.as_str().parse().ok())
+            .filter(|&size| size > 0);
+
+        EhloCaps {
+            eight_bit_mime: ehlo_data.has_capability("8BITMIME"),
+            smtputf8: ehlo_data.has_capability("SMTPUTF8"),
+            binary_mime: ehlo_data.has_capability("BINARYMIME"),
+            chunking: ehlo_data.has_capability("CHUNKING"),
+            size: ehlo_data.has_capability("SIZE"),
+            max_size,
+        }
+    }
+
+    /// Returns true if bodies with the `binary` transfer encoding can be sent.
+    ///
+    /// This requires both the `BINARYMIME` and the `CHUNKING` extension (rfc3030).
+    pub fn supports_binary_bodies(&self) -> bool {
+        self.binary_mime && self.chunking
+    }
+}
+
+impl<'a> From<&'a EhloData> for EhloCaps {
+    fn from(ehlo_data: &'a EhloData) -> Self {
+        EhloCaps::from_ehlo_data(ehlo_data)
+    }
+}
+
+/// Computes the `MailType` a mail should be encoded with to be sent to a server.
+///
+/// The least demanding mail type able to represent the mail is chosen, i.e.:
+///
+/// - `MailType::Internationalized` if any header can't be encoded as us-ascii
+///   (e.g. a mailbox with a non us-ascii local part),
+/// - `MailType::Mime8BitEnabled` if a body uses the `8bit` transfer encoding
+///   or uses (or is hinted to use) the `binary` transfer encoding,
+/// - `MailType::Ascii` else.
+///
+/// Bodies with the `binary` transfer encoding are not covered by the mail
+/// type, if there are any the server has to support `BINARYMIME` and
+/// `CHUNKING` (see `EhloCaps::supports_binary_bodies`) and the mail has to
+/// be encoded with a buffer allowing them (`EncodingBuffer::with_binary_bodies`).
+///
+/// Resources which are not loaded yet (`Resource::Source`) are assumed to
+/// be transfer encoded with a 7bit transfer encoding. The `max_size` is not
+/// checked as the size of the mail is only known once it's encoded.
+///
+/// # Error
+///
+/// Fails with `MailSendError::MailTypeNegotiation` if the server doesn't
+/// support the extensions needed to send the mail.
+pub fn negotiate_mail_type(caps: &EhloCaps, mail: &Mail) -> Result<MailType, MailSendError> {
+    let requirements = MailRequirements::of(mail);
+
+    if requirements.binary && !caps.supports_binary_bodies() {
+        return Err(MailTypeNegotiationError::BinaryMimeNotSupported.into());
+    }
+
+    let mail_type = if requirements.smtputf8 {
+        if !caps.smtputf8 {
+            return Err(MailTypeNegotiationError::Smtputf8NotSupported.into());
+        }
+        MailType::Internationalized
+    } else if requirements.binary {
+        // `BINARYMIME` covers `8bit` bodies, too
+        MailType::Mime8BitEnabled
+    } else if requirements.eight_bit {
+        if !caps.eight_bit_mime {
+            return Err(MailTypeNegotiationError::EightBitMimeNotSupported.into());
+        }
+        MailType::Mime8BitEnabled
+    } else {
+        MailType::Ascii
+    };
+
+    Ok(mail_type)
+}
+
+/// Returns true if any body of the mail uses (or is hinted to use) the `binary` transfer encoding.
+pub(crate) fn has_binary_bodies(mail: &Mail) -> bool {
+    MailRequirements::of(mail).binary
+}
+
+/// Checks if a mail with the given (encoded) size can be sent to the server.
+///
+/// # Error
+///
+/// Fails with `MailSendError::SizeLimitExceeded` if the server advertised
+/// a maximal message size (`SIZE` extension) which is less than `size`.
+pub fn check_mail_size(caps: &EhloCaps, size: u64) -> Result<(), MailSendError> {
+    match caps.max_size {
+        Some(max_size) if size > max_size => {
+            Err(MailSendError::SizeLimitExceeded { size, max_size })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// What to do if a mail contains `8bit` (or `binary`) bodies but the server doesn't support them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum On8BitUnsupported {
+    /// Re-encode the `8bit` (or `binary`) bodies with `quoted-printable` or `base64`.
+    Downgrade,
+
+    /// Fail with `MailTypeNegotiationError::EightBitMimeNotSupported`
+    /// (or `MailTypeNegotiationError::BinaryMimeNotSupported`), the default.
+    #[default]
+    Reject,
+}
+
+/// Like `negotiate_mail_type` but can downgrade `8bit` and `binary` bodies if needed.
+///
+/// If `on_8bit_unsupported` is `On8BitUnsupported::Downgrade` all bodies
+/// using the `binary` transfer encoding are re-encoded if the server
+/// doesn't support binary bodies (see `EhloCaps::supports_binary_bodies`
+/// and `EncodableMail::downgrade_binary_bodies`) and all bodies using the
+/// `8bit` transfer encoding are re-encoded if the server doesn't support
+/// `8BITMIME` (see `EncodableMail::downgrade_8bit_bodies`). This happens
+/// before the mail type is negotiated.
+///
+/// # Error
+///
+/// Fails for the same reasons as `negotiate_mail_type` or if downgrading
+/// a body fails (e.g. a `message/rfc822` body uses the `8bit` transfer
+/// encoding).
+pub fn negotiate_mail_type_for_encodable(
+    caps: &EhloCaps,
+    mail: &mut EncodableMail,
+    on_8bit_unsupported: On8BitUnsupported,
+) -> Result<MailType, MailSendError> {
+    if on_8bit_unsupported == On8BitUnsupported::Downgrade {
+        if !caps.supports_binary_bodies() {
+            mail.downgrade_binary_bodies()?;
+        }
+        if !caps.eight_bit_mime {
+            mail.downgrade_8bit_bodies()?;
+        }
+    }
+    negotiate_mail_type(caps, mail)
+}
+
+#[derive(Debug, Default)]
+struct MailRequirements {
+    smtputf8: bool,
+    eight_bit: bool,
+    binary: bool,
+}
+
+impl MailRequirements {
+    fn of(mail: &Mail) -> Self {
+        let mut requirements = MailRequirements::default();
+        requirements.add_mail(mail);
+        requirements
+    }
+
+    fn add_mail(&mut self, mail: &Mail) {
+        if mail.headers().requires_internationalized() {
+            self.smtputf8 = true;
+        }
+
+        match *mail.body() {
+            MailBody::SingleBody { ref body } => self.add_resource(body),
+            MailBody::MultipleBodies { ref bodies, .. } => {
+                for body in bodies {
+                    self.add_mail(body);
+                }
+            }
+        }
+    }
+
+    fn add_resource(&mut self, resource: &Resource) {
+        match *resource {
+            Resource::Source(..) => {}
+            Resource::Message(ref mail) => self.add_mail(mail),
+            Resource::Data(ref data) => {
+                if data.transfer_encoding_hint() == TransferEncodingHint::UseBinary {
+                    self.binary = true;
+                }
+            }
+            Resource::EncData(ref enc_data) => match enc_data.encoding() {
+                TransferEncoding::Binary => self.binary = true,
+                TransferEncoding::_8Bit => self.eight_bit = true,
+                _ => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use futures::Future;
+    use headers::headers::{Subject, _From, _To};
+    use mail::{test_utils::CTX, Context, Data};
+    use new_tokio_smtp::{Capability, Domain, EhloParam, EsmtpKeyword};
+
+    use super::*;
+
+    fn caps(smtputf8: bool) -> EhloCaps {
+        EhloCaps {
+            eight_bit_mime: true,
+            smtputf8,
+            ..Default::default()
+        }
+    }
+
+    fn mail_from(from: &str) -> Mail {
+        let mut mail = Mail::plain_text("hy there", CTX.unwrap());
+        mail.insert_headers(
+            headers! {
+                _From: [from],
+                _To: ["bob@example.test"],
+                Subject: "hy ↓"
+            }
+            .unwrap(),
+        );
+        mail
+    }
+
+    #[test]
+    fn ascii_mail_is_sent_as_ascii() {
+        let mail = mail_from("alice@example.test");
+        assert_eq!(
+            negotiate_mail_type(&caps(true), &mail).unwrap(),
+            MailType::Ascii
+        );
+    }
+
+    #[test]
+    fn utf8_mail_fails_without_smtputf8() {
+        let mail = mail_from("ålice@example.test");
+        match negotiate_mail_type(&caps(false), &mail) {
+            Err(MailSendError::MailTypeNegotiation(
+                MailTypeNegotiationError::Smtputf8NotSupported,
+            )) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn utf8_mail_is_internationalized_with_smtputf8() {
+        let mail = mail_from("ålice@example.test");
+        assert_eq!(
+            negotiate_mail_type(&caps(true), &mail).unwrap(),
+            MailType::Internationalized
+        );
+    }
+
+    #[test]
+    fn binary_bodies_need_binarymime_and_chunking() {
+        let data = Data::plain_text("hy there", CTX.unwrap().generate_content_id())
+            .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+        let mail = Mail::new_singlepart_mail(Resource::Data(data));
+
+        assert!(negotiate_mail_type(&caps(true), &mail).is_err());
+
+        let caps = EhloCaps {
+            binary_mime: true,
+            chunking: true,
+            ..caps(true)
+        };
+        assert_eq!(
+            negotiate_mail_type(&caps, &mail).unwrap(),
+            MailType::Mime8BitEnabled
+        );
+    }
+
+    #[test]
+    fn binary_bodies_can_be_combined_with_smtputf8() {
+        let data = Data::plain_text("hy there", CTX.unwrap().generate_content_id())
+            .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+        let mut mail = Mail::new_singlepart_mail(Resource::Data(data));
+        mail.insert_headers(headers! { _From: ["ålice@example.test"] }.unwrap());
+
+        let caps = EhloCaps {
+            binary_mime: true,
+            chunking: true,
+            ..caps(true)
+        };
+        assert_eq!(
+            negotiate_mail_type(&caps, &mail).unwrap(),
+            MailType::Internationalized
+        );
+    }
+
+    fn eight_bit_mail() -> EncodableMail {
+        let data = Data::plain_text(
+            "Grüße from a mostly us-ascii text",
+            CTX.unwrap().generate_content_id(),
+        )
+        .with_transfer_encoding_hint(TransferEncodingHint::AutoAllow8Bit);
+        let mut mail = Mail::new_singlepart_mail(Resource::Data(data));
+        mail.insert_headers(
+            headers! {
+                _From: ["alice@example.test"],
+                _To: ["bob@example.test"]
+            }
+            .unwrap(),
+        );
+        mail.into_encodable_mail(CTX.unwrap().clone())
+            .wait()
+            .unwrap()
+    }
+
+    #[test]
+    fn eight_bit_mail_is_rejected_without_8bitmime_by_default() {
+        let mut mail = eight_bit_mail();
+        let caps = EhloCaps::default();
+        match negotiate_mail_type_for_encodable(&caps, &mut mail, Default::default()) {
+            Err(MailSendError::MailTypeNegotiation(
+                MailTypeNegotiationError::EightBitMimeNotSupported,
+            )) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(mail.transfer_encodings(), vec![TransferEncoding::_8Bit]);
+    }
+
+    #[test]
+    fn eight_bit_mail_is_downgraded_without_8bitmime() {
+        let mut mail = eight_bit_mail();
+        let caps = EhloCaps::default();
+        let mail_type =
+            negotiate_mail_type_for_encodable(&caps, &mut mail, On8BitUnsupported::Downgrade)
+                .unwrap();
+        assert_eq!(mail_type, MailType::Ascii);
+        assert_eq!(
+            mail.transfer_encodings(),
+            vec![TransferEncoding::QuotedPrintable]
+        );
+    }
+
+    #[test]
+    fn eight_bit_mail_is_not_downgraded_with_8bitmime() {
+        let mut mail = eight_bit_mail();
+        let mail_type = negotiate_mail_type_for_encodable(
+            &caps(false),
+            &mut mail,
+            On8BitUnsupported::Downgrade,
+        )
+        .unwrap();
+        assert_eq!(mail_type, MailType::Mime8BitEnabled);
+        assert_eq!(mail.transfer_encodings(), vec![TransferEncoding::_8Bit]);
+    }
+
+    #[test]
+    fn binary_mail_is_downgraded_without_binarymime() {
+        let data = Data::plain_text("hy there", CTX.unwrap().generate_content_id())
+            .with_transfer_encoding_hint(TransferEncodingHint::UseBinary);
+        let mut mail = Mail::new_singlepart_mail(Resource::Data(data));
+        mail.insert_headers(headers! { _From: ["alice@example.test"] }.unwrap());
+        let mut mail = mail
+            .into_encodable_mail(CTX.unwrap().clone())
+            .wait()
+            .unwrap();
+
+        let mail_type = negotiate_mail_type_for_encodable(
+            &caps(false),
+            &mut mail,
+            On8BitUnsupported::Downgrade,
+        )
+        .unwrap();
+        assert_eq!(mail_type, MailType::Ascii);
+        assert_eq!(mail.transfer_encodings(), vec![TransferEncoding::_7Bit]);
+    }
+
+    fn ehlo_data(capabilities: &[(&str, &[&str])]) -> EhloData {
+        let map: HashMap<_, _> = capabilities
+            .iter()
+            .map(|&(cap, params)| {
+                let cap = Capability::from(cap.parse::<EsmtpKeyword>().unwrap());
+                let params = params
+                    .iter()
+                    .map(|param| param.parse::<EhloParam>().unwrap())
+                    .collect::<Vec<_>>();
+                (cap, params)
+            })
+            .collect();
+        EhloData::from((Domain::from_unchecked("mx.example.test"), map))
+    }
+
+    #[test]
+    fn reads_the_size_limit_from_ehlo_data() {
+        let caps = EhloCaps::from_ehlo_data(&ehlo_data(&[("SIZE", &["1000"])]));
+        assert!(caps.size);
+        assert_eq!(caps.max_size, Some(1000));
+
+        let caps = EhloCaps::from_ehlo_data(&ehlo_data(&[("SIZE", &[])]));
+        assert!(caps.size);
+        assert_eq!(caps.max_size, None);
+
+        let caps = EhloCaps::from_ehlo_data(&ehlo_data(&[("8BITMIME", &[])]));
+        assert!(!caps.size);
+    }
+
+    #[test]
+    fn mails_exceeding_the_size_limit_are_rejected() {
+        let caps = EhloCaps::from_ehlo_data(&ehlo_data(&[("SIZE", &["100"])]));
+        let size = eight_bit_mail()
+            .encode_into_bytes(MailType::Mime8BitEnabled)
+            .unwrap()
+            .len() as u64;
+        assert!(size > 100);
+
+        match check_mail_size(&caps, size) {
+            Err(MailSendError::SizeLimitExceeded {
+                size: err_size,
+                max_size: 100,
+            }) => assert_eq!(err_size, size),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(check_mail_size(&caps, 100).is_ok());
+        assert!(check_mail_size(&EhloCaps::default(), size).is_ok());
+    }
+}
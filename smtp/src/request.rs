@@ -2,10 +2,12 @@ use std::mem;
 
 use new_tokio_smtp::send_mail::{self as smtp, EnvelopData, MailAddress};
 
+use vec1::Vec1;
+
 use headers::{
     error::BuildInValidationError,
-    header_components::Mailbox,
-    headers::{Sender, _From, _To},
+    header_components::{Email, Mailbox},
+    headers::{Bcc, Sender, _From},
 };
 use mail::{
     error::{MailError, OtherValidationError},
@@ -68,13 +70,21 @@ impl MailRequest {
     }
 
     pub fn _into_mail_with_envelop(self) -> Result<(Mail, EnvelopData), MailError> {
-        let envelop = if let Some(envelop) = self.envelop_data {
+        let MailRequest {
+            mut mail,
+            envelop_data,
+        } = self;
+
+        let envelop = if let Some(envelop) = envelop_data {
             envelop
         } else {
-            derive_envelop_data_from_mail(&self.mail)?
+            derive_envelop_data_from_mail(&mail)?
         };
 
-        Ok((self.mail, envelop))
+        // the Bcc recipients are only addressed through the envelop
+        mail.headers_mut().remove(Bcc);
+
+        Ok((mail, envelop))
     }
 
     #[cfg(not(feature = "extended-api"))]
@@ -88,6 +98,8 @@ impl MailRequest {
     /// If envelop data was explicitly set it is returned.
     /// If no envelop data was explicitly given it is derived from the
     /// Mail header fields using `derive_envelop_data_from_mail`.
+    ///
+    /// In both cases the `Bcc` header is removed from the returned mail.
     #[cfg(feature = "extended-api")]
     #[inline(always)]
     pub fn into_mail_with_envelop(self) -> Result<(Mail, EnvelopData), MailError> {
@@ -96,7 +108,10 @@ impl MailRequest {
 }
 
 fn mailaddress_from_mailbox(mailbox: &Mailbox) -> Result<MailAddress, EncodingError> {
-    let email = &mailbox.email;
+    mailaddress_from_email(&mailbox.email)
+}
+
+fn mailaddress_from_email(email: &Email) -> Result<MailAddress, EncodingError> {
     let needs_smtputf8 = email.check_if_internationalized();
     let mt = if needs_smtputf8 {
         MailType::Internationalized
@@ -120,17 +135,19 @@ fn mailaddress_from_mailbox(mailbox: &Mailbox) -> Result<MailAddress, EncodingEr
 /// as smtp from else the single mailbox in from
 /// is used as smtp from.
 ///
-/// All `To`'s are used as smtp recipients.
+/// All recipients from `To`, `Cc` and `Bcc` are used as smtp
+/// recipients (see `Mail::recipients`).
 ///
-/// **`Cc`/`Bcc` is currently no supported/has no
-/// special handling**
+/// **Note that the `Bcc` header is not removed from the mail by this
+/// function**, but it is removed when the mail is sent (or encoded using
+/// `encode`) as part of a `MailRequest`.
 ///
 /// # Error
 ///
 /// An error is returned if there is:
 ///
 /// - No From header
-/// - No To header
+/// - No recipient (i.e. no `To`, `Cc` or `Bcc` header)
 /// - A From header with multiple addresses but no Sender header
 ///
 pub fn derive_envelop_data_from_mail(mail: &Mail) -> Result<smtp::EnvelopData, MailError> {
@@ -151,14 +168,14 @@ pub fn derive_envelop_data_from_mail(mail: &Mail) -> Result<smtp::EnvelopData, M
         mailaddress_from_mailbox(from.first())?
     };
 
-    let smtp_to = if let Some(to) = headers.get_single(_To) {
-        let to = to?;
-        to.try_mapped_ref(mailaddress_from_mailbox)?
-    } else {
-        return Err(AnotherOtherValidationError::NoTo.into());
-    };
+    let smtp_to = mail
+        .recipients()?
+        .iter()
+        .map(mailaddress_from_email)
+        .collect::<Result<Vec<_>, _>>()?;
 
-    //TODO Cc, Bcc
+    let smtp_to =
+        Vec1::try_from_vec(smtp_to).map_err(|_| AnotherOtherValidationError::NoTo)?;
 
     Ok(EnvelopData {
         from: Some(smtp_from),
@@ -171,7 +188,11 @@ mod test {
 
     mod derive_envelop_data_from_mail {
         use super::super::derive_envelop_data_from_mail;
-        use headers::headers::{Sender, _From, _To};
+        use headers::{
+            headers::{Bcc, Cc, Sender, _From, _To},
+            soft_ascii_string::SoftAsciiString,
+            HeaderKind,
+        };
         use mail::{test_utils::CTX, Mail, Resource};
 
         fn mock_resource() -> Resource {
@@ -250,6 +271,119 @@ mod test {
 
             assert_eq!(envelop_data.to.first().as_str(), "das@ding.test");
         }
+
+        #[test]
+        fn use_cc_and_bcc() {
+            let mut mail = Mail::new_singlepart_mail(mock_resource());
+            mail.insert_headers(
+                headers! {
+                    _From: ["ape@caffe.test"],
+                    _To: ["das@ding.test"],
+                    Cc: ["der@ding.test", "das@ding.test"],
+                    Bcc: ["die@ding.test"]
+                }
+                .unwrap(),
+            );
+
+            let envelop_data = derive_envelop_data_from_mail(&mail).unwrap();
+            let to = envelop_data
+                .to
+                .iter()
+                .map(|address| address.as_str())
+                .collect::<Vec<_>>();
+
+            assert_eq!(to, vec!["das@ding.test", "der@ding.test", "die@ding.test"]);
+        }
+
+        #[test]
+        fn fail_if_no_recipients() {
+            let mut mail = Mail::new_singlepart_mail(mock_resource());
+            mail.insert_headers(
+                headers! {
+                    _From: ["ape@caffe.test"]
+                }
+                .unwrap(),
+            );
+
+            let envelop_data = derive_envelop_data_from_mail(&mail);
+
+            //assert is_err
+            envelop_data.unwrap_err();
+        }
+
+        #[test]
+        fn fail_if_recipient_header_is_mistyped() {
+            let mut mail = Mail::new_singlepart_mail(mock_resource());
+            mail.insert_headers(
+                headers! {
+                    _From: ["ape@caffe.test"],
+                    _To: ["das@ding.test"]
+                }
+                .unwrap(),
+            );
+            mail.headers_mut()
+                .insert_raw(Cc::name(), SoftAsciiString::from_unchecked("der@ding.test"));
+
+            let envelop_data = derive_envelop_data_from_mail(&mail);
+
+            //assert is_err
+            envelop_data.unwrap_err();
+        }
+    }
+
+    mod mail_request {
+        use futures::Future;
+
+        use headers::headers::{Bcc, Cc, _From, _To};
+        use mail::{test_utils::CTX, Mail, Resource};
+
+        use super::super::MailRequest;
+        use send_mail::encode;
+
+        fn mail_with_bcc() -> Mail {
+            let mut mail = Mail::new_singlepart_mail(Resource::plain_text("abcd", CTX.unwrap()));
+            mail.insert_headers(
+                headers! {
+                    _From: ["ape@caffe.test"],
+                    _To: ["das@ding.test"],
+                    Cc: ["der@ding.test"],
+                    Bcc: ["die@ding.test"]
+                }
+                .unwrap(),
+            );
+            mail
+        }
+
+        #[test]
+        fn removes_bcc_but_keeps_it_in_the_envelop() {
+            let request = MailRequest::from(mail_with_bcc());
+            let (mail, envelop_data) = request._into_mail_with_envelop().unwrap();
+
+            assert!(!mail.headers().contains(Bcc));
+            assert!(envelop_data
+                .to
+                .iter()
+                .any(|address| address.as_str() == "die@ding.test"));
+        }
+
+        #[test]
+        fn bcc_is_not_part_of_the_encoded_mail() {
+            let envelop = encode(mail_with_bcc().into(), CTX.unwrap().clone())
+                .wait()
+                .unwrap();
+
+            let to = envelop
+                .to_address()
+                .iter()
+                .map(|address| address.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(to, vec!["das@ding.test", "der@ding.test", "die@ding.test"]);
+
+            let raw = String::from_utf8(envelop.mail().raw_data().to_vec()).unwrap();
+            assert!(raw.contains("der@ding.test"));
+            assert!(!raw.contains("die@ding.test"));
+            assert!(!raw.contains("Bcc:"));
+        }
     }
 
     mod mailaddress_from_mailbox {
@@ -0,0 +1,457 @@
+//! Module implementing retrying mails which failed to be sent because of transient errors.
+
+use std::{
+    io as std_io,
+    time::{Duration, Instant},
+};
+
+use futures::future::{self, Either, Future, Loop};
+use tokio_timer::Delay;
+
+use mail::Context;
+use new_tokio_smtp::{
+    command,
+    error::{ConnectingFailed, LogicError},
+    send_mail::MailEnvelop,
+    Cmd, Connection, ConnectionConfig, SetupTls,
+};
+
+use {
+    error::MailSendError,
+    request::MailRequest,
+    send_mail::{encode, send_mail_with_params},
+};
+
+/// Policy for retrying mails which failed to be sent because of a transient error.
+///
+/// See `MailSendError::is_transient` for which errors are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximal number of attempts to send a mail, including the first attempt.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry, it is doubled for each further retry.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, with a delay of 1s before the first and 2s before the second retry.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The result of sending a mail with `send_batch_with_retry`.
+#[derive(Debug)]
+pub struct RetryResult {
+    /// The number of attempts made to send the mail.
+    ///
+    /// This is 0 if the mail could not be encoded.
+    pub attempts: u32,
+
+    /// The result of the last attempt.
+    pub result: Result<(), MailSendError>,
+}
+
+/// Sends a batch of mails to a server, retrying mails which failed because of transient errors.
+///
+/// This works like `send_batch`, except that:
+///
+/// - Mails which failed with a transient error (a 4xx response code or
+///   a connection/I/O error, see `MailSendError::is_transient`) are sent
+///   again using a new connection, until they are sent successfully,
+///   fail with a non-transient error or `policy.max_attempts` attempts
+///   were made. Mails which failed with a permanent (5xx) error are
+///   not retried, neither are mails for which the connection broke
+///   after the `DATA` command was sent, as the server might have
+///   accepted them.
+/// - If setting up the connection fails, the error is reported for
+///   each mail which should have been sent through it.
+/// - Before each retry the future waits for `policy.backoff`, which is
+///   doubled for each further retry. Waiting uses the timer of the tokio
+///   runtime, so the future has to be run on it.
+/// - Mails are only encoded once, before the first attempt.
+/// - It returns a future resolving to one `RetryResult` for each mail
+///   (in the order the mails had been supplied). It never fails, errors
+///   are reported through the results.
+pub fn send_batch_with_retry<A, S, C>(
+    mails: Vec<MailRequest>,
+    conconf: ConnectionConfig<A, S>,
+    ctx: C,
+    policy: RetryPolicy,
+) -> impl Future<Item = Vec<RetryResult>, Error = ()>
+where
+    A: Cmd + Clone,
+    S: SetupTls + Clone,
+    C: Context,
+{
+    // `futures_ordered` yields errors out of order, so `join_all` is used
+    let iter = mails
+        .into_iter()
+        .map(move |mail| encode(mail, ctx.clone()).then(Ok::<_, ()>));
+
+    future::join_all(iter).and_then(move |encoded| {
+        let mut envelops = Vec::with_capacity(encoded.len());
+        let mut results = Vec::with_capacity(encoded.len());
+        for res in encoded {
+            let (envelop, result) = match res {
+                Ok(envelop) => (Some(envelop), Ok(())),
+                Err(err) => (None, Err(err)),
+            };
+            envelops.push(envelop);
+            results.push(RetryResult {
+                attempts: 0,
+                result,
+            });
+        }
+
+        let retryable = vec![true; results.len()];
+        future::loop_fn((results, retryable), move |(results, retryable)| {
+            let pending = results
+                .iter()
+                .enumerate()
+                .filter(|&(idx, res)| {
+                    envelops[idx].is_some() && retryable[idx] && needs_attempt(res, policy)
+                })
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+
+            if pending.is_empty() {
+                return Either::A(future::ok(Loop::Break(results)));
+            }
+
+            let delay = retry_delay(&results[pending[0]], policy);
+            let mails = pending
+                .iter()
+                .map(|&idx| {
+                    envelops[idx]
+                        .clone()
+                        .expect("[BUG] only encoded mails are sent")
+                })
+                .collect::<Vec<_>>();
+            let conconf = conconf.clone();
+
+            let fut = wait(delay)
+                .and_then(move |()| send_attempt(conconf, mails))
+                .map(move |attempts| {
+                    let (mut results, mut retryable) = (results, retryable);
+                    for (idx, attempt) in pending.into_iter().zip(attempts) {
+                        results[idx].attempts += 1;
+                        results[idx].result = attempt.result;
+                        retryable[idx] = !attempt.after_data;
+                    }
+                    Loop::Continue((results, retryable))
+                });
+
+            Either::B(fut)
+        })
+    })
+}
+
+/// The outcome of one attempt to send a mail.
+struct Attempt {
+    result: Result<(), MailSendError>,
+
+    /// True if the connection broke after the `DATA` command was sent.
+    after_data: bool,
+}
+
+impl Attempt {
+    fn failed(err: MailSendError) -> Self {
+        Attempt {
+            result: Err(err),
+            after_data: false,
+        }
+    }
+}
+
+/// Sends the mails through a new connection, resolving to one `Attempt` per mail.
+fn send_attempt<A, S>(
+    conconf: ConnectionConfig<A, S>,
+    mails: Vec<MailEnvelop>,
+) -> impl Future<Item = Vec<Attempt>, Error = ()>
+where
+    A: Cmd,
+    S: SetupTls,
+{
+    let count = mails.len();
+    Connection::connect(conconf).then(move |res| match res {
+        Err(err) => {
+            let attempts = (0..count)
+                .map(|_| Attempt::failed(copy_connecting_failed(&err).into()))
+                .collect();
+            Either::A(future::ok(attempts))
+        }
+        Ok(con) => Either::B(send_all(con, mails)),
+    })
+}
+
+/// Sends all mails through the connection and quits it afterwards.
+///
+/// Once the connection broke all remaining mails fail with an I/O error.
+fn send_all(
+    con: Connection,
+    mails: Vec<MailEnvelop>,
+) -> impl Future<Item = Vec<Attempt>, Error = ()> {
+    let initial = (Some(con), mails.into_iter(), Vec::new());
+    future::loop_fn(initial, |(con, mut mails, mut attempts)| {
+        let (con, mail) = match (con, mails.next()) {
+            (con, None) => return Either::A(future::ok(Loop::Break((con, attempts)))),
+            (None, Some(_)) => {
+                attempts.push(Attempt::failed(MailSendError::Io(std_io::Error::new(
+                    std_io::ErrorKind::NotConnected,
+                    "previous error killed connection",
+                ))));
+                return Either::A(future::ok(Loop::Continue((None, mails, attempts))));
+            }
+            (Some(con), Some(mail)) => (con, mail),
+        };
+
+        let fut =
+            send_mail_with_params(con, mail, command::Params::new(), false).then(move |res| {
+                let (con, attempt) = match res {
+                    Ok((con, result)) => (
+                        Some(con),
+                        Attempt {
+                            result: result.map_err(|(_idx, err)| err.into()),
+                            after_data: false,
+                        },
+                    ),
+                    Err(err) => (
+                        None,
+                        Attempt {
+                            after_data: err.after_data,
+                            result: Err(err.into()),
+                        },
+                    ),
+                };
+                attempts.push(attempt);
+                Ok(Loop::Continue((con, mails, attempts)))
+            });
+        Either::B(fut)
+    })
+    .and_then(|(con, attempts)| match con {
+        Some(con) => Either::A(con.quit().then(move |_| Ok(attempts))),
+        None => Either::B(future::ok(attempts)),
+    })
+}
+
+/// Creates an equivalent `ConnectingFailed` error, as it doesn't implement `Clone`.
+fn copy_connecting_failed(err: &ConnectingFailed) -> ConnectingFailed {
+    match *err {
+        ConnectingFailed::Io(ref err) => {
+            ConnectingFailed::Io(std_io::Error::new(err.kind(), err.to_string()))
+        }
+        ConnectingFailed::Setup(ref err) => ConnectingFailed::Setup(copy_logic_error(err)),
+        ConnectingFailed::Auth(ref err) => ConnectingFailed::Auth(copy_logic_error(err)),
+    }
+}
+
+fn copy_logic_error(err: &LogicError) -> LogicError {
+    match *err {
+        LogicError::Code(ref response) => LogicError::Code(response.clone()),
+        LogicError::UnexpectedCode(ref response) => LogicError::UnexpectedCode(response.clone()),
+        LogicError::Custom(ref err) => LogicError::Custom(err.to_string().into()),
+        LogicError::MissingCapabilities(ref caps) => LogicError::MissingCapabilities(caps.clone()),
+    }
+}
+
+/// Returns true if the mail was not yet sent and (another) attempt should be made.
+fn needs_attempt(result: &RetryResult, policy: RetryPolicy) -> bool {
+    if result.attempts == 0 {
+        return true;
+    }
+    match result.result {
+        Ok(()) => false,
+        Err(ref err) => err.is_transient() && result.attempts < policy.max_attempts,
+    }
+}
+
+/// Returns the delay before the next attempt to send the mail.
+fn retry_delay(result: &RetryResult, policy: RetryPolicy) -> Duration {
+    if result.attempts == 0 {
+        return Duration::from_secs(0);
+    }
+    let factor = 1u32.checked_shl(result.attempts - 1).unwrap_or(u32::MAX);
+    policy.backoff.checked_mul(factor).unwrap_or(policy.backoff)
+}
+
+fn wait(delay: Duration) -> impl Future<Item = (), Error = ()> {
+    if delay == Duration::from_secs(0) {
+        return Either::A(future::ok(()));
+    }
+    // if there is no timer retrying right away is better than not retrying at all
+    Either::B(Delay::new(Instant::now() + delay).then(|_| Ok(())))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use tokio::runtime::Runtime;
+
+    use headers::headers::{_From, _To};
+    use mail::{test_utils::CTX, Mail};
+    use new_tokio_smtp::{error::LogicError, response::codes, Domain, Response};
+
+    use super::*;
+    use connect::{ConnectionBuilder, Security};
+
+    fn mail_request(from: Option<&str>) -> MailRequest {
+        let mut mail = Mail::plain_text("hy there", CTX.unwrap());
+        mail.insert_headers(headers! { _To: ["bob@example.test"] }.unwrap());
+        if let Some(from) = from {
+            mail.insert_headers(headers! { _From: [from] }.unwrap());
+        }
+        mail.into()
+    }
+
+    fn unused_local_addr() -> ::std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    /// Minimal smtp server which breaks the connection once it received the mail data.
+    fn serve_dropping_after_data(listener: TcpListener) -> thread::JoinHandle<usize> {
+        thread::spawn(move || {
+            let mut connections = 0;
+            for stream in listener.incoming() {
+                connections += 1;
+                let mut stream = stream.unwrap();
+                stream.write_all(b"220 localhost ready\r\n").unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 {
+                    let response: &[u8] = match line.get(..4).unwrap_or("") {
+                        "QUIT" => return connections,
+                        "DATA" => b"354 go ahead\r\n",
+                        _ => b"250 ok\r\n",
+                    };
+                    stream.write_all(response).unwrap();
+                    if line.starts_with("DATA") {
+                        line.clear();
+                        while reader.read_line(&mut line).unwrap() > 0 && line != ".\r\n" {
+                            line.clear();
+                        }
+                        break;
+                    }
+                    line.clear();
+                }
+            }
+            connections
+        })
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let policy = RetryPolicy::default();
+        let failed = |err: MailSendError| RetryResult {
+            attempts: 1,
+            result: Err(err),
+        };
+        let smtp_err = |code| MailSendError::Smtp(LogicError::Code(Response::new(code, vec![])));
+
+        assert!(needs_attempt(
+            &failed(smtp_err(codes::SERVICE_UNAVAILABLE)),
+            policy
+        ));
+        assert!(!needs_attempt(
+            &failed(smtp_err(codes::MAILBOX_UNAVAILABLE)),
+            policy
+        ));
+        let io_err = || MailSendError::Io(std_io::ErrorKind::BrokenPipe.into());
+        assert!(needs_attempt(&failed(io_err()), policy));
+        let mut result = failed(io_err());
+        result.attempts = policy.max_attempts;
+        assert!(!needs_attempt(&result, policy));
+    }
+
+    #[test]
+    fn delay_is_doubled_for_each_retry() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            backoff: Duration::from_millis(100),
+        };
+        let result = |attempts| RetryResult {
+            attempts,
+            result: Ok(()),
+        };
+        assert_eq!(retry_delay(&result(0), policy), Duration::from_millis(0));
+        assert_eq!(retry_delay(&result(1), policy), Duration::from_millis(100));
+        assert_eq!(retry_delay(&result(3), policy), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn retries_connection_errors_up_to_max_attempts() {
+        let config = ConnectionBuilder::new_with_addr(
+            unused_local_addr(),
+            Domain::from_unchecked("localhost"),
+        )
+        .security(Security::Plaintext)
+        .build();
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_secs(0),
+        };
+        let mails = vec![
+            mail_request(Some("alice@example.test")),
+            mail_request(None),
+            mail_request(Some("carol@example.test")),
+        ];
+
+        let fut = send_batch_with_retry(mails, config, CTX.unwrap().clone(), policy);
+        let results = Runtime::new().unwrap().block_on(fut).unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].attempts, 3);
+        assert!(results[0].result.as_ref().unwrap_err().is_transient());
+        assert_eq!(results[1].attempts, 0);
+        assert!(!results[1].result.as_ref().unwrap_err().is_transient());
+        assert_eq!(results[2].attempts, 3);
+        for idx in &[0, 2] {
+            match results[*idx].result {
+                Err(MailSendError::Connecting(ConnectingFailed::Io(ref err))) => {
+                    assert_eq!(err.kind(), std_io::ErrorKind::ConnectionRefused)
+                }
+                ref other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn mails_are_not_retried_if_the_connection_broke_after_data() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = serve_dropping_after_data(listener);
+
+        let config = ConnectionBuilder::new_with_addr(addr, Domain::from_unchecked("localhost"))
+            .security(Security::Plaintext)
+            .build();
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_secs(0),
+        };
+        let mails = vec![mail_request(Some("alice@example.test"))];
+
+        let fut = send_batch_with_retry(mails, config, CTX.unwrap().clone(), policy);
+        let results = Runtime::new().unwrap().block_on(fut).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].attempts, 1);
+        assert!(results[0].result.as_ref().unwrap_err().is_transient());
+
+        // connect once more to stop the server
+        TcpStream::connect(addr)
+            .unwrap()
+            .write_all(b"QUIT\r\n")
+            .unwrap();
+        assert_eq!(server.join().unwrap(), 2);
+    }
+}
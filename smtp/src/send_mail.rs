@@ -1,20 +1,33 @@
 //! Module implementing mail sending using `new-tokio-smtp::send_mail`.
 
-use std::iter::once as one;
+use std::{io as std_io, iter::once as one};
 
 use futures::{
     future::{self, Either, Future},
     stream::{self, Stream},
 };
 
-use mail::Context;
+use mail::{Context, EncodableMail};
 use mail_internals::{encoder::EncodingBuffer, MailType};
 
 use new_tokio_smtp::{
-    send_mail as smtp, send_mail::MailEnvelop, Cmd, Connection, ConnectionConfig, SetupTls,
+    chain::{chain, OnError},
+    command,
+    error::MissingCapabilities,
+    send_mail as smtp,
+    send_mail::MailEnvelop,
+    Cmd, Connection, ConnectionConfig, EhloData, EsmtpKeyword, EsmtpValue, ExecFuture, Io,
+    ReversePath, SetupTls,
 };
 
-use {error::MailSendError, request::MailRequest};
+use {
+    error::MailSendError,
+    negotiate::{
+        check_mail_size, has_binary_bodies, negotiate_mail_type_for_encodable, EhloCaps,
+        On8BitUnsupported,
+    },
+    request::MailRequest,
+};
 
 /// Sends a given mail (request).
 ///
@@ -47,6 +60,256 @@ where
     fut
 }
 
+/// Sends a given mail (request) encoding it based on the servers capabilities.
+///
+/// Like `send` but the mail is only encoded after the connection was set
+/// up (including EHLO). The `MailType` used to encode the mail is chosen
+/// based on the capabilities the server advertised (see `negotiate_mail_type`).
+///
+/// If the mail contains `8bit` bodies but the server doesn't support
+/// `8BITMIME` the mail is either rejected or the bodies are re-encoded
+/// with `quoted-printable`/`base64`, depending on `on_8bit_unsupported`.
+///
+/// If the server supports the `SIZE` extension the size of the encoded
+/// mail is passed with the `MAIL FROM` command. If the mail exceeds the
+/// maximal message size advertised by the server it fails with
+/// `MailSendError::SizeLimitExceeded` before the mail transaction is
+/// started.
+///
+/// Mails with `binary` bodies are sent with the `BDAT` command (rfc3030)
+/// if the server supports `BINARYMIME` and `CHUNKING`, else they fail with
+/// `MailTypeNegotiationError::BinaryMimeNotSupported` (or the bodies are
+/// re-encoded, depending on `on_8bit_unsupported`).
+pub fn send_negotiated<A, S>(
+    mail: MailRequest,
+    conconf: ConnectionConfig<A, S>,
+    ctx: impl Context,
+    on_8bit_unsupported: On8BitUnsupported,
+) -> impl Future<Item = (), Error = MailSendError>
+where
+    A: Cmd,
+    S: SetupTls,
+{
+    let (mail, envelop_data) = match mail.into_mail_with_envelop() {
+        Ok(pair) => pair,
+        Err(e) => return Either::A(future::err(e.into())),
+    };
+
+    let fut = mail
+        .into_encodable_mail(ctx)
+        .map_err(MailSendError::from)
+        .and_then(move |enc_mail| {
+            Connection::connect(conconf)
+                .map_err(MailSendError::from)
+                .map(move |con| (con, enc_mail))
+        })
+        .and_then(move |(con, enc_mail)| {
+            let caps = con
+                .ehlo_data()
+                .map(EhloCaps::from_ehlo_data)
+                .unwrap_or_default();
+
+            let envelop = encode_for_server(&caps, enc_mail, on_8bit_unsupported).and_then(
+                |(smtp_mail, binary)| {
+                    check_mail_size(&caps, smtp_mail.raw_data().len() as u64)?;
+                    Ok((smtp::MailEnvelop::from((smtp_mail, envelop_data)), binary))
+                },
+            );
+
+            match envelop {
+                Err(err) => Either::A(con.quit().then(move |_| Err(err))),
+                Ok((envelop, binary)) => {
+                    let params = size_params(&caps, &envelop);
+                    Either::B(
+                        send_mail_with_params(con, envelop, params, binary)
+                            .map_err(MailSendError::from)
+                            .and_then(|(con, result)| {
+                                con.quit().map_err(MailSendError::from).and_then(|_| {
+                                    result.map_err(|(_idx, err)| MailSendError::from(err))
+                                })
+                            }),
+                    )
+                }
+            }
+        });
+
+    Either::B(fut)
+}
+
+/// Encodes the mail with the `MailType` negotiated for the given capabilities.
+///
+/// Returns the encoded mail and if it contains `binary` bodies, in which
+/// case it has to be sent with `BDAT` (the capabilities needed for this
+/// are checked by the negotiation).
+fn encode_for_server(
+    caps: &EhloCaps,
+    mut enc_mail: EncodableMail,
+    on_8bit_unsupported: On8BitUnsupported,
+) -> Result<(smtp::Mail, bool), MailSendError> {
+    let mail_type = negotiate_mail_type_for_encodable(caps, &mut enc_mail, on_8bit_unsupported)?;
+    let binary = has_binary_bodies(&enc_mail);
+    let requirement = match mail_type {
+        MailType::Ascii => smtp::EncodingRequirement::None,
+        MailType::Mime8BitEnabled => smtp::EncodingRequirement::Mime8bit,
+        MailType::Internationalized => smtp::EncodingRequirement::Smtputf8,
+    };
+
+    let mut buffer = EncodingBuffer::new(mail_type);
+    if binary {
+        buffer = buffer.with_binary_bodies();
+    }
+    enc_mail.encode(&mut buffer)?;
+
+    let vec_buffer: Vec<_> = buffer.into();
+    Ok((smtp::Mail::new(requirement, vec_buffer), binary))
+}
+
+/// Returns the `SIZE=<bytes>` `MAIL FROM` parameter (rfc1870) if the server supports it.
+fn size_params(caps: &EhloCaps, envelop: &MailEnvelop) -> command::Params {
+    let mut params = command::Params::new();
+    if caps.size {
+        let size = envelop.mail().raw_data().len();
+        params.insert(
+            EsmtpKeyword::from_unchecked("SIZE"),
+            Some(EsmtpValue::from_unchecked(size.to_string())),
+        );
+    }
+    params
+}
+
+/// An I/O error which happened while sending a mail through a connection.
+#[derive(Debug)]
+pub(crate) struct SendIoError {
+    pub(crate) error: std_io::Error,
+
+    /// True if the error happened after the `DATA` command was sent.
+    ///
+    /// In this case the server might have accepted the mail.
+    pub(crate) after_data: bool,
+}
+
+impl From<SendIoError> for MailSendError {
+    fn from(err: SendIoError) -> Self {
+        MailSendError::Io(err.error)
+    }
+}
+
+/// Sends the mail through the connection adding `params` to the `MAIL FROM` command.
+///
+/// This is like `Connection::send_mail` except that the given parameters
+/// are passed to the `command::Mail` starting the mail transaction and
+/// that I/O errors tell if they happened after the `DATA` command was sent.
+///
+/// If `binary` is true the mail is sent with `BODY=BINARYMIME` using the
+/// `BDAT` command instead of `DATA` (rfc3030).
+pub(crate) fn send_mail_with_params(
+    con: Connection,
+    envelop: MailEnvelop,
+    mut params: command::Params,
+    binary: bool,
+) -> impl Future<Item = (Connection, smtp::MailSendResult), Error = SendIoError> {
+    let use_smtputf8 = envelop.needs_smtputf8();
+    // `BINARYMIME` covers `8bit` bodies, too
+    let missing_capabilities = (use_smtputf8 && !con.has_capability("SMTPUTF8"))
+        || (!use_smtputf8
+            && !binary
+            && envelop.mail().encoding_requirement() == smtp::EncodingRequirement::Mime8bit
+            && !con.has_capability("8BITMIME"));
+
+    if missing_capabilities {
+        // `send_mail` reports missing capabilities without starting a mail transaction
+        return Either::A(con.send_mail(envelop).map_err(|error| SendIoError {
+            error,
+            after_data: false,
+        }));
+    }
+
+    let (mail, smtp::EnvelopData { from, to }) = envelop.into();
+
+    let reverse_path = from
+        .map(ReversePath::from)
+        .unwrap_or_else(|| ReversePath::from_unchecked(""));
+    if binary {
+        params.insert(
+            EsmtpKeyword::from_unchecked("BODY"),
+            Some(EsmtpValue::from_unchecked("BINARYMIME")),
+        );
+    }
+    let mut mail_cmd = command::Mail::new(reverse_path);
+    mail_cmd.params = if use_smtputf8 {
+        command::params_with_smtputf8(params)
+    } else {
+        params
+    };
+
+    let mut cmd_chain = vec![mail_cmd.boxed()];
+    for to in to.into_iter() {
+        cmd_chain.push(command::Recipient::new(to.into()).boxed());
+    }
+    let data_idx = cmd_chain.len();
+    let data = if binary {
+        BdatLast::new(mail.into_raw_data()).boxed()
+    } else {
+        command::Data::from_buf(mail.into_raw_data()).boxed()
+    };
+
+    let fut = chain(con, cmd_chain, OnError::StopAndReset)
+        .map_err(|error| SendIoError {
+            error,
+            after_data: false,
+        })
+        .and_then(move |(con, result)| match result {
+            Err(err) => Either::A(future::ok((con, Err(err)))),
+            Ok(()) => Either::B(
+                chain(con, vec![data], OnError::StopAndReset)
+                    .map(move |(con, result)| (con, result.map_err(|(_, err)| (data_idx, err))))
+                    .map_err(|error| SendIoError {
+                        error,
+                        after_data: true,
+                    }),
+            ),
+        });
+
+    Either::B(fut)
+}
+
+/// The `BDAT` command (rfc3030) sending the whole mail as a single, last chunk.
+///
+/// Unlike `DATA` the mail is sent as is (i.e. without dot-stashing and
+/// without a terminating `.` line), which allows sending `binary` bodies.
+struct BdatLast<B> {
+    data: B,
+}
+
+impl<B> BdatLast<B> {
+    fn new(data: B) -> Self {
+        BdatLast { data }
+    }
+}
+
+impl<B> Cmd for BdatLast<B>
+where
+    B: AsRef<[u8]> + Send + 'static,
+{
+    fn check_cmd_availability(&self, caps: Option<&EhloData>) -> Result<(), MissingCapabilities> {
+        match caps {
+            Some(caps) if caps.has_capability("CHUNKING") => Ok(()),
+            _ => Err(MissingCapabilities::new_from_unchecked("CHUNKING")),
+        }
+    }
+
+    fn exec(self, mut io: Io) -> ExecFuture {
+        let data = self.data.as_ref();
+        let command = format!("BDAT {} LAST\r\n", data.len());
+        {
+            let buffer = io.out_buffer(command.len() + data.len());
+            buffer.extend_from_slice(command.as_bytes());
+            buffer.extend_from_slice(data);
+        }
+        Box::new(io.flush().and_then(Io::parse_response))
+    }
+}
+
 /// Sends a batch of mails to a server.
 ///
 /// - This will use the given context to encode all mails.
@@ -105,6 +368,11 @@ where
 /// then take a connection, test it, use the mail envelops with `new-tokio-smtp`'s
 /// `SendAllMails` stream with a `on_completion` handler which places it
 /// back in the pool.
+///
+/// Mails containing bodies with the `binary` transfer encoding can not be
+/// encoded by this function and will fail with an encoding error, as
+/// sending them requires the `BINARYMIME` and `CHUNKING` smtp extensions
+/// and the `BDAT` command (see `send_negotiated`).
 pub fn encode<C>(
     request: MailRequest,
     ctx: C,
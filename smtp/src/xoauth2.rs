@@ -0,0 +1,297 @@
+//! Module implementing the `AUTH XOAUTH2` smtp authentication.
+
+use std::{
+    error::Error as ErrorTrait,
+    fmt::{self, Debug, Display},
+};
+
+use futures::future::{self, Either, Future};
+
+use mail_internals::bind::base64;
+use new_tokio_smtp::{
+    error::{LogicError, MissingCapabilities},
+    future_ext::ResultWithContextExt,
+    Capability, Cmd, EhloData, EsmtpKeyword, ExecFuture, Io, Response,
+};
+
+/// `AUTH XOAUTH2` smtp authentication using an OAuth2 bearer access token.
+///
+/// This is the authentication method required by some providers
+/// (e.g. Gmail and Outlook) which don't allow `AUTH PLAIN`.
+///
+/// If the server rejects the access token (e.g. because it expired) it
+/// sends a (base64 encoded) JSON challenge describing the error. In that
+/// case the command fails with a `LogicError::Custom` containing a
+/// `XOauth2Error`, which can be accessed with `XOauth2Error::from_logic_error`
+/// or `MailSendError::xoauth2_error`.
+#[derive(Clone)]
+pub struct XOauth2 {
+    username: String,
+    access_token: String,
+}
+
+impl XOauth2 {
+    /// Creates a new `AUTH XOAUTH2` command from a username and a bearer access token.
+    ///
+    /// # Error
+    ///
+    /// Fails if the username or the access token contains a `\x01` byte,
+    /// which is used as separator in the initial response.
+    pub fn new<I1, I2>(username: I1, access_token: I2) -> Result<Self, SeparatorCodePointError>
+    where
+        I1: Into<String>,
+        I2: Into<String>,
+    {
+        let username = username.into();
+        let access_token = access_token.into();
+        if username.contains('\x01') || access_token.contains('\x01') {
+            return Err(SeparatorCodePointError);
+        }
+        Ok(XOauth2 {
+            username,
+            access_token,
+        })
+    }
+
+    /// Returns the username which will be used.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    //intentionally no fn access_token(&self)!
+
+    /// Returns the base64 encoded initial response sent with the `AUTH XOAUTH2` command.
+    fn initial_response(&self) -> String {
+        let response = format!(
+            "user={}\x01auth=Bearer {}\x01\x01",
+            self.username, self.access_token
+        );
+        base64::normal_encode_with_line_length(response, 0).into()
+    }
+}
+
+impl Debug for XOauth2 {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.debug_struct("XOauth2")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+impl Cmd for XOauth2 {
+    fn check_cmd_availability(&self, caps: Option<&EhloData>) -> Result<(), MissingCapabilities> {
+        let supported = caps
+            .and_then(|ehlo_data| ehlo_data.get_capability_params("AUTH"))
+            .map(|methods| {
+                methods
+                    .iter()
+                    .any(|method| method.as_str().eq_ignore_ascii_case("XOAUTH2"))
+            })
+            .unwrap_or(false);
+
+        if supported {
+            Ok(())
+        } else {
+            let capability = Capability::from(EsmtpKeyword::from_unchecked("AUTH"));
+            Err(MissingCapabilities::new(vec![capability]))
+        }
+    }
+
+    fn exec(self, io: Io) -> ExecFuture {
+        let initial_response = self.initial_response();
+
+        let fut = io
+            .exec_simple_cmd(&["AUTH XOAUTH2 ", initial_response.as_str()])
+            .ctx_and_then(|io: Io, response: Response| {
+                if !response.code().is_intermediate() {
+                    let result = if response.code().is_positive() {
+                        Ok(response)
+                    } else {
+                        Err(LogicError::UnexpectedCode(response))
+                    };
+                    return Either::A(future::ok((io, result)));
+                }
+
+                // the server sent the error challenge, it expects an empty
+                // response after which it fails the command
+                let challenge = decode_challenge(&response);
+                let fut = io
+                    .flush_line_from_parts(&[""])
+                    .and_then(Io::parse_response)
+                    .map(move |(io, result)| {
+                        let response = match result {
+                            Err(LogicError::Code(response)) => response,
+                            Ok(response) => response,
+                            Err(err) => return (io, Err(err)),
+                        };
+                        let err = XOauth2Error {
+                            challenge,
+                            response,
+                        };
+                        (io, Err(LogicError::Custom(Box::new(err))))
+                    });
+
+                Either::B(fut)
+            });
+
+        Box::new(fut)
+    }
+}
+
+fn decode_challenge(response: &Response) -> Option<String> {
+    let line = response.msg().first()?;
+    let decoded = base64::normal_decode(line.trim()).ok()?;
+    String::from_utf8(decoded).ok()
+}
+
+/// Error returned if the server rejected the `AUTH XOAUTH2` authentication.
+#[derive(Debug)]
+pub struct XOauth2Error {
+    challenge: Option<String>,
+    response: Response,
+}
+
+impl XOauth2Error {
+    /// Returns the `XOauth2Error` contained in the `LogicError`, if there is one.
+    pub fn from_logic_error(err: &LogicError) -> Option<&XOauth2Error> {
+        match *err {
+            LogicError::Custom(ref err) => err.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the (base64 decoded) challenge the server sent.
+    ///
+    /// This is normally a JSON object like
+    /// `{"status":"401","schemes":"bearer","scope":"https://mail.google.com/"}`,
+    /// it's `None` if the challenge wasn't valid base64 encoded utf-8.
+    pub fn challenge(&self) -> Option<&str> {
+        self.challenge.as_deref()
+    }
+
+    /// Returns the value of the `status` field of the challenge.
+    ///
+    /// Servers use a HTTP status code like `"401"` if the access token is
+    /// invalid or expired, in which case it should be refreshed.
+    pub fn status(&self) -> Option<&str> {
+        let challenge = self.challenge()?;
+        let rest = &challenge[challenge.find("\"status\"")? + "\"status\"".len()..];
+        let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+        let rest = rest.strip_prefix('"')?;
+        rest.find('"').map(|end| &rest[..end])
+    }
+
+    /// Returns the final (error) response of the server.
+    pub fn response(&self) -> &Response {
+        &self.response
+    }
+}
+
+impl Display for XOauth2Error {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "XOAUTH2 authentication failed")?;
+        if let Some(challenge) = self.challenge() {
+            write!(fter, ": {}", challenge)?;
+        }
+        Ok(())
+    }
+}
+
+impl ErrorTrait for XOauth2Error {}
+
+/// Error returned if the username or access token passed to `XOauth2` contained a `\x01` byte.
+#[derive(Copy, Clone, Debug)]
+pub struct SeparatorCodePointError;
+
+impl Display for SeparatorCodePointError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "input (username/access token) contained \\x01 byte")
+    }
+}
+
+impl ErrorTrait for SeparatorCodePointError {}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use new_tokio_smtp::{response::codes, Domain, EhloParam};
+
+    use super::*;
+
+    fn error_with_challenge(challenge: &str) -> XOauth2Error {
+        let response = Response::new(codes::TRANSACTION_FAILED, vec![]);
+        XOauth2Error {
+            challenge: Some(challenge.to_owned()),
+            response,
+        }
+    }
+
+    #[test]
+    fn initial_response_has_the_xoauth2_format() {
+        let cmd = XOauth2::new("someuser@example.com", "ya29.vF9dft4qmTc2Nvb3RlckBh").unwrap();
+        let decoded = base64::normal_decode(cmd.initial_response()).unwrap();
+        assert_eq!(
+            decoded,
+            &b"user=someuser@example.com\x01auth=Bearer ya29.vF9dft4qmTc2Nvb3RlckBh\x01\x01"[..]
+        );
+        assert!(!cmd.initial_response().contains('\n'));
+    }
+
+    #[test]
+    fn rejects_the_separator_in_the_input() {
+        assert!(XOauth2::new("user\x01", "token").is_err());
+        assert!(XOauth2::new("user", "tok\x01en").is_err());
+    }
+
+    #[test]
+    fn debug_output_does_not_contain_the_token() {
+        let cmd = XOauth2::new("user", "secret-token").unwrap();
+        assert!(!format!("{:?}", cmd).contains("secret-token"));
+    }
+
+    #[test]
+    fn requires_the_xoauth2_auth_capability() {
+        let cmd = XOauth2::new("user", "token").unwrap();
+        let ehlo_data = |methods: &[&str]| {
+            let mut map = HashMap::new();
+            map.insert(
+                Capability::from(EsmtpKeyword::from_unchecked("AUTH")),
+                methods
+                    .iter()
+                    .map(|method| method.parse::<EhloParam>().unwrap())
+                    .collect(),
+            );
+            EhloData::from((Domain::from_unchecked("mx.example.test"), map))
+        };
+
+        assert!(cmd
+            .check_cmd_availability(Some(&ehlo_data(&["PLAIN", "xoauth2"])))
+            .is_ok());
+        assert!(cmd
+            .check_cmd_availability(Some(&ehlo_data(&["PLAIN"])))
+            .is_err());
+        assert!(cmd.check_cmd_availability(None).is_err());
+    }
+
+    #[test]
+    fn extracts_the_status_from_the_challenge() {
+        let err = error_with_challenge(
+            r#"{"status":"401","schemes":"bearer","scope":"https://mail.google.com/"}"#,
+        );
+        assert_eq!(err.status(), Some("401"));
+
+        let err = error_with_challenge(r#"{ "status" : "400" }"#);
+        assert_eq!(err.status(), Some("400"));
+
+        let err = error_with_challenge(r#"{"schemes":"bearer"}"#);
+        assert_eq!(err.status(), None);
+    }
+
+    #[test]
+    fn can_be_accessed_through_the_logic_error() {
+        let err = LogicError::Custom(Box::new(error_with_challenge(r#"{"status":"401"}"#)));
+        let xoauth2_err = XOauth2Error::from_logic_error(&err).unwrap();
+        assert_eq!(xoauth2_err.status(), Some("401"));
+    }
+}
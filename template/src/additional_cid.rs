@@ -5,6 +5,8 @@ use serde::{Serialize, Serializer};
 use mail_core::Resource;
 use mail_headers::header_components::ContentId;
 
+use crate::cid_references::cid_references;
+
 pub struct AdditionalCIds<'a> {
     additional_resources: &'a [&'a HashMap<String, Resource>],
 }
@@ -41,6 +43,40 @@ impl<'a> AdditionalCIds<'a> {
         }
         None
     }
+
+    /// Returns the resource with the given content id.
+    ///
+    /// The content id can be given with or without a `cid:` prefix,
+    /// e.g. as referenced in a rendered html body. If multiple resources
+    /// have the content id the first one is returned.
+    pub fn resolve(&self, cid: &str) -> Option<&Resource> {
+        let cid = strip_cid_scheme(cid);
+        self.additional_resources
+            .iter()
+            .flat_map(|map| map.values())
+            .find(|resource| resource.content_id().map(ContentId::as_str) == Some(cid))
+    }
+
+    /// Returns all `cid:` references in given (rendered) body which can not be resolved.
+    ///
+    /// This can be used to detect typos in templates which would lead
+    /// to e.g. broken images, see `AdditionalCIds::resolve`.
+    pub fn unresolved_cid_references(&self, body: &str) -> HashSet<String> {
+        cid_references(body)
+            .into_iter()
+            .filter(|reference| self.resolve(reference).is_none())
+            .map(ToOwned::to_owned)
+            .collect()
+    }
+}
+
+fn strip_cid_scheme(cid: &str) -> &str {
+    let scheme = cid.get(.."cid:".len()).unwrap_or("");
+    if scheme.eq_ignore_ascii_case("cid:") {
+        &cid["cid:".len()..]
+    } else {
+        cid
+    }
 }
 
 impl<'a> Serialize for AdditionalCIds<'a> {
@@ -65,3 +101,45 @@ impl<'a> Serialize for AdditionalCIds<'a> {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use mail_core::default_impl::simple_context;
+
+    use super::*;
+
+    fn logo_and_ids() -> (HashMap<String, Resource>, String) {
+        let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+        let mut resources = HashMap::new();
+        resources.insert("logo".to_owned(), Resource::plain_text("logo", &ctx));
+        let cid = resources["logo"].content_id().unwrap().as_str().to_owned();
+        (resources, cid)
+    }
+
+    #[test]
+    fn resolves_content_ids_with_and_without_scheme() {
+        let (resources, cid) = logo_and_ids();
+        let maps = [&resources];
+        let cids = AdditionalCIds::new(&maps);
+
+        assert!(cids.resolve(&cid).is_some());
+        assert!(cids.resolve(&format!("CID:{}", cid)).is_some());
+        assert!(cids.resolve("logo").is_none());
+        assert!(cids.resolve("cid:").is_none());
+    }
+
+    #[test]
+    fn returns_unresolved_references() {
+        let (resources, cid) = logo_and_ids();
+        let maps = [&resources];
+        let cids = AdditionalCIds::new(&maps);
+
+        let html = format!(
+            r#"<img src="cid:{}"><img src="cid:lgo"><img src="cid:lgo">"#,
+            cid
+        );
+        let unresolved = cids.unresolved_cid_references(&html);
+        assert_eq!(unresolved.len(), 1);
+        assert!(unresolved.contains("lgo"));
+    }
+}
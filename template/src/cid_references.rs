@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use mail_core::Resource;
+
+/// Returns all `cid:` references in `html` which do not match any of the given content ids.
+///
+/// Every reference is only returned once, in the order they first
+/// appear in the html.
+pub(crate) fn find_dangling_cid_references(html: &str, known_cids: &[&str]) -> Vec<String> {
+    let mut dangling = Vec::new();
+    for reference in cid_references(html) {
+        let is_known = known_cids.contains(&reference);
+        if !is_known && !dangling.iter().any(|known: &String| known == reference) {
+            dangling.push(reference.to_owned());
+        }
+    }
+    dangling
+}
+
+/// Returns the content ids of all resources in the given maps.
+///
+/// Resources which have no content id (i.e. are not loaded) are skipped.
+pub(crate) fn content_ids_of<'a>(maps: &[&'a HashMap<String, Resource>]) -> Vec<&'a str> {
+    maps.iter()
+        .flat_map(|map| map.values())
+        .filter_map(|resource| resource.content_id())
+        .map(|cid| cid.as_str())
+        .collect()
+}
+
+pub(crate) fn cid_references(html: &str) -> Vec<&str> {
+    // `to_ascii_lowercase` doesn't change byte offsets
+    let lowercase = html.to_ascii_lowercase();
+    let mut references = Vec::new();
+    let mut search_start = 0;
+    while let Some(found) = lowercase[search_start..].find("cid:") {
+        let start = search_start + found;
+        let ref_start = start + "cid:".len();
+        search_start = ref_start;
+
+        // e.g. don't treat `acid:` as reference
+        let prev_char = html[..start].chars().next_back();
+        if let Some(ch) = prev_char {
+            if ch.is_alphanumeric() {
+                continue;
+            }
+        }
+
+        let ref_len = html[ref_start..]
+            .find(|ch: char| ch.is_whitespace() || "\"'<>()".contains(ch))
+            .unwrap_or(html.len() - ref_start);
+        if ref_len > 0 {
+            references.push(&html[ref_start..ref_start + ref_len]);
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_references_in_attributes_and_css() {
+        let html = concat!(
+            r#"<img src="cid:a@b.c"><img src='CID:d@e.f'>"#,
+            r#"<div style="background: url(cid:g@h.i)">acid:no</div>"#
+        );
+        assert_eq!(cid_references(html), vec!["a@b.c", "d@e.f", "g@h.i"]);
+    }
+
+    #[test]
+    fn reports_each_dangling_reference_once() {
+        let html = r#"<img src="cid:logo"><img src="cid:a@b.c"><img src="cid:logo">"#;
+        let dangling = find_dangling_cid_references(html, &["a@b.c"]);
+        assert_eq!(dangling, vec!["logo".to_owned()]);
+    }
+}
@@ -1,3 +1,190 @@
 //! Module re-exporting all error types.
+use std::fmt::{self, Display};
+
+use failure::{Error, Fail};
 
 pub use crate::path_rebase::UnsupportedPathError;
+
+/// Error returned if a template engine failed to render a template.
+///
+/// It wraps the error returned by the template engine and adds the
+/// id of the template which failed to render as well as the location
+/// in the template the error originated from, if the template engine
+/// provides it.
+#[derive(Debug)]
+pub struct TemplateRenderError {
+    template_id: String,
+    line: Option<usize>,
+    column: Option<usize>,
+    cause: Error,
+}
+
+impl TemplateRenderError {
+    /// Create a new instance from the id of the failed template and the underlying error.
+    pub fn new(template_id: impl Into<String>, cause: impl Into<Error>) -> Self {
+        TemplateRenderError {
+            template_id: template_id.into(),
+            line: None,
+            column: None,
+            cause: cause.into(),
+        }
+    }
+
+    /// Sets the line/column in the template at which the error occurred and returns self.
+    pub fn with_location(mut self, line: Option<usize>, column: Option<usize>) -> Self {
+        self.line = line;
+        self.column = column;
+        self
+    }
+
+    /// The id of the template which failed to render.
+    pub fn template_id(&self) -> &str {
+        &self.template_id
+    }
+
+    /// The line in the template at which the error occurred, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// The column in the template at which the error occurred, if known.
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+}
+
+impl Display for TemplateRenderError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "rendering template {:?} failed", self.template_id)?;
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(fter, " at line {}, column {}", line, column)?,
+            (Some(line), None) => write!(fter, " at line {}", line)?,
+            _ => {}
+        }
+        write!(fter, ": {}", self.cause)
+    }
+}
+
+impl Fail for TemplateRenderError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        Some(self.cause.as_fail())
+    }
+}
+
+/// Error returned if a rendered html body references content ids which are not embedded.
+///
+/// This is only returned if validating `cid:` references was enabled
+/// for the template using `Template::set_cid_reference_validation`.
+#[derive(Debug)]
+pub struct DanglingCIdReferencesError {
+    template_name: String,
+    dangling_cids: Vec<String>,
+}
+
+impl DanglingCIdReferencesError {
+    /// Create a new instance from the template name and the dangling references.
+    pub fn new(template_name: impl Into<String>, dangling_cids: Vec<String>) -> Self {
+        DanglingCIdReferencesError {
+            template_name: template_name.into(),
+            dangling_cids,
+        }
+    }
+
+    /// The name of the template which rendered the dangling references.
+    pub fn template_name(&self) -> &str {
+        &self.template_name
+    }
+
+    /// The referenced content ids (without the `cid:` prefix) which are not embedded.
+    pub fn dangling_cids(&self) -> &[String] {
+        &self.dangling_cids
+    }
+}
+
+impl Display for DanglingCIdReferencesError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fter,
+            "template {:?} references content ids which are not embedded: {:?}",
+            self.template_name, self.dangling_cids
+        )
+    }
+}
+
+impl Fail for DanglingCIdReferencesError {}
+
+/// Error returned if a template is created from pre-loaded parts but an embedding is not loaded.
+///
+/// See `Template::from_loaded_parts`.
+#[derive(Debug)]
+pub struct UnloadedEmbeddingError {
+    template_name: String,
+    embedding_name: String,
+}
+
+impl UnloadedEmbeddingError {
+    /// Create a new instance from the template name and the name of the embedding.
+    pub fn new(template_name: impl Into<String>, embedding_name: impl Into<String>) -> Self {
+        UnloadedEmbeddingError {
+            template_name: template_name.into(),
+            embedding_name: embedding_name.into(),
+        }
+    }
+
+    /// The name of the template.
+    pub fn template_name(&self) -> &str {
+        &self.template_name
+    }
+
+    /// The name of the embedding which is not loaded.
+    pub fn embedding_name(&self) -> &str {
+        &self.embedding_name
+    }
+}
+
+impl Display for UnloadedEmbeddingError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fter,
+            "embedding {:?} of template {:?} is not loaded",
+            self.embedding_name, self.template_name
+        )
+    }
+}
+
+impl Fail for UnloadedEmbeddingError {}
+
+/// Error returned if all bodies of a template rendered to empty output and were skipped.
+///
+/// This is only returned if skipping empty bodies was enabled for
+/// the template using `Template::set_skip_empty_bodies`.
+#[derive(Debug)]
+pub struct AllBodiesEmptyError {
+    template_name: String,
+}
+
+impl AllBodiesEmptyError {
+    /// Create a new instance from the template name.
+    pub fn new(template_name: impl Into<String>) -> Self {
+        AllBodiesEmptyError {
+            template_name: template_name.into(),
+        }
+    }
+
+    /// The name of the template whose bodies all rendered to empty output.
+    pub fn template_name(&self) -> &str {
+        &self.template_name
+    }
+}
+
+impl Display for AllBodiesEmptyError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fter,
+            "all bodies of template {:?} rendered to empty output",
+            self.template_name
+        )
+    }
+}
+
+impl Fail for AllBodiesEmptyError {}
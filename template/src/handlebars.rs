@@ -1,11 +1,18 @@
 #![allow(clippy::new_without_default)]
 
+use std::{cell::RefCell, collections::HashMap};
+
 use failure::Error;
+use futures::Future;
 use hbs;
 use serde::Serialize;
 
+use mail_core::{Context, Resource, Source};
+use mail_headers::header_components::MediaType;
+
 use super::{
-    serde_impl, AdditionalCIds, BodyTemplate, TemplateEngine, TemplateEngineCanHandleData,
+    error::TemplateRenderError, serde_impl, AdditionalCIds, BodyTemplate, RenderedBody,
+    TemplateEngine, TemplateEngineCanHandleData,
 };
 
 //TODO[FEAT] add custom engine config section to loading
@@ -17,6 +24,15 @@ use super::{
 //
 // Just specific to each engine.
 
+/// Bindings for the handlebars template engine.
+///
+/// Besides the data (`data`) and the content ids of the embeddings (`cids`)
+/// bodies can use the `embed` helper to embed a file which is not declared
+/// as embedding of the template, e.g. `<img src="{{embed "logo.png"}}">`.
+/// The file is loaded through the context (like `Resource::from_path`)
+/// while rendering, added as inline embedding of the body and the helper
+/// is replaced with its `cid:` url. As loading blocks until the file is
+/// loaded, rendering must not happen on the thread pool of the context.
 pub struct Handlebars {
     inner: hbs::Handlebars,
     name_counter: usize,
@@ -24,8 +40,10 @@ pub struct Handlebars {
 
 impl Handlebars {
     pub fn new() -> Self {
+        let mut inner = hbs::Handlebars::new();
+        inner.register_helper("embed", Box::new(embed_helper));
         Handlebars {
-            inner: hbs::Handlebars::new(),
+            inner,
             name_counter: 0,
         }
     }
@@ -42,6 +60,28 @@ impl Handlebars {
         &mut self.inner
     }
 
+    /// Loads a body template from a string instead of a file.
+    ///
+    /// The returned body has no inline embeddings and uses the default
+    /// transfer encoding hint, both can be changed through its public
+    /// fields. It can be used to create a template with
+    /// `Template::from_loaded_parts`.
+    pub fn load_body_template_from_str(
+        &mut self,
+        template_string: &str,
+        media_type: MediaType,
+    ) -> Result<BodyTemplate<Self>, Error> {
+        let name = self.next_body_template_name();
+        self.inner
+            .register_template_string(&name, template_string)?;
+        Ok(BodyTemplate {
+            template_id: name,
+            media_type,
+            transfer_encoding_hint: Default::default(),
+            inline_embeddings: Default::default(),
+        })
+    }
+
     fn next_body_template_name(&mut self) -> String {
         let name = format!("body_{}", self.name_counter);
         self.name_counter += 1;
@@ -61,6 +101,7 @@ impl TemplateEngine for Handlebars {
             path,
             embeddings,
             media_type,
+            transfer_encoding,
         } = tmpl;
 
         let name = self.next_body_template_name();
@@ -86,6 +127,7 @@ impl TemplateEngine for Handlebars {
         Ok(BodyTemplate {
             template_id: name,
             media_type,
+            transfer_encoding_hint: transfer_encoding.unwrap_or_default(),
             inline_embeddings: embeddings,
         })
     }
@@ -111,13 +153,48 @@ where
         data: &'r D,
         additional_cids: AdditionalCIds<'r>,
     ) -> Result<String, Error> {
-        Ok(self.inner.render(
+        let res = self.inner.render(
             id,
             &SerHelper {
                 data,
                 cids: additional_cids,
             },
-        )?)
+        );
+
+        res.map_err(|err| {
+            let (line, column) = (err.line_no, err.column_no);
+            TemplateRenderError::new(id.as_str(), err)
+                .with_location(line, column)
+                .into()
+        })
+    }
+
+    fn render_body<'r>(
+        &'r self,
+        id: &'r Self::Id,
+        data: &'r D,
+        additional_cids: AdditionalCIds<'r>,
+        ctx: &impl Context,
+    ) -> Result<RenderedBody, Error> {
+        let ctx = ctx.clone();
+        let collector = EmbeddingCollector {
+            load: Box::new(move |path| {
+                let loaded = ctx.load_resource(&Source::from_path(path)).wait()?;
+                Ok(loaded.into_resource())
+            }),
+            embeddings: HashMap::new(),
+        };
+
+        let outer = EMBEDDING_COLLECTOR.with(|cell| cell.replace(Some(collector)));
+        let res = self.render(id, data, additional_cids);
+        let collector = EMBEDDING_COLLECTOR
+            .with(|cell| cell.replace(outer))
+            .expect("[BUG] embedding collector removed while rendering");
+
+        Ok(RenderedBody {
+            body: res?,
+            inline_embeddings: collector.embeddings,
+        })
     }
 }
 
@@ -126,3 +203,253 @@ struct SerHelper<'r, D: 'r> {
     data: &'r D,
     cids: AdditionalCIds<'r>,
 }
+
+thread_local! {
+    /// Collects the embeddings of the `embed` helper for the body currently rendered.
+    static EMBEDDING_COLLECTOR: RefCell<Option<EmbeddingCollector>> = const { RefCell::new(None) };
+}
+
+/// Function loading the file at the given path as resource.
+type LoadFn = Box<dyn Fn(&str) -> Result<Resource, Error>>;
+
+struct EmbeddingCollector {
+    load: LoadFn,
+    embeddings: HashMap<String, Resource>,
+}
+
+impl EmbeddingCollector {
+    /// Loads the file at path (if not already loaded) and returns its content id.
+    fn embed(&mut self, path: &str) -> Result<String, hbs::RenderError> {
+        if !self.embeddings.contains_key(path) {
+            let resource = (self.load)(path).map_err(|err| {
+                hbs::RenderError::new(format!("loading embedding {:?} failed: {}", path, err))
+            })?;
+            self.embeddings.insert(path.to_owned(), resource);
+        }
+
+        let cid = self.embeddings[path]
+            .content_id()
+            .ok_or_else(|| hbs::RenderError::new(format!("embedding {:?} is not loaded", path)))?;
+        Ok(cid.as_str().to_owned())
+    }
+}
+
+fn embed_helper(
+    helper: &hbs::Helper,
+    _: &hbs::Handlebars,
+    _: &hbs::Context,
+    _: &mut hbs::RenderContext,
+    out: &mut dyn hbs::Output,
+) -> hbs::HelperResult {
+    let path = helper
+        .param(0)
+        .and_then(|param| param.value().as_str())
+        .ok_or_else(|| hbs::RenderError::new("embed helper expects a path as parameter"))?;
+
+    let cid = EMBEDDING_COLLECTOR.with(|cell| match *cell.borrow_mut() {
+        Some(ref mut collector) => collector.embed(path),
+        None => Err(hbs::RenderError::new(
+            "embed helper can only be used in body templates",
+        )),
+    })?;
+
+    out.write("cid:")?;
+    out.write(&cid)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, env, fs, process};
+
+    use mail_core::{default_impl::simple_context, Resource};
+    use vec1::Vec1;
+
+    use super::*;
+    use crate::{
+        error::{AllBodiesEmptyError, UnloadedEmbeddingError},
+        AdditionalCIds, LoadedTemplateData, Template, TemplateExt,
+    };
+
+    #[derive(Serialize)]
+    struct Data {
+        name: &'static str,
+    }
+
+    #[test]
+    fn render_error_names_the_template() {
+        let mut engine = Handlebars::new();
+        engine.inner_mut().set_strict_mode(true);
+        let id = engine
+            .load_subject_template("Hy {{data.not_a_field}}".to_owned())
+            .unwrap();
+
+        let err = engine
+            .render(&id, &Data { name: "Ferris" }, AdditionalCIds::new(&[]))
+            .unwrap_err();
+
+        let err = err
+            .downcast::<TemplateRenderError>()
+            .expect("render error to be a TemplateRenderError");
+
+        assert_eq!(err.template_id(), "subject");
+        assert!(err.to_string().contains("\"subject\""));
+    }
+
+    #[test]
+    fn templates_can_be_created_from_strings() {
+        let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+        let mut engine = Handlebars::new();
+        let body = engine
+            .load_body_template_from_str(
+                r#"<p>Hy {{data.name}}</p><img src="cid:{{cids.logo}}">"#,
+                "text/html; charset=utf-8".parse().unwrap(),
+            )
+            .unwrap();
+        let mut embeddings = HashMap::new();
+        embeddings.insert("logo".to_owned(), Resource::plain_text("logo", &ctx));
+
+        let mut template = Template::from_loaded_parts(
+            "in-memory",
+            engine,
+            "Hy {{data.name}}",
+            Vec1::new(body),
+            embeddings,
+            Vec::new(),
+        )
+        .unwrap();
+        template.set_cid_reference_validation(true);
+
+        let data = LoadedTemplateData::from(Data { name: "Ferris" });
+        let (parts, subject) = template.render_to_mail_parts(data, &ctx).unwrap();
+
+        assert_eq!(subject.as_str(), "Hy Ferris");
+        match parts.alternative_bodies[0].resource {
+            Resource::Data(ref data) => {
+                assert!(data
+                    .buffer()
+                    .starts_with(b"<p>Hy Ferris</p><img src=\"cid:"))
+            }
+            ref other => panic!("unexpected resource: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn templates_from_strings_need_loaded_embeddings() {
+        let mut engine = Handlebars::new();
+        let body = engine
+            .load_body_template_from_str("Hy", "text/plain; charset=utf-8".parse().unwrap())
+            .unwrap();
+        let mut embeddings = HashMap::new();
+        embeddings.insert("logo".to_owned(), Resource::from_path("logo.png"));
+
+        let res = Template::from_loaded_parts(
+            "in-memory",
+            engine,
+            "Hy",
+            Vec1::new(body),
+            embeddings,
+            Vec::new(),
+        );
+        let err = match res {
+            Ok(_) => panic!("creating the template should have failed"),
+            Err(err) => err,
+        };
+
+        let err = err
+            .downcast::<UnloadedEmbeddingError>()
+            .expect("error to be a UnloadedEmbeddingError");
+        assert_eq!(err.embedding_name(), "logo");
+    }
+
+    fn template_with_bodies(text: &str, html: &str) -> Template<Handlebars> {
+        let mut engine = Handlebars::new();
+        let text = engine
+            .load_body_template_from_str(text, "text/plain; charset=utf-8".parse().unwrap())
+            .unwrap();
+        let html = engine
+            .load_body_template_from_str(html, "text/html; charset=utf-8".parse().unwrap())
+            .unwrap();
+        let mut bodies = Vec1::new(text);
+        bodies.push(html);
+        let mut template =
+            Template::from_loaded_parts("t", engine, "Hy", bodies, HashMap::new(), Vec::new())
+                .unwrap();
+        template.set_skip_empty_bodies(true);
+        template
+    }
+
+    #[test]
+    fn skips_bodies_rendering_to_whitespace() {
+        let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+        let template = template_with_bodies("Hy", "{{#if data.name}} \n {{/if}}");
+
+        let data = LoadedTemplateData::from(Data { name: "Ferris" });
+        let (parts, _subject) = template.render_to_mail_parts(data, &ctx).unwrap();
+
+        assert_eq!(parts.alternative_bodies.len(), 1);
+        match parts.alternative_bodies[0].resource {
+            Resource::Data(ref data) => assert_eq!(data.media_type().full_type(), "text/plain"),
+            ref other => panic!("unexpected resource: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fails_if_all_bodies_are_empty() {
+        let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+        let template = template_with_bodies(" ", "");
+
+        let data = LoadedTemplateData::from(Data { name: "Ferris" });
+        let err = match template.render_to_mail_parts(data, &ctx) {
+            Ok(_) => panic!("rendering should have failed"),
+            Err(err) => err,
+        };
+
+        let err = err
+            .downcast::<AllBodiesEmptyError>()
+            .expect("error to be a AllBodiesEmptyError");
+        assert_eq!(err.template_name(), "t");
+    }
+
+    #[test]
+    fn embed_helper_adds_inline_embeddings() {
+        let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+        let path = env::temp_dir().join(format!("mail-template-embed-{}.txt", process::id()));
+        fs::write(&path, "logo").unwrap();
+        let html = format!(
+            r#"<img src="{{{{embed "{0}"}}}}"><img src="{{{{embed "{0}"}}}}">"#,
+            path.display()
+        );
+        let mut template = template_with_bodies("Hy", &html);
+        template.set_cid_reference_validation(true);
+
+        let data = LoadedTemplateData::from(Data { name: "Ferris" });
+        let res = template.render_to_mail_parts(data, &ctx);
+        fs::remove_file(&path).unwrap();
+        let (parts, _subject) = res.unwrap();
+
+        let html_body = &parts.alternative_bodies[1];
+        assert_eq!(html_body.inline_embeddings.len(), 1);
+        let cid = html_body.inline_embeddings[0].content_id().unwrap();
+        match html_body.resource {
+            Resource::Data(ref data) => {
+                let expected = format!(r#"<img src="cid:{0}"><img src="cid:{0}">"#, cid.as_str());
+                assert_eq!(&**data.buffer(), expected.as_bytes());
+            }
+            ref other => panic!("unexpected resource: {:?}", other),
+        }
+        assert!(parts.alternative_bodies[0].inline_embeddings.is_empty());
+    }
+
+    #[test]
+    fn embed_helper_can_not_be_used_in_the_subject() {
+        let mut engine = Handlebars::new();
+        let id = engine
+            .load_subject_template(r#"{{embed "logo.png"}}"#.to_owned())
+            .unwrap();
+
+        let res = engine.render(&id, &Data { name: "Ferris" }, AdditionalCIds::new(&[]));
+
+        assert!(res.is_err());
+    }
+}
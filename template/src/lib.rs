@@ -31,12 +31,13 @@ use vec1::Vec1;
 
 use mail_core::{
     compose::{BodyPart, MailParts},
-    Context, Data, Mail, Metadata, Resource, ResourceContainerLoadingFuture,
+    Context, Data, Mail, Metadata, Resource, ResourceContainerLoadingFuture, TransferEncodingHint,
 };
 use mail_headers::{header_components::MediaType, headers, Header, HeaderKind};
 
 mod additional_cid;
 mod base_dir;
+mod cid_references;
 pub mod error;
 mod path_rebase;
 pub mod serde_impl;
@@ -67,12 +68,56 @@ pub trait TemplateEngine: Sized {
 /// This could for example be implemented in a wild card impl for the template engine for
 /// any data `D` which implements `Serialize`.
 pub trait TemplateEngineCanHandleData<D>: TemplateEngine {
+    /// Renders the template with the given id using the given data.
+    ///
+    /// # Error
+    ///
+    /// If rendering fails implementations should return a
+    /// `error::TemplateRenderError` wrapping the template engines
+    /// error, so that the failing template (and if provided by the
+    /// engine the location in it) can be identified.
     fn render<'r>(
         &'r self,
         id: &'r Self::Id,
         data: &'r D,
         additional_cids: AdditionalCIds<'r>,
     ) -> Result<String, Error>;
+
+    /// Renders the body template with the given id, returning embeddings added while rendering.
+    ///
+    /// This is used instead of `render` to render the bodies of a template.
+    /// It allows engines to add inline embeddings to the rendered body which
+    /// are not known before rendering (e.g. the `embed` helper of the
+    /// handlebars bindings). The context can be used to create or load them.
+    ///
+    /// The default implementation calls `render` and adds no embeddings.
+    fn render_body<'r>(
+        &'r self,
+        id: &'r Self::Id,
+        data: &'r D,
+        additional_cids: AdditionalCIds<'r>,
+        ctx: &impl Context,
+    ) -> Result<RenderedBody, Error> {
+        let _ = ctx;
+        let body = self.render(id, data, additional_cids)?;
+        Ok(RenderedBody {
+            body,
+            inline_embeddings: HashMap::new(),
+        })
+    }
+}
+
+/// A rendered body returned by `TemplateEngineCanHandleData::render_body`.
+#[derive(Debug)]
+pub struct RenderedBody {
+    /// The rendered body.
+    pub body: String,
+
+    /// Inline embeddings added while rendering.
+    ///
+    /// They are added to the inline embeddings of the body and have
+    /// to be loaded (i.e. have a content id).
+    pub inline_embeddings: HashMap<String, Resource>,
 }
 
 /// Load a template as described in a toml file.
@@ -128,7 +173,8 @@ where
 #[derive(Debug)]
 pub struct Template<TE: TemplateEngine> {
     template_name: String,
-    base_dir: CwdBaseDir,
+    /// `None` if the template was created from pre-loaded parts.
+    base_dir: Option<CwdBaseDir>,
     subject: Subject<TE>,
     /// This can only be in the loaded form _iff_ this is coupled
     /// with a template engine instance, as using it with the wrong
@@ -138,12 +184,71 @@ pub struct Template<TE: TemplateEngine> {
     embeddings: HashMap<String, Resource>,
     attachments: Vec<Resource>,
     engine: TE,
+    validate_cid_references: bool,
+    skip_empty_bodies: bool,
 }
 
 impl<TE> Template<TE>
 where
     TE: TemplateEngine,
 {
+    /// Creates a template from parts which are already loaded, without accessing the file system.
+    ///
+    /// This is an alternative to `load_toml_template_from_path` for templates
+    /// which are e.g. bundled with `include_str!`/`include_bytes!`:
+    ///
+    /// - The bodies have to be loaded into the `engine` beforehand, how this
+    ///   works depends on the engine (e.g. `Handlebars::load_body_template_from_str`).
+    /// - The `subject` template string is loaded into the `engine` by this function.
+    /// - The embeddings (both `embeddings` and the inline embeddings of the bodies)
+    ///   are used as they are, i.e. they are not rebased to any base dir and
+    ///   have to be loaded resources (`Resource::Data` or `Resource::EncData`).
+    /// - The attachments are used as they are, too.
+    ///
+    /// # Error
+    ///
+    /// Fails with a `error::UnloadedEmbeddingError` if an embedding is not
+    /// loaded or with the error returned by the engine if loading the
+    /// subject template failed.
+    pub fn from_loaded_parts(
+        template_name: impl Into<String>,
+        mut engine: TE,
+        subject: impl Into<String>,
+        bodies: Vec1<BodyTemplate<TE>>,
+        embeddings: HashMap<String, Resource>,
+        attachments: Vec<Resource>,
+    ) -> Result<Self, Error> {
+        let template_name = template_name.into();
+
+        let all_embeddings = bodies
+            .iter()
+            .flat_map(|body| body.inline_embeddings())
+            .chain(&embeddings);
+        for (name, embedding) in all_embeddings {
+            if embedding.content_id().is_none() {
+                return Err(
+                    error::UnloadedEmbeddingError::new(template_name, name.as_str()).into(),
+                );
+            }
+        }
+
+        let subject = Subject {
+            template_id: engine.load_subject_template(subject.into())?,
+        };
+
+        Ok(Template {
+            template_name,
+            base_dir: None,
+            subject,
+            bodies,
+            embeddings,
+            attachments,
+            engine,
+            validate_cid_references: false,
+            skip_empty_bodies: false,
+        })
+    }
+
     pub fn inline_embeddings(&self) -> &HashMap<String, Resource> {
         &self.embeddings
     }
@@ -163,6 +268,36 @@ where
     pub fn subject_template_id(&self) -> &TE::Id {
         &self.subject.template_id
     }
+
+    /// Enables or disables validating `cid:` references in rendered html bodies.
+    ///
+    /// If enabled rendering fails with a `error::DanglingCIdReferencesError`
+    /// if a rendered `text/html` body contains a `cid:` reference which
+    /// doesn't match the content id of any embedding available to the
+    /// body. This is disabled by default.
+    pub fn set_cid_reference_validation(&mut self, enable: bool) {
+        self.validate_cid_references = enable;
+    }
+
+    /// Returns true if `cid:` references in rendered html bodies are validated.
+    pub fn validates_cid_references(&self) -> bool {
+        self.validate_cid_references
+    }
+
+    /// Enables or disables skipping bodies which render to an empty string.
+    ///
+    /// If enabled bodies whose rendered output is empty or whitespace-only
+    /// are not included in the mail, e.g. if all content of a html body is
+    /// conditional. If all bodies render to empty output rendering fails
+    /// with a `error::AllBodiesEmptyError`. This is disabled by default.
+    pub fn set_skip_empty_bodies(&mut self, enable: bool) {
+        self.skip_empty_bodies = enable;
+    }
+
+    /// Returns true if bodies which render to an empty string are skipped.
+    pub fn skips_empty_bodies(&self) -> bool {
+        self.skip_empty_bodies
+    }
 }
 
 /// Represents one of potentially many alternate bodies in a template.
@@ -170,6 +305,7 @@ where
 pub struct BodyTemplate<TE: TemplateEngine> {
     pub template_id: TE::Id,
     pub media_type: MediaType,
+    pub transfer_encoding_hint: TransferEncodingHint,
     pub inline_embeddings: HashMap<String, Resource>,
 }
 
@@ -185,6 +321,11 @@ where
         &self.media_type
     }
 
+    /// Returns the hint used to transfer encode the rendered body.
+    pub fn transfer_encoding_hint(&self) -> TransferEncodingHint {
+        self.transfer_encoding_hint
+    }
+
     pub fn inline_embeddings(&self) -> &HashMap<String, Resource> {
         &self.inline_embeddings
     }
@@ -231,6 +372,28 @@ where
         mail.insert_header(subject);
         Ok(mail)
     }
+
+    /// Loads the resources of `data` and renders the template, blocking until done.
+    ///
+    /// This is a synchronous alternative to loading the data with
+    /// `TemplateData::load` and then calling `render`, e.g. for
+    /// command line tools which don't use a runtime. The resources
+    /// (inline embeddings and attachments) are still loaded through the
+    /// context, e.g. offloaded to its thread pool.
+    ///
+    /// This blocks the current thread, so it must not be called from
+    /// inside of a future running on a (single threaded) executor.
+    fn render_blocking<'r>(
+        &self,
+        data: impl Into<TemplateData<'r, D>>,
+        ctx: &impl Context,
+    ) -> Result<Mail, Error>
+    where
+        D: 'r,
+    {
+        let data = data.into().load(ctx).wait()?;
+        self.render(data, ctx)
+    }
 }
 
 impl<TE, D> TemplateExt<TE, D> for Template<TE>
@@ -254,29 +417,57 @@ where
 
         let subject = headers::Subject::auto_body(subject)?;
 
+        let content_ids = ctx.generate_content_ids(self.bodies().len());
+
         //TODO use Vec1 try_map instead of loop
         let mut bodies = Vec::new();
-        for body in self.bodies().iter() {
-            let raw = self.engine().render(
+        let mut dangling_cids = Vec::new();
+        for (body, content_id) in self.bodies().iter().zip(content_ids) {
+            let embedding_maps = [
+                &inline_embeddings,
+                body.inline_embeddings(),
+                self.inline_embeddings(),
+            ];
+            let RenderedBody {
+                body: raw,
+                inline_embeddings: rendered_embeddings,
+            } = self.engine().render_body(
                 body.template_id(),
                 &data,
-                AdditionalCIds::new(&[
-                    &inline_embeddings,
-                    body.inline_embeddings(),
-                    self.inline_embeddings(),
-                ]),
+                AdditionalCIds::new(&embedding_maps),
+                ctx,
             )?;
 
+            if self.skips_empty_bodies() && raw.trim().is_empty() {
+                continue;
+            }
+
+            if self.validates_cid_references() && body.media_type().full_type() == "text/html" {
+                let mut known_cids = cid_references::content_ids_of(&embedding_maps);
+                known_cids.extend(cid_references::content_ids_of(&[&rendered_embeddings]));
+                for cid in cid_references::find_dangling_cid_references(&raw, &known_cids) {
+                    if !dangling_cids.contains(&cid) {
+                        dangling_cids.push(cid);
+                    }
+                }
+            }
+
             let data = Data::new(
                 raw.into_bytes(),
                 Metadata {
                     file_meta: Default::default(),
                     media_type: body.media_type().clone(),
-                    content_id: ctx.generate_content_id(),
+                    content_id,
                 },
-            );
+            )
+            .with_transfer_encoding_hint(body.transfer_encoding_hint());
 
-            let inline_embeddings = body.inline_embeddings().values().cloned().collect();
+            let inline_embeddings = body
+                .inline_embeddings()
+                .values()
+                .cloned()
+                .chain(rendered_embeddings.into_values())
+                .collect();
 
             bodies.push(BodyPart {
                 resource: Resource::Data(data),
@@ -285,6 +476,21 @@ where
             });
         }
 
+        if !dangling_cids.is_empty() {
+            return Err(error::DanglingCIdReferencesError::new(
+                self.template_name.as_str(),
+                dangling_cids,
+            )
+            .into());
+        }
+
+        let bodies = match Vec1::try_from_vec(bodies) {
+            Ok(bodies) => bodies,
+            Err(_) => {
+                return Err(error::AllBodiesEmptyError::new(self.template_name.as_str()).into());
+            }
+        };
+
         attachments.extend(self.attachments().iter().cloned());
 
         let mut inline_embeddings_vec = Vec::new();
@@ -297,8 +503,7 @@ where
         inline_embeddings_vec.extend(inline_embeddings.into_iter().map(|(_, v)| v));
 
         let parts = MailParts {
-            //UNWRAP_SAFE (complexly mapping a Vec1 is safe)
-            alternative_bodies: Vec1::try_from_vec(bodies).unwrap(),
+            alternative_bodies: bodies,
             inline_embeddings: inline_embeddings_vec,
             attachments,
         };
@@ -407,3 +612,159 @@ impl<'a, D> Future for DataLoadingFuture<'a, D> {
         Ok(Async::Ready(LoadedTemplateData(inner)))
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    mod cid_reference_validation {
+        use mail_core::default_impl::simple_context;
+
+        use mail_core::MailBody;
+
+        use super::super::*;
+        use crate::error::DanglingCIdReferencesError;
+
+        /// Engine using the template source as id, replacing `{logo}` with the logos content id.
+        #[derive(Debug)]
+        struct ReplaceEngine;
+
+        impl TemplateEngine for ReplaceEngine {
+            type Id = String;
+            type LazyBodyTemplate = serde_impl::StandardLazyBodyTemplate;
+
+            fn load_body_template(
+                &mut self,
+                _tmpl: Self::LazyBodyTemplate,
+            ) -> Result<BodyTemplate<Self>, Error> {
+                Err(failure::err_msg("ReplaceEngine only renders inline templates"))
+            }
+
+            fn load_subject_template(&mut self, template_string: String) -> Result<String, Error> {
+                Ok(template_string)
+            }
+        }
+
+        impl TemplateEngineCanHandleData<()> for ReplaceEngine {
+            fn render<'r>(
+                &'r self,
+                id: &'r String,
+                _data: &'r (),
+                additional_cids: AdditionalCIds<'r>,
+            ) -> Result<String, Error> {
+                Ok(match additional_cids.get("logo") {
+                    Some(logo_cid) => id.replace("{logo}", logo_cid.as_str()),
+                    None => id.clone(),
+                })
+            }
+        }
+
+        fn template(html: &str, ctx: &impl Context) -> Template<ReplaceEngine> {
+            let mut embeddings = HashMap::new();
+            embeddings.insert("logo".to_owned(), Resource::plain_text("logo", ctx));
+
+            let mut template = Template {
+                template_name: "test".to_owned(),
+                base_dir: Some(CwdBaseDir::from_path(".").unwrap()),
+                subject: Subject {
+                    template_id: "Hy".to_owned(),
+                },
+                bodies: Vec1::new(BodyTemplate {
+                    template_id: html.to_owned(),
+                    media_type: MediaType::parse("text/html; charset=utf-8").unwrap(),
+                    transfer_encoding_hint: TransferEncodingHint::Auto,
+                    inline_embeddings: HashMap::new(),
+                }),
+                embeddings,
+                attachments: Vec::new(),
+                engine: ReplaceEngine,
+                validate_cid_references: false,
+                skip_empty_bodies: false,
+            };
+            template.set_cid_reference_validation(true);
+            template
+        }
+
+        #[test]
+        fn reports_dangling_cid_references() {
+            let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+            let template = template(r#"<img src="cid:{logo}"><img src="cid:missing">"#, &ctx);
+
+            let err = match template.render_to_mail_parts(LoadedTemplateData::from(()), &ctx) {
+                Ok(_) => panic!("rendering should have failed"),
+                Err(err) => err,
+            };
+            let err = err
+                .downcast::<DanglingCIdReferencesError>()
+                .expect("error to be a DanglingCIdReferencesError");
+
+            assert_eq!(err.template_name(), "test");
+            assert_eq!(err.dangling_cids(), &["missing".to_owned()]);
+        }
+
+        #[test]
+        fn render_blocking_loads_attachments() {
+            let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+            let template = template(r#"<img src="cid:{logo}">"#, &ctx);
+            let attachment =
+                Resource::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+                    .with_media_type(MediaType::parse("text/plain").unwrap());
+            let data = TemplateData {
+                data: ().into(),
+                attachments: vec![attachment],
+                inline_embeddings: HashMap::new(),
+            };
+
+            let mail = template.render_blocking(data, &ctx).unwrap();
+
+            fn collect_resources<'a>(mail: &'a Mail, out: &mut Vec<&'a Resource>) {
+                match mail.body() {
+                    MailBody::SingleBody { body } => out.push(body),
+                    MailBody::MultipleBodies { bodies, .. } => {
+                        for body in bodies {
+                            collect_resources(body, out);
+                        }
+                    }
+                }
+            }
+
+            let mut resources = Vec::new();
+            collect_resources(&mail, &mut resources);
+            // the html body, the logo and the attachment
+            assert_eq!(resources.len(), 3);
+            for resource in resources {
+                if let Resource::Source(source) = resource {
+                    panic!("resource not loaded: {:?}", source);
+                }
+            }
+        }
+
+        #[test]
+        fn rendered_bodies_use_the_transfer_encoding_hint() {
+            let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+            let mut template = template(r#"<img src="cid:{logo}">"#, &ctx);
+            template.bodies[0].transfer_encoding_hint = TransferEncodingHint::UseQuotedPrintable;
+
+            let (parts, _subject) = template
+                .render_to_mail_parts(LoadedTemplateData::from(()), &ctx)
+                .unwrap();
+
+            match parts.alternative_bodies[0].resource {
+                Resource::Data(ref data) => assert_eq!(
+                    data.transfer_encoding_hint(),
+                    TransferEncodingHint::UseQuotedPrintable
+                ),
+                ref other => panic!("unexpected resource: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn accepts_references_to_embeddings() {
+            let ctx = simple_context::from_str_parts("example.test", "t1").unwrap();
+            let template = template(r#"<img src="cid:{logo}">"#, &ctx);
+
+            assert!(template
+                .render_to_mail_parts(LoadedTemplateData::from(()), &ctx)
+                .is_ok());
+        }
+    }
+}
@@ -87,7 +87,8 @@ impl PathRebaseable for IRI {
         }
 
         let new_tail = {
-            let path = Path::new(self.tail());
+            let tail = self.decoded_tail();
+            let path = Path::new(&*tail);
             if path.is_relative() {
                 base_dir.as_ref().join(path)
             } else {
@@ -99,7 +100,8 @@ impl PathRebaseable for IRI {
             .to_str()
             .ok_or_else(|| UnsupportedPathError::new("utf-8"))?;
 
-        let new_iri = self.with_tail(new_tail);
+        let new_iri = IRI::with_scheme_and_tail(self.scheme(), new_tail)
+            .expect("the scheme of an IRI is valid");
         mem::replace(self, new_iri);
         Ok(())
     }
@@ -113,13 +115,15 @@ impl PathRebaseable for IRI {
         }
 
         let new_iri = {
-            let path = Path::new(self.tail());
+            let tail = self.decoded_tail();
+            let path = Path::new(&*tail);
 
             if let Ok(path) = path.strip_prefix(base_dir) {
                 //UNWRAP_SAFE: we just striped some parts, this can
                 // not make it lose it's string-ness
                 let new_tail = path.to_str().unwrap();
-                self.with_tail(new_tail)
+                IRI::with_scheme_and_tail(self.scheme(), new_tail)
+                    .expect("the scheme of an IRI is valid")
             } else {
                 return Ok(());
             }
@@ -181,6 +185,15 @@ mod test {
         assert_eq!(iri.as_str(), "path:/mfix/nfix/suffix.yup");
     }
 
+    #[test]
+    fn rebase_on_percent_encoded_iri() {
+        let mut iri = IRI::with_scheme_and_tail("path", "my dir/logo 1.png").unwrap();
+        iri.rebase_to_include_base_dir("/base 100%").unwrap();
+        assert_eq!(iri.as_str(), "path:/base%20100%25/my%20dir/logo%201.png");
+        iri.rebase_to_exclude_base_dir("/base 100%").unwrap();
+        assert_eq!(iri.decoded_tail(), "my dir/logo 1.png");
+    }
+
     #[test]
     fn rebase_on_resource() {
         let mut resource = Resource::Source(Source {
@@ -12,7 +12,7 @@ use futures::{
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use vec1::Vec1;
 
-use mail_core::{Context, Resource, Source, IRI};
+use mail_core::{Context, Resource, Source, TransferEncodingHint, IRI};
 use mail_headers::header_components::MediaType;
 
 use super::{CwdBaseDir, PathRebaseable, Subject, Template, TemplateEngine, UnsupportedPathError};
@@ -125,12 +125,14 @@ where
                 }
                 Template {
                     template_name,
-                    base_dir,
+                    base_dir: Some(base_dir),
                     subject,
                     bodies,
                     embeddings,
                     attachments,
                     engine,
+                    validate_cid_references: false,
+                    skip_empty_bodies: false,
                 }
             });
 
@@ -232,11 +234,16 @@ where
 /// names to embeddings (using `deserialize_embeddings`) a `path` field which
 /// allows specifying the template file (e.g. `"body.html"`) and can be relative
 /// to the base dir.
+///
+/// Optionally a `media_type` and a `transfer_encoding` (a `TransferEncodingHint`,
+/// e.g. `"UseQuotedPrintable"`) can be given, if no transfer encoding is given
+/// it is chosen automatically.
 #[derive(Debug, Serialize)]
 pub struct StandardLazyBodyTemplate {
     pub path: PathBuf,
     pub embeddings: HashMap<String, Resource>,
     pub media_type: Option<MediaType>,
+    pub transfer_encoding: Option<TransferEncodingHint>,
 }
 
 impl PathRebaseable for StandardLazyBodyTemplate {
@@ -276,6 +283,8 @@ enum StandardLazyBodyTemplateDeserializationHelper {
         embeddings: HashMap<String, Resource>,
         #[serde(default)]
         media_type: Option<MediaType>,
+        #[serde(default)]
+        transfer_encoding: Option<TransferEncodingHint>,
     },
 }
 
@@ -291,15 +300,18 @@ impl<'de> Deserialize<'de> for StandardLazyBodyTemplate {
                 path: string.into(),
                 embeddings: Default::default(),
                 media_type: Default::default(),
+                transfer_encoding: Default::default(),
             },
             LongForm {
                 path,
                 embeddings,
                 media_type,
+                transfer_encoding,
             } => StandardLazyBodyTemplate {
                 path,
                 embeddings,
                 media_type,
+                transfer_encoding,
             },
         };
         Ok(ok_val)
@@ -464,6 +476,20 @@ mod test {
             let Wrapper { body } = toml::from_str(toml_str).unwrap();
             assert_eq!(body.path.to_str().unwrap(), "t.d");
             assert_eq!(body.embeddings.len(), 0);
+            assert_eq!(body.transfer_encoding, None);
+        }
+
+        #[test]
+        fn should_deserialize_transfer_encoding() {
+            let toml_str = r#"
+                body = { path="t.html", transfer_encoding="UseQuotedPrintable" }
+            "#;
+
+            let Wrapper { body } = toml::from_str(toml_str).unwrap();
+            assert_eq!(
+                body.transfer_encoding,
+                Some(TransferEncodingHint::UseQuotedPrintable)
+            );
         }
 
         #[test]